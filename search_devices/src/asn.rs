@@ -0,0 +1,243 @@
+// Offline IP-to-ASN lookup (iptoasn TSV or MaxMind-style MMDB) and special-range labels
+
+use flate2::read::GzDecoder;
+use std::{
+    io::Read,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::Path,
+};
+
+/// Owner information for an address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsnInfo {
+    pub asn: u32,
+    pub name: String,
+    /// Covering prefix, e.g. "8.8.8.0/24" (or "start-end" when not CIDR-aligned)
+    pub prefix: String,
+    pub country: Option<String>,
+}
+
+/// One address range of a TSV database.
+#[derive(Debug, Clone)]
+pub struct Range<T> {
+    start: T,
+    end: T,
+    asn: u32,
+    country: String,
+    name: String,
+}
+
+/// A loaded IP-to-ASN database.
+pub enum AsnDb {
+    /// iptoasn.com `ip2asn-*.tsv` ranges, sorted by start address
+    Tsv { v4: Vec<Range<u32>>, v6: Vec<Range<u128>> },
+    /// MMDB with GeoLite2-ASN style records
+    Mmdb(maxminddb::Reader<Vec<u8>>),
+}
+
+impl AsnDb {
+    /// Open a database file. `.mmdb` files are read as MMDB, everything else as
+    /// iptoasn TSV (optionally gzip-compressed, `.gz`).
+    pub fn open(path: &Path) -> Result<AsnDb, String> {
+        let ext = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+        if ext == "mmdb" {
+            let reader = maxminddb::Reader::open_readfile(path).map_err(|e| format!("Invalid MMDB file: {}", e))?;
+            return Ok(AsnDb::Mmdb(reader));
+        }
+        let file = std::fs::File::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        let mut text = String::new();
+        let read = if ext == "gz" {
+            GzDecoder::new(file).read_to_string(&mut text)
+        } else {
+            std::io::BufReader::new(file).read_to_string(&mut text)
+        };
+        read.map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        AsnDb::from_tsv(&text)
+    }
+
+    /// Parse iptoasn TSV: `range_start  range_end  AS_number  country_code  AS_description`.
+    pub fn from_tsv(text: &str) -> Result<AsnDb, String> {
+        let mut v4 = Vec::new();
+        let mut v6 = Vec::new();
+        for line in text.lines() {
+            let cols: Vec<&str> = line.split('\t').collect();
+            if cols.len() < 5 { continue; }
+            let Ok(asn) = cols[2].trim().parse::<u32>() else { continue };
+            let (country, name) = (cols[3].trim().to_string(), cols[4].trim().to_string());
+            match (cols[0].trim().parse::<IpAddr>(), cols[1].trim().parse::<IpAddr>()) {
+                (Ok(IpAddr::V4(s)), Ok(IpAddr::V4(e))) => v4.push(Range { start: u32::from(s), end: u32::from(e), asn, country, name }),
+                (Ok(IpAddr::V6(s)), Ok(IpAddr::V6(e))) => v6.push(Range { start: u128::from(s), end: u128::from(e), asn, country, name }),
+                _ => continue,
+            }
+        }
+        if v4.is_empty() && v6.is_empty() {
+            return Err("No IP-to-ASN ranges found".to_string());
+        }
+        v4.sort_by_key(|r| r.start);
+        v6.sort_by_key(|r| r.start);
+        Ok(AsnDb::Tsv { v4, v6 })
+    }
+
+    /// Look up the AS owning `ip`. Unrouted space (AS 0) yields None.
+    pub fn lookup(&self, ip: IpAddr) -> Option<AsnInfo> {
+        match self {
+            AsnDb::Tsv { v4, v6 } => match ip {
+                IpAddr::V4(a) => {
+                    let r = find_range(v4, u32::from(a))?;
+                    let prefix = cidr_containing(r.start, r.end, u32::from(a))
+                        .map(|(net, len)| format!("{}/{}", Ipv4Addr::from(net), len))
+                        .unwrap_or_else(|| format!("{}-{}", Ipv4Addr::from(r.start), Ipv4Addr::from(r.end)));
+                    range_info(r, prefix)
+                }
+                IpAddr::V6(a) => {
+                    let r = find_range(v6, u128::from(a))?;
+                    range_info(r, format!("{}-{}", Ipv6Addr::from(r.start), Ipv6Addr::from(r.end)))
+                }
+            },
+            AsnDb::Mmdb(reader) => {
+                let (rec, len) = reader.lookup_prefix::<maxminddb::geoip2::Asn>(ip).ok()?;
+                let asn = rec.autonomous_system_number.filter(|n| *n != 0)?;
+                let prefix = match ip {
+                    IpAddr::V4(a) => {
+                        let mask = if len == 0 { 0 } else { u32::MAX << (32 - len.min(32)) };
+                        format!("{}/{}", Ipv4Addr::from(u32::from(a) & mask), len)
+                    }
+                    IpAddr::V6(a) => {
+                        let mask = if len == 0 { 0 } else { u128::MAX << (128 - len.min(128)) };
+                        format!("{}/{}", Ipv6Addr::from(u128::from(a) & mask), len)
+                    }
+                };
+                Some(AsnInfo { asn, name: rec.autonomous_system_organization.unwrap_or_default().to_string(), prefix, country: None })
+            }
+        }
+    }
+}
+
+fn find_range<T: Ord + Copy>(ranges: &[Range<T>], ip: T) -> Option<&Range<T>> {
+    let idx = ranges.partition_point(|r| r.start <= ip).checked_sub(1)?;
+    let r = &ranges[idx];
+    if ip <= r.end { Some(r) } else { None }
+}
+
+fn range_info<T>(r: &Range<T>, prefix: String) -> Option<AsnInfo> {
+    if r.asn == 0 { return None; }
+    let country = Some(r.country.clone()).filter(|c| !c.is_empty() && c != "None");
+    Some(AsnInfo { asn: r.asn, name: r.name.clone(), prefix, country })
+}
+
+/// Largest CIDR block inside `start..=end` that contains `ip`.
+fn cidr_containing(start: u32, end: u32, ip: u32) -> Option<(u32, u8)> {
+    (0..=32u8).find_map(|len| {
+        let mask = if len == 0 { 0 } else { u32::MAX << (32 - len) };
+        let net = ip & mask;
+        let last = net | !mask;
+        (net >= start && last <= end).then_some((net, len))
+    })
+}
+
+/// Label for private, shared and other special-purpose ranges.
+pub fn special_range_label(ip: IpAddr) -> Option<&'static str> {
+    match ip {
+        IpAddr::V4(a) => {
+            let o = a.octets();
+            if a.is_private() { Some("Private (RFC1918)") }
+            else if o[0] == 100 && (o[1] & 0xc0) == 64 { Some("CGNAT (RFC6598)") }
+            else if a.is_loopback() { Some("Loopback") }
+            else if a.is_link_local() { Some("Link-local") }
+            else if a.is_documentation() { Some("Documentation (RFC5737)") }
+            else if o[0] == 198 && (o[1] & 0xfe) == 18 { Some("Benchmarking (RFC2544)") }
+            else if a.is_multicast() { Some("Multicast") }
+            else if o[0] == 0 { Some("This network") }
+            else if o[0] >= 240 { Some("Reserved") }
+            else { None }
+        }
+        IpAddr::V6(a) => {
+            let seg0 = a.segments()[0];
+            if a.is_loopback() { Some("Loopback") }
+            else if (seg0 & 0xfe00) == 0xfc00 { Some("Unique local (RFC4193)") }
+            else if (seg0 & 0xffc0) == 0xfe80 { Some("Link-local") }
+            else if a.is_multicast() { Some("Multicast") }
+            else { None }
+        }
+    }
+}
+
+/// Annotation shown next to a hop address: special-range label, or "AS<n> <name> <prefix>".
+pub fn annotate(db: Option<&AsnDb>, ip: IpAddr) -> Option<String> {
+    if let Some(label) = special_range_label(ip) {
+        return Some(label.to_string());
+    }
+    let info = db?.lookup(ip)?;
+    Some(format!("AS{} {} {}", info.asn, info.name, info.prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const SAMPLE_TSV: &str = "1.0.0.0\t1.0.0.255\t13335\tUS\tCLOUDFLARENET
+1.0.1.0\t1.0.3.255\t0\tNone\tNot routed
+8.8.4.0\t8.8.4.255\t15169\tUS\tGOOGLE
+8.8.8.0\t8.8.8.255\t15169\tUS\tGOOGLE
+9.0.0.0\t9.0.0.2\t64500\tJP\tEXAMPLE-NET
+2001:4860::\t2001:4860:ffff:ffff:ffff:ffff:ffff:ffff\t15169\tUS\tGOOGLE
+";
+
+    fn ip(s: &str) -> IpAddr { s.parse().unwrap() }
+
+    #[test]
+    fn test_tsv_lookup() {
+        let db = AsnDb::from_tsv(SAMPLE_TSV).unwrap();
+        let g = db.lookup(ip("8.8.8.8")).unwrap();
+        assert_eq!((g.asn, g.name.as_str(), g.prefix.as_str()), (15169, "GOOGLE", "8.8.8.0/24"));
+        assert_eq!(g.country.as_deref(), Some("US"));
+        assert_eq!(db.lookup(ip("1.0.0.1")).unwrap().asn, 13335);
+        // gaps and unrouted space
+        assert!(db.lookup(ip("8.8.5.1")).is_none());
+        assert!(db.lookup(ip("1.0.2.1")).is_none());
+        assert!(db.lookup(ip("0.0.0.1")).is_none());
+        // non CIDR-aligned ranges report the covering block of the address
+        assert_eq!(db.lookup(ip("9.0.0.2")).unwrap().prefix, "9.0.0.2/32");
+        assert_eq!(db.lookup(ip("9.0.0.1")).unwrap().prefix, "9.0.0.0/31");
+        assert_eq!(db.lookup(ip("2001:4860:4860::8888")).unwrap().asn, 15169);
+    }
+
+    #[test]
+    fn test_tsv_invalid() {
+        assert!(AsnDb::from_tsv("").is_err());
+        assert!(AsnDb::from_tsv("garbage\nmore garbage").is_err());
+    }
+
+    #[test]
+    fn test_open_gzip_tsv() {
+        let path = std::env::temp_dir().join(format!("asn_test_{}.tsv.gz", std::process::id()));
+        let mut enc = flate2::write::GzEncoder::new(std::fs::File::create(&path).unwrap(), flate2::Compression::default());
+        enc.write_all(SAMPLE_TSV.as_bytes()).unwrap();
+        enc.finish().unwrap();
+        let db = AsnDb::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(db.lookup(ip("8.8.4.4")).unwrap().asn, 15169);
+    }
+
+    #[test]
+    fn test_special_ranges() {
+        assert_eq!(special_range_label(ip("192.168.1.1")), Some("Private (RFC1918)"));
+        assert_eq!(special_range_label(ip("172.31.255.1")), Some("Private (RFC1918)"));
+        assert_eq!(special_range_label(ip("10.1.2.3")), Some("Private (RFC1918)"));
+        assert_eq!(special_range_label(ip("100.64.0.1")), Some("CGNAT (RFC6598)"));
+        assert_eq!(special_range_label(ip("100.127.255.254")), Some("CGNAT (RFC6598)"));
+        assert_eq!(special_range_label(ip("100.128.0.1")), None);
+        assert_eq!(special_range_label(ip("169.254.1.1")), Some("Link-local"));
+        assert_eq!(special_range_label(ip("fd00::1")), Some("Unique local (RFC4193)"));
+        assert_eq!(special_range_label(ip("8.8.8.8")), None);
+    }
+
+    #[test]
+    fn test_annotate() {
+        let db = AsnDb::from_tsv(SAMPLE_TSV).unwrap();
+        assert_eq!(annotate(Some(&db), ip("8.8.8.8")).as_deref(), Some("AS15169 GOOGLE 8.8.8.0/24"));
+        assert_eq!(annotate(None, ip("10.0.0.1")).as_deref(), Some("Private (RFC1918)"));
+        assert_eq!(annotate(None, ip("8.8.8.8")), None);
+    }
+}
@@ -1,25 +1,37 @@
 use fltk::{
     prelude::*,
     frame::Frame,
-    input::{Input, IntInput},
-    button::Button,
+    input::{Input, MultilineInput, IntInput},
+    button::{Button, CheckButton},
     text::{TextDisplay, TextBuffer},
+    menu::Choice,
     app,
+    dialog,
 };
-use std::{net::{Ipv4Addr, IpAddr}, process::Command, sync::{Arc, atomic::{AtomicBool, Ordering}}};
-use ipnetwork::Ipv4Network;
-use dns_lookup::lookup_addr;
+use std::{net::{Ipv4Addr, IpAddr}, process::Command, sync::{Arc, atomic::{AtomicBool, Ordering}}, time::{SystemTime, UNIX_EPOCH}};
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
-use crate::utils::{ms_to_secs_ceil, ping_args_unix, ping_args_windows};
+use crate::utils::{ms_to_secs_ceil, ping_args_unix, ping_args_windows, ping_interval_args_unix, run_until_stopped, sort_rows_by_ip, ScanRun};
+use crate::rdns::{RdnsPool, DEFAULT_DNS_TIMEOUT_MS};
+use crate::config::Config;
+use crate::targets::{ScanOrder, SweepPlan};
+use crate::rate::RateLimiter;
+use crate::liveness::{Fallback, LivenessMethod};
+use crate::exclusions::{profile_choice, selected_profile, ExcludeList, SkipReport};
+use crate::netbios::HostQuery;
+use crate::wol::MacMemory;
 
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
+/// これを超えるアドレス数のスキャンは開始前に確認する（/20 相当）
+pub(crate) const LARGE_SWEEP: u64 = 4096;
+
 /// CIDRタブを構築し、実行フラグと結果バッファを返します
-pub fn build_cidr_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>) -> (Arc<AtomicBool>, TextBuffer) {
-    Frame::new(10, 30, 480, 30, "CIDR形式で入力 (例: 192.168.1.0/24)");
-    let mut input = Input::new(10, 70, 200, 30, "");
+pub fn build_cidr_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>, rdns: Arc<RdnsPool>, config: Arc<Config>, limiter: Arc<RateLimiter>, macs: Arc<MacMemory>) -> (Arc<AtomicBool>, TextBuffer) {
+    Frame::new(10, 30, 390, 30, "CIDR / 範囲 / ネットマスク (カンマ・改行区切り)");
+    let mut sort_btn = Button::new(410, 32, 80, 25, "Sort by IP");
+    let mut input = MultilineInput::new(10, 70, 200, 30, "");
     input.set_value("192.168.1.0/24");
     let mut scan_btn = Button::new(320, 70, 80, 30, "Scan");
     let mut stop_btn = Button::new(410, 70, 80, 30, "Stop");
@@ -32,84 +44,228 @@ pub fn build_cidr_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>) ->
     let _timeout_label = Frame::new(140, 110, 100, 25, "Timeout(ms)");
     let mut timeout_inp = IntInput::new(240, 110, 80, 25, "");
     timeout_inp.set_value("1000");
+    // スキャン順序（昇順 / ランダム / /24ごとに交互）
+    let mut order_choice = Choice::new(390, 110, 100, 25, "Order");
+    for o in ScanOrder::ALL {
+        order_choice.add_choice(o.name());
+    }
+    order_choice.set_value(0);
+
+    // 逆引き設定（有効/無効・タイムアウト）
+    let mut resolve_cb = CheckButton::new(10, 140, 110, 25, "Resolve DNS");
+    resolve_cb.set_value(true);
+    let _dns_to_label = Frame::new(130, 140, 110, 25, "DNS Timeout(ms)");
+    let mut dns_to_inp = IntInput::new(240, 140, 80, 25, "");
+    dns_to_inp.set_value(&DEFAULT_DNS_TIMEOUT_MS.to_string());
+    // 除外リストのプロファイル（全体の除外リストに追加）
+    let profile = profile_choice(390, 140, 100, 25, &config);
+    // ICMPに応答しないホスト向けの代替判定（ARPキャッシュ / TCP接続）
+    let fallback_cb = CheckButton::new(10, 170, 150, 25, "TCP/ARP fallback");
+    let _fb_ports_label = Frame::new(170, 170, 70, 25, "TCP ports");
+    let mut fb_ports_inp = Input::new(240, 170, 100, 25, "");
+    fb_ports_inp.set_value(&config.liveness.tcp_ports);
+    // 生存ホストのNetBIOS名 / SMB情報をHost Info列に追記
+    let mut netbios_cb = CheckButton::new(345, 170, 80, 25, "NetBIOS");
+    netbios_cb.set_value(true);
+    let smb_cb = CheckButton::new(425, 170, 65, 25, "SMB");
 
-    let mut display = TextDisplay::new(10, 140, 480, 230, "");
+    let mut display = TextDisplay::new(10, 200, 480, 170, "");
     let buff = TextBuffer::default();
     println!("[Debug] CIDR buffer created: {:p}", &buff);
     display.set_buffer(buff.clone());
-    // クリア
+    // 実行中かどうかと、実行ごとの停止フラグ
+    let run = ScanRun::default();
+    // クリア（実行中のスキャンも停止）
     {
         let mut b = buff.clone();
+        let run = run.clone();
         clear_btn.set_callback(move |_| {
+            run.stop();
             b.set_text("")
         });
     }
-    let running = Arc::new(AtomicBool::new(false));
+    // 結果行をIP順に並べ替え（ランダム順スキャンの後など）
+    {
+        let mut b = buff.clone();
+        sort_btn.set_callback(move |_| {
+            let sorted = sort_rows_by_ip(&b.text());
+            b.set_text(&sorted);
+        });
+    }
     // スキャン開始
     {
         let inp = input.clone();
         let s = sender.clone();
         println!("[Debug] CIDR: Using sender channel: {:p}", &s);
-        let flag = running.clone();
+        let run = run.clone();
         let mut buf_clone = buff.clone();
         let cnt_inp = count_inp.clone();
         let to_inp = timeout_inp.clone();
+        let res_cb = resolve_cb.clone();
+        let dns_to_inp = dns_to_inp.clone();
+        let profile = profile.clone();
+        let order_choice = order_choice.clone();
+        let fallback_cb = fallback_cb.clone();
+        let fb_ports_inp = fb_ports_inp.clone();
+        let netbios_cb = netbios_cb.clone();
+        let smb_cb = smb_cb.clone();
         scan_btn.set_callback(move |_| {
-            // 実行フラグを立てる
-            flag.store(true, Ordering::SeqCst);
+            if run.is_busy() {
+                return; // 前回のスキャンがまだ終了していない
+            }
+            // 除外リストの読み込み（不正な設定ならスキャンしない）
+            let excludes = match ExcludeList::for_profile(&config, selected_profile(&profile).as_deref()) {
+                Ok(l) => l,
+                Err(e) => {
+                    buf_clone.append(&format!("[Error] {}\n", e));
+                    return;
+                }
+            };
+            // 代替判定の設定（ポート指定が不正ならスキャンしない）
+            let fallback = if fallback_cb.value() {
+                match Fallback::new(&config.liveness, &fb_ports_inp.value(), &config.presets) {
+                    Ok(f) => Some(f),
+                    Err(e) => {
+                        buf_clone.append(&format!("[Error] Fallback ports: {}\n", e));
+                        return;
+                    }
+                }
+            } else {
+                None
+            };
+            // 対象の解析（重複・隣接する範囲は結合）
+            let plan = match SweepPlan::parse(&inp.value()) {
+                Ok(p) => p,
+                Err(errors) => {
+                    for e in errors {
+                        buf_clone.append(&format!("[Error] {}\n", e));
+                    }
+                    return;
+                }
+            };
+            let total = plan.total();
+            // 大規模な対象は開始前に確認
+            if total > LARGE_SWEEP {
+                let msg = format!("{} addresses in {} range(s) will be pinged.\nStart the scan?", total, plan.ranges.len());
+                if dialog::choice2_default(&msg, "Cancel", "Scan", "") != Some(1) {
+                    return;
+                }
+            }
+            let order = ScanOrder::ALL[order_choice.value().clamp(0, 2) as usize];
+            // 今回の実行専用の停止フラグ
+            let thread_flag = run.start();
+            let run = run.clone();
             // ヘッダーを表示
-            buf_clone.set_text(&format!("{:<15} {:<7} {:<12} {}\n",
+            buf_clone.set_text(&format!("[Info] {} address(es) in {} range(s), {} order, {} exclude rule(s)\n",
+                total, plan.ranges.len(), order.name(), excludes.len()));
+            buf_clone.append(&format!("[Info] Rate limit: {}\n", limiter.limits().describe()));
+            buf_clone.append(&format!("{:<15} {:<7} {:<12} {}\n",
                 "IP Address", "Result", "Status", "Host Info"));
-            let seg = inp.value();
-            let thread_flag = flag.clone();
             let sender_inner = s.clone();
             // 設定値の取得
             let count: u32 = cnt_inp.value().parse().ok().filter(|v| *v >= 1).unwrap_or(1);
             let timeout_ms: u32 = to_inp.value().parse().ok().filter(|v| *v >= 1).unwrap_or(1000);
+            let resolve_dns = res_cb.value();
+            let dns_timeout_ms: u64 = dns_to_inp.value().parse().ok().filter(|v| *v >= 1).unwrap_or(DEFAULT_DNS_TIMEOUT_MS);
+            let host_query = HostQuery { netbios: netbios_cb.value(), smb: smb_cb.value() };
+            let rdns = rdns.clone();
+            let limiter = limiter.clone();
+            let macs = macs.clone();
+            let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
             std::thread::spawn(move || {
-                if let Ok(net) = seg.parse::<Ipv4Network>() {
-                    for ip in net.iter() {
-                        if !thread_flag.load(Ordering::SeqCst) { break }
-                        if ip == net.network() || ip == net.broadcast() { continue }
-                        // ping 実行
-                        let alive = {
-                            let mut cmd = Command::new("ping");
+                let mut skipped = SkipReport::default();
+                // 処理済み（除外を含む）のアドレス数と停止の有無
+                let mut done: u64 = 0;
+                let mut stopped = false;
+                for ip in plan.into_ordered(order, seed) {
+                    if !thread_flag.load(Ordering::SeqCst) { stopped = true; break }
+                    // 除外対象には一切パケットを送らない
+                    if let Some(rule) = excludes.matches(ip) {
+                        skipped.record(rule);
+                        done += 1;
+                        continue;
+                    }
+                    // 同時実行数・送信レート・ホスト間隔の制限を待つ（停止時は中断）
+                    let Some(_permit) = limiter.in_flight(1, &thread_flag) else { stopped = true; break };
+                    if !limiter.pace(ip, count, &thread_flag) { stopped = true; break }
+                    // ping 実行（停止時はプロセスを即座に終了させる）
+                    let alive = {
+                        let mut cmd = Command::new("ping");
 
-                            #[cfg(windows)]
-                            {
-                                cmd.creation_flags(CREATE_NO_WINDOW);
-                                let args = ping_args_windows(count, timeout_ms, &ip.to_string());
-                                cmd.args(&args);
-                            }
+                        #[cfg(windows)]
+                        {
+                            cmd.creation_flags(CREATE_NO_WINDOW);
+                            let args = ping_args_windows(count, timeout_ms, &ip.to_string());
+                            cmd.args(&args);
+                        }
 
-                            #[cfg(not(windows))]
-                            {
-                                // Linuxの-Wは秒。ミリ秒→切り上げ秒へ変換
-                                let args = ping_args_unix(count, timeout_ms, &ip.to_string());
-                                cmd.args(&args);
-                            }
+                        #[cfg(not(windows))]
+                        {
+                            // Linuxの-Wは秒。ミリ秒→切り上げ秒へ変換
+                            let args = ping_args_unix(count, timeout_ms, &ip.to_string());
+                            cmd.args(ping_interval_args_unix(count, limiter.limits().ping_interval_ms));
+                            cmd.args(&args);
+                        }
 
-                            cmd.output()
-                                .map(|o| o.status.success())
-                                .unwrap_or(false)
-                        };
-                        let host_info = lookup_addr(&IpAddr::V4(ip)).unwrap_or_default();
-                        sender_inner.send(("CIDR".to_string(), ip, alive, host_info));
+                        match run_until_stopped(&mut cmd, &thread_flag) {
+                            Some(alive) => alive,
+                            None => { stopped = true; break }
+                        }
+                    };
+                    // ICMPで応答が無ければ代替判定（検出方法をHost Infoに [tcp/443] のように併記）
+                    let method = match &fallback {
+                        Some(_) if alive => Some(LivenessMethod::Icmp),
+                        Some(fb) => match fb.probe(ip, &limiter, &thread_flag) {
+                            Some(m) => m,
+                            None => { stopped = true; break }
+                        },
+                        None => None,
+                    };
+                    let alive = alive || method.is_some();
+                    done += 1;
+                    // 逆引きはキャッシュがあれば即表示、無ければ生存ホストのみプールで解決して後から補完
+                    let cached = if resolve_dns { rdns.cached(IpAddr::V4(ip)) } else { None };
+                    let name = cached.clone().flatten().unwrap_or_default();
+                    let host_info = match method {
+                        Some(m) => format!("[{}] {}", m.label(), name),
+                        None => name,
+                    };
+                    sender_inner.send(("CIDR".to_string(), ip, alive, host_info));
+                    // NetBIOS名 / SMB情報は別スレッドで取得して後から追記
+                    if alive && host_query.enabled() {
+                        host_query.spawn(ip, limiter.clone(), thread_flag.clone(), move |note| {
+                            sender_inner.send(("CIDR_NOTE".to_string(), ip, true, note));
+                        });
                     }
+                    if resolve_dns && alive && cached.is_none() {
+                        rdns.resolve_async(IpAddr::V4(ip), dns_timeout_ms, &thread_flag, move |name| {
+                            if let Some(name) = name {
+                                sender_inner.send(("CIDR_DNS".to_string(), ip, alive, name));
+                            }
+                        });
+                    }
+                }
+                if stopped {
+                    sender_inner.send(("CIDR_INFO".to_string(), Ipv4Addr::UNSPECIFIED, false,
+                        format!("[Info] Scan stopped after {} of {} addresses", done, total)));
                 }
-                // 実行完了フラグを倒す
-                thread_flag.store(false, Ordering::SeqCst);
+                // 除外したアドレスの件数と理由を報告
+                for line in skipped.format_lines() {
+                    sender_inner.send(("CIDR_INFO".to_string(), Ipv4Addr::UNSPECIFIED, false, line));
+                }
+                // ARPキャッシュのMACを記録（Wake-on-LANで使用）
+                macs.record_arp_cache();
+                // 実行完了（次のスキャンを受け付ける）
+                run.finish();
             });
         });
     }
     // 停止
     {
-        let flag = running.clone();
-        stop_btn.set_callback(move |_| {
-            flag.store(false, Ordering::SeqCst)
-        });
+        let run = run.clone();
+        stop_btn.set_callback(move |_| run.stop());
     }
-    (running, buff)
+    (run.busy_flag(), buff)
 }
 
 #[cfg(test)]
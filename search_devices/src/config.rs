@@ -0,0 +1,222 @@
+// User configuration loaded from `search_devices.toml`
+
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::PathBuf};
+use crate::rate::RateLimits;
+use crate::liveness::LivenessConfig;
+use crate::snmp::SnmpConfig;
+use crate::dhcp::DhcpConfig;
+use crate::wol::WolConfig;
+use crate::health::HealthConfig;
+
+/// File name of the optional user configuration.
+pub const CONFIG_FILE_NAME: &str = "search_devices.toml";
+
+/// Settings read from the configuration file. Every section is optional.
+///
+/// ```toml
+/// asn_db = "ip2asn-v4.tsv"
+/// exclude = ["10.0.0.1 # core router", "10.0.0.50 # monitoring"]
+///
+/// [presets]
+/// web = "80,443,8000-8010"
+/// mgmt = "ssh,telnet,https,snmp"
+///
+/// [profiles.plant]
+/// exclude = ["192.168.50.0/28 # PLC cell"]
+///
+/// [profiles.plant.snmp]
+/// version = "3"
+/// user = "monitor"
+/// auth_protocol = "sha"
+/// auth_password = "..."
+/// priv_protocol = "aes"
+/// priv_password = "..."
+///
+/// [profiles.plant.dhcp]
+/// allowed_servers = ["192.168.50.1 # plant router"]
+///
+/// [rate]
+/// pps = 20
+/// max_in_flight = 4
+/// host_delay_ms = 100
+/// ping_interval_ms = 1000
+///
+/// [liveness]
+/// tcp_ports = "80,443,445,22,3389"
+/// tcp_timeout_ms = 500
+/// arp = true
+///
+/// [snmp]
+/// version = "2c"
+/// communities = ["public", "monitor"]
+/// timeout_ms = 1000
+/// retries = 1
+///
+/// [dhcp]
+/// allowed_servers = ["10.0.0.10 # dhcp01", "10.0.0.11 # dhcp02"]
+/// wait_ms = 5000
+///
+/// [wol]
+/// broadcast = "10.0.5.255"
+/// port = 9
+/// password = ""
+/// wait_up_secs = 120
+///
+/// [health]
+/// test_name = "example.com"
+/// trace_target = "8.8.8.8"
+/// trace = true
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Offline IP-to-ASN database (iptoasn TSV, optionally .gz, or .mmdb).
+    pub asn_db: Option<String>,
+    /// User-defined port presets: preset name -> port list.
+    pub presets: BTreeMap<String, String>,
+    /// Addresses never scanned (IPs, CIDRs, ranges; text after `#` is the reason).
+    pub exclude: Vec<String>,
+    /// Named scan profiles.
+    pub profiles: BTreeMap<String, Profile>,
+    /// Global limits for every scanner.
+    pub rate: RateLimits,
+    /// Fallback probes for hosts that drop ICMP.
+    pub liveness: LivenessConfig,
+    /// SNMP credentials used when the profile does not set its own.
+    pub snmp: SnmpConfig,
+    /// DHCP check settings used when the profile does not set its own.
+    pub dhcp: DhcpConfig,
+    /// Wake-on-LAN destination and SecureOn password.
+    pub wol: WolConfig,
+    /// Network health check: test name, reference trace target and DNS servers.
+    pub health: HealthConfig,
+    /// Why the configuration file could not be used. While set, the exclusions are
+    /// unknown and every sweep and port scan is refused.
+    #[serde(skip)]
+    pub load_error: Option<String>,
+}
+
+/// Settings that apply when a profile is selected.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// Exclusions added to the global list.
+    pub exclude: Vec<String>,
+    /// SNMP credentials replacing the global `[snmp]` section.
+    pub snmp: Option<SnmpConfig>,
+    /// DHCP check settings replacing the global `[dhcp]` section.
+    pub dhcp: Option<DhcpConfig>,
+}
+
+impl Config {
+    /// Parse a configuration from TOML text.
+    pub fn from_toml(s: &str) -> Result<Config, String> {
+        toml::from_str(s).map_err(|e| format!("Invalid config: {}", e))
+    }
+
+    /// SNMP settings of `profile`, or the global ones.
+    pub fn snmp_for(&self, profile: Option<&str>) -> &SnmpConfig {
+        profile.and_then(|p| self.profiles.get(p)).and_then(|p| p.snmp.as_ref()).unwrap_or(&self.snmp)
+    }
+
+    /// DHCP check settings of `profile`, or the global ones.
+    pub fn dhcp_for(&self, profile: Option<&str>) -> &DhcpConfig {
+        profile.and_then(|p| self.profiles.get(p)).and_then(|p| p.dhcp.as_ref()).unwrap_or(&self.dhcp)
+    }
+
+    /// Load the configuration file. A missing file gives the defaults; a file that
+    /// cannot be read or parsed is an error.
+    pub fn load() -> Result<Config, String> {
+        let Some(path) = config_path() else { return Ok(Config::default()) };
+        let text = std::fs::read_to_string(&path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let cfg = Config::from_toml(&text).map_err(|e| format!("{} ({})", e, path.display()))?;
+        println!("[Debug] Config loaded from {}", path.display());
+        Ok(cfg)
+    }
+
+    /// Defaults standing in for a configuration file that failed to load. Scans stay
+    /// refused because the file's exclusions cannot be honoured.
+    pub fn unusable(error: String) -> Config {
+        Config { load_error: Some(error), ..Config::default() }
+    }
+}
+
+/// Locate the configuration file: the working directory first, then next to the executable.
+pub fn config_path() -> Option<PathBuf> {
+    let cwd = PathBuf::from(CONFIG_FILE_NAME);
+    if cwd.is_file() {
+        return Some(cwd);
+    }
+    let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
+    let p = exe_dir.join(CONFIG_FILE_NAME);
+    if p.is_file() { Some(p) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_presets() {
+        let cfg = Config::from_toml("[presets]\nweb = \"80,443\"\nmgmt = \"ssh,https\"\n").unwrap();
+        assert_eq!(cfg.presets.get("web").map(String::as_str), Some("80,443"));
+        assert_eq!(cfg.presets.len(), 2);
+        assert!(cfg.asn_db.is_none());
+    }
+
+    #[test]
+    fn test_config_asn_db() {
+        let cfg = Config::from_toml("asn_db = \"data/ip2asn-v4.tsv\"\n").unwrap();
+        assert_eq!(cfg.asn_db.as_deref(), Some("data/ip2asn-v4.tsv"));
+    }
+
+    #[test]
+    fn test_config_rate() {
+        assert_eq!(Config::from_toml("").unwrap().rate, RateLimits::default());
+        let cfg = Config::from_toml("[rate]\npps = 5.5\nhost_delay_ms = 250\n").unwrap();
+        assert_eq!(cfg.rate.pps, 5.5);
+        assert_eq!(cfg.rate.host_delay_ms, 250);
+        assert_eq!(cfg.rate.max_in_flight, RateLimits::default().max_in_flight);
+    }
+
+    #[test]
+    fn test_config_snmp_profiles() {
+        let cfg = Config::from_toml("[snmp]\ncommunities = [\"public\", \"ops\"]\n\n[profiles.core.snmp]\nversion = \"3\"\nuser = \"monitor\"\nauth_protocol = \"sha\"\n\n[profiles.lab]\n").unwrap();
+        assert_eq!(cfg.snmp_for(None).communities, vec!["public", "ops"]);
+        assert_eq!(cfg.snmp_for(Some("lab")).version, "2c");
+        let core = cfg.snmp_for(Some("core"));
+        assert_eq!((core.version.as_str(), core.user.as_str(), core.timeout_ms), ("3", "monitor", 1000));
+    }
+
+    #[test]
+    fn test_config_dhcp_profiles() {
+        let cfg = Config::from_toml("[dhcp]\nallowed_servers = [\"10.0.0.10 # dhcp01\"]\n\n[profiles.plant.dhcp]\nallowed_servers = [\"192.168.50.1\"]\nwait_ms = 2000\n").unwrap();
+        assert_eq!(cfg.dhcp_for(None).allowed_servers, vec!["10.0.0.10 # dhcp01"]);
+        assert_eq!(cfg.dhcp_for(None).wait_ms, 5000);
+        assert_eq!(cfg.dhcp_for(Some("plant")).wait_ms, 2000);
+        assert_eq!(cfg.dhcp_for(Some("other")).allowed_servers.len(), 1);
+    }
+
+    #[test]
+    fn test_config_wol() {
+        let cfg = Config::from_toml("[wol]\nbroadcast = \"10.0.5.255\"\npassword = \"01:02:03:04:05:06\"\n").unwrap();
+        assert_eq!((cfg.wol.broadcast.as_str(), cfg.wol.port, cfg.wol.wait_up_secs), ("10.0.5.255", 9, 120));
+        assert_eq!(cfg.wol.password, "01:02:03:04:05:06");
+        assert_eq!(Config::from_toml("").unwrap().wol.broadcast, "255.255.255.255");
+    }
+
+    #[test]
+    fn test_config_health() {
+        let cfg = Config::from_toml("[health]\ntrace = true\ndns_servers = [\"10.0.0.10\"]\n").unwrap();
+        assert!(cfg.health.trace);
+        assert_eq!(cfg.health.dns_servers, vec!["10.0.0.10"]);
+        assert_eq!((cfg.health.test_name.as_str(), cfg.health.trace_target.as_str()), ("example.com", "8.8.8.8"));
+    }
+
+    #[test]
+    fn test_config_empty_and_invalid() {
+        assert!(Config::from_toml("").unwrap().presets.is_empty());
+        assert!(Config::from_toml("[presets\n").is_err());
+    }
+}
@@ -0,0 +1,384 @@
+// DHCP server detection: broadcast a DHCPDISCOVER, collect every DHCPOFFER and flag
+// servers that are not in the allowed list. No REQUEST is ever sent, so no lease is taken.
+
+use serde::Deserialize;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::{
+    net::{Ipv4Addr, SocketAddrV4, UdpSocket},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use crate::targets::{parse_target, Target};
+
+pub const DHCP_SERVER_PORT: u16 = 67;
+pub const DHCP_CLIENT_PORT: u16 = 68;
+
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+const DHCPDISCOVER: u8 = 1;
+const DHCPOFFER: u8 = 2;
+
+/// Settings read from the `[dhcp]` section, or from `[profiles.<name>.dhcp]`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DhcpConfig {
+    /// Legitimate DHCP servers (or relays): IPs, CIDRs or ranges, text after `#` is a note.
+    /// Empty: offers are listed without a verdict.
+    pub allowed_servers: Vec<String>,
+    /// Time to collect offers after the DISCOVER.
+    pub wait_ms: u64,
+}
+
+impl Default for DhcpConfig {
+    fn default() -> Self {
+        DhcpConfig { allowed_servers: Vec::new(), wait_ms: 5000 }
+    }
+}
+
+/// Allowed DHCP servers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AllowList {
+    entries: Vec<(String, Target)>,
+}
+
+impl AllowList {
+    /// Parse entries like "192.168.1.1 # main router" or "10.0.0.0/29". Host names are rejected.
+    pub fn parse(entries: &[String]) -> Result<AllowList, String> {
+        let mut list = AllowList::default();
+        for raw in entries {
+            let entry = raw.split('#').next().unwrap_or("").trim();
+            if entry.is_empty() { continue; }
+            match parse_target(entry) {
+                Ok(Target::Host(_)) | Err(_) => return Err(format!("Invalid allowed DHCP server: {}", raw.trim())),
+                Ok(t) => list.entries.push((raw.trim().to_string(), t)),
+            }
+        }
+        Ok(list)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entry (as written) covering `ip`.
+    pub fn matches(&self, ip: Ipv4Addr) -> Option<&str> {
+        self.entries.iter().find(|(_, t)| match t {
+            Target::Ip(a) => *a == ip,
+            Target::Cidr(net) => net.contains(ip),
+            Target::Range(a, b) => *a <= ip && ip <= *b,
+            Target::Host(_) => false,
+        }).map(|(e, _)| e.as_str())
+    }
+}
+
+/// Random locally administered client MAC, so no real client's binding is touched.
+pub fn random_client_mac() -> [u8; 6] {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+    let seed = nanos ^ ((std::process::id() as u64) << 24);
+    let b = seed.to_le_bytes();
+    [0x02, b[0], b[1], b[2] ^ b[6], b[3] ^ b[5], b[4]]
+}
+
+/// Encode a DHCPDISCOVER with the broadcast flag set (we have no address for a unicast reply).
+pub fn build_discover(xid: u32, mac: [u8; 6]) -> Vec<u8> {
+    let mut msg = vec![1, 1, 6, 0];
+    msg.extend_from_slice(&xid.to_be_bytes());
+    msg.extend_from_slice(&[0, 0, 0x80, 0]); // secs, flags
+    msg.extend_from_slice(&[0; 16]); // ciaddr, yiaddr, siaddr, giaddr
+    msg.extend_from_slice(&mac);
+    msg.extend_from_slice(&[0; 10 + 64 + 128]); // chaddr padding, sname, file
+    msg.extend_from_slice(&MAGIC_COOKIE);
+    msg.extend_from_slice(&[53, 1, DHCPDISCOVER]);
+    // Parameter request list: mask, router, DNS, domain name, lease time, server identifier
+    msg.extend_from_slice(&[55, 6, 1, 3, 6, 15, 51, 54]);
+    msg.push(255);
+    // BOOTP minimum message size
+    msg.resize(msg.len().max(300), 0);
+    msg
+}
+
+/// Parsed DHCPOFFER.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offer {
+    /// Server identifier (option 54), or the sender when it is missing
+    pub server: Ipv4Addr,
+    pub offered: Ipv4Addr,
+    pub subnet_mask: Option<Ipv4Addr>,
+    pub gateways: Vec<Ipv4Addr>,
+    pub dns: Vec<Ipv4Addr>,
+    pub domain: String,
+    pub lease_secs: Option<u32>,
+    /// Relay agent address (`giaddr`) when the offer was relayed; informational only
+    pub relay: Option<Ipv4Addr>,
+    /// Sender of the packet: the server itself, or the relay that forwarded the offer
+    pub source: Ipv4Addr,
+}
+
+fn addr_list(v: &[u8]) -> Vec<Ipv4Addr> {
+    v.chunks_exact(4).map(|c| Ipv4Addr::new(c[0], c[1], c[2], c[3])).collect()
+}
+
+/// Parse a DHCPOFFER answering `xid`, received from `from`. None for anything else.
+pub fn parse_offer(msg: &[u8], xid: u32, from: Ipv4Addr) -> Option<Offer> {
+    if msg.len() < 240 || msg[0] != 2 || msg[4..8] != xid.to_be_bytes() || msg[236..240] != MAGIC_COOKIE {
+        return None;
+    }
+    let ip_at = |i: usize| Ipv4Addr::new(msg[i], msg[i + 1], msg[i + 2], msg[i + 3]);
+    let mut offer = Offer {
+        server: from,
+        offered: ip_at(16),
+        subnet_mask: None,
+        gateways: Vec::new(),
+        dns: Vec::new(),
+        domain: String::new(),
+        lease_secs: None,
+        relay: Some(ip_at(24)).filter(|a| !a.is_unspecified()),
+        source: from,
+    };
+    let mut msg_type = None;
+    let mut pos = 240;
+    while pos < msg.len() {
+        let code = msg[pos];
+        match code {
+            0 => { pos += 1; continue; }
+            255 => break,
+            _ => {}
+        }
+        let len = *msg.get(pos + 1)? as usize;
+        let v = msg.get(pos + 2..pos + 2 + len)?;
+        pos += 2 + len;
+        match (code, len) {
+            (53, 1) => msg_type = Some(v[0]),
+            (54, 4) => offer.server = Ipv4Addr::new(v[0], v[1], v[2], v[3]),
+            (1, 4) => offer.subnet_mask = Some(Ipv4Addr::new(v[0], v[1], v[2], v[3])),
+            (3, _) => offer.gateways = addr_list(v),
+            (6, _) => offer.dns = addr_list(v),
+            (15, _) => offer.domain = String::from_utf8_lossy(v).trim_end_matches('\0').to_string(),
+            (51, 4) => offer.lease_secs = Some(u32::from_be_bytes([v[0], v[1], v[2], v[3]])),
+            _ => {}
+        }
+    }
+    (msg_type == Some(DHCPOFFER)).then_some(offer)
+}
+
+/// Lease time as "8h 0m", "3d 0h" or "infinite".
+pub fn format_lease(secs: u32) -> String {
+    match secs {
+        u32::MAX => "infinite".to_string(),
+        s if s >= 86400 => format!("{}d {}h", s / 86400, s / 3600 % 24),
+        s if s >= 3600 => format!("{}h {}m", s / 3600, s / 60 % 60),
+        s => format!("{}m {}s", s / 60, s % 60),
+    }
+}
+
+impl Offer {
+    /// Lines shown in the DHCP tab; the first line carries the allowed / rogue verdict.
+    pub fn format_lines(&self, allowed: &AllowList) -> Vec<String> {
+        let verdict = if allowed.is_empty() {
+            String::new()
+        } else {
+            match self.allowed_by(allowed) {
+                Some(entry) => format!("  [allowed: {}]", entry),
+                None => "  [ROGUE: not in allowed_servers]".to_string(),
+            }
+        };
+        let offered = match self.subnet_mask {
+            Some(mask) => format!("{}/{}", self.offered, u32::from(mask).count_ones()),
+            None => self.offered.to_string(),
+        };
+        let mut lines = vec![format!("Server {}  offers {}{}", self.server, offered, verdict)];
+        let list = |v: &[Ipv4Addr]| if v.is_empty() { "-".to_string() } else { v.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ") };
+        lines.push(format!("    Gateway: {}  DNS: {}", list(&self.gateways), list(&self.dns)));
+        let lease = self.lease_secs.map(format_lease).unwrap_or_else(|| "-".to_string());
+        let mut detail = format!("    Lease: {}", lease);
+        if !self.domain.is_empty() {
+            detail.push_str(&format!("  Domain: {}", self.domain));
+        }
+        if let Some(relay) = self.relay {
+            detail.push_str(&format!("  Relay: {}", relay));
+        }
+        if self.source != self.server {
+            detail.push_str(&format!("  From: {}", self.source));
+        }
+        lines.push(detail);
+        lines
+    }
+
+    /// Allowed list entry covering the server identifier. The packet must also come from
+    /// that server or from an allowed relay; `giaddr` is never trusted, as any sender can set it.
+    fn allowed_by<'a>(&self, allowed: &'a AllowList) -> Option<&'a str> {
+        let entry = allowed.matches(self.server)?;
+        (self.source == self.server || allowed.matches(self.source).is_some()).then_some(entry)
+    }
+
+    /// True when an allowed list is configured and the server, or the sender of its offer,
+    /// is not in it.
+    pub fn is_rogue(&self, allowed: &AllowList) -> bool {
+        !allowed.is_empty() && self.allowed_by(allowed).is_none()
+    }
+}
+
+/// IPv4 interface the DISCOVER can be sent from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalInterface {
+    pub name: String,
+    pub ip: Ipv4Addr,
+}
+
+impl LocalInterface {
+    pub fn label(&self) -> String {
+        format!("{} ({})", self.name, self.ip)
+    }
+}
+
+/// Non-loopback IPv4 interfaces.
+pub fn ipv4_interfaces() -> Vec<LocalInterface> {
+    if_addrs::get_if_addrs().unwrap_or_default().into_iter()
+        .filter(|i| !i.is_loopback())
+        .filter_map(|i| match i.addr {
+            if_addrs::IfAddr::V4(a) => Some(LocalInterface { name: i.name, ip: a.ip }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Client socket on port 68 tied to `iface`. Linux binds to the device (a socket bound to an
+/// address would not see broadcast replies); other systems bind to the interface address.
+fn client_socket(iface: &LocalInterface) -> Result<UdpSocket, String> {
+    let err = |e: std::io::Error| {
+        let hint = if e.kind() == std::io::ErrorKind::PermissionDenied { " (port 68 needs administrator / root)" } else { "" };
+        format!("Cannot open DHCP client port on {}: {}{}", iface.label(), e, hint)
+    };
+    let sock = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).map_err(err)?;
+    sock.set_reuse_address(true).map_err(err)?;
+    sock.set_broadcast(true).map_err(err)?;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        sock.bind_device(Some(iface.name.as_bytes())).map_err(err)?;
+        sock.bind(&SockAddr::from(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, DHCP_CLIENT_PORT))).map_err(err)?;
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    sock.bind(&SockAddr::from(SocketAddrV4::new(iface.ip, DHCP_CLIENT_PORT))).map_err(err)?;
+    Ok(sock.into())
+}
+
+/// Send one DISCOVER from `sock` to `dest` and collect offers for `wait`.
+/// Each offer is passed to `on_offer` as it arrives; the same server/address pair is reported once.
+fn collect_offers(sock: &UdpSocket, dest: SocketAddrV4, wait: Duration, running: &AtomicBool, mut on_offer: impl FnMut(Offer)) -> Result<usize, String> {
+    let mac = random_client_mac();
+    let xid = u32::from_be_bytes([mac[2], mac[3], mac[4], mac[5]]) ^ std::process::id();
+    sock.set_read_timeout(Some(Duration::from_millis(100))).map_err(|e| e.to_string())?;
+    sock.send_to(&build_discover(xid, mac), dest).map_err(|e| format!("Cannot send DHCPDISCOVER: {}", e))?;
+    let deadline = Instant::now() + wait;
+    let mut seen = Vec::new();
+    let mut buf = [0u8; 1500];
+    while Instant::now() < deadline && running.load(Ordering::SeqCst) {
+        let Ok((n, from)) = sock.recv_from(&mut buf) else { continue };
+        let std::net::SocketAddr::V4(from) = from else { continue };
+        if let Some(offer) = parse_offer(&buf[..n], xid, *from.ip()) {
+            if !seen.contains(&(offer.server, offer.offered)) {
+                seen.push((offer.server, offer.offered));
+                on_offer(offer);
+            }
+        }
+    }
+    Ok(seen.len())
+}
+
+/// Broadcast a DHCPDISCOVER on `iface` and report the offers received within `wait`.
+pub fn discover(iface: &LocalInterface, wait: Duration, running: &AtomicBool, on_offer: impl FnMut(Offer)) -> Result<usize, String> {
+    let sock = client_socket(iface)?;
+    collect_offers(&sock, SocketAddrV4::new(Ipv4Addr::BROADCAST, DHCP_SERVER_PORT), wait, running, on_offer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// DHCPOFFER for a DISCOVER, as sent by a home router (options after the cookie).
+    fn offer_for(discover: &[u8], server: [u8; 4], offered: [u8; 4]) -> Vec<u8> {
+        let mut msg = discover[..240].to_vec();
+        msg[0] = 2;
+        msg[16..20].copy_from_slice(&offered);
+        msg.extend_from_slice(&[53, 1, DHCPOFFER, 54, 4]);
+        msg.extend_from_slice(&server);
+        msg.extend_from_slice(&[51, 4, 0, 1, 0x51, 0x80, 1, 4, 255, 255, 255, 0, 3, 4]);
+        msg.extend_from_slice(&server);
+        msg.extend_from_slice(&[6, 8, 192, 168, 1, 1, 8, 8, 8, 8, 15, 4]);
+        msg.extend_from_slice(b"home");
+        msg.extend_from_slice(&[0, 0, 255]);
+        msg
+    }
+
+    #[test]
+    fn test_discover_and_offer_parse() {
+        let mac = [0x02, 1, 2, 3, 4, 5];
+        let msg = build_discover(0xdeadbeef, mac);
+        assert_eq!(msg.len(), 300);
+        assert_eq!(&msg[4..8], &[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(&msg[10..12], &[0x80, 0]);
+        assert_eq!(&msg[28..34], &mac);
+        assert_eq!(&msg[236..243], &[99, 130, 83, 99, 53, 1, 1]);
+
+        let offer = parse_offer(&offer_for(&msg, [192, 168, 1, 1], [192, 168, 1, 57]), 0xdeadbeef, "192.168.1.1".parse().unwrap()).unwrap();
+        assert_eq!(offer.offered, Ipv4Addr::new(192, 168, 1, 57));
+        assert_eq!(offer.gateways, vec![Ipv4Addr::new(192, 168, 1, 1)]);
+        assert_eq!(offer.dns, vec![Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(8, 8, 8, 8)]);
+        assert_eq!(offer.lease_secs, Some(86400));
+        assert_eq!(offer.domain, "home");
+        assert_eq!(offer.relay, None);
+        // Other transaction, or a non-offer
+        assert!(parse_offer(&offer_for(&msg, [192, 168, 1, 1], [192, 168, 1, 57]), 1, Ipv4Addr::LOCALHOST).is_none());
+        assert!(parse_offer(&msg, 0xdeadbeef, Ipv4Addr::LOCALHOST).is_none());
+
+        let allowed = AllowList::parse(&["192.168.1.1 # router".to_string(), "10.0.0.0/29".to_string()]).unwrap();
+        assert_eq!(offer.format_lines(&allowed), vec![
+            "Server 192.168.1.1  offers 192.168.1.57/24  [allowed: 192.168.1.1 # router]",
+            "    Gateway: 192.168.1.1  DNS: 192.168.1.1, 8.8.8.8",
+            "    Lease: 1d 0h  Domain: home",
+        ]);
+        assert!(!offer.is_rogue(&allowed));
+        let rogue = Offer { server: Ipv4Addr::new(192, 168, 1, 50), ..offer.clone() };
+        assert!(rogue.is_rogue(&allowed));
+        assert!(rogue.format_lines(&allowed)[0].ends_with("[ROGUE: not in allowed_servers]"));
+        // An allowed address in giaddr does not vouch for the server
+        let spoofed_relay = Offer { relay: Some(Ipv4Addr::new(192, 168, 1, 1)), source: Ipv4Addr::new(192, 168, 1, 50), ..rogue.clone() };
+        assert!(spoofed_relay.is_rogue(&allowed));
+        // Nor does an allowed server identifier sent from another host
+        let spoofed_id = Offer { source: Ipv4Addr::new(192, 168, 1, 50), ..offer.clone() };
+        assert!(spoofed_id.is_rogue(&allowed));
+        assert_eq!(spoofed_id.format_lines(&allowed)[2], "    Lease: 1d 0h  Domain: home  From: 192.168.1.50");
+        // Relayed through an allowed relay
+        let relayed = Offer { relay: Some(Ipv4Addr::new(10, 0, 0, 2)), source: Ipv4Addr::new(10, 0, 0, 2), ..offer.clone() };
+        assert!(!relayed.is_rogue(&allowed));
+        // No allowed list: no verdict
+        assert!(!rogue.is_rogue(&AllowList::default()));
+        assert_eq!(rogue.format_lines(&AllowList::default())[0], "Server 192.168.1.50  offers 192.168.1.57/24");
+        assert!(AllowList::parse(&["dhcp.example".to_string()]).is_err());
+        assert_eq!(format_lease(28800), "8h 0m");
+        assert_eq!(format_lease(u32::MAX), "infinite");
+    }
+
+    #[test]
+    fn test_collect_offers_from_stand_in_servers() {
+        // Two servers answering the same DISCOVER (the second one is a rogue), one of them twice
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let dest = match server.local_addr().unwrap() { std::net::SocketAddr::V4(a) => a, _ => unreachable!() };
+        thread::spawn(move || {
+            let mut buf = [0u8; 1500];
+            if let Ok((n, from)) = server.recv_from(&mut buf) {
+                for (srv, ip) in [([10, 0, 0, 1], [10, 0, 0, 20]), ([10, 0, 0, 99], [10, 0, 0, 150]), ([10, 0, 0, 1], [10, 0, 0, 20])] {
+                    let _ = server.send_to(&offer_for(&buf[..n], srv, ip), from);
+                }
+            }
+        });
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let running = AtomicBool::new(true);
+        let mut offers = Vec::new();
+        let n = collect_offers(&client, dest, Duration::from_millis(500), &running, |o| offers.push(o)).unwrap();
+        assert_eq!(n, 2);
+        // The stand-in sends every offer from 127.0.0.1, acting as the relay
+        let allowed = AllowList::parse(&["10.0.0.1".to_string(), "127.0.0.1 # relay".to_string()]).unwrap();
+        assert_eq!(offers.iter().filter(|o| o.is_rogue(&allowed)).map(|o| o.server).collect::<Vec<_>>(), vec![Ipv4Addr::new(10, 0, 0, 99)]);
+    }
+}
@@ -0,0 +1,131 @@
+use fltk::{
+    prelude::*,
+    frame::Frame,
+    input::IntInput,
+    menu::Choice,
+    button::Button,
+    text::{TextDisplay, TextBuffer},
+    app,
+};
+use std::{
+    net::Ipv4Addr,
+    sync::{Arc, Mutex, atomic::AtomicBool},
+    thread,
+    time::Duration,
+};
+use crate::config::Config;
+use crate::dhcp::{self, AllowList};
+use crate::exclusions::{profile_choice, selected_profile};
+use crate::utils::ScanRun;
+
+/// Build the DHCP tab. Returns the running flag, the output buffer and the display.
+pub fn build_dhcp_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>, config: Arc<Config>) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>) {
+    Frame::new(10, 30, 480, 25, "Interface to send DHCPDISCOVER on (no lease is taken)");
+    let interfaces = dhcp::ipv4_interfaces();
+    let mut iface_choice = Choice::new(10, 70, 200, 30, "");
+    for iface in &interfaces {
+        // '/' would open a submenu
+        iface_choice.add_choice(&iface.label().replace('/', "\\/"));
+    }
+    iface_choice.set_value(0);
+
+    let mut clear_btn = Button::new(240, 70, 80, 30, "Clear");
+    let mut discover_btn = Button::new(320, 70, 80, 30, "Discover");
+    let mut stop_btn = Button::new(410, 70, 80, 30, "Stop");
+
+    let mut profile = profile_choice(60, 110, 150, 25, &config);
+    let _wait_label = Frame::new(240, 110, 80, 25, "Wait (s)");
+    let mut wait_inp = IntInput::new(320, 110, 70, 25, "");
+    wait_inp.set_value(&(config.dhcp.wait_ms.div_ceil(1000)).to_string());
+    // The wait time follows the profile's [profiles.X.dhcp] section
+    {
+        let config = config.clone();
+        let mut wait_inp = wait_inp.clone();
+        profile.set_callback(move |c| {
+            let cfg = config.dhcp_for(selected_profile(c).as_deref());
+            wait_inp.set_value(&cfg.wait_ms.div_ceil(1000).to_string());
+        });
+    }
+
+    let mut display = TextDisplay::new(10, 140, 480, 230, "");
+    let buff = TextBuffer::default();
+    display.set_buffer(buff.clone());
+    let display_ref = Arc::new(Mutex::new(display));
+
+    let run = ScanRun::default();
+
+    // Clear
+    {
+        let mut b = buff.clone();
+        clear_btn.set_callback(move |_| b.set_text(""));
+    }
+
+    // Discover: one DISCOVER, then every offer received within the wait time
+    {
+        let iface_choice = iface_choice.clone();
+        let wait_inp = wait_inp.clone();
+        let run = run.clone();
+        let mut b = buff.clone();
+        discover_btn.set_callback(move |_| {
+            if run.is_busy() {
+                return; // already running
+            }
+            let Some(iface) = usize::try_from(iface_choice.value()).ok().and_then(|i| interfaces.get(i)).cloned() else {
+                b.append("[Error] No IPv4 interface available\n");
+                return;
+            };
+            let cfg = config.dhcp_for(selected_profile(&profile).as_deref());
+            let allowed = match AllowList::parse(&cfg.allowed_servers) {
+                Ok(a) => a,
+                Err(e) => { b.append(&format!("[Error] {}\n", e)); return; }
+            };
+            let wait = match wait_inp.value().parse::<u64>().ok().filter(|v| (1..=60).contains(v)) {
+                Some(secs) => Duration::from_secs(secs),
+                None => Duration::from_millis(cfg.wait_ms.clamp(1000, 60_000)),
+            };
+            b.set_text(&format!("[Info] DHCPDISCOVER on {}, collecting offers for {} s...\n", iface.label(), wait.as_secs()));
+            if allowed.is_empty() {
+                b.append("[Info] No allowed_servers configured: offers are listed without a rogue check\n");
+            }
+
+            let flag_thread = run.start();
+            let run = run.clone();
+            thread::spawn(move || {
+                let send = |line: String| sender.send(("DHCP".to_string(), Ipv4Addr::UNSPECIFIED, false, line));
+                let mut servers = Vec::new();
+                let mut rogues = Vec::new();
+                let result = dhcp::discover(&iface, wait, &flag_thread, |offer| {
+                    for line in offer.format_lines(&allowed) {
+                        send(line);
+                    }
+                    if !servers.contains(&offer.server) {
+                        servers.push(offer.server);
+                        if offer.is_rogue(&allowed) {
+                            rogues.push(offer.server);
+                        }
+                    }
+                });
+                match result {
+                    Ok(0) => send("[Info] No DHCPOFFER received".to_string()),
+                    Ok(count) => {
+                        send(format!("[Info] {} offer(s) from {} server(s)", count, servers.len()));
+                        if !rogues.is_empty() {
+                            let list: Vec<String> = rogues.iter().map(|r| r.to_string()).collect();
+                            send(format!("[Error] Rogue DHCP server(s): {}", list.join(", ")));
+                        }
+                    }
+                    Err(e) => send(format!("[Error] {}", e)),
+                }
+                run.finish();
+            });
+        });
+    }
+
+    // Stop
+    {
+        let run = run.clone();
+        stop_btn.set_callback(move |_| run.stop());
+    }
+
+    (run.busy_flag(), buff, display_ref)
+}
@@ -0,0 +1,666 @@
+// Minimal DNS client: queries a chosen server over UDP or TCP and decodes the answer
+// (A/AAAA/PTR/MX/TXT/SRV/CNAME/SOA), plus a PTR-vs-forward consistency check.
+
+use std::{
+    fmt,
+    io::{Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket},
+    time::{Duration, Instant},
+};
+
+/// Well-known DNS port.
+pub const DNS_PORT: u16 = 53;
+
+/// Record types offered in the DNS tab.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordType {
+    A,
+    Aaaa,
+    Ptr,
+    Mx,
+    Txt,
+    Srv,
+    Cname,
+    Soa,
+}
+
+impl RecordType {
+    /// All types, in display order.
+    pub const ALL: [RecordType; 8] = [
+        RecordType::A, RecordType::Aaaa, RecordType::Ptr, RecordType::Mx,
+        RecordType::Txt, RecordType::Srv, RecordType::Cname, RecordType::Soa,
+    ];
+
+    pub fn code(self) -> u16 {
+        match self {
+            RecordType::A => 1,
+            RecordType::Cname => 5,
+            RecordType::Soa => 6,
+            RecordType::Ptr => 12,
+            RecordType::Mx => 15,
+            RecordType::Txt => 16,
+            RecordType::Aaaa => 28,
+            RecordType::Srv => 33,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RecordType::A => "A",
+            RecordType::Aaaa => "AAAA",
+            RecordType::Ptr => "PTR",
+            RecordType::Mx => "MX",
+            RecordType::Txt => "TXT",
+            RecordType::Srv => "SRV",
+            RecordType::Cname => "CNAME",
+            RecordType::Soa => "SOA",
+        }
+    }
+
+    pub fn from_name(s: &str) -> Option<RecordType> {
+        RecordType::ALL.into_iter().find(|t| t.name().eq_ignore_ascii_case(s.trim()))
+    }
+}
+
+/// Display name of a type code, e.g. "MX" or "TYPE99".
+pub fn type_name(code: u16) -> String {
+    match RecordType::ALL.into_iter().find(|t| t.code() == code) {
+        Some(t) => t.name().to_string(),
+        None if code == 2 => "NS".to_string(),
+        None => format!("TYPE{}", code),
+    }
+}
+
+/// Transport used to reach the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+/// Decoded record data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    /// PTR, CNAME and NS targets
+    Name(String),
+    Mx { preference: u16, exchange: String },
+    Txt(Vec<String>),
+    Srv { priority: u16, weight: u16, port: u16, target: String },
+    Soa { mname: String, rname: String, serial: u32, refresh: u32, retry: u32, expire: u32, minimum: u32 },
+    Other(Vec<u8>),
+}
+
+impl fmt::Display for RData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RData::A(ip) => write!(f, "{}", ip),
+            RData::Aaaa(ip) => write!(f, "{}", ip),
+            RData::Name(n) => write!(f, "{}", n),
+            RData::Mx { preference, exchange } => write!(f, "{} {}", preference, exchange),
+            RData::Txt(parts) => {
+                let quoted: Vec<String> = parts.iter().map(|p| format!("\"{}\"", p)).collect();
+                write!(f, "{}", quoted.join(" "))
+            }
+            RData::Srv { priority, weight, port, target } => write!(f, "{} {} {} {}", priority, weight, port, target),
+            RData::Soa { mname, rname, serial, refresh, retry, expire, minimum } => {
+                write!(f, "{} {} {} {} {} {} {}", mname, rname, serial, refresh, retry, expire, minimum)
+            }
+            RData::Other(bytes) => write!(f, "\\# {}", bytes.len()),
+        }
+    }
+}
+
+/// One resource record of a response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub rtype: u16,
+    pub ttl: u32,
+    pub data: RData,
+}
+
+/// A decoded response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsResponse {
+    pub id: u16,
+    pub rcode: u8,
+    pub authoritative: bool,
+    pub truncated: bool,
+    pub answers: Vec<DnsRecord>,
+    pub authority: Vec<DnsRecord>,
+    /// Additional section (mDNS responders put SRV/TXT/A records here)
+    pub additional: Vec<DnsRecord>,
+    /// Round-trip time of the query
+    pub elapsed: Duration,
+    /// Transport that produced the answer (UDP falls back to TCP when truncated)
+    pub transport: Transport,
+}
+
+impl DnsResponse {
+    /// Addresses from the A records of the answer section.
+    pub fn ipv4_answers(&self) -> Vec<Ipv4Addr> {
+        self.answers.iter().filter_map(|r| match r.data { RData::A(ip) => Some(ip), _ => None }).collect()
+    }
+
+    /// Names from the PTR records of the answer section.
+    pub fn ptr_answers(&self) -> Vec<String> {
+        self.answers.iter()
+            .filter(|r| r.rtype == RecordType::Ptr.code())
+            .filter_map(|r| match &r.data { RData::Name(n) => Some(n.clone()), _ => None })
+            .collect()
+    }
+}
+
+/// Name of a response code.
+pub fn rcode_name(rcode: u8) -> String {
+    match rcode {
+        0 => "NOERROR".to_string(),
+        1 => "FORMERR".to_string(),
+        2 => "SERVFAIL".to_string(),
+        3 => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        n => format!("RCODE{}", n),
+    }
+}
+
+/// Parse "8.8.8.8", "8.8.8.8:5353", "::1" or "[::1]:53". The port defaults to 53.
+pub fn parse_server(s: &str) -> Result<SocketAddr, String> {
+    let s = s.trim();
+    if let Ok(addr) = s.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    if let Ok(ip) = s.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, DNS_PORT));
+    }
+    Err(format!("Invalid DNS server: {}", s))
+}
+
+/// Reverse lookup name for an address ("4.3.2.1.in-addr.arpa" / nibble ip6.arpa).
+pub fn ptr_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(a) => {
+            let o = a.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+        }
+        IpAddr::V6(a) => {
+            let mut labels: Vec<String> = Vec::with_capacity(32);
+            for b in a.octets().iter().rev() {
+                labels.push(format!("{:x}", b & 0x0f));
+                labels.push(format!("{:x}", b >> 4));
+            }
+            format!("{}.ip6.arpa", labels.join("."))
+        }
+    }
+}
+
+/// Encode a query message with recursion desired.
+pub fn build_query(id: u16, name: &str, rtype: RecordType) -> Result<Vec<u8>, String> {
+    let mut msg = Vec::with_capacity(32 + name.len());
+    msg.extend_from_slice(&id.to_be_bytes());
+    msg.extend_from_slice(&[0x01, 0x00]); // RD
+    msg.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]); // QDCOUNT=1
+    encode_name(&mut msg, name)?;
+    msg.extend_from_slice(&rtype.code().to_be_bytes());
+    msg.extend_from_slice(&[0, 1]); // class IN
+    Ok(msg)
+}
+
+pub(crate) fn encode_name(out: &mut Vec<u8>, name: &str) -> Result<(), String> {
+    let name = name.trim().trim_end_matches('.');
+    let labels: Vec<&str> = name.split('.').filter(|l| !l.is_empty()).collect();
+    encode_labels(out, &labels)
+}
+
+/// Encode a name given as separate labels; a label may itself contain dots
+/// (e.g. the DNS-SD instance "Printer 4.0").
+pub(crate) fn encode_labels<S: AsRef<str>>(out: &mut Vec<u8>, labels: &[S]) -> Result<(), String> {
+    let len: usize = labels.iter().map(|l| l.as_ref().len() + 1).sum();
+    if len > 254 {
+        let name: Vec<&str> = labels.iter().map(|l| l.as_ref()).collect();
+        return Err(format!("Name too long: {}", name.join(".")));
+    }
+    for label in labels.iter().map(|l| l.as_ref()) {
+        if label.len() > 63 {
+            return Err(format!("Label too long: {}", label));
+        }
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+    out.push(0);
+    Ok(())
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Result<u16, String> {
+    buf.get(pos..pos + 2).map(|b| u16::from_be_bytes([b[0], b[1]])).ok_or_else(|| "Truncated DNS message".to_string())
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Result<u32, String> {
+    buf.get(pos..pos + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]])).ok_or_else(|| "Truncated DNS message".to_string())
+}
+
+/// Decode a (possibly compressed) name at `pos`. Returns the name and the offset after it.
+fn decode_name(buf: &[u8], mut pos: usize) -> Result<(String, usize), String> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    for _ in 0..128 {
+        let len = *buf.get(pos).ok_or("Truncated DNS name")? as usize;
+        if len & 0xc0 == 0xc0 {
+            let ptr = (read_u16(buf, pos)? & 0x3fff) as usize;
+            end.get_or_insert(pos + 2);
+            pos = ptr;
+        } else if len == 0 {
+            let name = if labels.is_empty() { ".".to_string() } else { format!("{}.", labels.join(".")) };
+            return Ok((name, end.unwrap_or(pos + 1)));
+        } else {
+            let label = buf.get(pos + 1..pos + 1 + len).ok_or("Truncated DNS name")?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            pos += 1 + len;
+        }
+    }
+    Err("DNS name compression loop".to_string())
+}
+
+fn decode_rdata(buf: &[u8], rtype: u16, start: usize, len: usize) -> Result<RData, String> {
+    let rd = buf.get(start..start + len).ok_or("Truncated record data")?;
+    let data = match rtype {
+        1 if len == 4 => RData::A(Ipv4Addr::new(rd[0], rd[1], rd[2], rd[3])),
+        28 if len == 16 => {
+            let mut o = [0u8; 16];
+            o.copy_from_slice(rd);
+            RData::Aaaa(Ipv6Addr::from(o))
+        }
+        2 | 5 | 12 => RData::Name(decode_name(buf, start)?.0),
+        15 => RData::Mx { preference: read_u16(buf, start)?, exchange: decode_name(buf, start + 2)?.0 },
+        16 => {
+            let mut parts = Vec::new();
+            let mut i = 0;
+            while i < rd.len() {
+                let n = rd[i] as usize;
+                let part = rd.get(i + 1..i + 1 + n).ok_or("Truncated TXT record")?;
+                parts.push(String::from_utf8_lossy(part).into_owned());
+                i += 1 + n;
+            }
+            RData::Txt(parts)
+        }
+        33 => RData::Srv {
+            priority: read_u16(buf, start)?,
+            weight: read_u16(buf, start + 2)?,
+            port: read_u16(buf, start + 4)?,
+            target: decode_name(buf, start + 6)?.0,
+        },
+        6 => {
+            let (mname, p) = decode_name(buf, start)?;
+            let (rname, p) = decode_name(buf, p)?;
+            RData::Soa {
+                mname, rname,
+                serial: read_u32(buf, p)?,
+                refresh: read_u32(buf, p + 4)?,
+                retry: read_u32(buf, p + 8)?,
+                expire: read_u32(buf, p + 12)?,
+                minimum: read_u32(buf, p + 16)?,
+            }
+        }
+        _ => RData::Other(rd.to_vec()),
+    };
+    Ok(data)
+}
+
+fn decode_records(buf: &[u8], mut pos: usize, count: u16) -> Result<(Vec<DnsRecord>, usize), String> {
+    let mut out = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (name, p) = decode_name(buf, pos)?;
+        let rtype = read_u16(buf, p)?;
+        let ttl = read_u32(buf, p + 4)?;
+        let len = read_u16(buf, p + 8)? as usize;
+        let data = decode_rdata(buf, rtype, p + 10, len)?;
+        out.push(DnsRecord { name, rtype, ttl, data });
+        pos = p + 10 + len;
+    }
+    Ok((out, pos))
+}
+
+/// Decode a response message. `elapsed` and `transport` are left for the caller to fill.
+pub fn parse_response(buf: &[u8]) -> Result<DnsResponse, String> {
+    if buf.len() < 12 {
+        return Err("DNS response too short".to_string());
+    }
+    let flags = read_u16(buf, 2)?;
+    if flags & 0x8000 == 0 {
+        return Err("Not a DNS response".to_string());
+    }
+    let (qd, an, ns, ar) = (read_u16(buf, 4)?, read_u16(buf, 6)?, read_u16(buf, 8)?, read_u16(buf, 10)?);
+    let mut pos = 12;
+    for _ in 0..qd {
+        pos = decode_name(buf, pos)?.1 + 4;
+    }
+    let truncated = flags & 0x0200 != 0;
+    // A truncated UDP answer may end mid-record; keep what could be decoded
+    let (answers, pos) = match decode_records(buf, pos, an) {
+        Ok(r) => r,
+        Err(_) if truncated => (Vec::new(), buf.len()),
+        Err(e) => return Err(e),
+    };
+    let (authority, pos) = decode_records(buf, pos, ns).unwrap_or((Vec::new(), buf.len()));
+    let additional = decode_records(buf, pos, ar).map(|r| r.0).unwrap_or_default();
+    Ok(DnsResponse {
+        id: read_u16(buf, 0)?,
+        rcode: (flags & 0x000f) as u8,
+        authoritative: flags & 0x0400 != 0,
+        truncated,
+        answers,
+        authority,
+        additional,
+        elapsed: Duration::ZERO,
+        transport: Transport::Udp,
+    })
+}
+
+fn new_query_id() -> u16 {
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    (nanos ^ (nanos >> 16) ^ std::process::id()) as u16
+}
+
+fn exchange_udp(server: SocketAddr, msg: &[u8], id: u16, timeout: Duration) -> Result<Vec<u8>, String> {
+    let bind: SocketAddr = if server.is_ipv4() { "0.0.0.0:0".parse().unwrap() } else { "[::]:0".parse().unwrap() };
+    let sock = UdpSocket::bind(bind).map_err(|e| format!("UDP socket error: {}", e))?;
+    sock.connect(server).map_err(|e| format!("Cannot reach {}: {}", server, e))?;
+    sock.send(msg).map_err(|e| format!("Send failed: {}", e))?;
+    let deadline = Instant::now() + timeout;
+    let mut buf = vec![0u8; 4096];
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(format!("Timed out waiting for {}", server));
+        }
+        sock.set_read_timeout(Some(left)).map_err(|e| e.to_string())?;
+        match sock.recv(&mut buf) {
+            // Ignore stray datagrams that do not answer this query
+            Ok(n) if n >= 2 && u16::from_be_bytes([buf[0], buf[1]]) == id => return Ok(buf[..n].to_vec()),
+            Ok(_) => continue,
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {
+                return Err(format!("Timed out waiting for {}", server));
+            }
+            Err(e) => return Err(format!("Receive failed: {}", e)),
+        }
+    }
+}
+
+fn exchange_tcp(server: SocketAddr, msg: &[u8], timeout: Duration) -> Result<Vec<u8>, String> {
+    let mut stream = TcpStream::connect_timeout(&server, timeout).map_err(|e| format!("Cannot connect to {}: {}", server, e))?;
+    stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    let mut framed = (msg.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(msg);
+    stream.write_all(&framed).map_err(|e| format!("Send failed: {}", e))?;
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len).map_err(|e| format!("Receive failed: {}", e))?;
+    let mut buf = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut buf).map_err(|e| format!("Receive failed: {}", e))?;
+    Ok(buf)
+}
+
+/// Query `server` for `name`. A truncated UDP answer is retried over TCP.
+pub fn query(server: SocketAddr, name: &str, rtype: RecordType, transport: Transport, timeout: Duration) -> Result<DnsResponse, String> {
+    let id = new_query_id();
+    let msg = build_query(id, name, rtype)?;
+    let started = Instant::now();
+    let (raw, used) = match transport {
+        Transport::Udp => {
+            let raw = exchange_udp(server, &msg, id, timeout)?;
+            if parse_response(&raw).map(|r| r.truncated).unwrap_or(false) {
+                (exchange_tcp(server, &msg, timeout)?, Transport::Tcp)
+            } else {
+                (raw, Transport::Udp)
+            }
+        }
+        Transport::Tcp => (exchange_tcp(server, &msg, timeout)?, Transport::Tcp),
+    };
+    let mut resp = parse_response(&raw)?;
+    if resp.id != id {
+        return Err("DNS response ID mismatch".to_string());
+    }
+    resp.elapsed = started.elapsed();
+    resp.transport = used;
+    Ok(resp)
+}
+
+/// Render a response like `dig`: a status line followed by one line per record.
+pub fn format_response(name: &str, rtype: RecordType, server: SocketAddr, resp: &DnsResponse) -> Vec<String> {
+    let mut lines = vec![
+        format!(";; {} {} @{} ({})", name, rtype.name(), server, if resp.transport == Transport::Tcp { "TCP" } else { "UDP" }),
+        format!(
+            ";; Status: {}, {} answer(s), {:.1} ms{}",
+            rcode_name(resp.rcode),
+            resp.answers.len(),
+            resp.elapsed.as_secs_f64() * 1000.0,
+            if resp.authoritative { ", authoritative" } else { "" }
+        ),
+    ];
+    for r in &resp.answers {
+        lines.push(format!("{:<32} {:>7} {:<6} {}", r.name, r.ttl, type_name(r.rtype), r.data));
+    }
+    if resp.answers.is_empty() {
+        for r in resp.authority.iter().filter(|r| r.rtype == RecordType::Soa.code()) {
+            lines.push(format!("{:<32} {:>7} {:<6} {}  (authority)", r.name, r.ttl, type_name(r.rtype), r.data));
+        }
+    }
+    lines
+}
+
+/// Outcome of comparing an address's PTR name with the A records of that name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PtrStatus {
+    /// The PTR name resolves back to the address
+    Match,
+    /// The PTR name resolves, but not to the address
+    Mismatch,
+    /// No PTR record
+    NoPtr,
+    /// The PTR name has no A record
+    NoForward,
+    Error(String),
+}
+
+/// Result of a PTR-vs-forward check for one address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PtrCheck {
+    pub ip: Ipv4Addr,
+    pub ptr: Option<String>,
+    pub forward: Vec<Ipv4Addr>,
+    pub status: PtrStatus,
+}
+
+impl PtrCheck {
+    /// One output line, e.g. "192.168.1.10    OK        nas.lan. -> 192.168.1.10".
+    pub fn format_line(&self) -> String {
+        let status = match &self.status {
+            PtrStatus::Match => "OK".to_string(),
+            PtrStatus::Mismatch => "MISMATCH".to_string(),
+            PtrStatus::NoPtr => "NO-PTR".to_string(),
+            PtrStatus::NoForward => "NO-A".to_string(),
+            PtrStatus::Error(e) => format!("ERROR {}", e),
+        };
+        let mut line = format!("{:<15} {:<9}", self.ip, status);
+        if let Some(name) = &self.ptr {
+            let fwd: Vec<String> = self.forward.iter().map(|ip| ip.to_string()).collect();
+            line.push_str(&format!(" {} -> {}", name, if fwd.is_empty() { "-".to_string() } else { fwd.join(", ") }));
+        }
+        line
+    }
+}
+
+/// Look up the PTR of `ip` on `server` and check that the name resolves back to `ip`.
+pub fn check_ptr(server: SocketAddr, ip: Ipv4Addr, transport: Transport, timeout: Duration) -> PtrCheck {
+    let mut check = PtrCheck { ip, ptr: None, forward: Vec::new(), status: PtrStatus::NoPtr };
+    let ptr = match query(server, &ptr_name(IpAddr::V4(ip)), RecordType::Ptr, transport, timeout) {
+        Ok(resp) => resp.ptr_answers().into_iter().next(),
+        Err(e) => { check.status = PtrStatus::Error(e); return check; }
+    };
+    let Some(name) = ptr else { return check };
+    check.status = match query(server, &name, RecordType::A, transport, timeout) {
+        Ok(resp) => {
+            check.forward = resp.ipv4_answers();
+            if check.forward.contains(&ip) { PtrStatus::Match }
+            else if check.forward.is_empty() { PtrStatus::NoForward }
+            else { PtrStatus::Mismatch }
+        }
+        Err(e) => PtrStatus::Error(e),
+    };
+    check.ptr = Some(name);
+    check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Stub zone served by the test servers.
+    fn stub_answer(qname: &str, qtype: u16) -> Vec<(u16, u32, Vec<u8>)> {
+        let name = |n: &str| { let mut v = Vec::new(); encode_name(&mut v, n).unwrap(); v };
+        match (qname, qtype) {
+            ("host.test", 1) => vec![(1, 300, vec![192, 0, 2, 10])],
+            ("host.test", 28) => vec![(28, 300, "2001:db8::10".parse::<Ipv6Addr>().unwrap().octets().to_vec())],
+            ("alias.test", 5) => vec![(5, 60, name("host.test"))],
+            ("test", 15) => vec![(15, 3600, [vec![0, 10], name("mail.test")].concat())],
+            ("test", 16) => vec![(16, 120, b"\x0bv=spf1 -all\x03two".to_vec())],
+            ("_sip._tcp.test", 33) => vec![(33, 600, [vec![0, 1, 0, 5, 0x13, 0xc4], name("sip.test")].concat())],
+            ("test", 6) => vec![(6, 900, [name("ns.test"), name("admin.test"), [1u32, 7200, 600, 86400, 300].iter().flat_map(|v| v.to_be_bytes()).collect()].concat())],
+            ("10.2.0.192.in-addr.arpa", 12) => vec![(12, 300, name("host.test"))],
+            ("11.2.0.192.in-addr.arpa", 12) => vec![(12, 300, name("host.test"))],
+            ("12.2.0.192.in-addr.arpa", 12) => vec![(12, 300, name("orphan.test"))],
+            ("big.test", 16) => (0..40).map(|_| (16, 1, [vec![200], vec![b'x'; 200]].concat())).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Build the stub response for a query. Over UDP, answers larger than 512 bytes
+    /// are truncated and flagged TC.
+    fn stub_response(query: &[u8], udp: bool) -> Vec<u8> {
+        let (qname, p) = decode_name(query, 12).unwrap();
+        let qname = qname.trim_end_matches('.').to_string();
+        let qtype = read_u16(query, p).unwrap();
+        let answers = stub_answer(&qname, qtype);
+        let mut msg = query[..2].to_vec();
+        let rcode = if answers.is_empty() && qname != "test" { 3 } else { 0 };
+        msg.extend_from_slice(&[0x85, 0x80 | rcode]);
+        msg.extend_from_slice(&[0, 1]);
+        msg.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+        msg.extend_from_slice(&[0, 0, 0, 0]);
+        msg.extend_from_slice(&query[12..p + 4]);
+        for (rtype, ttl, rdata) in answers {
+            msg.extend_from_slice(&[0xc0, 0x0c]); // pointer to the question name
+            msg.extend_from_slice(&rtype.to_be_bytes());
+            msg.extend_from_slice(&[0, 1]);
+            msg.extend_from_slice(&ttl.to_be_bytes());
+            msg.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            msg.extend_from_slice(&rdata);
+        }
+        if udp && msg.len() > 512 {
+            msg.truncate(512);
+            msg[2] |= 0x02;
+        }
+        msg
+    }
+
+    /// Local UDP+TCP stub server on the same port. Returns its address.
+    fn start_stub_server() -> SocketAddr {
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let udp = UdpSocket::bind(addr).unwrap();
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            while let Ok((n, peer)) = udp.recv_from(&mut buf) {
+                let _ = udp.send_to(&stub_response(&buf[..n], true), peer);
+            }
+        });
+        thread::spawn(move || {
+            for mut s in tcp.incoming().flatten() {
+                let mut len = [0u8; 2];
+                if s.read_exact(&mut len).is_err() { continue; }
+                let mut q = vec![0u8; u16::from_be_bytes(len) as usize];
+                if s.read_exact(&mut q).is_err() { continue; }
+                let resp = stub_response(&q, false);
+                let _ = s.write_all(&[(resp.len() as u16).to_be_bytes().to_vec(), resp].concat());
+            }
+        });
+        addr
+    }
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    #[test]
+    fn test_query_record_types() {
+        let server = start_stub_server();
+        let q = |name: &str, t: RecordType| query(server, name, t, Transport::Udp, TIMEOUT).unwrap();
+
+        let a = q("host.test", RecordType::A);
+        assert_eq!(a.rcode, 0);
+        assert!(a.authoritative);
+        assert_eq!(a.answers[0].ttl, 300);
+        assert_eq!(a.answers[0].name, "host.test.");
+        assert_eq!(a.ipv4_answers(), vec![Ipv4Addr::new(192, 0, 2, 10)]);
+        assert_eq!(q("host.test", RecordType::Aaaa).answers[0].data, RData::Aaaa("2001:db8::10".parse().unwrap()));
+        assert_eq!(q("alias.test", RecordType::Cname).answers[0].data, RData::Name("host.test.".to_string()));
+        assert_eq!(q("test", RecordType::Mx).answers[0].data.to_string(), "10 mail.test.");
+        assert_eq!(q("test", RecordType::Txt).answers[0].data, RData::Txt(vec!["v=spf1 -all".to_string(), "two".to_string()]));
+        assert_eq!(q("_sip._tcp.test", RecordType::Srv).answers[0].data.to_string(), "1 5 5060 sip.test.");
+        assert_eq!(q("test", RecordType::Soa).answers[0].data.to_string(), "ns.test. admin.test. 1 7200 600 86400 300");
+        assert_eq!(q(&ptr_name("192.0.2.10".parse().unwrap()), RecordType::Ptr).ptr_answers(), vec!["host.test.".to_string()]);
+        assert_eq!(q("missing.test", RecordType::A).rcode, 3);
+    }
+
+    #[test]
+    fn test_tcp_and_truncation_fallback() {
+        let server = start_stub_server();
+        let tcp = query(server, "host.test", RecordType::A, Transport::Tcp, TIMEOUT).unwrap();
+        assert_eq!(tcp.transport, Transport::Tcp);
+        assert_eq!(tcp.ipv4_answers().len(), 1);
+        // 40 large TXT records do not fit in 512 bytes: UDP is retried over TCP
+        let big = query(server, "big.test", RecordType::Txt, Transport::Udp, TIMEOUT).unwrap();
+        assert_eq!(big.transport, Transport::Tcp);
+        assert!(!big.truncated);
+        assert_eq!(big.answers.len(), 40);
+    }
+
+    #[test]
+    fn test_ptr_consistency() {
+        let server = start_stub_server();
+        let check = |ip: &str| check_ptr(server, ip.parse().unwrap(), Transport::Udp, TIMEOUT);
+        assert_eq!(check("192.0.2.10").status, PtrStatus::Match);
+        let mismatch = check("192.0.2.11");
+        assert_eq!(mismatch.status, PtrStatus::Mismatch);
+        assert_eq!(mismatch.format_line(), format!("{:<15} {:<9} host.test. -> 192.0.2.10", "192.0.2.11", "MISMATCH"));
+        assert_eq!(check("192.0.2.12").status, PtrStatus::NoForward);
+        assert_eq!(check("192.0.2.13").status, PtrStatus::NoPtr);
+    }
+
+    #[test]
+    fn test_query_timeout() {
+        // A bound socket that never answers
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let err = query(silent.local_addr().unwrap(), "host.test", RecordType::A, Transport::Udp, Duration::from_millis(100)).unwrap_err();
+        assert!(err.contains("Timed out"), "{}", err);
+    }
+
+    #[test]
+    fn test_names_and_helpers() {
+        assert_eq!(ptr_name("192.168.1.10".parse().unwrap()), "10.1.168.192.in-addr.arpa");
+        assert!(ptr_name("2001:db8::1".parse().unwrap()).starts_with("1.0.0.0.0.0.0.0."));
+        assert!(ptr_name("2001:db8::1".parse().unwrap()).ends_with("8.b.d.0.1.0.0.2.ip6.arpa"));
+        assert_eq!(parse_server("8.8.8.8").unwrap(), "8.8.8.8:53".parse().unwrap());
+        assert_eq!(parse_server("127.0.0.1:5353").unwrap().port(), 5353);
+        assert_eq!(parse_server("[::1]").unwrap(), "[::1]:53".parse().unwrap());
+        assert!(parse_server("dns.example").is_err());
+        assert_eq!(RecordType::from_name("aaaa"), Some(RecordType::Aaaa));
+        assert_eq!(type_name(99), "TYPE99");
+        assert!(build_query(1, &"a".repeat(64), RecordType::A).is_err());
+        // compression pointer loops are rejected
+        let looped = [0u8, 0, 0x81, 0x80, 0, 1, 0, 0, 0, 0, 0, 0, 0xc0, 12, 0, 1, 0, 1];
+        assert!(parse_response(&looped).is_err());
+    }
+}
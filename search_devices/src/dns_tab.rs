@@ -0,0 +1,215 @@
+use fltk::{
+    prelude::*,
+    frame::Frame,
+    input::{Input, IntInput},
+    button::{Button, CheckButton},
+    dialog,
+    menu::Choice,
+    text::{TextDisplay, TextBuffer},
+    app,
+};
+use ipnetwork::Ipv4Network;
+use std::{
+    collections::VecDeque,
+    net::{IpAddr, Ipv4Addr},
+    sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}},
+    thread,
+    time::Duration,
+};
+use crate::cidr_tab::LARGE_SWEEP;
+use crate::dns_client::{self, RecordType, Transport};
+use crate::rate::RateLimiter;
+use crate::utils::{alive_rows, ScanRun};
+
+/// Default server shown in the Server field.
+const DEFAULT_SERVER: &str = "8.8.8.8";
+
+/// Build the DNS tab. `cidr_buff` and `list_buff` are the CIDR and IP List results whose
+/// alive hosts the PTR check covers. Returns the running flag, the output buffer and the display.
+pub fn build_dns_tab(
+    sender: app::Sender<(String, Ipv4Addr, bool, String)>,
+    limiter: Arc<RateLimiter>,
+    cidr_buff: TextBuffer,
+    list_buff: TextBuffer,
+) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>) {
+    Frame::new(10, 30, 300, 25, "Name or IP (CIDR for PTR Check)");
+    let mut input = Input::new(10, 70, 200, 30, "");
+    input.set_value("example.com");
+
+    let mut clear_btn = Button::new(240, 70, 80, 30, "Clear");
+    let mut query_btn = Button::new(320, 70, 80, 30, "Query");
+    let mut stop_btn = Button::new(410, 70, 80, 30, "Stop");
+
+    // Server / record type / transport
+    let _server_label = Frame::new(10, 110, 50, 25, "Server");
+    let mut server_inp = Input::new(60, 110, 150, 25, "");
+    server_inp.set_value(DEFAULT_SERVER);
+    let mut type_choice = Choice::new(260, 110, 70, 25, "Type");
+    for t in RecordType::ALL {
+        type_choice.add_choice(t.name());
+    }
+    type_choice.set_value(0);
+    let mut proto_choice = Choice::new(380, 110, 60, 25, "Proto");
+    proto_choice.add_choice("UDP|TCP");
+    proto_choice.set_value(0);
+
+    let _to_label = Frame::new(10, 140, 90, 25, "Timeout (ms)");
+    let mut to_inp = IntInput::new(100, 140, 70, 25, "");
+    to_inp.set_value("2000");
+    let mut ptr_btn = Button::new(240, 140, 160, 25, "PTR Check (PTR vs A)");
+    let mut alive_cb = CheckButton::new(405, 140, 85, 25, "Alive only");
+    alive_cb.set_value(true);
+
+    let mut display = TextDisplay::new(10, 170, 480, 200, "");
+    let buff = TextBuffer::default();
+    display.set_buffer(buff.clone());
+    let display_ref = Arc::new(Mutex::new(display));
+
+    let run = ScanRun::default();
+
+    // Clear
+    {
+        let mut b = buff.clone();
+        clear_btn.set_callback(move |_| b.set_text(""));
+    }
+
+    // Single query
+    {
+        let inp = input.clone();
+        let server_inp = server_inp.clone();
+        let type_choice = type_choice.clone();
+        let proto_choice = proto_choice.clone();
+        let to_inp = to_inp.clone();
+        let run = run.clone();
+        let mut b = buff.clone();
+        query_btn.set_callback(move |_| {
+            if run.is_busy() {
+                return; // already running
+            }
+            let server = match dns_client::parse_server(&server_inp.value()) {
+                Ok(s) => s,
+                Err(e) => { b.append(&format!("[Error] {}\n", e)); return; }
+            };
+            let rtype = type_choice.choice().and_then(|c| RecordType::from_name(&c)).unwrap_or(RecordType::A);
+            let mut name = inp.value().trim().to_string();
+            if name.is_empty() {
+                b.append("[Error] Name is empty.\n");
+                return;
+            }
+            // PTR queries accept a plain address
+            if rtype == RecordType::Ptr {
+                if let Ok(ip) = name.parse::<IpAddr>() { name = dns_client::ptr_name(ip); }
+            }
+            let transport = if proto_choice.value() == 1 { Transport::Tcp } else { Transport::Udp };
+            let timeout = Duration::from_millis(to_inp.value().parse().ok().filter(|v| *v >= 1).unwrap_or(2000));
+
+            run.start();
+            let run = run.clone();
+            thread::spawn(move || {
+                let lines = match dns_client::query(server, &name, rtype, transport, timeout) {
+                    Ok(resp) => dns_client::format_response(&name, rtype, server, &resp),
+                    Err(e) => vec![format!("[Error] {} {} @{}: {}", name, rtype.name(), server, e)],
+                };
+                for line in lines {
+                    sender.send(("DNS".to_string(), Ipv4Addr::UNSPECIFIED, false, line));
+                }
+                sender.send(("DNS".to_string(), Ipv4Addr::UNSPECIFIED, false, String::new()));
+                run.finish();
+            });
+        });
+    }
+
+    // PTR vs forward A consistency over a subnet
+    {
+        let inp = input.clone();
+        let server_inp = server_inp.clone();
+        let proto_choice = proto_choice.clone();
+        let to_inp = to_inp.clone();
+        let run = run.clone();
+        let mut b = buff.clone();
+        ptr_btn.set_callback(move |_| {
+            if run.is_busy() {
+                return;
+            }
+            let server = match dns_client::parse_server(&server_inp.value()) {
+                Ok(s) => s,
+                Err(e) => { b.append(&format!("[Error] {}\n", e)); return; }
+            };
+            let net = match inp.value().trim().parse::<Ipv4Network>() {
+                Ok(n) => n,
+                Err(_) => { b.append("[Error] Enter an IPv4 address or CIDR (e.g. 192.168.1.0/24).\n"); return; }
+            };
+            // Hosts found alive by a sweep, or every address of the network after confirmation
+            let hosts: Vec<Ipv4Addr> = if alive_cb.value() {
+                let mut hosts = alive_rows(&cidr_buff.text());
+                for ip in alive_rows(&list_buff.text()) {
+                    if !hosts.contains(&ip) {
+                        hosts.push(ip);
+                    }
+                }
+                hosts.retain(|ip| net.contains(*ip));
+                if hosts.is_empty() {
+                    b.append(&format!("[Error] No alive hosts of {} in the CIDR / IP List results (run a sweep first, or untick Alive only)\n", net));
+                    return;
+                }
+                hosts
+            } else {
+                let total = crate::targets::Target::Cidr(net).address_count();
+                if total > LARGE_SWEEP {
+                    let msg = format!("{} addresses will be looked up.\nStart the PTR check?", total);
+                    if dialog::choice2_default(&msg, "Cancel", "Check", "") != Some(1) {
+                        return;
+                    }
+                }
+                crate::targets::cidr_hosts(&net)
+            };
+            let transport = if proto_choice.value() == 1 { Transport::Tcp } else { Transport::Udp };
+            let timeout = Duration::from_millis(to_inp.value().parse().ok().filter(|v| *v >= 1).unwrap_or(2000));
+            let total = hosts.len();
+            b.append(&format!("PTR check of {} host(s) in {} @{}\n{:<15} {:<9} {}\n", total, net, server, "IP Address", "Result", "PTR -> A"));
+
+            let flag_thread = run.start();
+            let run = run.clone();
+            let limiter = limiter.clone();
+            thread::spawn(move || {
+                // Every query goes to the server, so its address is the one paced
+                let paced = move |ip: Ipv4Addr| match server.ip() { IpAddr::V4(v4) => v4, IpAddr::V6(_) => ip };
+                let queue = Arc::new(Mutex::new(VecDeque::from(hosts)));
+                let counts = Arc::new(Mutex::new((0usize, 0usize)));
+                let workers: Vec<_> = (0..limiter.max_batch(4).min(total as u32)).map(|_| {
+                    let (queue, counts, flag, limiter) = (queue.clone(), counts.clone(), flag_thread.clone(), limiter.clone());
+                    thread::spawn(move || loop {
+                        let Some(ip) = queue.lock().ok().and_then(|mut q| q.pop_front()) else { return };
+                        let Some(_permit) = limiter.in_flight(1, &flag) else { return };
+                        // PTR query, then A for the name
+                        if !limiter.pace(paced(ip), 2, &flag) { return }
+                        let check = dns_client::check_ptr(server, ip, transport, timeout);
+                        if let Ok(mut c) = counts.lock() {
+                            match check.status {
+                                dns_client::PtrStatus::Match => c.0 += 1,
+                                dns_client::PtrStatus::NoPtr => {}
+                                _ => c.1 += 1,
+                            }
+                        }
+                        sender.send(("DNS".to_string(), ip, check.status == dns_client::PtrStatus::Match, check.format_line()));
+                    })
+                }).collect();
+                for w in workers {
+                    let _ = w.join();
+                }
+                let (ok, bad) = counts.lock().map(|c| *c).unwrap_or_default();
+                let state = if flag_thread.load(Ordering::SeqCst) { "done" } else { "stopped" };
+                sender.send(("DNS".to_string(), Ipv4Addr::UNSPECIFIED, false, format!("[Info] PTR check {}: {} consistent, {} inconsistent", state, ok, bad)));
+                run.finish();
+            });
+        });
+    }
+
+    // Stop
+    {
+        let run = run.clone();
+        stop_btn.set_callback(move |_| run.stop());
+    }
+
+    (run.busy_flag(), buff, display_ref)
+}
@@ -0,0 +1,191 @@
+// Exclusion lists: addresses that sweeps and port scans must never touch
+
+use fltk::{prelude::*, menu::Choice};
+use std::{collections::BTreeMap, net::Ipv4Addr};
+use crate::config::Config;
+use crate::targets::{parse_target, Target};
+
+/// One excluded IP, CIDR or range, with the reason given after `#`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExcludeRule {
+    /// Entry as written, e.g. "10.0.5.0/24"
+    pub entry: String,
+    pub target: Target,
+    pub reason: String,
+    /// "global" or "profile <name>"
+    pub source: String,
+}
+
+impl ExcludeRule {
+    fn contains(&self, ip: Ipv4Addr) -> bool {
+        match &self.target {
+            Target::Ip(a) => *a == ip,
+            Target::Cidr(net) => net.contains(ip),
+            Target::Range(a, b) => *a <= ip && ip <= *b,
+            Target::Host(_) => false,
+        }
+    }
+
+    /// Label used in skip reports, e.g. "10.0.5.0/24 (PLC cell) [global]".
+    pub fn label(&self) -> String {
+        if self.reason.is_empty() {
+            format!("{} [{}]", self.entry, self.source)
+        } else {
+            format!("{} ({}) [{}]", self.entry, self.reason, self.source)
+        }
+    }
+}
+
+/// Combined global and profile exclusions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExcludeList {
+    rules: Vec<ExcludeRule>,
+}
+
+impl ExcludeList {
+    /// Parse entries like "10.0.0.1 # core router", "10.0.5.0/24" or "10.0.9.5-20 # PLCs".
+    /// Host names are rejected: exclusions must not depend on DNS.
+    pub fn parse(entries: &[String], source: &str) -> Result<ExcludeList, String> {
+        let mut rules = Vec::new();
+        for raw in entries {
+            let (entry, reason) = match raw.split_once('#') {
+                Some((e, r)) => (e.trim(), r.trim()),
+                None => (raw.trim(), ""),
+            };
+            if entry.is_empty() { continue; }
+            let target = match parse_target(entry) {
+                Ok(Target::Host(_)) | Err(_) => return Err(format!("Invalid exclude entry ({}): {}", source, raw.trim())),
+                Ok(t) => t,
+            };
+            rules.push(ExcludeRule { entry: entry.to_string(), target, reason: reason.to_string(), source: source.to_string() });
+        }
+        Ok(ExcludeList { rules })
+    }
+
+    /// Global exclusions plus those of `profile` from the configuration.
+    /// Fails while the configuration file is unusable, so that nothing is scanned
+    /// without its exclusions.
+    pub fn for_profile(config: &Config, profile: Option<&str>) -> Result<ExcludeList, String> {
+        if let Some(e) = &config.load_error {
+            return Err(format!("Scanning disabled until the configuration file is fixed: {}", e));
+        }
+        let mut list = ExcludeList::parse(&config.exclude, "global")?;
+        if let Some(name) = profile {
+            let p = config.profiles.get(name).ok_or_else(|| format!("Unknown profile: {}", name))?;
+            list.rules.extend(ExcludeList::parse(&p.exclude, &format!("profile {}", name))?.rules);
+        }
+        Ok(list)
+    }
+
+    /// First rule covering `ip`.
+    pub fn matches(&self, ip: Ipv4Addr) -> Option<&ExcludeRule> {
+        self.rules.iter().find(|r| r.contains(ip))
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+}
+
+/// Counts of skipped addresses per exclusion rule.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SkipReport {
+    counts: BTreeMap<String, usize>,
+}
+
+impl SkipReport {
+    pub fn record(&mut self, rule: &ExcludeRule) {
+        *self.counts.entry(rule.label()).or_insert(0) += 1;
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// Report lines: a total followed by one line per rule.
+    pub fn format_lines(&self) -> Vec<String> {
+        if self.counts.is_empty() {
+            return Vec::new();
+        }
+        let mut lines = vec![format!("[Info] Skipped {} excluded address(es):", self.total())];
+        for (label, n) in &self.counts {
+            lines.push(format!("  {:>5} x {}", n, label));
+        }
+        lines
+    }
+}
+
+/// Profile selector: "(none)" followed by the profiles of the configuration.
+pub fn profile_choice(x: i32, y: i32, w: i32, h: i32, config: &Config) -> Choice {
+    let mut choice = Choice::new(x, y, w, h, "Profile");
+    choice.add_choice("(none)");
+    for name in config.profiles.keys() {
+        choice.add_choice(name);
+    }
+    choice.set_value(0);
+    choice
+}
+
+/// Profile picked in a selector built by `profile_choice`.
+pub fn selected_profile(choice: &Choice) -> Option<String> {
+    if choice.value() <= 0 { None } else { choice.choice() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> Ipv4Addr { s.parse().unwrap() }
+
+    #[test]
+    fn test_exclude_matching() {
+        let entries = vec![
+            "10.0.0.1 # core router".to_string(),
+            "10.0.5.0/24 # PLC cell".to_string(),
+            "10.0.9.5-20".to_string(),
+            "".to_string(),
+        ];
+        let list = ExcludeList::parse(&entries, "global").unwrap();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.matches(ip("10.0.0.1")).unwrap().reason, "core router");
+        assert_eq!(list.matches(ip("10.0.5.77")).unwrap().label(), "10.0.5.0/24 (PLC cell) [global]");
+        assert_eq!(list.matches(ip("10.0.9.20")).unwrap().label(), "10.0.9.5-20 [global]");
+        assert!(list.matches(ip("10.0.9.21")).is_none());
+        assert!(list.matches(ip("10.0.0.2")).is_none());
+        assert!(ExcludeList::parse(&["monitoring.lan".to_string()], "global").is_err());
+        assert!(ExcludeList::parse(&["10.0.0.0/40".to_string()], "global").is_err());
+    }
+
+    #[test]
+    fn test_profiles_from_config() {
+        let cfg = Config::from_toml(
+            "exclude = [\"10.0.0.1 # core\"]\n[profiles.plant]\nexclude = [\"192.168.50.0/28 # PLC\"]\n[profiles.office]\n",
+        ).unwrap();
+        let global = ExcludeList::for_profile(&cfg, None).unwrap();
+        assert_eq!(global.len(), 1);
+        let plant = ExcludeList::for_profile(&cfg, Some("plant")).unwrap();
+        assert_eq!(plant.len(), 2);
+        assert_eq!(plant.matches(ip("192.168.50.3")).unwrap().source, "profile plant");
+        assert_eq!(ExcludeList::for_profile(&cfg, Some("office")).unwrap().len(), 1);
+        assert!(ExcludeList::for_profile(&cfg, Some("nope")).is_err());
+
+        let broken = Config::unusable(Config::from_toml("exclude = [\"10.0.0.1\"").unwrap_err());
+        assert!(ExcludeList::for_profile(&broken, None).unwrap_err().starts_with("Scanning disabled"));
+    }
+
+    #[test]
+    fn test_skip_report() {
+        let list = ExcludeList::parse(&["10.0.5.0/24 # PLC".to_string(), "10.0.0.1".to_string()], "global").unwrap();
+        let mut report = SkipReport::default();
+        assert!(report.format_lines().is_empty());
+        for a in ["10.0.5.1", "10.0.5.2", "10.0.0.1"] {
+            report.record(list.matches(ip(a)).unwrap());
+        }
+        assert_eq!(report.total(), 3);
+        assert_eq!(report.format_lines(), vec![
+            "[Info] Skipped 3 excluded address(es):".to_string(),
+            "      1 x 10.0.0.1 [global]".to_string(),
+            "      2 x 10.0.5.0/24 (PLC) [global]".to_string(),
+        ]);
+    }
+}
@@ -0,0 +1,391 @@
+// Network health check: routing table and default gateways, DNS servers of the system,
+// pass/fail results of each step and the optional trace to a reference target
+
+use serde::Deserialize;
+use std::{
+    io::{BufRead, BufReader},
+    net::{IpAddr, Ipv4Addr},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+};
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+use crate::hops::{parse_hop_line, HopRecord, TraceFormat};
+use crate::native_trace::{self, ProbeMode, TraceOptions};
+use crate::rate::RateLimiter;
+use crate::utils::{tracert_args_windows, traceroute_args_unix};
+
+/// Settings read from the `[health]` section of the configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// Name resolved through each DNS server and the system resolver.
+    pub test_name: String,
+    /// Reference target of the optional trace.
+    pub trace_target: String,
+    /// Run the trace by default.
+    pub trace: bool,
+    /// DNS servers to check instead of the ones the system uses.
+    pub dns_servers: Vec<String>,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig { test_name: "example.com".to_string(), trace_target: "8.8.8.8".to_string(), trace: false, dns_servers: Vec::new() }
+    }
+}
+
+/// One IPv4 route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub destination: Ipv4Addr,
+    pub prefix: u8,
+    /// None for on-link routes
+    pub gateway: Option<Ipv4Addr>,
+    /// Interface name (Windows: the interface address)
+    pub interface: String,
+    pub metric: u32,
+}
+
+impl Route {
+    pub fn is_default(&self) -> bool {
+        self.prefix == 0
+    }
+
+    /// Table line, e.g. "0.0.0.0/0          via 192.168.1.1     eth0 (metric 100)".
+    pub fn format_line(&self) -> String {
+        let via = match self.gateway {
+            Some(gw) => format!("via {}", gw),
+            None => "on-link".to_string(),
+        };
+        format!("{:<18} {:<19} {} (metric {})", format!("{}/{}", self.destination, self.prefix), via, self.interface, self.metric)
+    }
+}
+
+fn prefix_len(mask: Ipv4Addr) -> u8 {
+    u32::from(mask).count_ones() as u8
+}
+
+/// Linux `/proc/net/route`: addresses are little-endian hex.
+pub fn parse_proc_net_route(text: &str) -> Vec<Route> {
+    let hex_ip = |s: &str| u32::from_str_radix(s, 16).ok().map(|v| Ipv4Addr::from(v.to_le_bytes()));
+    let mut routes = Vec::new();
+    for line in text.lines().skip(1) {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        if cols.len() < 8 {
+            continue;
+        }
+        let (Some(dest), Some(gw), Some(mask)) = (hex_ip(cols[1]), hex_ip(cols[2]), hex_ip(cols[7])) else { continue };
+        let flags = u16::from_str_radix(cols[3], 16).unwrap_or(0);
+        // RTF_UP
+        if flags & 0x1 == 0 {
+            continue;
+        }
+        routes.push(Route {
+            destination: dest,
+            prefix: prefix_len(mask),
+            gateway: Some(gw).filter(|g| !g.is_unspecified()),
+            interface: cols[0].to_string(),
+            metric: cols[6].parse().unwrap_or(0),
+        });
+    }
+    routes
+}
+
+/// Windows `route print -4`: the "Active Routes" rows (destination, netmask, gateway, interface, metric).
+pub fn parse_route_print(text: &str) -> Vec<Route> {
+    let mut routes = Vec::new();
+    for line in text.lines() {
+        // Persistent routes are repeated in the active list when they apply
+        if line.trim_start().starts_with("Persistent") {
+            break;
+        }
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        if cols.len() != 5 {
+            continue;
+        }
+        let (Ok(dest), Ok(mask), Ok(metric)) = (cols[0].parse::<Ipv4Addr>(), cols[1].parse::<Ipv4Addr>(), cols[4].parse::<u32>()) else { continue };
+        // "On-link" is localized on some systems: anything that is not an address
+        routes.push(Route {
+            destination: dest,
+            prefix: prefix_len(mask),
+            gateway: cols[2].parse().ok(),
+            interface: cols[3].to_string(),
+            metric,
+        });
+    }
+    routes
+}
+
+/// BSD / macOS `netstat -rn -f inet`: "default", "10/8", "192.168.1" or "192.168.1.1/32" destinations.
+pub fn parse_netstat_rn(text: &str) -> Vec<Route> {
+    let mut routes = Vec::new();
+    for line in text.lines() {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        if cols.len() < 4 {
+            continue;
+        }
+        let (destination, prefix) = if cols[0] == "default" {
+            (Ipv4Addr::UNSPECIFIED, 0)
+        } else {
+            let (addr, len) = match cols[0].split_once('/') {
+                Some((a, l)) => (a, l.parse::<u8>().ok()),
+                None => (cols[0], None),
+            };
+            let parts: Vec<u8> = match addr.split('.').map(|p| p.parse::<u8>()).collect::<Result<Vec<_>, _>>() {
+                Ok(p) if (1..=4).contains(&p.len()) => p,
+                _ => continue,
+            };
+            let mut octets = [0u8; 4];
+            octets[..parts.len()].copy_from_slice(&parts);
+            (Ipv4Addr::from(octets), len.unwrap_or(parts.len() as u8 * 8).min(32))
+        };
+        routes.push(Route {
+            destination,
+            prefix,
+            gateway: if cols[2].contains('G') { cols[1].parse().ok() } else { None },
+            interface: cols[3].to_string(),
+            metric: 0,
+        });
+    }
+    routes
+}
+
+fn command_output(program: &str, args: &[&str]) -> Result<String, String> {
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    #[cfg(windows)]
+    cmd.creation_flags(0x08000000);
+    let out = cmd.output().map_err(|e| format!("Cannot run {}: {}", program, e))?;
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// IPv4 routing table of the system: `route print` on Windows, `/proc/net/route` on Linux,
+/// `netstat -rn` elsewhere.
+pub fn routing_table() -> Result<Vec<Route>, String> {
+    if cfg!(windows) {
+        command_output("route", &["print", "-4"]).map(|t| parse_route_print(&t))
+    } else if let Ok(text) = std::fs::read_to_string("/proc/net/route") {
+        Ok(parse_proc_net_route(&text))
+    } else {
+        command_output("netstat", &["-rn", "-f", "inet"]).map(|t| parse_netstat_rn(&t))
+    }
+}
+
+/// Default routes, lowest metric first.
+pub fn default_routes(routes: &[Route]) -> Vec<Route> {
+    let mut out: Vec<Route> = routes.iter().filter(|r| r.is_default() && r.gateway.is_some()).cloned().collect();
+    out.sort_by_key(|r| r.metric);
+    out
+}
+
+/// `nameserver` lines of resolv.conf.
+pub fn parse_resolv_conf(text: &str) -> Vec<IpAddr> {
+    text.lines()
+        .filter_map(|l| l.trim().strip_prefix("nameserver"))
+        .filter_map(|v| v.split_whitespace().next()?.split('%').next()?.parse().ok())
+        .collect()
+}
+
+/// DNS server addresses of `ipconfig /all`: values of the labels containing "DNS" (also in
+/// localized output, e.g. "DNS サーバー") and the address-only lines that continue them.
+pub fn parse_ipconfig_dns(text: &str) -> Vec<IpAddr> {
+    let parse = |s: &str| s.trim().split('%').next().and_then(|a| a.parse::<IpAddr>().ok());
+    let mut out: Vec<IpAddr> = Vec::new();
+    let mut in_dns = false;
+    for line in text.lines() {
+        if let Some((label, value)) = line.split_once(" : ") {
+            in_dns = label.contains("DNS");
+            if let Some(a) = parse(value).filter(|_| in_dns) {
+                if !out.contains(&a) {
+                    out.push(a);
+                }
+            }
+        } else if in_dns {
+            match parse(line) {
+                Some(a) if !out.contains(&a) => out.push(a),
+                Some(_) => {}
+                None => in_dns = false,
+            }
+        }
+    }
+    out
+}
+
+/// DNS servers the system resolver uses.
+pub fn system_dns_servers() -> Vec<IpAddr> {
+    if cfg!(windows) {
+        command_output("ipconfig", &["/all"]).map(|t| parse_ipconfig_dns(&t)).unwrap_or_default()
+    } else {
+        std::fs::read_to_string("/etc/resolv.conf").map(|t| parse_resolv_conf(&t)).unwrap_or_default()
+    }
+}
+
+/// Outcome of one health check step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: String,
+    pub pass: bool,
+    pub detail: String,
+}
+
+impl Check {
+    pub fn new(name: impl Into<String>, pass: bool, detail: impl Into<String>) -> Check {
+        Check { name: name.into(), pass, detail: detail.into() }
+    }
+
+    /// e.g. "[PASS] Ping gateway        192.168.1.1 (eth0)".
+    pub fn format_line(&self) -> String {
+        format!("[{}] {:<22} {}", if self.pass { "PASS" } else { "FAIL" }, self.name, self.detail).trim_end().to_string()
+    }
+}
+
+/// Summary line of a run.
+pub fn format_summary(checks: &[Check]) -> String {
+    let failed = checks.iter().filter(|c| !c.pass).count();
+    if failed == 0 {
+        format!("[Info] All {} check(s) passed", checks.len())
+    } else {
+        format!("[Info] {} of {} check(s) failed", failed, checks.len())
+    }
+}
+
+/// Trace to `target` with the system trace command (numeric output), or the built-in ICMP
+/// engine when the command is missing. Each hop is passed to `on_hop`; returns all hops.
+pub fn trace_hops(target: Ipv4Addr, max_hops: u32, running: &AtomicBool, limiter: &RateLimiter, mut on_hop: impl FnMut(&HopRecord)) -> Result<Vec<HopRecord>, String> {
+    let mut cmd = if cfg!(windows) {
+        let mut c = Command::new("tracert");
+        c.args(tracert_args_windows(max_hops, 1000, false, &target.to_string()));
+        c
+    } else {
+        let mut c = Command::new("traceroute");
+        c.args(traceroute_args_unix(max_hops, 1000, false, &target.to_string()));
+        c
+    };
+    #[cfg(windows)]
+    cmd.creation_flags(0x08000000);
+    let mut hops = Vec::new();
+    match cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn() {
+        Ok(mut child) => {
+            if let Some(out) = child.stdout.take() {
+                for line in BufReader::new(out).lines() {
+                    if !running.load(Ordering::SeqCst) {
+                        let _ = child.kill();
+                        break;
+                    }
+                    if let Some(rec) = line.ok().and_then(|l| parse_hop_line(&l, TraceFormat::native())) {
+                        on_hop(&rec);
+                        hops.push(rec);
+                    }
+                }
+            }
+            let _ = child.wait();
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let opts = TraceOptions { mode: ProbeMode::Icmp, max_hops: max_hops.min(255) as u8, queries: 1, resolve_dns: false, ..TraceOptions::default() };
+            native_trace::trace(target, &opts, running, limiter, |rec| {
+                on_hop(&rec);
+                hops.push(rec);
+            })?;
+        }
+        Err(e) => return Err(format!("Failed to start traceroute: {}", e)),
+    }
+    Ok(hops)
+}
+
+/// Whether the last hops of a trace include `target`.
+pub fn trace_reached(hops: &[HopRecord], target: Ipv4Addr) -> bool {
+    hops.iter().any(|h| h.responders.iter().any(|r| r.addr == IpAddr::V4(target)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> Ipv4Addr { s.parse().unwrap() }
+
+    #[test]
+    fn test_parse_proc_net_route() {
+        let text = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                    eth0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0\n\
+                    eth0\t0001A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n\
+                    wlan0\t00000000\t0100000A\t0003\t0\t0\t600\t00000000\t0\t0\t0\n\
+                    eth1\t0000000A\t00000000\t0000\t0\t0\t0\t000000FF\t0\t0\t0\n";
+        let routes = parse_proc_net_route(text);
+        assert_eq!(routes.len(), 3);
+        assert_eq!(routes[1], Route { destination: ip("192.168.1.0"), prefix: 24, gateway: None, interface: "eth0".to_string(), metric: 100 });
+        let defaults = default_routes(&routes);
+        assert_eq!(defaults.iter().map(|r| (r.gateway.unwrap(), r.interface.as_str())).collect::<Vec<_>>(),
+            vec![(ip("192.168.1.1"), "eth0"), (ip("10.0.0.1"), "wlan0")]);
+        assert_eq!(defaults[0].format_line(), "0.0.0.0/0          via 192.168.1.1     eth0 (metric 100)");
+    }
+
+    #[test]
+    fn test_parse_route_print_and_netstat() {
+        let windows = "===========================================================================\n\
+                       IPv4 Route Table\n\
+                       ===========================================================================\n\
+                       Active Routes:\n\
+                       Network Destination        Netmask          Gateway       Interface  Metric\n\
+                       \x20         0.0.0.0          0.0.0.0      192.168.1.1    192.168.1.10     25\n\
+                       \x20       127.0.0.0        255.0.0.0         On-link         127.0.0.1    331\n\
+                       \x20     192.168.1.0    255.255.255.0         On-link      192.168.1.10    281\n\
+                       ===========================================================================\n\
+                       Persistent Routes:\n\
+                       \x20 Network Address          Netmask  Gateway Address  Metric\n\
+                       \x20         0.0.0.0          0.0.0.0      192.168.1.1  Default\n";
+        let routes = parse_route_print(windows);
+        assert_eq!(routes.len(), 3);
+        assert_eq!(default_routes(&routes)[0].gateway, Some(ip("192.168.1.1")));
+        assert_eq!(routes[1].gateway, None);
+        assert_eq!(routes[2].prefix, 24);
+
+        let bsd = "Routing tables\n\nInternet:\nDestination        Gateway            Flags               Netif Expire\n\
+                   default            192.168.1.1        UGScg                 en0\n\
+                   127                127.0.0.1          UCS                   lo0\n\
+                   192.168.1          link#6             UCS                   en0      !\n\
+                   192.168.1.1/32     link#6             UCS                   en0      !\n";
+        let routes = parse_netstat_rn(bsd);
+        assert_eq!(routes.len(), 4);
+        assert_eq!(default_routes(&routes)[0].gateway, Some(ip("192.168.1.1")));
+        assert_eq!((routes[1].destination, routes[1].prefix), (ip("127.0.0.0"), 8));
+        assert_eq!((routes[2].destination, routes[2].prefix, routes[2].gateway), (ip("192.168.1.0"), 24, None));
+        assert_eq!(routes[3].prefix, 32);
+    }
+
+    #[test]
+    fn test_parse_dns_servers() {
+        let resolv = "# generated\nnameserver 127.0.0.53\nnameserver fe80::1%eth0\noptions edns0\nsearch corp.example\n";
+        assert_eq!(parse_resolv_conf(resolv), vec!["127.0.0.53".parse::<IpAddr>().unwrap(), "fe80::1".parse().unwrap()]);
+
+        let english = "   Default Gateway . . . . . . . . . : 192.168.1.1\n\
+                       \x20                                      10.0.0.1\n\
+                       \x20  DNS Servers . . . . . . . . . . . : 192.168.1.1\n\
+                       \x20                                      8.8.8.8\n\
+                       \x20  NetBIOS over Tcpip. . . . . . . . : Enabled\n";
+        assert_eq!(parse_ipconfig_dns(english), vec!["192.168.1.1".parse::<IpAddr>().unwrap(), "8.8.8.8".parse().unwrap()]);
+        let japanese = "   DNS サフィックス検索一覧. . . . . . . .: corp.example\n\
+                        \x20  デフォルト ゲートウェイ . . . . . . .: 10.0.0.1\n\
+                        \x20  DNS サーバー. . . . . . . . . . . . : 10.0.0.10\n\
+                        \x20                                        10.0.0.11\n\
+                        \x20  NetBIOS over TCP/IP . . . . . . . . : 有効\n";
+        assert_eq!(parse_ipconfig_dns(japanese), vec!["10.0.0.10".parse::<IpAddr>().unwrap(), "10.0.0.11".parse().unwrap()]);
+    }
+
+    #[test]
+    fn test_check_lines() {
+        let checks = vec![
+            Check::new("Ping gateway", true, "192.168.1.1 (eth0)"),
+            Check::new("DNS 8.8.8.8", false, "example.com: timeout"),
+        ];
+        assert_eq!(checks[0].format_line(), "[PASS] Ping gateway           192.168.1.1 (eth0)");
+        assert_eq!(checks[1].format_line(), "[FAIL] DNS 8.8.8.8            example.com: timeout");
+        assert_eq!(format_summary(&checks), "[Info] 1 of 2 check(s) failed");
+        assert_eq!(format_summary(&checks[..1]), "[Info] All 1 check(s) passed");
+
+        let mut hop = HopRecord::new(3);
+        hop.add_responder(IpAddr::V4(ip("8.8.8.8")), None);
+        assert!(trace_reached(&[HopRecord::new(1), hop.clone()], ip("8.8.8.8")));
+        assert!(!trace_reached(&[hop], ip("1.1.1.1")));
+    }
+}
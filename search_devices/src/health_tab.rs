@@ -0,0 +1,232 @@
+use fltk::{
+    prelude::*,
+    frame::Frame,
+    input::Input,
+    button::{Button, CheckButton},
+    text::{TextDisplay, TextBuffer},
+    app,
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}},
+    thread,
+    time::Duration,
+};
+use crate::config::Config;
+use crate::dns_client::{self, RecordType, Transport, DNS_PORT};
+use crate::health::{self, Check};
+use crate::hops::format_hop_line;
+use crate::ip_list_tab::is_alive;
+use crate::rate::RateLimiter;
+use crate::utils::{resolve_target_ipv4, ScanRun};
+
+/// Timeout of each DNS query.
+const DNS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Hop limit of the reference trace.
+const TRACE_MAX_HOPS: u32 = 30;
+
+fn send_line(sender: app::Sender<(String, Ipv4Addr, bool, String)>, line: String) {
+    sender.send(("HEALTH".to_string(), Ipv4Addr::UNSPECIFIED, false, line));
+}
+
+/// Ping `ip` with two echo requests, one second each, paced by the rate limiter.
+fn ping_check(name: &str, ip: Ipv4Addr, label: String, limiter: &RateLimiter, running: &AtomicBool) -> Option<Check> {
+    let _permit = limiter.in_flight(1, running)?;
+    if !limiter.pace(ip, 2, running) { return None }
+    is_alive(&ip, 2, 1000, limiter.limits().ping_interval_ms, running).map(|up| Check::new(name, up, if up { label } else { format!("{}: no reply", label) }))
+}
+
+/// Resolve `test_name` through `server`.
+fn dns_check(server: SocketAddr, test_name: &str) -> Check {
+    let name = format!("DNS {}", server.ip());
+    match dns_client::query(server, test_name, RecordType::A, Transport::Udp, DNS_TIMEOUT) {
+        Ok(resp) if resp.rcode == 0 && !resp.ipv4_answers().is_empty() => {
+            let addrs: Vec<String> = resp.ipv4_answers().iter().map(|a| a.to_string()).collect();
+            Check::new(name, true, format!("{} -> {} ({} ms)", test_name, addrs.join(", "), resp.elapsed.as_millis()))
+        }
+        Ok(resp) if resp.rcode == 0 => Check::new(name, false, format!("{}: no A record", test_name)),
+        Ok(resp) => Check::new(name, false, format!("{}: {}", test_name, dns_client::rcode_name(resp.rcode))),
+        Err(e) => Check::new(name, false, format!("{}: {}", test_name, e)),
+    }
+}
+
+/// Run every step, reporting each result as it completes. Returns None when stopped.
+fn run_checks(config: &Config, test_name: &str, trace_target: Option<String>, limiter: &RateLimiter, running: &AtomicBool, sender: app::Sender<(String, Ipv4Addr, bool, String)>) -> Option<Vec<Check>> {
+    let mut checks = Vec::new();
+    let mut report = |c: Check| {
+        send_line(sender, c.format_line());
+        checks.push(c);
+    };
+
+    // Routing table and default gateways
+    let routes = match health::routing_table() {
+        Ok(r) => r,
+        Err(e) => {
+            report(Check::new("Routing table", false, e));
+            Vec::new()
+        }
+    };
+    let defaults = health::default_routes(&routes);
+    if !routes.is_empty() {
+        report(Check::new("Routing table", true, format!("{} route(s)", routes.len())));
+    }
+    if defaults.is_empty() {
+        report(Check::new("Default route", false, "no default gateway"));
+    }
+    let mut gateways: Vec<(Ipv4Addr, String)> = Vec::new();
+    for r in &defaults {
+        let Some(gw) = r.gateway else { continue };
+        if !gateways.iter().any(|(g, _)| *g == gw) {
+            report(Check::new("Default route", true, format!("via {} ({}, metric {})", gw, r.interface, r.metric)));
+            gateways.push((gw, r.interface.clone()));
+        }
+    }
+    for (gw, iface) in &gateways {
+        if !running.load(Ordering::SeqCst) { return None; }
+        report(ping_check("Ping gateway", *gw, format!("{} ({})", gw, iface), limiter, running)?);
+    }
+
+    // DNS servers: reachability and resolution of the test name
+    let servers: Vec<SocketAddr> = if config.health.dns_servers.is_empty() {
+        health::system_dns_servers().into_iter().map(|ip| SocketAddr::new(ip, DNS_PORT)).collect()
+    } else {
+        let mut servers = Vec::new();
+        for s in &config.health.dns_servers {
+            match dns_client::parse_server(s) {
+                Ok(a) => servers.push(a),
+                Err(e) => report(Check::new("DNS server", false, e)),
+            }
+        }
+        servers
+    };
+    if servers.is_empty() {
+        report(Check::new("DNS servers", false, "none configured"));
+    }
+    for server in servers {
+        if !running.load(Ordering::SeqCst) { return None; }
+        // Local stub resolvers (127.0.0.53) answer without a ping
+        if let IpAddr::V4(ip) = server.ip() {
+            if !ip.is_loopback() {
+                report(ping_check("Ping DNS server", ip, ip.to_string(), limiter, running)?);
+            }
+        }
+        report(dns_check(server, test_name));
+    }
+
+    // System resolver
+    if !running.load(Ordering::SeqCst) { return None; }
+    match dns_lookup::lookup_host(test_name) {
+        Ok(addrs) if !addrs.is_empty() => {
+            let addrs: Vec<String> = addrs.iter().map(|a| a.to_string()).collect();
+            report(Check::new("Resolve (system)", true, format!("{} -> {}", test_name, addrs.join(", "))));
+        }
+        Ok(_) => report(Check::new("Resolve (system)", false, format!("{}: no address", test_name))),
+        Err(e) => report(Check::new("Resolve (system)", false, format!("{}: {}", test_name, e))),
+    }
+
+    // Optional trace to the reference target
+    if let Some(target) = trace_target {
+        if !running.load(Ordering::SeqCst) { return None; }
+        let name = format!("Trace {}", target);
+        match resolve_target_ipv4(&target) {
+            None => report(Check::new(name, false, "cannot resolve target")),
+            Some(ip) => {
+                send_line(sender, format!("[Info] Tracing to {} ({})...", target, ip));
+                let result = health::trace_hops(ip, TRACE_MAX_HOPS, running, limiter, |hop| {
+                    send_line(sender, format!("    {}", format_hop_line(hop)));
+                });
+                if !running.load(Ordering::SeqCst) { return None; }
+                match result {
+                    Ok(hops) if health::trace_reached(&hops, ip) => report(Check::new(name, true, format!("reached in {} hop(s)", hops.len()))),
+                    Ok(hops) => report(Check::new(name, false, format!("not reached after {} hop(s)", hops.len()))),
+                    Err(e) => report(Check::new(name, false, e)),
+                }
+            }
+        }
+    }
+    Some(checks)
+}
+
+/// Build the Network health tab. Returns the running flag, the output buffer and the display.
+pub fn build_health_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>, config: Arc<Config>, limiter: Arc<RateLimiter>) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>) {
+    Frame::new(10, 30, 480, 25, "Test name resolved through each DNS server");
+    let mut name_inp = Input::new(10, 70, 200, 30, "");
+    name_inp.set_value(&config.health.test_name);
+
+    let mut clear_btn = Button::new(240, 70, 80, 30, "Clear");
+    let mut run_btn = Button::new(320, 70, 80, 30, "Run");
+    let mut stop_btn = Button::new(410, 70, 80, 30, "Stop");
+
+    let mut trace_cb = CheckButton::new(10, 105, 80, 25, "Trace to");
+    trace_cb.set_value(config.health.trace);
+    let mut trace_inp = Input::new(90, 105, 120, 25, "");
+    trace_inp.set_value(&config.health.trace_target);
+    let mut routes_btn = Button::new(240, 105, 80, 25, "Routes");
+
+    let mut display = TextDisplay::new(10, 140, 480, 230, "");
+    let buff = TextBuffer::default();
+    display.set_buffer(buff.clone());
+    let display_ref = Arc::new(Mutex::new(display));
+
+    let run = ScanRun::default();
+
+    // Clear
+    {
+        let mut b = buff.clone();
+        clear_btn.set_callback(move |_| b.set_text(""));
+    }
+
+    // Routes: the IPv4 routing table
+    {
+        let mut b = buff.clone();
+        routes_btn.set_callback(move |_| {
+            match health::routing_table() {
+                Ok(routes) => {
+                    b.set_text(&format!("[Info] {} IPv4 route(s)\n", routes.len()));
+                    for r in routes {
+                        b.append(&format!("{}\n", r.format_line()));
+                    }
+                }
+                Err(e) => b.append(&format!("[Error] {}\n", e)),
+            }
+        });
+    }
+
+    // Run: every check in order, pass/fail per step
+    {
+        let run = run.clone();
+        let mut b = buff.clone();
+        run_btn.set_callback(move |_| {
+            if run.is_busy() {
+                return; // already running
+            }
+            let test_name = match name_inp.value().trim() {
+                "" => config.health.test_name.clone(),
+                s => s.to_string(),
+            };
+            let trace_target = Some(trace_inp.value().trim().to_string()).filter(|t| trace_cb.value() && !t.is_empty());
+            b.set_text(&format!("[Info] Network health check (test name {})\n", test_name));
+
+            let flag_thread = run.start();
+            let run = run.clone();
+            let config = config.clone();
+            let limiter = limiter.clone();
+            thread::spawn(move || {
+                match run_checks(&config, &test_name, trace_target, &limiter, &flag_thread, sender) {
+                    Some(checks) => send_line(sender, health::format_summary(&checks)),
+                    None => send_line(sender, "[Info] Health check stopped".to_string()),
+                }
+                run.finish();
+            });
+        });
+    }
+
+    // Stop
+    {
+        let run = run.clone();
+        stop_btn.set_callback(move |_| run.stop());
+    }
+
+    (run.busy_flag(), buff, display_ref)
+}
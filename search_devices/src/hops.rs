@@ -0,0 +1,397 @@
+// Traceroute hop records: parsers for tracert/traceroute output, table rendering and export
+
+use serde::Serialize;
+use std::net::IpAddr;
+
+/// Output format of the system trace command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// Windows `tracert`
+    Windows,
+    /// Linux/Unix `traceroute`
+    Unix,
+}
+
+impl TraceFormat {
+    /// Format produced by the trace command of the current platform.
+    pub fn native() -> TraceFormat {
+        if cfg!(windows) { TraceFormat::Windows } else { TraceFormat::Unix }
+    }
+}
+
+/// A router that answered probes for a hop.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Responder {
+    pub addr: IpAddr,
+    pub hostname: Option<String>,
+    /// Network owner ("AS15169 GOOGLE 8.8.8.0/24") or special-range label ("Private (RFC1918)")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+/// One hop of a trace. `rtts` holds one entry per probe (`None` = timeout).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HopRecord {
+    pub hop: u32,
+    pub responders: Vec<Responder>,
+    pub rtts: Vec<Option<f64>>,
+    /// Annotations such as `!H`, `!N` or "Destination host unreachable."
+    pub annotations: Vec<String>,
+}
+
+impl HopRecord {
+    pub fn new(hop: u32) -> HopRecord {
+        HopRecord { hop, responders: Vec::new(), rtts: Vec::new(), annotations: Vec::new() }
+    }
+
+    /// Packet loss in percent over the probes of this hop.
+    pub fn loss_pct(&self) -> f64 {
+        if self.rtts.is_empty() { return 100.0; }
+        let lost = self.rtts.iter().filter(|r| r.is_none()).count();
+        lost as f64 * 100.0 / self.rtts.len() as f64
+    }
+
+    /// Add a responder unless it is already listed. A hostname equal to the address is dropped.
+    pub fn add_responder(&mut self, addr: IpAddr, hostname: Option<String>) {
+        let hostname = hostname.filter(|h| h.parse::<IpAddr>().ok() != Some(addr));
+        if !self.responders.iter().any(|r| r.addr == addr) {
+            self.responders.push(Responder { addr, hostname, owner: None });
+        }
+    }
+}
+
+/// Parse one line of trace output. Returns `None` for headers, blank lines and other text.
+pub fn parse_hop_line(line: &str, format: TraceFormat) -> Option<HopRecord> {
+    match format {
+        TraceFormat::Windows => parse_windows_hop(line),
+        TraceFormat::Unix => parse_unix_hop(line),
+    }
+}
+
+/// Parse an RTT token such as "12", "0.512" or Windows' "<1".
+/// "<1" is stored as its upper bound (1 ms).
+fn parse_rtt(tok: &str) -> Option<f64> {
+    tok.trim_start_matches('<').parse::<f64>().ok().filter(|v| *v >= 0.0)
+}
+
+/// Windows `tracert` line, e.g.
+/// `  2     5 ms     4 ms    <1 ms  gw.example [10.0.0.1]` or
+/// `  3     *        *        *     Request timed out.` or
+/// `  6  10.0.0.254  reports: Destination host unreachable.` (three lost probes)
+fn parse_windows_hop(line: &str) -> Option<HopRecord> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let hop: u32 = tokens.first()?.parse().ok()?;
+    let mut rec = HopRecord::new(hop);
+    let mut i = 1;
+    while i < tokens.len() && rec.rtts.len() < 3 {
+        if tokens[i] == "*" {
+            rec.rtts.push(None);
+            i += 1;
+        } else if let (Some(v), Some(&"ms")) = (parse_rtt(tokens[i]), tokens.get(i + 1)) {
+            rec.rtts.push(Some(v));
+            i += 2;
+        } else {
+            break;
+        }
+    }
+    if rec.rtts.is_empty() {
+        // `  6  10.0.0.254  reports: Destination host unreachable.` has no RTT columns
+        let reports = tokens.get(1).is_some_and(|t| t.parse::<IpAddr>().is_ok()) && tokens.get(2) == Some(&"reports:");
+        if !reports { return None; }
+        rec.rtts = vec![None; 3];
+    }
+    let rest = &tokens[i..];
+    if let Some(last) = rest.last() {
+        if let Some(ip) = last.strip_prefix('[').and_then(|t| t.strip_suffix(']')).and_then(|t| t.parse().ok()) {
+            let name = rest[..rest.len() - 1].join(" ");
+            rec.add_responder(ip, Some(name).filter(|n| !n.is_empty()));
+        } else if let Ok(ip) = rest[0].trim_end_matches(':').parse::<IpAddr>() {
+            rec.add_responder(ip, None);
+            if let Some(pos) = rest.iter().position(|t| *t == "reports:") {
+                let note = rest[pos + 1..].join(" ");
+                if !note.is_empty() { rec.annotations.push(note); }
+            }
+        }
+    }
+    Some(rec)
+}
+
+/// Linux `traceroute` line, e.g.
+/// ` 5  r1.example (10.0.0.1)  10.123 ms  r2.example (10.0.0.2)  11.2 ms !H  12.0 ms` or
+/// ` 6  * * *`
+fn parse_unix_hop(line: &str) -> Option<HopRecord> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let hop: u32 = tokens.first()?.parse().ok()?;
+    let mut rec = HopRecord::new(hop);
+    let mut i = 1;
+    while i < tokens.len() {
+        let tok = tokens[i];
+        if tok == "*" {
+            rec.rtts.push(None);
+            i += 1;
+        } else if let (Some(v), Some(&"ms")) = (parse_rtt(tok), tokens.get(i + 1)) {
+            rec.rtts.push(Some(v));
+            i += 2;
+        } else if tok.starts_with('!') {
+            if !rec.annotations.iter().any(|a| a == tok) {
+                rec.annotations.push(tok.to_string());
+            }
+            i += 1;
+        } else {
+            // Responder: "host (ip)", "ip (ip)" or bare "ip"
+            let paren_ip = tokens.get(i + 1)
+                .and_then(|t| t.strip_prefix('(')).and_then(|t| t.strip_suffix(')'))
+                .and_then(|t| t.parse::<IpAddr>().ok());
+            if let Some(ip) = paren_ip {
+                rec.add_responder(ip, Some(tok.to_string()));
+                i += 2;
+            } else {
+                if let Ok(ip) = tok.parse::<IpAddr>() {
+                    rec.add_responder(ip, None);
+                }
+                i += 1;
+            }
+        }
+    }
+    if rec.rtts.is_empty() && rec.responders.is_empty() { return None; }
+    Some(rec)
+}
+
+/// Format a hop as a `traceroute`-style line (parsable with `TraceFormat::Unix`).
+pub fn format_hop_line(h: &HopRecord) -> String {
+    let mut out = format!("{:>2} ", h.hop);
+    for r in &h.responders {
+        match &r.hostname {
+            Some(name) => out.push_str(&format!(" {} ({})", name, r.addr)),
+            None => out.push_str(&format!(" {}", r.addr)),
+        }
+    }
+    for rtt in &h.rtts {
+        match rtt {
+            Some(v) => out.push_str(&format!("  {:.3} ms", v)),
+            None => out.push_str(" *"),
+        }
+    }
+    for a in &h.annotations {
+        out.push_str(&format!(" {}", a));
+    }
+    out
+}
+
+fn fmt_rtt(rtt: &Option<f64>) -> String {
+    match rtt {
+        Some(v) => format!("{:.1} ms", v),
+        None => "*".to_string(),
+    }
+}
+
+/// Render hops as a fixed-width table. Additional responders of a hop are listed on
+/// continuation rows.
+pub fn format_hop_table(hops: &[HopRecord]) -> String {
+    let probes = hops.iter().map(|h| h.rtts.len()).max().unwrap_or(3).max(1);
+    let with_owner = hops.iter().flat_map(|h| &h.responders).any(|r| r.owner.is_some());
+    let mut out = format!("{:<4} {:<16} {:<28}", "Hop", "Address", "Hostname");
+    for n in 1..=probes {
+        out.push_str(&format!(" {:>9}", format!("RTT{}", n)));
+    }
+    out.push_str(&format!(" {:>6}", "Loss"));
+    if with_owner {
+        out.push_str("  AS / Owner");
+    }
+    out.push('\n');
+    for h in hops {
+        let first = h.responders.first();
+        let addr = first.map(|r| r.addr.to_string()).unwrap_or_else(|| "*".to_string());
+        let host = first.and_then(|r| r.hostname.clone()).unwrap_or_default();
+        out.push_str(&format!("{:<4} {:<16} {:<28}", h.hop, addr, host));
+        for n in 0..probes {
+            let cell = h.rtts.get(n).map(fmt_rtt).unwrap_or_default();
+            out.push_str(&format!(" {:>9}", cell));
+        }
+        out.push_str(&format!(" {:>5.0}%", h.loss_pct()));
+        if let Some(owner) = first.and_then(|r| r.owner.as_ref()) {
+            out.push_str(&format!("  {}", owner));
+        }
+        if !h.annotations.is_empty() {
+            out.push_str(&format!("  {}", h.annotations.join(" ")));
+        }
+        out.push('\n');
+        for r in h.responders.iter().skip(1) {
+            let host = r.hostname.clone().unwrap_or_default();
+            match &r.owner {
+                Some(owner) => out.push_str(&format!("{:<4} {:<16} {:<28}  {}\n", "", r.addr, host, owner)),
+                None => out.push_str(&format!("{:<4} {:<16} {}\n", "", r.addr, host)),
+            }
+        }
+    }
+    out
+}
+
+/// Quote a CSV field when needed.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Export hops as CSV, one row per responder (timeouts yield a row with empty address).
+pub fn hops_to_csv(hops: &[HopRecord]) -> String {
+    let probes = hops.iter().map(|h| h.rtts.len()).max().unwrap_or(3).max(1);
+    let mut out = String::from("hop,address,hostname,owner");
+    for n in 1..=probes {
+        out.push_str(&format!(",rtt{}_ms", n));
+    }
+    out.push_str(",loss_pct,annotations\n");
+    for h in hops {
+        let rtts: String = (0..probes)
+            .map(|n| match h.rtts.get(n) {
+                Some(Some(v)) => format!(",{:.3}", v),
+                _ => ",".to_string(),
+            })
+            .collect();
+        let tail = format!(",{:.1},{}\n", h.loss_pct(), csv_field(&h.annotations.join(" ")));
+        if h.responders.is_empty() {
+            out.push_str(&format!("{},,,{}{}", h.hop, rtts, tail));
+        }
+        for r in &h.responders {
+            let host = r.hostname.clone().unwrap_or_default();
+            let owner = r.owner.clone().unwrap_or_default();
+            out.push_str(&format!("{},{},{},{}{}{}", h.hop, r.addr, csv_field(&host), csv_field(&owner), rtts, tail));
+        }
+    }
+    out
+}
+
+/// Export hops as pretty-printed JSON.
+pub fn hops_to_json(hops: &[HopRecord]) -> String {
+    serde_json::to_string_pretty(hops).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_trace_output(text: &str, format: TraceFormat) -> Vec<HopRecord> {
+        text.lines().filter_map(|l| parse_hop_line(l, format)).collect()
+    }
+
+    const WINDOWS_SAMPLE: &str = "
+Tracing route to dns.google [8.8.8.8]
+over a maximum of 30 hops:
+
+  1    <1 ms    <1 ms    <1 ms  router.local [192.168.0.1]
+  2     5 ms     4 ms     6 ms  10.10.0.1
+  3     *        *        *     Request timed out.
+  4     *       12 ms    11 ms  72.14.215.85
+  5    10 ms     9 ms     9 ms  dns.google [8.8.8.8]
+
+Trace complete.
+";
+
+    const UNIX_SAMPLE: &str = "traceroute to 8.8.8.8 (8.8.8.8), 30 hops max, 60 byte packets
+ 1  _gateway (192.168.0.1)  0.412 ms  0.380 ms  0.371 ms
+ 2  * * *
+ 3  r1.isp.example (10.0.0.1)  10.123 ms r2.isp.example (10.0.0.2)  11.200 ms  12.004 ms
+ 4  172.16.5.1 (172.16.5.1)  20.5 ms !H  * 21.0 ms !H
+ 5  203.0.113.9  30.1 ms !N  30.2 ms !N  30.3 ms !N
+ 6  dns.google (8.8.8.8)  9.871 ms  9.802 ms  9.799 ms
+";
+
+    #[test]
+    fn test_parse_windows_sample() {
+        let hops = parse_trace_output(WINDOWS_SAMPLE, TraceFormat::Windows);
+        assert_eq!(hops.len(), 5);
+        assert_eq!(hops[0].hop, 1);
+        assert_eq!(hops[0].responders[0].addr, "192.168.0.1".parse::<IpAddr>().unwrap());
+        assert_eq!(hops[0].responders[0].hostname.as_deref(), Some("router.local"));
+        assert_eq!(hops[0].rtts, vec![Some(1.0), Some(1.0), Some(1.0)]);
+        assert_eq!(hops[1].responders[0].hostname, None);
+        assert_eq!(hops[1].rtts, vec![Some(5.0), Some(4.0), Some(6.0)]);
+        assert!(hops[2].responders.is_empty());
+        assert_eq!(hops[2].loss_pct(), 100.0);
+        assert_eq!(hops[3].rtts, vec![None, Some(12.0), Some(11.0)]);
+        assert!((hops[3].loss_pct() - 33.333).abs() < 0.01);
+        assert_eq!(hops[4].responders[0].addr, "8.8.8.8".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_parse_windows_unreachable() {
+        let h = parse_hop_line("  6  10.0.0.254  reports: Destination host unreachable.", TraceFormat::Windows).unwrap();
+        assert_eq!(h.hop, 6);
+        assert_eq!(h.rtts, vec![None, None, None]);
+        assert_eq!(h.responders[0].addr, "10.0.0.254".parse::<IpAddr>().unwrap());
+        assert_eq!(h.annotations, vec!["Destination host unreachable.".to_string()]);
+        assert!(parse_hop_line("  6  Request timed out.", TraceFormat::Windows).is_none());
+        let h = parse_hop_line("  6     1 ms     *        *     10.0.0.254  reports: Destination host unreachable.", TraceFormat::Windows).unwrap();
+        assert_eq!(h.responders[0].addr, "10.0.0.254".parse::<IpAddr>().unwrap());
+        assert_eq!(h.annotations, vec!["Destination host unreachable.".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_unix_sample() {
+        let hops = parse_trace_output(UNIX_SAMPLE, TraceFormat::Unix);
+        assert_eq!(hops.len(), 6);
+        assert_eq!(hops[0].responders[0].hostname.as_deref(), Some("_gateway"));
+        assert_eq!(hops[0].rtts, vec![Some(0.412), Some(0.380), Some(0.371)]);
+        assert!(hops[1].responders.is_empty());
+        assert_eq!(hops[1].rtts, vec![None, None, None]);
+        // multiple responders on one hop
+        assert_eq!(hops[2].responders.len(), 2);
+        assert_eq!(hops[2].responders[1].addr, "10.0.0.2".parse::<IpAddr>().unwrap());
+        assert_eq!(hops[2].rtts.len(), 3);
+        // "ip (ip)" does not repeat the address as hostname; annotations collected once
+        assert_eq!(hops[3].responders[0].hostname, None);
+        assert_eq!(hops[3].rtts, vec![Some(20.5), None, Some(21.0)]);
+        assert_eq!(hops[3].annotations, vec!["!H".to_string()]);
+        assert_eq!(hops[4].annotations, vec!["!N".to_string()]);
+        assert_eq!(hops[4].loss_pct(), 0.0);
+    }
+
+    #[test]
+    fn test_parse_ignores_non_hop_lines() {
+        assert!(parse_hop_line("traceroute to x (1.2.3.4), 30 hops max", TraceFormat::Unix).is_none());
+        assert!(parse_hop_line("Trace complete.", TraceFormat::Windows).is_none());
+        assert!(parse_hop_line("", TraceFormat::Unix).is_none());
+    }
+
+    #[test]
+    fn test_format_hop_line_roundtrip() {
+        for rec in parse_trace_output(UNIX_SAMPLE, TraceFormat::Unix) {
+            let line = format_hop_line(&rec);
+            assert_eq!(parse_hop_line(&line, TraceFormat::Unix), Some(rec), "{}", line);
+        }
+        assert_eq!(format_hop_line(&HopRecord { hop: 2, responders: vec![], rtts: vec![None, None], annotations: vec![] }), " 2  * *");
+    }
+
+    #[test]
+    fn test_table_and_exports() {
+        let hops = parse_trace_output(UNIX_SAMPLE, TraceFormat::Unix);
+        let table = format_hop_table(&hops);
+        assert!(table.starts_with("Hop"));
+        assert!(table.contains("RTT3"));
+        assert!(table.contains("10.0.0.2"));
+        assert!(table.contains("!H"));
+
+        let csv = hops_to_csv(&hops);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "hop,address,hostname,owner,rtt1_ms,rtt2_ms,rtt3_ms,loss_pct,annotations");
+        assert_eq!(lines[1], "1,192.168.0.1,_gateway,,0.412,0.380,0.371,0.0,");
+        assert_eq!(lines[2], "2,,,,,,,100.0,");
+        // one row per responder
+        assert_eq!(csv.lines().filter(|l| l.starts_with("3,")).count(), 2);
+
+        let mut hops = hops;
+        hops[5].responders[0].owner = Some("AS15169 GOOGLE 8.8.8.0/24".to_string());
+        assert!(format_hop_table(&hops).contains("AS / Owner"));
+        assert!(hops_to_csv(&hops).contains("8.8.8.8,dns.google,AS15169 GOOGLE 8.8.8.0/24,"));
+
+        let json = hops_to_json(&hops);
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v.as_array().unwrap().len(), 6);
+        assert_eq!(v[0]["responders"][0]["addr"], "192.168.0.1");
+        assert!(v[0]["responders"][0].get("owner").is_none());
+        assert_eq!(v[5]["responders"][0]["owner"], "AS15169 GOOGLE 8.8.8.0/24");
+    }
+}
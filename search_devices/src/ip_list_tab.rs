@@ -2,21 +2,31 @@ use fltk::{
     prelude::*,
     frame::Frame,
     input::{MultilineInput, IntInput},
-    button::Button,
+    button::{Button, CheckButton},
+    dialog,
     text::{TextDisplay, TextBuffer},
     app,
 };
 use std::{net::{Ipv4Addr, IpAddr}, process::Command, sync::{Arc, atomic::{AtomicBool, Ordering}, Mutex}, thread};
-use dns_lookup::lookup_addr;
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
-use crate::utils::{ms_to_secs_ceil, ping_args_unix, ping_args_windows};
+use crate::utils::{ms_to_secs_ceil, ping_args_unix, ping_args_windows, ping_interval_args_unix, run_until_stopped};
+use crate::rate::RateLimiter;
+use crate::config::Config;
+use crate::exclusions::{profile_choice, selected_profile, ExcludeList, SkipReport};
+use crate::netbios::HostQuery;
+use crate::wol::MacMemory;
+use crate::rdns::{RdnsPool, DEFAULT_DNS_TIMEOUT_MS};
+use crate::cidr_tab::LARGE_SWEEP;
+use crate::targets::{parse_target, parse_target_list, Target, MAX_EXPANDED_ADDRESSES};
+use crate::target_import::{detect_format, import_targets, ImportFormat};
+use crate::utils::{choose_open_path, ScanRun};
 
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
-/// 指定した IP に ping を実行し、生存を判定します
-fn is_alive(ip: &Ipv4Addr, count: u32, timeout_ms: u32) -> bool {
+/// 指定した IP に ping を実行し、生存を判定します（停止された場合は None）
+pub fn is_alive(ip: &Ipv4Addr, count: u32, timeout_ms: u32, interval_ms: u64, running: &AtomicBool) -> Option<bool> {
     let ip_str = ip.to_string();
     let mut cmd = Command::new("ping");
     
@@ -33,17 +43,16 @@ fn is_alive(ip: &Ipv4Addr, count: u32, timeout_ms: u32) -> bool {
         // Linux/Unix 用の引数
         // -W は秒単位。ミリ秒→切り上げ秒へ変換
         let args = ping_args_unix(count, timeout_ms, &ip_str);
+        cmd.args(ping_interval_args_unix(count, interval_ms));
         cmd.args(&args);
     }
     
-    cmd.output()
-        .map(|o| o.status.success())
-        .unwrap_or(false)
+    run_until_stopped(&mut cmd, running)
 }
 
 /// IPリストタブを構築し、実行中フラグと結果バッファ、TextDisplayを返します
-pub fn build_ip_list_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>) {
-    Frame::new(10, 30, 480, 30, "Enter IP addresses (one per line)");
+pub fn build_ip_list_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>, rdns: Arc<RdnsPool>, config: Arc<Config>, limiter: Arc<RateLimiter>, macs: Arc<MacMemory>) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>) {
+    Frame::new(10, 30, 480, 30, "IP / hostname / CIDR / range per line (# comment)");
     let mut input = MultilineInput::new(10, 70, 200, 150, "");  // 高さを150に増加
     input.set_value("192.168.0.1\n192.168.0.2\n192.168.0.3");
     input.wrap();  // 自動改行を有効化
@@ -57,85 +66,225 @@ pub fn build_ip_list_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>)
     let _timeout_label = Frame::new(370, 100, 80, 25, "Timeout (ms)");
     let mut timeout_inp = IntInput::new(450, 100, 50, 25, "");
     timeout_inp.set_value("1000");
+    // 逆引き設定（有効/無効・タイムアウト）
+    let mut resolve_cb = CheckButton::new(240, 130, 120, 25, "Resolve DNS");
+    resolve_cb.set_value(true);
+    let _dns_to_label = Frame::new(370, 130, 80, 25, "DNS (ms)");
+    let mut dns_to_inp = IntInput::new(450, 130, 50, 25, "");
+    dns_to_inp.set_value(&DEFAULT_DNS_TIMEOUT_MS.to_string());
+    let mut import_btn = Button::new(240, 165, 80, 25, "Import");
+    // 生存ホストのNetBIOS名 / SMB情報をHost Info列に追記
+    let mut netbios_cb = CheckButton::new(330, 165, 85, 25, "NetBIOS");
+    netbios_cb.set_value(true);
+    let smb_cb = CheckButton::new(420, 165, 70, 25, "SMB");
+    // 除外リストのプロファイル（全体の除外リストに追加）
+    let profile = profile_choice(300, 195, 190, 25, &config);
     let mut display = TextDisplay::new(10, 230, 480, 150, "");  // Y位置を230に、高さを150に調整
     let buff = TextBuffer::default();
     println!("[Debug] IP List buffer created: {:p}", &buff);
     display.set_buffer(buff.clone());
     let display_ref = Arc::new(Mutex::new(display));
-    // クリア処理
+    // 実行中かどうかと、実行ごとの停止フラグ
+    let run = ScanRun::default();
+    // クリア処理（実行中のスキャンも停止）
     {
         let mut b = buff.clone();
+        let run = run.clone();
         clear_btn.set_callback(move |_| {
+            run.stop();
             b.set_text("")
         });
     }
-    let running = Arc::new(AtomicBool::new(false));
+    // ファイルからの取り込み（テキスト / CSV / hosts / nmap XML / JSON）
+    {
+        let mut inp = input.clone();
+        let mut b = buff.clone();
+        import_btn.set_callback(move |_| {
+            let Some(path) = choose_open_path("Import targets", "Target lists\t*.{txt,csv,xml,json,hosts}\nAll files\t*") else { return };
+            let content = match std::fs::read_to_string(&path) {
+                Ok(c) => c,
+                Err(e) => { b.append(&format!("[Error] Cannot read {}: {}\n", path.display(), e)); return; }
+            };
+            let mut format = detect_format(&path, &content);
+            if let ImportFormat::Csv { column } = &format {
+                // 取り込む列を指定（ヘッダ名または1始まりの列番号）
+                let Some(column) = dialog::input_default("CSV column (header name or number)", column) else { return };
+                format = ImportFormat::Csv { column };
+            }
+            let mut result = match import_targets(&content, &format) {
+                Ok(r) => r,
+                Err(e) => { b.append(&format!("[Error] {}\n", e)); return; }
+            };
+            // 既存の入力と重複するものは除外
+            result.drop_existing(&inp.value());
+            if result.targets.is_empty() {
+                dialog::alert_default(&format!("No new targets found.\n{}", result.summary()));
+                return;
+            }
+            // プレビュー（件数と先頭数件）
+            let mut preview = format!("{}\n\n", result.summary());
+            for t in result.targets.iter().take(8) {
+                preview.push_str(&format!("{}\n", t.token));
+            }
+            if result.targets.len() > 8 { preview.push_str("...\n"); }
+            match dialog::choice2_default(&preview, "Cancel", "Append", "Replace") {
+                Some(1) => {
+                    let mut text = inp.value();
+                    if !text.is_empty() && !text.ends_with('\n') { text.push('\n'); }
+                    inp.set_value(&(text + &result.to_list_text()));
+                }
+                Some(2) => inp.set_value(&result.to_list_text()),
+                _ => return,
+            }
+            b.append(&format!("[Info] Imported {} targets from {}\n", result.targets.len(), path.display()));
+        });
+    }
     // スキャン開始処理
     {
         let inp = input.clone();
-        let flag = running.clone();
+        let run = run.clone();
         let mut buf_clone = buff.clone();
         let s = sender.clone();
         println!("[Debug] IP List: Using sender channel: {:p}", &s);
         scan_btn.set_callback(move |_| {
+            if run.is_busy() {
+                return; // 前回のスキャンがまだ終了していない
+            }
+            // 除外リストの読み込み（不正な設定ならスキャンしない）
+            let excludes = match ExcludeList::for_profile(&config, selected_profile(&profile).as_deref()) {
+                Ok(l) => l,
+                Err(e) => {
+                    buf_clone.append(&format!("[Error] {}\n", e));
+                    return;
+                }
+            };
             // ヘッダー行：Result 列を追加
             let header = format!("{:<15} {:<7} {:<12} {}\n",
                 "IP Address", "Result", "Status", "Host Info");
             buf_clone.set_text(&header);
             buf_clone.append("[Debug] Scan started\n");
 
-            let lines: Vec<String> = inp.value()
-                .lines()
-                .map(|s| s.trim().to_string())
-                .filter(|s| !s.is_empty())
-                .collect();
-            if lines.is_empty() {
+            // IP・ホスト名・CIDR・範囲を解析（#以降はコメント）
+            let entries = parse_target_list(&inp.value());
+            if entries.is_empty() {
                 buf_clone.append("[Error] IPアドレスが入力されていません\n");
                 return;
             }
+            // 展開前に対象数を確認（巨大なCIDR・範囲はメモリを使い切るため拒否、大きい場合は確認）
+            let total: u64 = entries.iter().filter_map(|(_, t)| t.as_ref().ok()).map(Target::address_count).sum();
+            if total > MAX_EXPANDED_ADDRESSES {
+                buf_clone.append(&format!("[Error] Too many addresses: {} (limit {})\n", total, MAX_EXPANDED_ADDRESSES));
+                return;
+            }
+            if total > LARGE_SWEEP {
+                let msg = format!("{} addresses will be pinged.\nStart the scan?", total);
+                if dialog::choice2_default(&msg, "Cancel", "Scan", "") != Some(1) {
+                    return;
+                }
+            }
 
             // 設定値の取得
             let count: u32 = count_inp.value().parse().ok().filter(|v| *v >= 1).unwrap_or(1);
             let timeout_ms: u32 = timeout_inp.value().parse().ok().filter(|v| *v >= 1).unwrap_or(1000);
+            let resolve_dns = resolve_cb.value();
+            let dns_timeout_ms: u64 = dns_to_inp.value().parse().ok().filter(|v| *v >= 1).unwrap_or(DEFAULT_DNS_TIMEOUT_MS);
+            let host_query = HostQuery { netbios: netbios_cb.value(), smb: smb_cb.value() };
+            let rdns = rdns.clone();
+            let limiter = limiter.clone();
+            let macs = macs.clone();
+            buf_clone.append(&format!("[Info] Rate limit: {}, {} exclude rule(s)\n", limiter.limits().describe(), excludes.len()));
 
-            flag.store(true, Ordering::SeqCst);
-            let flag_clone = flag.clone();
+            // 今回の実行専用の停止フラグ
+            let flag_clone = run.start();
+            let run = run.clone();
             let sender = s.clone();
 
             // 別スレッドでスキャンを実行
             thread::spawn(move || {
-                println!("[Debug] IP List: Thread started with {} IPs", lines.len());
-                for ip_str in lines {
+                println!("[Debug] IP List: Thread started with {} entries", entries.len());
+                // ホスト名は解決（複数アドレスの場合あり）、CIDR・範囲は展開して対象数を確定
+                let mut targets: Vec<(Ipv4Addr, Option<Arc<str>>)> = Vec::new();
+                for (token, parsed) in entries {
                     if !flag_clone.load(Ordering::SeqCst) { break }
+                    match parsed.and_then(|t| t.expand()) {
+                        Ok(addrs) => {
+                            // 入力がIPそのものでない場合は元の記述をHost Infoに併記
+                            // 元の記述は全アドレスで共有する
+                            let shown: Option<Arc<str>> = (!matches!(parse_target(&token), Ok(Target::Ip(_)))).then(|| Arc::from(token.as_str()));
+                            targets.extend(addrs.into_iter().map(|a| (a, shown.clone())));
+                        }
+                        Err(e) => {
+                            // 無効な入力・解決できないホスト名の場合
+                            sender.send(("IPLIST".to_string(), Ipv4Addr::UNSPECIFIED, false, format!("[{}] {}", token, e)));
+                        }
+                    }
+                }
+                let total = targets.len();
+                let mut done = 0;
+                let mut skipped = SkipReport::default();
+                for (addr, token) in targets {
+                    if !flag_clone.load(Ordering::SeqCst) { break }
+                    // 除外対象には一切パケットを送らない
+                    if let Some(rule) = excludes.matches(addr) {
+                        skipped.record(rule);
+                        done += 1;
+                        continue;
+                    }
+                    // 同時実行数・送信レート・ホスト間隔の制限を待つ（停止時は中断）
+                    let Some(_permit) = limiter.in_flight(1, &flag_clone) else { break };
+                    if !limiter.pace(addr, count, &flag_clone) { break }
+                    println!("[Debug] Checking IP: {}", addr);
+                    // 停止時は ping プロセスを即座に終了させる
+                    let Some(alive) = is_alive(&addr, count, timeout_ms, limiter.limits().ping_interval_ms, &flag_clone) else { break };
+                    done += 1;
+                    // 逆引きはキャッシュがあれば即表示、無ければ生存ホストのみプールで解決して後から補完
+                    let cached = if resolve_dns { rdns.cached(IpAddr::V4(addr)) } else { None };
+                    let name = cached.clone().flatten().unwrap_or_default();
+                    let host_info = match &token {
+                        Some(token) => format!("[{}] {}", token, name),
+                        None => name,
+                    };
+                    println!("[Debug] IP {} - alive: {}, host: {}", addr, alive, host_info);
 
-                    if let Ok(addr) = ip_str.parse::<Ipv4Addr>() {
-                        println!("[Debug] Checking IP: {}", addr);
-                        let alive = is_alive(&addr, count, timeout_ms);
-                        let host_info = lookup_addr(&IpAddr::V4(addr)).unwrap_or_default();
-                        println!("[Debug] IP {} - alive: {}, host: {}", addr, alive, host_info);
-
-                        // 結果をチャンネル経由で送信
-                        println!("[Debug] IP List: About to send to channel {:p}", &sender);
-                        sender.send(("IPLIST".to_string(), addr, alive, host_info));
-                        println!("[Debug] IP List: Sent result for {}", addr);
-                    } else {
-                        // 無効なIPアドレスの場合
-                        sender.send(("IPLIST".to_string(), Ipv4Addr::UNSPECIFIED, false, "Invalid IP".to_string()));
+                    // 結果をチャンネル経由で送信
+                    println!("[Debug] IP List: About to send to channel {:p}", &sender);
+                    sender.send(("IPLIST".to_string(), addr, alive, host_info));
+                    println!("[Debug] IP List: Sent result for {}", addr);
+                    // NetBIOS名 / SMB情報は別スレッドで取得して後から追記
+                    if alive && host_query.enabled() {
+                        host_query.spawn(addr, limiter.clone(), flag_clone.clone(), move |note| {
+                            sender.send(("IPLIST_NOTE".to_string(), addr, true, note));
+                        });
+                    }
+                    if resolve_dns && alive && cached.is_none() {
+                        rdns.resolve_async(IpAddr::V4(addr), dns_timeout_ms, &flag_clone, move |name| {
+                            if let Some(name) = name {
+                                sender.send(("IPLIST_DNS".to_string(), addr, alive, name));
+                            }
+                        });
                     }
                 }
+                if !flag_clone.load(Ordering::SeqCst) {
+                    sender.send(("IPLIST_INFO".to_string(), Ipv4Addr::UNSPECIFIED, false,
+                        format!("[Info] Scan stopped after {} of {} addresses", done, total)));
+                }
+                // 除外したアドレスの件数と理由を報告
+                for line in skipped.format_lines() {
+                    sender.send(("IPLIST_INFO".to_string(), Ipv4Addr::UNSPECIFIED, false, line));
+                }
+                // ARPキャッシュのMACを記録（Wake-on-LANで使用）
+                macs.record_arp_cache();
                 println!("[Debug] Thread finished");
-                flag_clone.store(false, Ordering::SeqCst);
+                run.finish();
             });
         });
     }
     // 停止処理
     {
-        let flag = running.clone();
-        stop_btn.set_callback(move |_| {
-            flag.store(false, Ordering::SeqCst)
-        });
+        let run = run.clone();
+        stop_btn.set_callback(move |_| run.stop());
     }
-    (running, buff, display_ref)
+    (run.busy_flag(), buff, display_ref)
 }
 
 #[cfg(test)]
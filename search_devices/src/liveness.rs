@@ -0,0 +1,301 @@
+// Liveness fallbacks for hosts that drop ICMP: TCP connect (an answer or a RST means
+// the host is up) and a freshly confirmed ARP entry on local segments
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    net::{Ipv4Addr, SocketAddrV4},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+use crate::native_trace::connect_in_progress;
+use crate::rate::RateLimiter;
+use crate::services::parse_port_spec;
+
+/// Settings read from the `[liveness]` section of the configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct LivenessConfig {
+    /// Ports tried with TCP connect, same syntax as the Ports tab (services and presets allowed).
+    pub tcp_ports: String,
+    /// Timeout of each TCP connect.
+    pub tcp_timeout_ms: u64,
+    /// Solicit ARP after the ping and accept an entry the host confirmed just now (Linux).
+    pub arp: bool,
+}
+
+impl Default for LivenessConfig {
+    fn default() -> Self {
+        LivenessConfig { tcp_ports: "80,443,445,22,3389".to_string(), tcp_timeout_ms: 500, arp: true }
+    }
+}
+
+/// How a host was found to be up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LivenessMethod {
+    Icmp,
+    /// TCP connection accepted
+    Tcp(u16),
+    /// TCP connection refused with a RST
+    TcpRst(u16),
+    Arp,
+}
+
+impl LivenessMethod {
+    /// Short label shown in results, e.g. "tcp/443" or "rst/22".
+    pub fn label(self) -> String {
+        match self {
+            LivenessMethod::Icmp => "icmp".to_string(),
+            LivenessMethod::Tcp(p) => format!("tcp/{}", p),
+            LivenessMethod::TcpRst(p) => format!("rst/{}", p),
+            LivenessMethod::Arp => "arp".to_string(),
+        }
+    }
+}
+
+/// Outcome of a TCP connect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpState {
+    Open,
+    /// Actively refused (RST)
+    Refused,
+    /// Timeout or an unreachable error
+    NoAnswer,
+}
+
+/// Non-blocking TCP connect, giving up at the timeout or as soon as `running` is cleared
+/// (None; the socket is closed at once).
+pub fn tcp_connect(ip: Ipv4Addr, port: u16, timeout_ms: u64, running: &AtomicBool) -> Option<TcpState> {
+    let Ok(sock) = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP)) else { return Some(TcpState::NoAnswer) };
+    if sock.set_nonblocking(true).is_err() {
+        return Some(TcpState::NoAnswer);
+    }
+    let refused = |e: &std::io::Error| if e.kind() == ErrorKind::ConnectionRefused { TcpState::Refused } else { TcpState::NoAnswer };
+    match sock.connect(&SockAddr::from(SocketAddrV4::new(ip, port))) {
+        Ok(_) => return Some(TcpState::Open),
+        Err(e) if connect_in_progress(&e) => {}
+        Err(e) => return Some(refused(&e)),
+    }
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms.max(1));
+    loop {
+        match sock.take_error() {
+            Ok(None) => {}
+            Ok(Some(e)) => return Some(refused(&e)),
+            Err(_) => return Some(TcpState::NoAnswer),
+        }
+        if sock.peer_addr().is_ok() {
+            return Some(TcpState::Open);
+        }
+        if !running.load(Ordering::SeqCst) {
+            return None;
+        }
+        if Instant::now() >= deadline {
+            return Some(TcpState::NoAnswer);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// How long to wait for the neighbour entry to be confirmed after soliciting ARP. The
+/// kernel re-probes a STALE entry only after delay_first_probe_time (5 s by default).
+#[cfg(target_os = "linux")]
+const ARP_CONFIRM_WAIT: Duration = Duration::from_secs(7);
+
+/// Fallback probes for a host that did not answer the ping.
+#[derive(Debug, Clone)]
+pub struct Fallback {
+    pub tcp_ports: Vec<u16>,
+    pub tcp_timeout_ms: u64,
+    pub arp: bool,
+}
+
+impl Fallback {
+    /// Build from the configuration; `tcp_ports` overrides the configured port list.
+    pub fn new(cfg: &LivenessConfig, tcp_ports: &str, presets: &BTreeMap<String, String>) -> Result<Fallback, String> {
+        let tcp_ports = parse_port_spec(tcp_ports, presets)?;
+        Ok(Fallback { tcp_ports, tcp_timeout_ms: cfg.tcp_timeout_ms, arp: cfg.arp })
+    }
+
+    /// Try ARP, then each TCP port. Returns the method that found the host,
+    /// or None when stopped.
+    pub fn probe(&self, ip: Ipv4Addr, limiter: &RateLimiter, running: &AtomicBool) -> Option<Option<LivenessMethod>> {
+        if self.arp {
+            if !limiter.pace(ip, 1, running) {
+                return None;
+            }
+            if arp_confirmed(ip, running)? {
+                return Some(Some(LivenessMethod::Arp));
+            }
+        }
+        for &port in &self.tcp_ports {
+            if !limiter.pace(ip, 1, running) {
+                return None;
+            }
+            match tcp_connect(ip, port, self.tcp_timeout_ms, running)? {
+                TcpState::Open => return Some(Some(LivenessMethod::Tcp(port))),
+                TcpState::Refused => return Some(Some(LivenessMethod::TcpRst(port))),
+                TcpState::NoAnswer => {}
+            }
+        }
+        Some(None)
+    }
+}
+
+/// MAC address-like token: six hex pairs separated by ':' or '-'.
+fn is_mac(s: &str) -> bool {
+    let parts: Vec<&str> = s.split([':', '-']).collect();
+    parts.len() == 6 && parts.iter().all(|p| !p.is_empty() && p.len() <= 2 && p.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Complete entries of an ARP table listing: Linux `/proc/net/arp`, Windows `arp -a`
+/// or BSD/macOS `arp -a`. Incomplete, all-zero and broadcast entries are left out.
+pub fn parse_arp_table(text: &str) -> Vec<(Ipv4Addr, String)> {
+    let mut out = Vec::new();
+    for line in text.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let ip = tokens.iter().find_map(|t| t.trim_matches(|c| c == '(' || c == ')').parse::<Ipv4Addr>().ok());
+        let mac = tokens.iter().find(|t| is_mac(t));
+        let (Some(ip), Some(mac)) = (ip, mac) else { continue };
+        let mac = mac.replace('-', ":").to_ascii_lowercase();
+        if mac.split(':').all(|p| p.trim_start_matches('0').is_empty()) || mac.split(':').all(|p| p == "ff") {
+            continue;
+        }
+        out.push((ip, mac));
+    }
+    out
+}
+
+/// Complete entries of the system ARP cache (empty when it cannot be read).
+pub fn arp_table() -> Vec<(Ipv4Addr, String)> {
+    #[cfg(target_os = "linux")]
+    let text = std::fs::read_to_string("/proc/net/arp").unwrap_or_default();
+    #[cfg(not(target_os = "linux"))]
+    let text = {
+        let mut cmd = std::process::Command::new("arp");
+        cmd.arg("-a");
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            cmd.creation_flags(0x08000000);
+        }
+        cmd.output().map(|o| String::from_utf8_lossy(&o.stdout).into_owned()).unwrap_or_default()
+    };
+    parse_arp_table(&text)
+}
+
+/// MAC address of `ip` in the system ARP cache.
+pub fn arp_lookup(ip: Ipv4Addr) -> Option<String> {
+    arp_table().into_iter().find(|(a, _)| *a == ip).map(|(_, mac)| mac)
+}
+
+/// Seconds since the neighbour entry of `ip` was last confirmed by the host, from
+/// `ip -s neigh` output ("... lladdr <mac> used 12/3/3 probes 1 REACHABLE").
+/// None without a usable entry.
+pub fn parse_neigh_confirmed(text: &str, ip: Ipv4Addr) -> Option<u64> {
+    text.lines().find_map(|line| {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.first()?.parse::<Ipv4Addr>().ok()? != ip || !tokens.contains(&"lladdr") {
+            return None;
+        }
+        if tokens.iter().any(|t| matches!(*t, "FAILED" | "INCOMPLETE")) {
+            return None;
+        }
+        let used = tokens.iter().position(|t| *t == "used")?;
+        tokens.get(used + 1)?.split('/').nth(1)?.parse().ok()
+    })
+}
+
+/// Send a datagram to the discard port so the kernel resolves (or re-probes) `ip`, then
+/// wait for the host to confirm its neighbour entry. A cached entry alone proves nothing:
+/// it stays REACHABLE or STALE for minutes after the host went down. None when stopped.
+#[cfg(target_os = "linux")]
+fn arp_confirmed(ip: Ipv4Addr, running: &AtomicBool) -> Option<bool> {
+    let start = Instant::now();
+    if std::net::UdpSocket::bind("0.0.0.0:0").and_then(|s| s.send_to(&[], SocketAddrV4::new(ip, 9))).is_err() {
+        return Some(false);
+    }
+    while start.elapsed() < ARP_CONFIRM_WAIT {
+        if !running.load(Ordering::SeqCst) {
+            return None;
+        }
+        thread::sleep(Duration::from_millis(100));
+        let out = std::process::Command::new("ip").args(["-4", "-s", "neigh", "show", &ip.to_string()]).output();
+        let Ok(out) = out else { return Some(false) };
+        let text = String::from_utf8_lossy(&out.stdout);
+        // No entry (off-link) or resolution failed: nothing will be confirmed
+        if text.trim().is_empty() || text.contains("FAILED") {
+            return Some(false);
+        }
+        if parse_neigh_confirmed(&text, ip).is_some_and(|age| age <= start.elapsed().as_secs()) {
+            return Some(true);
+        }
+    }
+    Some(false)
+}
+
+/// Other systems do not report when an entry was confirmed, so ARP is never taken as proof.
+#[cfg(not(target_os = "linux"))]
+fn arp_confirmed(_ip: Ipv4Addr, _running: &AtomicBool) -> Option<bool> {
+    Some(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_arp_tables() {
+        let linux = "IP address       HW type     Flags       HW address            Mask     Device\n\
+                     192.168.1.1      0x1         0x2         aa:bb:cc:dd:ee:01     *        eth0\n\
+                     192.168.1.7      0x1         0x0         00:00:00:00:00:00     *        eth0\n";
+        assert_eq!(parse_arp_table(linux), vec![("192.168.1.1".parse().unwrap(), "aa:bb:cc:dd:ee:01".to_string())]);
+
+        let windows = "\nInterface: 192.168.1.10 --- 0xb\n  Internet Address      Physical Address      Type\n\
+                       \x20 192.168.1.1           AA-BB-CC-DD-EE-02     dynamic\n\
+                       \x20 192.168.1.255         ff-ff-ff-ff-ff-ff     static\n";
+        assert_eq!(parse_arp_table(windows), vec![("192.168.1.1".parse().unwrap(), "aa:bb:cc:dd:ee:02".to_string())]);
+
+        let bsd = "? (192.168.1.1) at a:bb:cc:d:ee:3 on en0 ifscope [ethernet]\n? (192.168.1.9) at (incomplete) on en0 ifscope [ethernet]\n";
+        assert_eq!(parse_arp_table(bsd), vec![("192.168.1.1".parse().unwrap(), "a:bb:cc:d:ee:3".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_neigh_confirmed() {
+        let ip = |s: &str| s.parse::<Ipv4Addr>().unwrap();
+        let text = "192.168.1.1 dev eth0 lladdr aa:bb:cc:dd:ee:01 used 40/0/0 probes 1 REACHABLE\n\
+                    192.168.1.7 dev eth0 lladdr aa:bb:cc:dd:ee:07 used 300/240/240 probes 1 STALE\n\
+                    192.168.1.9 dev eth0 used 3/3/0 probes 3 FAILED\n\
+                    192.168.1.12 dev eth0 lladdr aa:bb:cc:dd:ee:0c used 5/4/4 probes 6 FAILED\n\
+                    192.168.1.20 dev eth0 lladdr aa:bb:cc:dd:ee:14  ref 1 used 0/106/0probes 1 DELAY\n";
+        assert_eq!(parse_neigh_confirmed(text, ip("192.168.1.1")), Some(0));
+        assert_eq!(parse_neigh_confirmed(text, ip("192.168.1.7")), Some(240));
+        assert_eq!(parse_neigh_confirmed(text, ip("192.168.1.9")), None);
+        assert_eq!(parse_neigh_confirmed(text, ip("192.168.1.12")), None);
+        assert_eq!(parse_neigh_confirmed(text, ip("192.168.1.20")), Some(106));
+        assert_eq!(parse_neigh_confirmed(text, ip("192.168.1.2")), None);
+    }
+
+    #[test]
+    fn test_tcp_connect_and_fallback() {
+        let running = AtomicBool::new(true);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap().port();
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        assert_eq!(tcp_connect(Ipv4Addr::LOCALHOST, open, 1000, &running), Some(TcpState::Open));
+        assert_eq!(tcp_connect(Ipv4Addr::LOCALHOST, closed, 1000, &running), Some(TcpState::Refused));
+
+        let limiter = RateLimiter::new(crate::rate::RateLimits { pps: 0.0, max_in_flight: 0, host_delay_ms: 0, ping_interval_ms: 0 });
+        let cfg = LivenessConfig { arp: false, ..LivenessConfig::default() };
+        let fb = Fallback::new(&cfg, &format!("{},{}", closed, open), &BTreeMap::new()).unwrap();
+        assert_eq!(fb.probe(Ipv4Addr::LOCALHOST, &limiter, &running), Some(Some(LivenessMethod::TcpRst(closed))));
+        let fb = Fallback::new(&cfg, &open.to_string(), &BTreeMap::new()).unwrap();
+        assert_eq!(fb.probe(Ipv4Addr::LOCALHOST, &limiter, &running), Some(Some(LivenessMethod::Tcp(open))));
+        assert!(Fallback::new(&cfg, "nope", &BTreeMap::new()).is_err());
+        running.store(false, Ordering::SeqCst);
+        assert_eq!(fb.probe(Ipv4Addr::LOCALHOST, &limiter, &running), None);
+        assert_eq!(LivenessMethod::TcpRst(22).label(), "rst/22");
+    }
+}
@@ -0,0 +1,471 @@
+// Passive LLDP / CDP listener: decodes the advertisements the switch sends on the port
+// the machine is plugged into (switch name, port, VLAN, management address)
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::atomic::AtomicBool,
+    time::Duration,
+};
+
+/// Destination MAC of LLDP frames (nearest bridge).
+pub const LLDP_MAC: [u8; 6] = [0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e];
+
+/// Destination MAC of CDP frames.
+pub const CDP_MAC: [u8; 6] = [0x01, 0x00, 0x0c, 0xcc, 0xcc, 0xcc];
+
+const ETHERTYPE_LLDP: u16 = 0x88cc;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+/// LLC/SNAP header of CDP: DSAP/SSAP 0xaa, UI, Cisco OUI, protocol 0x2000.
+const CDP_SNAP: [u8; 8] = [0xaa, 0xaa, 0x03, 0x00, 0x00, 0x0c, 0x20, 0x00];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Lldp,
+    Cdp,
+}
+
+impl Protocol {
+    pub fn label(self) -> &'static str {
+        match self {
+            Protocol::Lldp => "LLDP",
+            Protocol::Cdp => "CDP",
+        }
+    }
+}
+
+/// Neighbor announced in one LLDP or CDP frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Neighbor {
+    pub protocol: Protocol,
+    /// Source MAC of the frame (the switch port)
+    pub source_mac: String,
+    /// LLDP chassis ID (MAC, address or name); the device ID for CDP
+    pub chassis_id: String,
+    pub port_id: String,
+    pub port_description: String,
+    pub system_name: String,
+    /// LLDP system description or CDP software version
+    pub system_description: String,
+    /// CDP platform, e.g. "cisco WS-C3750E-24PD"
+    pub platform: String,
+    /// Port VLAN ID (LLDP 802.1 PVID) or CDP native VLAN
+    pub vlan: Option<u16>,
+    pub mgmt_addrs: Vec<IpAddr>,
+    /// Seconds the information stays valid
+    pub ttl: u16,
+}
+
+impl Neighbor {
+    fn new(protocol: Protocol, source: &[u8]) -> Neighbor {
+        Neighbor {
+            protocol,
+            source_mac: format_mac(source),
+            chassis_id: String::new(),
+            port_id: String::new(),
+            port_description: String::new(),
+            system_name: String::new(),
+            system_description: String::new(),
+            platform: String::new(),
+            vlan: None,
+            mgmt_addrs: Vec::new(),
+            ttl: 0,
+        }
+    }
+
+    /// Key of the switch port: the same neighbor re-announces every 30-60 s.
+    pub fn key(&self) -> (Protocol, String, String) {
+        (self.protocol, self.chassis_id.clone(), self.port_id.clone())
+    }
+
+    /// Lines shown in the LLDP tab.
+    pub fn format_lines(&self) -> Vec<String> {
+        let name = if self.system_name.is_empty() { &self.chassis_id } else { &self.system_name };
+        let mut lines = vec![format!("[{}] {}  port {}", self.protocol.label(), name, if self.port_id.is_empty() { "?" } else { &self.port_id })];
+        if !self.port_description.is_empty() && self.port_description != self.port_id {
+            lines.push(format!("    Port descr: {}", self.port_description));
+        }
+        if self.chassis_id != self.system_name {
+            lines.push(format!("    Chassis ID: {}", self.chassis_id));
+        }
+        let vlan = self.vlan.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+        let mgmt = if self.mgmt_addrs.is_empty() {
+            "-".to_string()
+        } else {
+            self.mgmt_addrs.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ")
+        };
+        lines.push(format!("    VLAN: {}  Mgmt: {}", vlan, mgmt));
+        if !self.platform.is_empty() {
+            lines.push(format!("    Platform: {}", self.platform));
+        }
+        if let Some(descr) = self.system_description.lines().map(str::trim).find(|l| !l.is_empty()) {
+            lines.push(format!("    Descr: {}", descr));
+        }
+        lines.push(format!("    From {}  TTL {} s", self.source_mac, self.ttl));
+        lines
+    }
+}
+
+fn format_mac(b: &[u8]) -> String {
+    b.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
+
+fn text(b: &[u8]) -> String {
+    String::from_utf8_lossy(b).trim_end_matches('\0').trim().to_string()
+}
+
+/// IANA address family number + address (LLDP network address subtype).
+fn family_address(b: &[u8]) -> Option<IpAddr> {
+    match (b.first()?, b.len()) {
+        (1, 5) => Some(IpAddr::V4(Ipv4Addr::new(b[1], b[2], b[3], b[4]))),
+        (2, 17) => <[u8; 16]>::try_from(&b[1..]).ok().map(|a| IpAddr::V6(Ipv6Addr::from(a))),
+        _ => None,
+    }
+}
+
+/// Chassis ID / port ID value by subtype: MACs and addresses are formatted, names kept as text.
+fn id_value(b: &[u8], mac_subtype: u8, addr_subtype: u8) -> String {
+    let Some((&subtype, value)) = b.split_first() else { return String::new() };
+    if subtype == mac_subtype && value.len() == 6 {
+        format_mac(value)
+    } else if subtype == addr_subtype {
+        family_address(value).map(|a| a.to_string()).unwrap_or_else(|| format_mac(value))
+    } else {
+        text(value)
+    }
+}
+
+/// Decode an Ethernet frame carrying LLDP or CDP. None for any other frame.
+pub fn parse_frame(frame: &[u8]) -> Option<Neighbor> {
+    let source = frame.get(6..12)?;
+    let mut pos = 12;
+    let mut ethertype = u16::from_be_bytes([*frame.get(pos)?, *frame.get(pos + 1)?]);
+    if ethertype == ETHERTYPE_VLAN {
+        pos += 4;
+        ethertype = u16::from_be_bytes([*frame.get(pos)?, *frame.get(pos + 1)?]);
+    }
+    let payload = frame.get(pos + 2..)?;
+    if ethertype == ETHERTYPE_LLDP {
+        parse_lldp(payload, source)
+    } else if ethertype <= 1500 && payload.starts_with(&CDP_SNAP) {
+        // 802.3 length field: trailing padding is cut off
+        let end = (ethertype as usize).min(payload.len());
+        parse_cdp(&payload[CDP_SNAP.len()..end], source)
+    } else {
+        None
+    }
+}
+
+/// LLDPDU: TLVs with a 7-bit type and 9-bit length, up to the End TLV.
+fn parse_lldp(pdu: &[u8], source: &[u8]) -> Option<Neighbor> {
+    let mut n = Neighbor::new(Protocol::Lldp, source);
+    let mut pos = 0;
+    while pos + 2 <= pdu.len() {
+        let header = u16::from_be_bytes([pdu[pos], pdu[pos + 1]]);
+        let (tlv_type, len) = (header >> 9, (header & 0x01ff) as usize);
+        let value = pdu.get(pos + 2..pos + 2 + len)?;
+        pos += 2 + len;
+        match tlv_type {
+            0 => break,
+            1 => n.chassis_id = id_value(value, 4, 5),
+            2 => n.port_id = id_value(value, 3, 4),
+            3 if len >= 2 => n.ttl = u16::from_be_bytes([value[0], value[1]]),
+            4 => n.port_description = text(value),
+            5 => n.system_name = text(value),
+            6 => n.system_description = text(value),
+            8 => {
+                // Address string length covers the subtype byte
+                let addr_len = *value.first()? as usize;
+                if let Some(addr) = value.get(1..1 + addr_len).and_then(family_address) {
+                    n.mgmt_addrs.push(addr);
+                }
+            }
+            // IEEE 802.1 organizationally specific: Port VLAN ID
+            127 if value.len() >= 6 && value[..4] == [0x00, 0x80, 0xc2, 0x01] => {
+                n.vlan = Some(u16::from_be_bytes([value[4], value[5]])).filter(|v| *v != 0);
+            }
+            _ => {}
+        }
+    }
+    // Chassis ID and port ID are mandatory
+    (!n.chassis_id.is_empty() && !n.port_id.is_empty()).then_some(n)
+}
+
+/// CDP address list: count, then (protocol type, protocol, address) entries; only IP is kept.
+fn cdp_addresses(b: &[u8]) -> Vec<IpAddr> {
+    let mut out = Vec::new();
+    let Some(count) = b.get(..4).map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]])) else { return out };
+    let mut pos = 4;
+    for _ in 0..count {
+        let Some(&proto_len) = b.get(pos + 1) else { break };
+        let proto = b.get(pos + 2..pos + 2 + proto_len as usize);
+        pos += 2 + proto_len as usize;
+        let Some(len) = b.get(pos..pos + 2).map(|l| u16::from_be_bytes([l[0], l[1]]) as usize) else { break };
+        let Some(addr) = b.get(pos + 2..pos + 2 + len) else { break };
+        pos += 2 + len;
+        match (proto, addr.len()) {
+            // NLPID 0xcc: IPv4
+            (Some([0xcc]), 4) => out.push(IpAddr::V4(Ipv4Addr::new(addr[0], addr[1], addr[2], addr[3]))),
+            // 802.2 protocol with the IPv6 ethertype
+            (Some([.., 0x86, 0xdd]), 16) => {
+                if let Ok(a) = <[u8; 16]>::try_from(addr) {
+                    out.push(IpAddr::V6(Ipv6Addr::from(a)));
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// CDP: version, TTL, checksum, then TLVs with 16-bit type and length (header included).
+fn parse_cdp(pdu: &[u8], source: &[u8]) -> Option<Neighbor> {
+    let mut n = Neighbor::new(Protocol::Cdp, source);
+    n.ttl = *pdu.get(1)? as u16;
+    let mut pos = 4;
+    while pos + 4 <= pdu.len() {
+        let tlv_type = u16::from_be_bytes([pdu[pos], pdu[pos + 1]]);
+        let len = u16::from_be_bytes([pdu[pos + 2], pdu[pos + 3]]) as usize;
+        if len < 4 {
+            return None;
+        }
+        let value = pdu.get(pos + 4..pos + len)?;
+        pos += len;
+        match tlv_type {
+            0x0001 => {
+                n.system_name = text(value);
+                n.chassis_id = n.system_name.clone();
+            }
+            0x0002 => n.mgmt_addrs.extend(cdp_addresses(value)),
+            0x0003 => n.port_id = text(value),
+            0x0005 => n.system_description = text(value),
+            0x0006 => n.platform = text(value),
+            0x000a if value.len() >= 2 => n.vlan = Some(u16::from_be_bytes([value[0], value[1]])),
+            // Management addresses come first; the interface addresses follow
+            0x0016 => {
+                let mut addrs = cdp_addresses(value);
+                addrs.retain(|a| !n.mgmt_addrs.contains(a));
+                n.mgmt_addrs.splice(0..0, addrs);
+            }
+            _ => {}
+        }
+    }
+    (!n.chassis_id.is_empty()).then_some(n)
+}
+
+/// Interfaces that can be listened on: up, not loopback, with a MAC address.
+#[cfg(not(windows))]
+pub fn capture_interfaces() -> Vec<String> {
+    pnet_datalink::interfaces().into_iter()
+        .filter(|i| i.is_up() && !i.is_loopback() && i.mac.is_some_and(|m| m.octets() != [0; 6]))
+        .map(|i| i.name)
+        .collect()
+}
+
+#[cfg(windows)]
+pub fn capture_interfaces() -> Vec<String> {
+    Vec::new()
+}
+
+/// Listen on `iface` for up to `wait` (or until `running` is cleared) and pass each
+/// distinct neighbor to `on_neighbor`. Returns the number of neighbors seen.
+/// Needs root / CAP_NET_RAW; the interface is put in promiscuous mode while listening.
+#[cfg(not(windows))]
+pub fn listen(iface: &str, wait: Duration, running: &AtomicBool, mut on_neighbor: impl FnMut(Neighbor)) -> Result<usize, String> {
+    use pnet_datalink::{Channel, Config};
+    use std::{io::ErrorKind, sync::atomic::Ordering, time::Instant};
+
+    let interface = pnet_datalink::interfaces().into_iter().find(|i| i.name == iface)
+        .ok_or_else(|| format!("No such interface: {}", iface))?;
+    let config = Config { read_timeout: Some(Duration::from_millis(200)), ..Config::default() };
+    let mut rx = match pnet_datalink::channel(&interface, config) {
+        Ok(Channel::Ethernet(_, rx)) => rx,
+        Ok(_) => return Err(format!("Unsupported channel type on {}", iface)),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            return Err(format!("Cannot capture on {}: {} (run as root or grant CAP_NET_RAW)", iface, e));
+        }
+        Err(e) => return Err(format!("Cannot capture on {}: {}", iface, e)),
+    };
+    let deadline = Instant::now() + wait;
+    let mut seen = Vec::new();
+    while Instant::now() < deadline && running.load(Ordering::SeqCst) {
+        let frame = match rx.next() {
+            Ok(frame) => frame,
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted) => continue,
+            Err(e) => return Err(format!("Capture on {} failed: {}", iface, e)),
+        };
+        if frame.len() < 14 || (frame[..6] != LLDP_MAC && frame[..6] != CDP_MAC) {
+            continue;
+        }
+        if let Some(n) = parse_frame(frame) {
+            if !seen.contains(&n.key()) {
+                seen.push(n.key());
+                on_neighbor(n);
+            }
+        }
+    }
+    Ok(seen.len())
+}
+
+#[cfg(windows)]
+pub fn listen(_iface: &str, _wait: Duration, _running: &AtomicBool, _on_neighbor: impl FnMut(Neighbor)) -> Result<usize, String> {
+    Err("LLDP/CDP capture is not supported on Windows (no raw Ethernet socket)".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    /// LLDP frame of a Catalyst access port: MAC chassis ID, interface-name port ID,
+    /// management address and 802.1 port VLAN ID. Assembled by hand from the TLV layout
+    /// of a C3750E, not taken from a capture.
+    const LLDP_FRAME: &str = "0180c200000e001b54aabb9888cc020704001b54aabb800409054769312f302f32340602007808154769676162697445746865726e6574312f302f32340a1773772d636f72652d30312e636f72702e6578616d706c650c1a436973636f20494f5320536f6674776172652c204333373530450e0400140004100c05010a000002020000001f00fe060080c20100140000";
+
+    /// CDPv2 frame from the same port: device ID, address, port ID, capabilities, software
+    /// version, platform, native VLAN, duplex and management address TLVs, with the PDU
+    /// checksum a switch would send. Assembled by hand, not taken from a capture.
+    const CDP_FRAME: &str = "01000ccccccc001b54aabb9800dfaaaa0300000c200002b4932a0001001b73772d636f72652d30312e636f72702e6578616d706c6500020011000000010101cc00040a000002000300194769676162697445746865726e6574312f302f3234000400080000002800050052436973636f20494f5320536f6674776172652c2043333735304520536f66747761726520284333373530452d554e4956455253414c4b392d4d292c2056657273696f6e2031352e3228342945313000060018636973636f2057532d4333373530452d32345044000a00060014000b00050100160011000000010101cc00040a000002";
+
+    #[test]
+    fn test_parse_lldp_frame() {
+        let n = parse_frame(&unhex(LLDP_FRAME)).unwrap();
+        assert_eq!(n.protocol, Protocol::Lldp);
+        assert_eq!(n.source_mac, "00:1b:54:aa:bb:98");
+        assert_eq!(n.chassis_id, "00:1b:54:aa:bb:80");
+        assert_eq!(n.port_id, "Gi1/0/24");
+        assert_eq!(n.port_description, "GigabitEthernet1/0/24");
+        assert_eq!(n.system_name, "sw-core-01.corp.example");
+        assert_eq!(n.system_description, "Cisco IOS Software, C3750E");
+        assert_eq!(n.vlan, Some(20));
+        assert_eq!(n.mgmt_addrs, vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]);
+        assert_eq!(n.ttl, 120);
+        assert_eq!(n.format_lines(), vec![
+            "[LLDP] sw-core-01.corp.example  port Gi1/0/24",
+            "    Port descr: GigabitEthernet1/0/24",
+            "    Chassis ID: 00:1b:54:aa:bb:80",
+            "    VLAN: 20  Mgmt: 10.0.0.2",
+            "    Descr: Cisco IOS Software, C3750E",
+            "    From 00:1b:54:aa:bb:98  TTL 120 s",
+        ]);
+
+        // The same frame with an 802.1Q tag
+        let mut tagged = unhex(LLDP_FRAME);
+        tagged.splice(12..12, [0x81, 0x00, 0x00, 0x14]);
+        assert_eq!(parse_frame(&tagged), Some(n));
+    }
+
+    #[test]
+    fn test_parse_cdp_frame() {
+        let mut frame = unhex(CDP_FRAME);
+        // Minimum-size padding after the PDU is ignored
+        frame.extend_from_slice(&[0; 8]);
+        // 802.3 length, LLC/SNAP header, then a PDU whose checksum verifies
+        assert_eq!(usize::from(u16::from_be_bytes([frame[12], frame[13]])), unhex(CDP_FRAME).len() - 14);
+        assert_eq!(crate::native_trace::checksum(&unhex(CDP_FRAME)[22..]), 0);
+        let n = parse_frame(&frame).unwrap();
+        assert_eq!(n.protocol, Protocol::Cdp);
+        assert_eq!(n.system_name, "sw-core-01.corp.example");
+        assert_eq!(n.chassis_id, n.system_name);
+        assert_eq!(n.port_id, "GigabitEthernet1/0/24");
+        assert_eq!(n.platform, "cisco WS-C3750E-24PD");
+        assert!(n.system_description.starts_with("Cisco IOS Software, C3750E Software"));
+        assert_eq!(n.vlan, Some(20));
+        // Address and management address TLVs carry the same IP
+        assert_eq!(n.mgmt_addrs, vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]);
+        assert_eq!(n.ttl, 180);
+        assert_eq!(n.format_lines()[..3], [
+            "[CDP] sw-core-01.corp.example  port GigabitEthernet1/0/24",
+            "    VLAN: 20  Mgmt: 10.0.0.2",
+            "    Platform: cisco WS-C3750E-24PD",
+        ]);
+    }
+
+    #[test]
+    fn test_parse_other_and_truncated_frames() {
+        let lldp = unhex(LLDP_FRAME);
+        let cdp = unhex(CDP_FRAME);
+        // Truncated inside a TLV
+        assert!(parse_frame(&lldp[..40]).is_none());
+        assert!(parse_frame(&cdp[..60]).is_none());
+        // IPv4 frame
+        let mut ip = lldp.clone();
+        ip[12..14].copy_from_slice(&[0x08, 0x00]);
+        assert!(parse_frame(&ip).is_none());
+        assert!(parse_frame(&lldp[..10]).is_none());
+        // Network address chassis ID and IPv6 management address
+        let mut pdu = vec![0x02, 0x06, 0x05, 0x01, 192, 0, 2, 1, 0x04, 0x04, 0x07, b'p', b'2', b'4'];
+        pdu.extend_from_slice(&[0x10, 0x18, 17, 2]);
+        pdu.extend_from_slice(&"2001:db8::2".parse::<Ipv6Addr>().unwrap().octets());
+        pdu.extend_from_slice(&[1, 0, 0, 0, 0, 0]);
+        let n = parse_lldp(&pdu, &[0; 6]).unwrap();
+        assert_eq!(n.chassis_id, "192.0.2.1");
+        assert_eq!(n.port_id, "p24");
+        assert_eq!(n.mgmt_addrs, vec!["2001:db8::2".parse::<IpAddr>().unwrap()]);
+        assert_eq!(n.vlan, None);
+    }
+
+    /// Frames of a classic pcap file (either byte order, Ethernet link type).
+    fn pcap_frames(data: &[u8]) -> Vec<Vec<u8>> {
+        let le = match data.get(..4) {
+            Some([0xd4, 0xc3, 0xb2, 0xa1]) | Some([0x4d, 0x3c, 0xb2, 0xa1]) => true,
+            Some([0xa1, 0xb2, 0xc3, 0xd4]) | Some([0xa1, 0xb2, 0x3c, 0x4d]) => false,
+            _ => panic!("not a pcap file"),
+        };
+        let u32_at = |i: usize| {
+            let b = [data[i], data[i + 1], data[i + 2], data[i + 3]];
+            (if le { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) }) as usize
+        };
+        assert_eq!(u32_at(20), 1, "link type must be Ethernet");
+        let mut frames = Vec::new();
+        let mut pos = 24;
+        while pos + 16 <= data.len() {
+            let len = u32_at(pos + 8);
+            frames.push(data[pos + 16..pos + 16 + len].to_vec());
+            pos += 16 + len;
+        }
+        frames
+    }
+
+    #[test]
+    fn test_pcap_frames() {
+        let frame = unhex(LLDP_FRAME);
+        let mut pcap = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0, 0, 1, 0, 0, 0];
+        for _ in 0..2 {
+            pcap.extend_from_slice(&[0; 8]);
+            pcap.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            pcap.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            pcap.extend_from_slice(&frame);
+        }
+        assert_eq!(pcap_frames(&pcap), vec![frame.clone(), frame]);
+    }
+
+    /// Real switch captures in data/captures (see the README there): every LLDP / CDP
+    /// frame of `<name>.pcap` must decode to the lines recorded in `<name>.expected`.
+    #[test]
+    #[ignore = "needs switch captures in data/captures"]
+    fn test_parse_captured_frames() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("data/captures");
+        let mut seen = Vec::new();
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some("pcap") { continue }
+            let expected = std::fs::read_to_string(path.with_extension("expected")).unwrap();
+            let mut lines = Vec::new();
+            for frame in pcap_frames(&std::fs::read(&path).unwrap()) {
+                if let Some(n) = parse_frame(&frame) {
+                    if n.protocol == Protocol::Cdp {
+                        assert_eq!(crate::native_trace::checksum(&frame[22..]), 0, "{}", path.display());
+                    }
+                    seen.push(n.protocol);
+                    lines.extend(n.format_lines());
+                }
+            }
+            assert_eq!(lines, expected.lines().collect::<Vec<_>>(), "{}", path.display());
+        }
+        assert!(seen.contains(&Protocol::Lldp) && seen.contains(&Protocol::Cdp), "need an LLDP and a CDP capture");
+    }
+}
@@ -0,0 +1,93 @@
+use fltk::{
+    prelude::*,
+    frame::Frame,
+    input::IntInput,
+    menu::Choice,
+    button::Button,
+    text::{TextDisplay, TextBuffer},
+    app,
+};
+use std::{
+    net::Ipv4Addr,
+    sync::{Arc, Mutex, atomic::AtomicBool},
+    thread,
+    time::Duration,
+};
+use crate::lldp;
+use crate::utils::ScanRun;
+
+/// Build the LLDP / CDP tab. Returns the running flag, the output buffer and the display.
+pub fn build_lldp_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>) {
+    Frame::new(10, 30, 480, 25, "Interface to listen on for LLDP / CDP (switch port info)");
+    let mut iface_choice = Choice::new(10, 70, 200, 30, "");
+    for name in lldp::capture_interfaces() {
+        iface_choice.add_choice(&name);
+    }
+    iface_choice.set_value(0);
+
+    let mut clear_btn = Button::new(240, 70, 80, 30, "Clear");
+    let mut listen_btn = Button::new(320, 70, 80, 30, "Listen");
+    let mut stop_btn = Button::new(410, 70, 80, 30, "Stop");
+
+    // Switches advertise every 30 s (LLDP) / 60 s (CDP) by default
+    let _wait_label = Frame::new(10, 110, 80, 25, "Listen (s)");
+    let mut wait_inp = IntInput::new(90, 110, 70, 25, "");
+    wait_inp.set_value("65");
+
+    let mut display = TextDisplay::new(10, 140, 480, 230, "");
+    let buff = TextBuffer::default();
+    display.set_buffer(buff.clone());
+    let display_ref = Arc::new(Mutex::new(display));
+
+    let run = ScanRun::default();
+
+    // Clear
+    {
+        let mut b = buff.clone();
+        clear_btn.set_callback(move |_| b.set_text(""));
+    }
+
+    // Listen: report each neighbor as its first frame arrives
+    {
+        let iface_choice = iface_choice.clone();
+        let wait_inp = wait_inp.clone();
+        let run = run.clone();
+        let mut b = buff.clone();
+        listen_btn.set_callback(move |_| {
+            if run.is_busy() {
+                return; // already running
+            }
+            let Some(iface) = iface_choice.choice() else {
+                b.append("[Error] No interface available for capture\n");
+                return;
+            };
+            let wait = Duration::from_secs(wait_inp.value().parse().ok().filter(|v| (1..=300).contains(v)).unwrap_or(65));
+            b.set_text(&format!("[Info] Listening on {} for {} s...\n", iface, wait.as_secs()));
+
+            let flag_thread = run.start();
+            let run = run.clone();
+            thread::spawn(move || {
+                let send = |line: String| sender.send(("LLDP".to_string(), Ipv4Addr::UNSPECIFIED, false, line));
+                let result = lldp::listen(&iface, wait, &flag_thread, |n| {
+                    for line in n.format_lines() {
+                        send(line);
+                    }
+                });
+                match result {
+                    Ok(0) => send("[Info] No LLDP / CDP frames received (disabled on the switch port?)".to_string()),
+                    Ok(count) => send(format!("[Info] {} neighbor(s) seen", count)),
+                    Err(e) => send(format!("[Error] {}", e)),
+                }
+                run.finish();
+            });
+        });
+    }
+
+    // Stop
+    {
+        let run = run.clone();
+        stop_btn.set_callback(move |_| run.stop());
+    }
+
+    (run.busy_flag(), buff, display_ref)
+}
@@ -1,18 +1,61 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
-use fltk::{prelude::*, app, window::Window, group::{Tabs, Group}, enums::FrameType};
-use std::net::Ipv4Addr;
+use fltk::{prelude::*, app, dialog, window::Window, group::{Tabs, TabsOverflow, Group}, enums::FrameType};
+use std::{net::Ipv4Addr, sync::Arc};
 mod cidr_tab;
 mod ip_list_tab;
 mod tracert_tab;
+mod pmtu_tab;
 mod port_tab;
+mod dns_tab;
+mod mdns_tab;
+mod ssdp_tab;
+mod snmp_tab;
+mod lldp_tab;
+mod dhcp_tab;
+mod wol_tab;
+mod health_tab;
+mod hops;
+mod native_trace;
+mod pmtu;
+mod mtr;
+mod asn;
+mod route_graph;
+mod rdns;
+mod dns_client;
+mod mdns;
+mod lldp;
+mod dhcp;
+mod wol;
+mod health;
+mod netbios;
+mod smb;
+mod snmp;
+mod ssdp;
+mod targets;
+mod target_import;
+mod exclusions;
+mod rate;
+mod liveness;
+mod services;
+mod config;
 mod utils;
 
 fn main() {
     // FLTKアプリケーションを初期化
     let app = app::App::default();
+    // 設定ファイルが読めない場合は除外リストが不明なため、スキャンを拒否する
+    let config = Arc::new(config::Config::load().unwrap_or_else(config::Config::unusable));
+    // 逆引きプールとキャッシュは全タブで共有
+    let rdns = Arc::new(rdns::RdnsPool::new());
+    // 送信レート・同時実行数の制限は全スキャナで共有
+    let limiter = Arc::new(rate::RateLimiter::new(config.rate.clone()));
+    // スキャン後にARPキャッシュから記録したMAC（Wake-on-LAN用、ファイルに保存）
+    let macs = Arc::new(wol::MacMemory::load());
     let mut wind = Window::new(100, 100, 500, 400, "Ping Scanner GUI");
     let mut tabs = Tabs::new(0, 0, 500, 400, "");
     tabs.set_frame(FrameType::DownBox);
+    // タブが横幅に収まらない場合はプルダウンで選択
+    tabs.handle_overflow(TabsOverflow::Pulldown);
     tabs.begin();
 
     // 単一チャネルでタブIDによる振り分け方式
@@ -22,35 +65,113 @@ fn main() {
     // CIDRタブの構築
     let cidr_group = Group::new(0, 25, 500, 375, "CIDR");
     cidr_group.begin();
-    let (_running, mut buff) = cidr_tab::build_cidr_tab(sender.clone());
+    let (_running, mut buff) = cidr_tab::build_cidr_tab(sender.clone(), rdns.clone(), config.clone(), limiter.clone(), macs.clone());
     println!("[Debug] Main received CIDR buffer: {:p}", &buff);
     cidr_group.end();
     
     // IP Listタブの構築
     let list_group = Group::new(0, 25, 500, 375, "IP List");
     list_group.begin();
-    let (_running_list, mut buff_list, display_list) = ip_list_tab::build_ip_list_tab(sender.clone());
+    let (_running_list, mut buff_list, display_list) = ip_list_tab::build_ip_list_tab(sender.clone(), rdns.clone(), config.clone(), limiter.clone(), macs.clone());
     println!("[Debug] Main received IP List buffer: {:p}", &buff_list);
     list_group.end();
 
     // Tracertタブの構築
     let tracert_group = Group::new(0, 25, 500, 375, "Tracert");
     tracert_group.begin();
-    let (_running_tr, mut buff_tr, display_tr) = tracert_tab::build_tracert_tab(sender.clone());
+    let (_running_tr, mut buff_tr, display_tr, mut buff_tr_table) = tracert_tab::build_tracert_tab(sender.clone(), config.clone(), limiter.clone(), rdns.clone());
     println!("[Debug] Main received Tracert buffer: {:p}", &buff_tr);
     tracert_group.end();
 
+    // PMTUタブの構築（Path MTUの確認）
+    let pmtu_group = Group::new(0, 25, 500, 375, "PMTU");
+    pmtu_group.begin();
+    let (_running_pmtu, mut buff_pmtu, display_pmtu) = pmtu_tab::build_pmtu_tab(sender.clone(), limiter.clone());
+    pmtu_group.end();
+
     // Portsタブの構築
     let ports_group = Group::new(0, 25, 500, 375, "Ports");
     ports_group.begin();
-    let (_running_ports, mut buff_ports, display_ports) = port_tab::build_port_tab(sender.clone());
+    let (_running_ports, mut buff_ports, display_ports) = port_tab::build_port_tab(sender.clone(), config.clone(), limiter.clone());
     println!("[Debug] Main received Ports buffer: {:p}", &buff_ports);
     ports_group.end();
 
+    // DNSタブの構築
+    let dns_group = Group::new(0, 25, 500, 375, "DNS");
+    dns_group.begin();
+    let (_running_dns, mut buff_dns, display_dns) = dns_tab::build_dns_tab(sender.clone(), limiter.clone(), buff.clone(), buff_list.clone());
+    println!("[Debug] Main received DNS buffer: {:p}", &buff_dns);
+    dns_group.end();
+
+    // mDNSタブの構築
+    let mdns_group = Group::new(0, 25, 500, 375, "mDNS");
+    mdns_group.begin();
+    let (_running_mdns, mut buff_mdns, display_mdns) = mdns_tab::build_mdns_tab(sender.clone());
+    mdns_group.end();
+
+    // SSDPタブの構築
+    let ssdp_group = Group::new(0, 25, 500, 375, "SSDP");
+    ssdp_group.begin();
+    let (_running_ssdp, mut buff_ssdp, display_ssdp) = ssdp_tab::build_ssdp_tab(sender.clone());
+    ssdp_group.end();
+
+    // SNMPタブの構築（CIDR / IP Listの生存ホストを照会）
+    let snmp_group = Group::new(0, 25, 500, 375, "SNMP");
+    snmp_group.begin();
+    let (_running_snmp, mut buff_snmp, display_snmp) = snmp_tab::build_snmp_tab(sender.clone(), config.clone(), limiter.clone(), buff.clone(), buff_list.clone());
+    snmp_group.end();
+
+    // LLDPタブの構築（接続先スイッチ・ポートの確認）
+    let lldp_group = Group::new(0, 25, 500, 375, "LLDP");
+    lldp_group.begin();
+    let (_running_lldp, mut buff_lldp, display_lldp) = lldp_tab::build_lldp_tab(sender.clone());
+    lldp_group.end();
+
+    // DHCPタブの構築（DHCPサーバ検出・不正DHCPサーバの確認）
+    let dhcp_group = Group::new(0, 25, 500, 375, "DHCP");
+    dhcp_group.begin();
+    let (_running_dhcp, mut buff_dhcp, display_dhcp) = dhcp_tab::build_dhcp_tab(sender.clone(), config.clone());
+    dhcp_group.end();
+
+    // WoLタブの構築（CIDR / IP Listの応答なしホストを起動）
+    let wol_group = Group::new(0, 25, 500, 375, "WoL");
+    wol_group.begin();
+    let (_running_wol, mut buff_wol, display_wol) = wol_tab::build_wol_tab(sender.clone(), config.clone(), macs.clone(), limiter.clone(), buff.clone(), buff_list.clone());
+    wol_group.end();
+
+    // Healthタブの構築（ゲートウェイ・DNS・経路の確認）
+    let health_group = Group::new(0, 25, 500, 375, "Health");
+    health_group.begin();
+    let (_running_health, mut buff_health, display_health) = health_tab::build_health_tab(sender.clone(), config.clone(), limiter.clone());
+    health_group.end();
+
     tabs.end();
     wind.end();
     wind.show();
 
+    // 設定ファイルのエラーは起動時に表示（リリースビルドにはコンソールがない）
+    if let Some(e) = &config.load_error {
+        let msg = format!("[Error] {}\nSweeps and port scans are disabled until the configuration file is fixed.", e);
+        buff.append(&format!("{}\n", msg));
+        dialog::alert_default(&msg);
+    }
+
+    // スキャン中は実効レートをタイトルに表示（1秒ごとに更新）
+    {
+        let mut wind = wind.clone();
+        let limiter = limiter.clone();
+        app::add_timeout3(1.0, move |handle| {
+            let title = match limiter.status_line() {
+                Some(status) => format!("Ping Scanner GUI - {}", status),
+                None => "Ping Scanner GUI".to_string(),
+            };
+            if wind.label() != title {
+                wind.set_label(&title);
+            }
+            app::repeat_timeout3(1.0, handle);
+        });
+    }
+
     // イベントループ
     println!("[Debug] Starting event loop");
     while app.wait() {
@@ -69,10 +190,10 @@ fn main() {
                 }
                 "IPLIST" => {
                     println!("[Debug] Processing IP List result");
-                    // 無効なIPアドレスの場合の処理
-                    if ip == Ipv4Addr::UNSPECIFIED && !alive && host_info == "Invalid IP" {
+                    // 無効な入力・解決できないホスト名の場合の処理
+                    if ip == Ipv4Addr::UNSPECIFIED && !alive {
                         buff_list.append(&format!("{:<15} {:<7} {:<12} {}\n",
-                            "Invalid", "×", "invalid", "Invalid IP"));
+                            "Invalid", "×", "invalid", host_info));
                     } else {
                         buff_list.append(&format!("{:<15} {:<7} {:<12} {}\n",
                             ip, mark, status, host_info));
@@ -89,6 +210,40 @@ fn main() {
                     app::awake();
                     app::redraw();
                 }
+                "CIDR_INFO" => {
+                    // スキャンの報告行（除外件数・停止など）をそのまま追記
+                    buff.append(&format!("{}\n", host_info));
+                }
+                "IPLIST_INFO" => {
+                    buff_list.append(&format!("{}\n", host_info));
+                }
+                "PORTS_INFO" => {
+                    buff_ports.append(&format!("{}\n", host_info));
+                }
+                "CIDR_DNS" | "IPLIST_DNS" => {
+                    // 後から解決したホスト名を該当行のHost Info列に補完
+                    let target = if tab_id == "CIDR_DNS" { &mut buff } else { &mut buff_list };
+                    if let Some(pos) = crate::utils::buffer_host_info_pos(target, &ip.to_string()) {
+                        target.insert(pos, &host_info);
+                    }
+                    // CIDRタブはCIDRの結果と同様にバッファ更新で再描画される
+                    if tab_id == "IPLIST_DNS" {
+                        if let Ok(mut display) = display_list.lock() {
+                            display.redraw();
+                        }
+                    }
+                    app::awake();
+                    app::redraw();
+                }
+                "CIDR_NOTE" | "IPLIST_NOTE" => {
+                    // NetBIOS名 / SMB情報を該当行のHost Info列に追記
+                    let target = if tab_id == "CIDR_NOTE" { &mut buff } else { &mut buff_list };
+                    if let Some(pos) = crate::utils::buffer_note_pos(target, &ip.to_string(), &host_info) {
+                        target.insert(pos, &format!("  {}", host_info));
+                    }
+                    app::awake();
+                    app::redraw();
+                }
                 "TRACERT" => {
                     println!("[Debug] Processing Tracert result");
                     if let Some(line) = crate::utils::sanitize_line(&host_info) {
@@ -101,6 +256,15 @@ fn main() {
                     app::awake();
                     app::redraw();
                 }
+                "TRACERT_TABLE" => {
+                    // 解析済みホップ表を丸ごと置き換え
+                    buff_tr_table.set_text(&host_info);
+                    if let Ok(mut display) = display_tr.lock() {
+                        display.redraw();
+                    }
+                    app::awake();
+                    app::redraw();
+                }
                 "PORTS" => {
                     println!("[Debug] Processing Ports result");
                     let mark = if alive { "〇" } else { "×" };
@@ -113,6 +277,103 @@ fn main() {
                     app::awake();
                     app::redraw();
                 }
+                "DNS" => {
+                    buff_dns.append(&format!("{}\n", host_info));
+                    if let Ok(mut display) = display_dns.lock() {
+                        display.redraw();
+                    }
+                    app::awake();
+                    app::redraw();
+                }
+                "MDNS" => {
+                    buff_mdns.append(&format!("{}\n", host_info));
+                    if let Ok(mut display) = display_mdns.lock() {
+                        display.redraw();
+                    }
+                    app::awake();
+                    app::redraw();
+                }
+                "SSDP" => {
+                    buff_ssdp.append(&format!("{}\n", host_info));
+                    if let Ok(mut display) = display_ssdp.lock() {
+                        display.redraw();
+                    }
+                    app::awake();
+                    app::redraw();
+                }
+                "SNMP" => {
+                    buff_snmp.append(&format!("{}\n", host_info));
+                    if let Ok(mut display) = display_snmp.lock() {
+                        display.redraw();
+                    }
+                    app::awake();
+                    app::redraw();
+                }
+                "LLDP" => {
+                    buff_lldp.append(&format!("{}\n", host_info));
+                    if let Ok(mut display) = display_lldp.lock() {
+                        display.redraw();
+                    }
+                    app::awake();
+                    app::redraw();
+                }
+                "DHCP" => {
+                    buff_dhcp.append(&format!("{}\n", host_info));
+                    if let Ok(mut display) = display_dhcp.lock() {
+                        display.redraw();
+                    }
+                    app::awake();
+                    app::redraw();
+                }
+                "WOL" => {
+                    buff_wol.append(&format!("{}\n", host_info));
+                    if let Ok(mut display) = display_wol.lock() {
+                        display.redraw();
+                    }
+                    app::awake();
+                    app::redraw();
+                }
+                "PMTU" => {
+                    buff_pmtu.append(&format!("{}\n", host_info));
+                    if let Ok(mut display) = display_pmtu.lock() {
+                        display.redraw();
+                    }
+                    app::awake();
+                    app::redraw();
+                }
+                "HEALTH" => {
+                    buff_health.append(&format!("{}\n", host_info));
+                    if let Ok(mut display) = display_health.lock() {
+                        display.redraw();
+                    }
+                    app::awake();
+                    app::redraw();
+                }
+                "SSDP_MERGE" => {
+                    // UPnP機器情報を該当IP行に追記し、どちらの一覧にも行がなければCIDRの一覧に行を追加
+                    let ip_str = ip.to_string();
+                    let mut found = false;
+                    for target in [&mut buff, &mut buff_list] {
+                        if let Some(pos) = crate::utils::buffer_note_pos(target, &ip_str, &host_info) {
+                            target.insert(pos, &format!("  {}", host_info));
+                        }
+                        found |= crate::utils::buffer_has_row(target, &ip_str);
+                    }
+                    if !found {
+                        buff.append(&format!("{:<15} {:<7} {:<12} {}\n",
+                            ip, mark, status, host_info));
+                    }
+                    app::redraw();
+                }
+                "MDNS_MERGE" | "SNMP_MERGE" => {
+                    // 発見したサービス / SNMP機器情報をCIDR / IP Listの該当IP行に追記
+                    for target in [&mut buff, &mut buff_list] {
+                        if let Some(pos) = crate::utils::buffer_note_pos(target, &ip.to_string(), &host_info) {
+                            target.insert(pos, &format!("  {}", host_info));
+                        }
+                    }
+                    app::redraw();
+                }
                 _ => {
                     println!("[Debug] Unknown tab_id: {}", tab_id);
                 }
@@ -0,0 +1,397 @@
+// mDNS / DNS-SD service discovery: multicast PTR queries for service types and
+// instances, followed by SRV/TXT/A queries until every instance is resolved
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+use crate::dns_client::{encode_labels, parse_response, DnsRecord, RData, RecordType};
+
+/// mDNS multicast group and port.
+pub const MDNS_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(224, 0, 0, 251)), 5353);
+
+/// Meta query listing every service type announced on the link.
+pub const SERVICES_META: &str = "_services._dns-sd._udp.local";
+
+/// Service types always browsed, besides those listed by the meta query.
+pub const COMMON_SERVICES: &[&str] = &[
+    "_http._tcp", "_https._tcp", "_ipp._tcp", "_ipps._tcp", "_printer._tcp", "_pdl-datastream._tcp",
+    "_scanner._tcp", "_uscan._tcp", "_googlecast._tcp", "_airplay._tcp", "_raop._tcp",
+    "_spotify-connect._tcp", "_smb._tcp", "_afpovertcp._tcp", "_nfs._tcp", "_ssh._tcp", "_sftp-ssh._tcp",
+    "_workstation._tcp", "_device-info._tcp", "_hap._tcp", "_homekit._tcp", "_mqtt._tcp",
+];
+
+/// Full name of a service type, e.g. "_ipp._tcp" -> "_ipp._tcp.local".
+pub fn service_name(service: &str) -> String {
+    let s = service.trim().trim_end_matches('.');
+    if s.ends_with(".local") { s.to_string() } else { format!("{}.local", s) }
+}
+
+/// Labels of a dotted name, e.g. "_ipp._tcp.local" -> ["_ipp", "_tcp", "local"].
+pub fn name_labels(name: &str) -> Vec<String> {
+    name.trim_end_matches('.').split('.').filter(|l| !l.is_empty()).map(str::to_string).collect()
+}
+
+/// Labels of an instance name: the instance label as one label (it may contain dots,
+/// e.g. "Printer 4.0") followed by the labels of the service type.
+fn instance_labels(label: &str, service: &str) -> Vec<String> {
+    let mut labels = vec![label.to_string()];
+    labels.extend(name_labels(service));
+    labels
+}
+
+/// Encode a multicast query (ID 0, no recursion) with several questions, each
+/// name given as its labels.
+pub fn build_mdns_query(questions: &[(Vec<String>, RecordType)]) -> Result<Vec<u8>, String> {
+    let mut msg = vec![0, 0, 0, 0];
+    msg.extend_from_slice(&(questions.len() as u16).to_be_bytes());
+    msg.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    for (labels, rtype) in questions {
+        encode_labels(&mut msg, labels)?;
+        msg.extend_from_slice(&rtype.code().to_be_bytes());
+        msg.extend_from_slice(&[0, 1]); // class IN
+    }
+    Ok(msg)
+}
+
+/// A resolved service instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MdnsInstance {
+    /// Instance label, e.g. "Office Printer"
+    pub instance: String,
+    /// Service type, e.g. "_ipp._tcp"
+    pub service: String,
+    pub host: String,
+    pub port: u16,
+    pub addrs: Vec<Ipv4Addr>,
+    pub txt: Vec<String>,
+}
+
+impl MdnsInstance {
+    /// Lines shown in the mDNS tab.
+    pub fn format_lines(&self) -> Vec<String> {
+        let addrs: Vec<String> = self.addrs.iter().map(|a| a.to_string()).collect();
+        let mut lines = vec![
+            format!("{} ({})", self.instance, self.service),
+            format!("  host {}  addr {}  port {}", if self.host.is_empty() { "?" } else { &self.host },
+                if addrs.is_empty() { "?".to_string() } else { addrs.join(",") }, self.port),
+        ];
+        if !self.txt.is_empty() {
+            lines.push(format!("  txt  {}", self.txt.join(" ")));
+        }
+        lines
+    }
+
+    /// Text appended to scan result rows of the instance's addresses.
+    pub fn merge_label(&self) -> String {
+        format!("mDNS: {} ({})", self.instance, self.service)
+    }
+}
+
+fn key(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Records collected from all responses, and the queries already sent.
+#[derive(Debug, Default)]
+pub struct Discovery {
+    service_types: BTreeSet<String>,
+    /// instance key -> (instance label, service type key)
+    instances: BTreeMap<String, (String, String)>,
+    srv: HashMap<String, (String, u16)>,
+    txt: HashMap<String, Vec<String>>,
+    addrs: HashMap<String, Vec<Ipv4Addr>>,
+    /// Responder address per instance, used when no A record is known
+    sources: HashMap<String, Ipv4Addr>,
+    asked: HashSet<(String, u16)>,
+}
+
+impl Discovery {
+    /// Record every answer and additional record of a response from `from`.
+    pub fn add_records(&mut self, from: Ipv4Addr, records: &[DnsRecord]) {
+        for r in records {
+            let name = key(&r.name);
+            match &r.data {
+                RData::Name(target) if r.rtype == RecordType::Ptr.code() => {
+                    if name == SERVICES_META {
+                        self.service_types.insert(key(target));
+                    } else if name.ends_with("._tcp.local") || name.ends_with("._udp.local") {
+                        // The instance label is everything in front of the service type,
+                        // dots included
+                        let inst = key(target);
+                        let Some(label) = inst.strip_suffix(&format!(".{}", name))
+                            .and_then(|l| target.get(..l.len()))
+                            .map(str::to_string) else { continue };
+                        self.sources.entry(inst.clone()).or_insert(from);
+                        self.instances.entry(inst).or_insert((label, name));
+                    }
+                }
+                RData::Srv { port, target, .. } => {
+                    self.srv.insert(name.clone(), (target.trim_end_matches('.').to_string(), *port));
+                    self.sources.entry(name).or_insert(from);
+                }
+                RData::Txt(parts) => {
+                    self.txt.insert(name, parts.iter().filter(|p| !p.is_empty()).cloned().collect());
+                }
+                RData::A(ip) => {
+                    let list = self.addrs.entry(name).or_default();
+                    if !list.contains(ip) { list.push(*ip); }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Queries still needed: PTR for every known service type, SRV/TXT for every
+    /// instance and A for every SRV target. Each query is returned once.
+    pub fn next_queries(&mut self) -> Vec<(Vec<String>, RecordType)> {
+        let mut wanted: Vec<(Vec<String>, RecordType)> = Vec::new();
+        for t in &self.service_types {
+            wanted.push((name_labels(t), RecordType::Ptr));
+        }
+        for (inst, (label, service)) in &self.instances {
+            if !self.srv.contains_key(inst) { wanted.push((instance_labels(label, service), RecordType::Srv)); }
+            if !self.txt.contains_key(inst) { wanted.push((instance_labels(label, service), RecordType::Txt)); }
+        }
+        for (host, _) in self.srv.values() {
+            if !self.addrs.contains_key(&key(host)) { wanted.push((name_labels(host), RecordType::A)); }
+        }
+        wanted.retain(|(labels, rtype)| self.asked.insert((key(&labels.join(".")), rtype.code())));
+        wanted
+    }
+
+    /// Mark queries as sent.
+    pub fn mark_asked(&mut self, questions: &[(Vec<String>, RecordType)]) {
+        for (labels, rtype) in questions {
+            self.asked.insert((key(&labels.join(".")), rtype.code()));
+        }
+    }
+
+    /// Service types announced through the meta query.
+    pub fn service_types(&self) -> impl Iterator<Item = &String> {
+        self.service_types.iter()
+    }
+
+    /// Every instance found so far, sorted by service type and name.
+    pub fn instances(&self) -> Vec<MdnsInstance> {
+        let mut out: Vec<MdnsInstance> = self.instances.iter().map(|(inst, (label, service))| {
+            let service = service.trim_end_matches(".local").to_string();
+            let label = label.clone();
+            let (host, port) = self.srv.get(inst).cloned().unwrap_or_default();
+            let mut addrs = self.addrs.get(&key(&host)).cloned().unwrap_or_default();
+            if addrs.is_empty() {
+                addrs.extend(self.sources.get(inst));
+            }
+            MdnsInstance { instance: label, service, host, port, addrs, txt: self.txt.get(inst).cloned().unwrap_or_default() }
+        }).collect();
+        out.sort_by(|a, b| (&a.service, &a.instance).cmp(&(&b.service, &b.instance)));
+        out
+    }
+}
+
+/// Browse `services` (and every type listed by the meta query) by sending queries to
+/// `target` (normally `MDNS_ADDR`) and collecting answers for `listen`. Follow-up
+/// queries are sent as new names appear. Stops early when `running` is cleared.
+pub fn discover(target: SocketAddr, services: &[String], listen: Duration, running: &AtomicBool) -> Result<Discovery, String> {
+    let sock = UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("Failed to open UDP socket: {}", e))?;
+    sock.set_read_timeout(Some(Duration::from_millis(100))).map_err(|e| e.to_string())?;
+    if target.ip().is_multicast() {
+        let _ = sock.set_multicast_ttl_v4(255);
+        let _ = sock.set_multicast_loop_v4(true);
+    }
+    let send = |questions: &[(Vec<String>, RecordType)]| -> Result<(), String> {
+        // Keep each message well below the usual MTU
+        for chunk in questions.chunks(8) {
+            let msg = build_mdns_query(chunk)?;
+            sock.send_to(&msg, target).map_err(|e| format!("Failed to send mDNS query: {}", e))?;
+        }
+        Ok(())
+    };
+
+    let mut disc = Discovery::default();
+    let mut first: Vec<(Vec<String>, RecordType)> = vec![(name_labels(SERVICES_META), RecordType::Ptr)];
+    first.extend(services.iter().map(|s| (name_labels(&service_name(s)), RecordType::Ptr)));
+    disc.mark_asked(&first);
+    send(&first)?;
+
+    let deadline = Instant::now() + listen;
+    let mut resent = false;
+    let mut buf = [0u8; 9000];
+    while Instant::now() < deadline && running.load(Ordering::SeqCst) {
+        // Multicast is lossy: repeat the first queries once
+        if !resent && deadline.saturating_duration_since(Instant::now()) < listen / 2 {
+            resent = true;
+            send(&first)?;
+        }
+        let (n, from) = match sock.recv_from(&mut buf) {
+            Ok(r) => r,
+            Err(_) => continue,
+        };
+        let Ok(resp) = parse_response(&buf[..n]) else { continue };
+        let from = match from.ip() { IpAddr::V4(v4) => v4, _ => continue };
+        disc.add_records(from, &resp.answers);
+        disc.add_records(from, &resp.additional);
+        let next = disc.next_queries();
+        if !next.is_empty() {
+            send(&next)?;
+        }
+    }
+    Ok(disc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    fn name(out: &mut Vec<u8>, n: &str) {
+        crate::dns_client::encode_name(out, n).unwrap();
+    }
+
+    fn rr(out: &mut Vec<u8>, owner: &str, rtype: u16, data: &[u8]) {
+        name(out, owner);
+        out.extend_from_slice(&rtype.to_be_bytes());
+        out.extend_from_slice(&[0x80, 0x01, 0, 0, 0x11, 0x94]); // cache-flush IN, TTL 4500
+        out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        out.extend_from_slice(data);
+    }
+
+    fn encoded(n: &str) -> Vec<u8> {
+        let mut v = Vec::new();
+        name(&mut v, n);
+        v
+    }
+
+    /// Response with the given answer and additional records.
+    fn response(answers: &[(String, u16, Vec<u8>)], additional: &[(String, u16, Vec<u8>)]) -> Vec<u8> {
+        let mut msg = vec![0, 0, 0x84, 0, 0, 0];
+        msg.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+        msg.extend_from_slice(&[0, 0]);
+        msg.extend_from_slice(&(additional.len() as u16).to_be_bytes());
+        for (n, t, d) in answers.iter().chain(additional) {
+            rr(&mut msg, n, *t, d);
+        }
+        msg
+    }
+
+    /// Questions (name, type) of a query.
+    fn questions(msg: &[u8]) -> Vec<(String, u16)> {
+        let count = u16::from_be_bytes([msg[4], msg[5]]);
+        let mut pos = 12;
+        let mut out = Vec::new();
+        for _ in 0..count {
+            let mut labels = Vec::new();
+            while msg[pos] != 0 {
+                let len = msg[pos] as usize;
+                labels.push(String::from_utf8_lossy(&msg[pos + 1..pos + 1 + len]).into_owned());
+                pos += 1 + len;
+            }
+            out.push((labels.join("."), u16::from_be_bytes([msg[pos + 1], msg[pos + 2]])));
+            pos += 5;
+        }
+        out
+    }
+
+    /// Stand-in responder: a printer announced through the meta query, whose SRV/TXT
+    /// answers only come on request, and a Chromecast answered in one go.
+    fn spawn_responder() -> SocketAddr {
+        let sock = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = sock.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buf = [0u8; 1500];
+            while let Ok((n, from)) = sock.recv_from(&mut buf) {
+                for (q, t) in questions(&buf[..n]) {
+                    let reply = match (q.as_str(), t) {
+                        ("_services._dns-sd._udp.local", 12) => response(&[(q.clone(), 12, encoded("_ipp._tcp.local"))], &[]),
+                        ("_ipp._tcp.local", 12) => response(&[(q.clone(), 12, encoded("Office Printer._ipp._tcp.local"))], &[]),
+                        ("Office Printer._ipp._tcp.local", 33) => {
+                            let mut d = vec![0, 0, 0, 0, 0x02, 0x77];
+                            d.extend(encoded("printer.local"));
+                            response(&[(q.clone(), 33, d)], &[])
+                        }
+                        ("Office Printer._ipp._tcp.local", 16) => response(&[(q.clone(), 16, b"\x0crp=ipp/print\x05ty=HP".to_vec())], &[]),
+                        ("printer.local", 1) => response(&[(q.clone(), 1, vec![192, 168, 1, 50])], &[]),
+                        ("_googlecast._tcp.local", 12) => {
+                            let inst = "Living Room._googlecast._tcp.local".to_string();
+                            let mut srv = vec![0, 0, 0, 0, 0x1f, 0x49];
+                            srv.extend(encoded("cc-1234.local"));
+                            response(&[(q.clone(), 12, encoded(&inst))], &[
+                                (inst.clone(), 33, srv),
+                                (inst, 16, b"\x07fn=Cast".to_vec()),
+                                ("cc-1234.local".to_string(), 1, vec![192, 168, 1, 60]),
+                            ])
+                        }
+                        _ => continue,
+                    };
+                    let _ = sock.send_to(&reply, from);
+                }
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_build_query_and_service_names() {
+        let q = build_mdns_query(&[(name_labels(SERVICES_META), RecordType::Ptr), (name_labels("_ipp._tcp.local"), RecordType::Ptr)]).unwrap();
+        assert_eq!(&q[..6], &[0, 0, 0, 0, 0, 2]);
+        assert_eq!(questions(&q), vec![("_services._dns-sd._udp.local".to_string(), 12), ("_ipp._tcp.local".to_string(), 12)]);
+        assert_eq!(service_name("_ipp._tcp"), "_ipp._tcp.local");
+        assert_eq!(service_name("_ipp._tcp.local."), "_ipp._tcp.local");
+    }
+
+    #[test]
+    fn test_discover_against_stand_in() {
+        let addr = spawn_responder();
+        let running = Arc::new(AtomicBool::new(true));
+        let disc = discover(addr, &["_googlecast._tcp".to_string()], Duration::from_millis(600), &running).unwrap();
+        assert_eq!(disc.service_types().collect::<Vec<_>>(), vec!["_ipp._tcp.local"]);
+        let found = disc.instances();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0], MdnsInstance {
+            instance: "Living Room".to_string(),
+            service: "_googlecast._tcp".to_string(),
+            host: "cc-1234.local".to_string(),
+            port: 8009,
+            addrs: vec![Ipv4Addr::new(192, 168, 1, 60)],
+            txt: vec!["fn=Cast".to_string()],
+        });
+        assert_eq!(found[1].instance, "Office Printer");
+        assert_eq!(found[1].port, 631);
+        assert_eq!(found[1].addrs, vec![Ipv4Addr::new(192, 168, 1, 50)]);
+        assert_eq!(found[1].txt, vec!["rp=ipp/print", "ty=HP"]);
+        assert_eq!(found[1].merge_label(), "mDNS: Office Printer (_ipp._tcp)");
+        assert_eq!(found[1].format_lines()[1], "  host printer.local  addr 192.168.1.50  port 631");
+    }
+
+    #[test]
+    fn test_instance_without_address_uses_responder() {
+        let mut disc = Discovery::default();
+        let resp = parse_response(&response(&[("_ssh._tcp.local".to_string(), 12, encoded("nas._ssh._tcp.local"))], &[])).unwrap();
+        disc.add_records(Ipv4Addr::new(10, 0, 0, 9), &resp.answers);
+        let next = disc.next_queries();
+        assert_eq!(next.len(), 2);
+        assert!(disc.next_queries().is_empty(), "each query is sent once");
+        let found = disc.instances();
+        assert_eq!(found[0].instance, "nas");
+        assert_eq!(found[0].addrs, vec![Ipv4Addr::new(10, 0, 0, 9)]);
+    }
+
+    #[test]
+    fn test_instance_label_with_dots() {
+        // "Printer 4.0" is a single label; the PTR target is built from raw labels
+        let mut target = vec![11];
+        target.extend_from_slice(b"Printer 4.0");
+        target.extend(encoded("_ipp._tcp.local"));
+        let mut disc = Discovery::default();
+        let resp = parse_response(&response(&[("_ipp._tcp.local".to_string(), 12, target)], &[])).unwrap();
+        disc.add_records(Ipv4Addr::new(10, 0, 0, 7), &resp.answers);
+        let next = disc.next_queries();
+        assert_eq!(next[0].0, vec!["Printer 4.0", "_ipp", "_tcp", "local"]);
+        let q = build_mdns_query(&next[..1]).unwrap();
+        assert_eq!(&q[12..24], b"\x0bPrinter 4.0");
+        assert_eq!(disc.instances()[0].instance, "Printer 4.0");
+        assert_eq!(disc.instances()[0].service, "_ipp._tcp");
+    }
+}
@@ -0,0 +1,99 @@
+use fltk::{
+    prelude::*,
+    frame::Frame,
+    input::{Input, IntInput},
+    button::{Button, CheckButton},
+    text::{TextDisplay, TextBuffer},
+    app,
+};
+use std::{
+    net::Ipv4Addr,
+    sync::{Arc, Mutex, atomic::AtomicBool},
+    thread,
+    time::Duration,
+};
+use crate::mdns::{self, COMMON_SERVICES, MDNS_ADDR};
+use crate::utils::ScanRun;
+
+/// Build the mDNS tab. Returns the running flag, the output buffer and the display.
+pub fn build_mdns_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>) {
+    Frame::new(10, 30, 480, 25, "Extra service types (e.g. _ipp._tcp, _myapp._udp)");
+    let input = Input::new(10, 70, 200, 30, "");
+
+    let mut clear_btn = Button::new(240, 70, 80, 30, "Clear");
+    let mut browse_btn = Button::new(320, 70, 80, 30, "Browse");
+    let mut stop_btn = Button::new(410, 70, 80, 30, "Stop");
+
+    let _listen_label = Frame::new(10, 110, 80, 25, "Listen (ms)");
+    let mut listen_inp = IntInput::new(90, 110, 70, 25, "");
+    listen_inp.set_value("3000");
+    let mut merge_cb = CheckButton::new(240, 110, 250, 25, "Merge into CIDR / IP List results");
+    merge_cb.set_value(true);
+
+    let mut display = TextDisplay::new(10, 140, 480, 230, "");
+    let buff = TextBuffer::default();
+    display.set_buffer(buff.clone());
+    let display_ref = Arc::new(Mutex::new(display));
+
+    let run = ScanRun::default();
+
+    // Clear
+    {
+        let mut b = buff.clone();
+        clear_btn.set_callback(move |_| b.set_text(""));
+    }
+
+    // Browse: meta query plus common and extra service types
+    {
+        let inp = input.clone();
+        let listen_inp = listen_inp.clone();
+        let merge_cb = merge_cb.clone();
+        let run = run.clone();
+        let mut b = buff.clone();
+        browse_btn.set_callback(move |_| {
+            if run.is_busy() {
+                return; // already running
+            }
+            let mut services: Vec<String> = COMMON_SERVICES.iter().map(|s| s.to_string()).collect();
+            services.extend(inp.value().split(|c: char| c == ',' || c.is_whitespace()).filter(|s| !s.is_empty()).map(str::to_string));
+            let listen = Duration::from_millis(listen_inp.value().parse().ok().filter(|v| *v >= 100).unwrap_or(3000));
+            let merge = merge_cb.value();
+            b.set_text(&format!("[Info] Browsing {} service types for {} ms...\n", services.len() + 1, listen.as_millis()));
+
+            let flag_thread = run.start();
+            let run = run.clone();
+            thread::spawn(move || {
+                match mdns::discover(MDNS_ADDR, &services, listen, &flag_thread) {
+                    Ok(disc) => {
+                        let types: Vec<String> = disc.service_types().cloned().collect();
+                        if !types.is_empty() {
+                            sender.send(("MDNS".to_string(), Ipv4Addr::UNSPECIFIED, false, format!("Announced types: {}", types.join(", "))));
+                        }
+                        let found = disc.instances();
+                        for inst in &found {
+                            for line in inst.format_lines() {
+                                sender.send(("MDNS".to_string(), Ipv4Addr::UNSPECIFIED, false, line));
+                            }
+                            if merge {
+                                for ip in &inst.addrs {
+                                    sender.send(("MDNS_MERGE".to_string(), *ip, true, inst.merge_label()));
+                                }
+                            }
+                        }
+                        sender.send(("MDNS".to_string(), Ipv4Addr::UNSPECIFIED, false, format!("[Info] {} service instance(s) found", found.len())));
+                    }
+                    Err(e) => sender.send(("MDNS".to_string(), Ipv4Addr::UNSPECIFIED, false, format!("[Error] {}", e))),
+                }
+                run.finish();
+            });
+        });
+    }
+
+    // Stop
+    {
+        let run = run.clone();
+        stop_btn.set_callback(move |_| run.stop());
+    }
+
+    (run.busy_flag(), buff, display_ref)
+}
@@ -0,0 +1,228 @@
+// MTR-style per-hop statistics accumulated over repeated trace rounds
+
+use serde_json::json;
+use std::collections::BTreeMap;
+use crate::hops::{HopRecord, Responder};
+
+/// Pause between the start of two rounds in continuous mode.
+pub const ROUND_INTERVAL_MS: u64 = 1000;
+
+/// Running statistics of one hop.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HopStats {
+    pub hop: u32,
+    pub responders: Vec<Responder>,
+    pub sent: u32,
+    pub received: u32,
+    pub last: Option<f64>,
+    pub best: Option<f64>,
+    pub worst: Option<f64>,
+    sum: f64,
+    jitter_sum: f64,
+    jitter_count: u32,
+}
+
+impl HopStats {
+    fn record(&mut self, rtt: Option<f64>) {
+        self.sent += 1;
+        let Some(v) = rtt else { return };
+        if let Some(prev) = self.last {
+            self.jitter_sum += (v - prev).abs();
+            self.jitter_count += 1;
+        }
+        self.received += 1;
+        self.sum += v;
+        self.last = Some(v);
+        self.best = Some(self.best.map_or(v, |b| b.min(v)));
+        self.worst = Some(self.worst.map_or(v, |w| w.max(v)));
+    }
+
+    /// Lost probes in percent.
+    pub fn loss_pct(&self) -> f64 {
+        if self.sent == 0 { return 0.0; }
+        f64::from(self.sent - self.received) * 100.0 / f64::from(self.sent)
+    }
+
+    /// Mean RTT of the answered probes.
+    pub fn avg(&self) -> Option<f64> {
+        if self.received == 0 { None } else { Some(self.sum / f64::from(self.received)) }
+    }
+
+    /// Mean absolute difference between consecutive answered RTTs.
+    pub fn jitter(&self) -> Option<f64> {
+        if self.jitter_count == 0 { None } else { Some(self.jitter_sum / f64::from(self.jitter_count)) }
+    }
+}
+
+/// Statistics of all hops over the rounds run so far.
+#[derive(Debug, Clone, Default)]
+pub struct MtrStats {
+    pub target: String,
+    /// Start time of the session, for reports.
+    pub started: String,
+    pub rounds: u32,
+    hops: BTreeMap<u32, HopStats>,
+}
+
+impl MtrStats {
+    pub fn new(target: &str, started: &str) -> MtrStats {
+        MtrStats { target: target.to_string(), started: started.to_string(), ..Default::default() }
+    }
+
+    /// Merge the hop records of one round.
+    pub fn add_round(&mut self, round: &[HopRecord]) {
+        self.rounds += 1;
+        for rec in round {
+            let st = self.hops.entry(rec.hop).or_insert_with(|| HopStats { hop: rec.hop, ..Default::default() });
+            for r in &rec.responders {
+                // A name resolved after the first round fills in the known responder
+                match st.responders.iter_mut().find(|x| x.addr == r.addr) {
+                    Some(x) if x.hostname.is_none() => x.hostname = r.hostname.clone(),
+                    Some(_) => {}
+                    None => st.responders.push(r.clone()),
+                }
+            }
+            for rtt in &rec.rtts {
+                st.record(*rtt);
+            }
+        }
+    }
+
+    pub fn hops(&self) -> impl Iterator<Item = &HopStats> {
+        self.hops.values()
+    }
+
+    /// Render the statistics like `mtr`'s live view.
+    pub fn format_table(&self) -> String {
+        let mut out = format!(
+            "{:<4} {:<16} {:>6} {:>5} {:>5} {:>7} {:>7} {:>7} {:>7} {:>7}\n",
+            "Hop", "Host", "Loss%", "Snt", "Rcv", "Last", "Avg", "Best", "Wrst", "Jttr"
+        );
+        let ms = |v: Option<f64>| v.map(|v| format!("{:.1}", v)).unwrap_or_else(|| "-".to_string());
+        for st in self.hops() {
+            let host = st.responders.first().map(|r| r.addr.to_string()).unwrap_or_else(|| "???".to_string());
+            out.push_str(&format!(
+                "{:<4} {:<16} {:>5.1}% {:>5} {:>5} {:>7} {:>7} {:>7} {:>7} {:>7}\n",
+                st.hop, host, st.loss_pct(), st.sent, st.received,
+                ms(st.last), ms(st.avg()), ms(st.best), ms(st.worst), ms(st.jitter())
+            ));
+            for r in &st.responders {
+                let details: Vec<&str> = [&r.hostname, &r.owner].into_iter().flatten().map(String::as_str).collect();
+                if !details.is_empty() {
+                    out.push_str(&format!("{:<4}   {} = {}\n", "", r.addr, details.join("  ")));
+                }
+            }
+        }
+        out
+    }
+
+    /// Plain-text report for sharing (e.g. with an ISP).
+    pub fn to_report(&self) -> String {
+        format!(
+            "MTR report\nTarget: {}\nStarted: {}\nRounds: {}\n\n{}",
+            self.target, self.started, self.rounds, self.format_table()
+        )
+    }
+
+    /// CSV export, one row per hop.
+    pub fn to_csv(&self) -> String {
+        let ms = |v: Option<f64>| v.map(|v| format!("{:.3}", v)).unwrap_or_default();
+        let mut out = String::from("hop,addresses,hostnames,loss_pct,sent,received,last_ms,avg_ms,best_ms,worst_ms,jitter_ms\n");
+        for st in self.hops() {
+            let addrs: Vec<String> = st.responders.iter().map(|r| r.addr.to_string()).collect();
+            let names: Vec<String> = st.responders.iter().filter_map(|r| r.hostname.clone()).collect();
+            out.push_str(&format!(
+                "{},{},{},{:.1},{},{},{},{},{},{},{}\n",
+                st.hop, addrs.join(" "), names.join(" "), st.loss_pct(), st.sent, st.received,
+                ms(st.last), ms(st.avg()), ms(st.best), ms(st.worst), ms(st.jitter())
+            ));
+        }
+        out
+    }
+
+    /// JSON export.
+    pub fn to_json(&self) -> String {
+        let hops: Vec<serde_json::Value> = self.hops().map(|st| json!({
+            "hop": st.hop,
+            "responders": st.responders,
+            "loss_pct": st.loss_pct(),
+            "sent": st.sent,
+            "received": st.received,
+            "last_ms": st.last,
+            "avg_ms": st.avg(),
+            "best_ms": st.best,
+            "worst_ms": st.worst,
+            "jitter_ms": st.jitter(),
+        })).collect();
+        let doc = json!({ "target": self.target, "started": self.started, "rounds": self.rounds, "hops": hops });
+        serde_json::to_string_pretty(&doc).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::IpAddr;
+
+    fn hop(n: u32, addr: Option<&str>, rtts: Vec<Option<f64>>) -> HopRecord {
+        let mut rec = HopRecord::new(n);
+        if let Some(a) = addr {
+            rec.add_responder(a.parse::<IpAddr>().unwrap(), None);
+        }
+        rec.rtts = rtts;
+        rec
+    }
+
+    #[test]
+    fn test_stats_over_rounds() {
+        let mut m = MtrStats::default();
+        m.add_round(&[hop(1, Some("192.168.0.1"), vec![Some(1.0)]), hop(2, Some("10.0.0.1"), vec![Some(10.0)])]);
+        m.add_round(&[hop(1, Some("192.168.0.1"), vec![Some(3.0)]), hop(2, None, vec![None])]);
+        m.add_round(&[hop(1, Some("192.168.0.1"), vec![Some(2.0)]), hop(2, Some("10.0.0.2"), vec![Some(14.0)])]);
+        assert_eq!(m.rounds, 3);
+        let hops: Vec<&HopStats> = m.hops().collect();
+        let h1 = hops[0];
+        assert_eq!((h1.sent, h1.received), (3, 3));
+        assert_eq!(h1.last, Some(2.0));
+        assert_eq!(h1.best, Some(1.0));
+        assert_eq!(h1.worst, Some(3.0));
+        assert_eq!(h1.avg(), Some(2.0));
+        assert_eq!(h1.jitter(), Some(1.5)); // |3-1|, |2-3|
+        assert_eq!(h1.loss_pct(), 0.0);
+
+        let h2 = hops[1];
+        assert_eq!((h2.sent, h2.received), (3, 2));
+        assert!((h2.loss_pct() - 33.333).abs() < 0.01);
+        assert_eq!(h2.responders.len(), 2, "responders are merged across rounds");
+        assert_eq!(h2.jitter(), Some(4.0));
+    }
+
+    #[test]
+    fn test_empty_hop_stats() {
+        let st = HopStats::default();
+        assert_eq!(st.loss_pct(), 0.0);
+        assert_eq!(st.avg(), None);
+        assert_eq!(st.jitter(), None);
+    }
+
+    #[test]
+    fn test_report_exports() {
+        let mut m = MtrStats::new("8.8.8.8", "now");
+        m.add_round(&[hop(1, Some("192.168.0.1"), vec![Some(1.0)]), hop(2, None, vec![None])]);
+        let table = m.format_table();
+        assert!(table.starts_with("Hop"));
+        assert!(table.contains("192.168.0.1"));
+        assert!(table.contains("???"));
+        assert!(m.to_report().contains("Target: 8.8.8.8\nStarted: now\nRounds: 1"));
+
+        let csv = m.to_csv();
+        assert_eq!(csv.lines().nth(1), Some("1,192.168.0.1,,0.0,1,1,1.000,1.000,1.000,1.000,"));
+        assert_eq!(csv.lines().nth(2), Some("2,,,100.0,1,0,,,,,"));
+
+        let v: serde_json::Value = serde_json::from_str(&m.to_json()).unwrap();
+        assert_eq!(v["rounds"], 1);
+        assert_eq!(v["target"], "8.8.8.8");
+        assert_eq!(v["hops"][1]["loss_pct"], 100.0);
+        assert!(v["hops"][1]["avg_ms"].is_null());
+    }
+}
@@ -0,0 +1,424 @@
+// In-process traceroute: sends probes with increasing TTL and matches the ICMP replies
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read},
+    net::{IpAddr, Ipv4Addr, SocketAddrV4, UdpSocket},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+use crate::hops::HopRecord;
+use crate::rate::RateLimiter;
+
+/// First destination port of UDP probes (same as classic traceroute).
+pub const UDP_BASE_PORT: u16 = 33434;
+
+/// Default destination port of TCP-SYN probes.
+pub const TCP_DEFAULT_PORT: u16 = 80;
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_DEST_UNREACH: u8 = 3;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_TIME_EXCEEDED: u8 = 11;
+
+/// Kind of probe packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeMode {
+    /// UDP datagrams to increasing high ports
+    Udp,
+    /// ICMP echo requests
+    Icmp,
+    /// TCP SYN (connection attempt) to a fixed port
+    Tcp,
+}
+
+/// Settings of a native trace.
+#[derive(Debug, Clone)]
+pub struct TraceOptions {
+    pub mode: ProbeMode,
+    pub first_ttl: u8,
+    pub max_hops: u8,
+    /// Probes sent per hop.
+    pub queries: u32,
+    pub timeout_ms: u32,
+    /// Number of consecutive hops probed at the same time.
+    pub parallel: u32,
+    /// UDP base port or TCP destination port.
+    pub port: u16,
+    /// Look up responder names; done by the caller through the reverse DNS pool.
+    pub resolve_dns: bool,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        TraceOptions {
+            mode: ProbeMode::Udp,
+            first_ttl: 1,
+            max_hops: 30,
+            queries: 3,
+            timeout_ms: 1000,
+            parallel: 1,
+            port: UDP_BASE_PORT,
+            resolve_dns: true,
+        }
+    }
+}
+
+/// Identifies which probe an ICMP message refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ProbeKey {
+    /// ICMP echo sequence number
+    Icmp(u16),
+    /// UDP destination port
+    Udp(u16),
+    /// TCP source port
+    Tcp(u16),
+}
+
+/// An ICMP message matched to a probe.
+#[derive(Debug, Clone, PartialEq)]
+struct IcmpReply {
+    from: Ipv4Addr,
+    icmp_type: u8,
+    code: u8,
+    key: ProbeKey,
+}
+
+#[derive(Debug, Clone)]
+struct ProbeResult {
+    from: Ipv4Addr,
+    rtt_ms: f64,
+    annotation: Option<&'static str>,
+}
+
+struct Probe {
+    ttl: u8,
+    sent: Instant,
+    result: Option<ProbeResult>,
+    tcp: Option<Socket>,
+}
+
+/// Internet checksum (RFC 1071).
+pub(crate) fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    for chunk in data.chunks(2) {
+        let word = if chunk.len() == 2 { u16::from_be_bytes([chunk[0], chunk[1]]) } else { u16::from(chunk[0]) << 8 };
+        sum += u32::from(word);
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Build an ICMP echo request with a small payload.
+pub(crate) fn build_echo_request(ident: u16, seq: u16, payload_len: usize) -> Vec<u8> {
+    let mut pkt = vec![0u8; 8 + payload_len];
+    pkt[0] = ICMP_ECHO_REQUEST;
+    pkt[4..6].copy_from_slice(&ident.to_be_bytes());
+    pkt[6..8].copy_from_slice(&seq.to_be_bytes());
+    for (i, b) in pkt[8..].iter_mut().enumerate() {
+        *b = 0x20 + (i % 64) as u8;
+    }
+    let sum = checksum(&pkt);
+    pkt[2..4].copy_from_slice(&sum.to_be_bytes());
+    pkt
+}
+
+/// Parse a packet read from a raw ICMP socket (IPv4 header included) and match it
+/// against probes sent to `target`.
+fn parse_icmp_packet(buf: &[u8], target: Ipv4Addr, ident: u16) -> Option<IcmpReply> {
+    if buf.len() < 20 || buf[0] >> 4 != 4 || buf[9] != 1 { return None; }
+    let ihl = usize::from(buf[0] & 0x0f) * 4;
+    let from = Ipv4Addr::new(buf[12], buf[13], buf[14], buf[15]);
+    let icmp = buf.get(ihl..)?;
+    if icmp.len() < 8 { return None; }
+    let (icmp_type, code) = (icmp[0], icmp[1]);
+    let key = match icmp_type {
+        ICMP_ECHO_REPLY => {
+            if from != target || u16::from_be_bytes([icmp[4], icmp[5]]) != ident { return None; }
+            ProbeKey::Icmp(u16::from_be_bytes([icmp[6], icmp[7]]))
+        }
+        ICMP_DEST_UNREACH | ICMP_TIME_EXCEEDED => {
+            // Quoted original datagram: IPv4 header + first 8 bytes of its payload
+            let inner = &icmp[8..];
+            if inner.len() < 20 || inner[0] >> 4 != 4 { return None; }
+            let inner_ihl = usize::from(inner[0] & 0x0f) * 4;
+            if Ipv4Addr::new(inner[16], inner[17], inner[18], inner[19]) != target { return None; }
+            let quote = inner.get(inner_ihl..inner_ihl + 8)?;
+            match inner[9] {
+                1 => {
+                    if quote[0] != ICMP_ECHO_REQUEST || u16::from_be_bytes([quote[4], quote[5]]) != ident { return None; }
+                    ProbeKey::Icmp(u16::from_be_bytes([quote[6], quote[7]]))
+                }
+                17 => ProbeKey::Udp(u16::from_be_bytes([quote[2], quote[3]])),
+                6 => ProbeKey::Tcp(u16::from_be_bytes([quote[0], quote[1]])),
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(IcmpReply { from, icmp_type, code, key })
+}
+
+/// traceroute-style annotation for a destination-unreachable code.
+fn unreach_annotation(code: u8) -> Option<&'static str> {
+    match code {
+        0 => Some("!N"),
+        1 => Some("!H"),
+        2 => Some("!P"),
+        4 => Some("!F"),
+        5 => Some("!S"),
+        9 | 10 | 13 => Some("!X"),
+        _ => None,
+    }
+}
+
+/// Local address the OS would use to reach `target`.
+pub(crate) fn local_ipv4_for(target: Ipv4Addr) -> Ipv4Addr {
+    UdpSocket::bind("0.0.0.0:0")
+        .and_then(|s| { s.connect((target, 9))?; s.local_addr() })
+        .ok()
+        .and_then(|a| match a.ip() { IpAddr::V4(v4) => Some(v4), _ => None })
+        .unwrap_or(Ipv4Addr::UNSPECIFIED)
+}
+
+/// Send one TCP SYN with the given TTL by starting a non-blocking connect.
+/// Returns the socket and its local port.
+fn start_tcp_probe(target: Ipv4Addr, port: u16, ttl: u8) -> std::io::Result<(Socket, u16)> {
+    let sock = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP))?;
+    sock.set_ttl(u32::from(ttl))?;
+    sock.set_nonblocking(true)?;
+    sock.bind(&SockAddr::from(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)))?;
+    let local_port = sock.local_addr()?.as_socket_ipv4().map(|a| a.port()).unwrap_or(0);
+    match sock.connect(&SockAddr::from(SocketAddrV4::new(target, port))) {
+        Ok(_) => {}
+        Err(e) if connect_in_progress(&e) => {}
+        Err(e) => return Err(e),
+    }
+    Ok((sock, local_port))
+}
+
+/// Whether a non-blocking connect error only means "SYN sent, not yet answered".
+pub(crate) fn connect_in_progress(e: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    { e.kind() == ErrorKind::WouldBlock || e.raw_os_error() == Some(libc::EINPROGRESS) }
+    #[cfg(not(unix))]
+    { e.kind() == ErrorKind::WouldBlock }
+}
+
+/// Run a trace to `target`, calling `on_hop` for every finished hop in TTL order.
+/// Responders are reported without host names.
+/// Stops early when `running` is cleared. Probes are paced by `limiter`, which also
+/// caps how many hops are probed at once. Needs raw-socket privileges
+/// (administrator / root or CAP_NET_RAW) to receive ICMP.
+pub fn trace(target: Ipv4Addr, opts: &TraceOptions, running: &AtomicBool, limiter: &RateLimiter, mut on_hop: impl FnMut(HopRecord)) -> Result<(), String> {
+    let raw = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))
+        .map_err(|e| format!("Raw ICMP socket unavailable (run as administrator/root or grant CAP_NET_RAW): {}", e))?;
+    raw.bind(&SockAddr::from(SocketAddrV4::new(local_ipv4_for(target), 0)))
+        .map_err(|e| format!("Failed to bind ICMP socket: {}", e))?;
+    raw.set_read_timeout(Some(Duration::from_millis(20))).map_err(|e| e.to_string())?;
+    let udp = if opts.mode == ProbeMode::Udp {
+        Some(UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("Failed to open UDP socket: {}", e))?)
+    } else {
+        None
+    };
+
+    let ident = (std::process::id() & 0xffff) as u16;
+    let first = opts.first_ttl.max(1);
+    let last = opts.max_hops.max(first);
+    let queries = opts.queries.clamp(1, 10);
+    // Never hold more probes in flight than the rate limits allow
+    let window = opts.parallel.clamp(1, 64).min((limiter.max_batch(64 * queries) / queries).max(1)) as u8;
+    let timeout = Duration::from_millis(u64::from(opts.timeout_ms.max(1)));
+    let target_sa = SockAddr::from(SocketAddrV4::new(target, 0));
+    let mut seq: u16 = 0;
+
+    let mut ttl = first;
+    while ttl <= last {
+        if !running.load(Ordering::SeqCst) { break; }
+        let window_end = ttl.saturating_add(window - 1).min(last);
+
+        // Send every probe of the window
+        let Some(_permit) = limiter.in_flight(u32::from(window_end - ttl + 1) * queries, running) else { break };
+        let mut probes: HashMap<ProbeKey, Probe> = HashMap::new();
+        let mut order: Vec<ProbeKey> = Vec::new();
+        for t in ttl..=window_end {
+            for _ in 0..queries {
+                if !limiter.pace(target, 1, running) { return Ok(()); }
+                seq = seq.wrapping_add(1);
+                let sent = Instant::now();
+                let (key, tcp) = match opts.mode {
+                    ProbeMode::Icmp => {
+                        raw.set_ttl(u32::from(t)).map_err(|e| e.to_string())?;
+                        let pkt = build_echo_request(ident, seq, 32);
+                        let _ = raw.send_to(&pkt, &target_sa);
+                        (ProbeKey::Icmp(seq), None)
+                    }
+                    ProbeMode::Udp => {
+                        let port = opts.port.wrapping_add(seq);
+                        if let Some(u) = &udp {
+                            u.set_ttl(u32::from(t)).map_err(|e| e.to_string())?;
+                            let _ = u.send_to(&[0u8; 32], (target, port));
+                        }
+                        (ProbeKey::Udp(port), None)
+                    }
+                    ProbeMode::Tcp => match start_tcp_probe(target, opts.port, t) {
+                        Ok((s, local_port)) => (ProbeKey::Tcp(local_port), Some(s)),
+                        Err(e) => return Err(format!("Failed to send TCP probe: {}", e)),
+                    },
+                };
+                order.push(key);
+                probes.insert(key, Probe { ttl: t, sent, result: None, tcp });
+            }
+        }
+
+        // Collect replies until every probe is answered or the timeout expires
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0u8; 1500];
+        while Instant::now() < deadline && probes.values().any(|p| p.result.is_none()) {
+            if !running.load(Ordering::SeqCst) { return Ok(()); }
+            if let Ok(n) = (&raw).read(&mut buf) {
+                if let Some(reply) = parse_icmp_packet(&buf[..n], target, ident) {
+                    if let Some(p) = probes.get_mut(&reply.key) {
+                        if p.result.is_none() {
+                            let annotation = if reply.icmp_type == ICMP_DEST_UNREACH { unreach_annotation(reply.code) } else { None };
+                            p.result = Some(ProbeResult { from: reply.from, rtt_ms: p.sent.elapsed().as_secs_f64() * 1000.0, annotation });
+                        }
+                    }
+                }
+            }
+            // TCP: an accepted connection or a RST means the destination answered
+            for p in probes.values_mut().filter(|p| p.result.is_none()) {
+                let Some(sock) = &p.tcp else { continue };
+                let answered = match sock.take_error() {
+                    Ok(Some(e)) => e.kind() == ErrorKind::ConnectionRefused,
+                    _ => sock.peer_addr().is_ok(),
+                };
+                if answered {
+                    p.result = Some(ProbeResult { from: target, rtt_ms: p.sent.elapsed().as_secs_f64() * 1000.0, annotation: None });
+                }
+            }
+        }
+
+        // Report hops of the window in order, stopping at the destination
+        let mut reached = false;
+        for t in ttl..=window_end {
+            let mut rec = HopRecord::new(u32::from(t));
+            for key in order.iter().filter(|k| probes[*k].ttl == t) {
+                match &probes[key].result {
+                    Some(r) => {
+                        rec.rtts.push(Some(r.rtt_ms));
+                        rec.add_responder(IpAddr::V4(r.from), None);
+                        if let Some(a) = r.annotation {
+                            if !rec.annotations.iter().any(|x| x == a) { rec.annotations.push(a.to_string()); }
+                        }
+                        reached |= r.from == target || r.annotation.is_some();
+                    }
+                    None => rec.rtts.push(None),
+                }
+            }
+            on_hop(rec);
+            if reached { return Ok(()); }
+        }
+        ttl = match window_end.checked_add(1) { Some(t) => t, None => break };
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal IPv4 header (no options) carrying `payload`.
+    fn ipv4(src: Ipv4Addr, dst: Ipv4Addr, proto: u8, payload: &[u8]) -> Vec<u8> {
+        let mut h = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, proto, 0, 0];
+        h.extend_from_slice(&src.octets());
+        h.extend_from_slice(&dst.octets());
+        h.extend_from_slice(payload);
+        h
+    }
+
+    fn icmp(icmp_type: u8, code: u8, rest: &[u8]) -> Vec<u8> {
+        let mut v = vec![icmp_type, code, 0, 0, 0, 0, 0, 0];
+        v.extend_from_slice(rest);
+        v
+    }
+
+    const ME: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 10);
+    const ROUTER: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const TARGET: Ipv4Addr = Ipv4Addr::new(8, 8, 8, 8);
+
+    #[test]
+    fn test_checksum_and_echo_request() {
+        let pkt = build_echo_request(0x1234, 7, 32);
+        assert_eq!(pkt.len(), 40);
+        assert_eq!(pkt[0], ICMP_ECHO_REQUEST);
+        assert_eq!(&pkt[4..8], &[0x12, 0x34, 0, 7]);
+        // a packet with a valid checksum sums to zero
+        assert_eq!(checksum(&pkt), 0);
+        assert_eq!(checksum(&[0x00, 0x01, 0xf2]), !(0x0001u16 + 0xf200));
+    }
+
+    #[test]
+    fn test_parse_echo_reply() {
+        let mut reply = build_echo_request(0x1234, 9, 8);
+        reply[0] = ICMP_ECHO_REPLY;
+        let pkt = ipv4(TARGET, ME, 1, &reply);
+        let r = parse_icmp_packet(&pkt, TARGET, 0x1234).unwrap();
+        assert_eq!(r.key, ProbeKey::Icmp(9));
+        assert_eq!(r.from, TARGET);
+        // other identifier or other source is ignored
+        assert!(parse_icmp_packet(&pkt, TARGET, 0x4321).is_none());
+        assert!(parse_icmp_packet(&pkt, ROUTER, 0x1234).is_none());
+    }
+
+    #[test]
+    fn test_parse_time_exceeded_for_each_mode() {
+        // ICMP probe
+        let echo = build_echo_request(0x1234, 3, 0);
+        let quoted = ipv4(ME, TARGET, 1, &echo);
+        let pkt = ipv4(ROUTER, ME, 1, &icmp(ICMP_TIME_EXCEEDED, 0, &quoted));
+        let r = parse_icmp_packet(&pkt, TARGET, 0x1234).unwrap();
+        assert_eq!((r.from, r.icmp_type, r.key), (ROUTER, ICMP_TIME_EXCEEDED, ProbeKey::Icmp(3)));
+
+        // UDP probe: matched by destination port
+        let udp_hdr = [0x9c, 0x40, 0x82, 0x9b, 0, 40, 0, 0]; // 40000 -> 33435
+        let quoted = ipv4(ME, TARGET, 17, &udp_hdr);
+        let pkt = ipv4(ROUTER, ME, 1, &icmp(ICMP_TIME_EXCEEDED, 0, &quoted));
+        assert_eq!(parse_icmp_packet(&pkt, TARGET, 1).unwrap().key, ProbeKey::Udp(33435));
+
+        // TCP probe: matched by source port
+        let tcp_hdr = [0xc3, 0x50, 0x00, 0x50, 0, 0, 0, 1]; // 50000 -> 80
+        let quoted = ipv4(ME, TARGET, 6, &tcp_hdr);
+        let pkt = ipv4(ROUTER, ME, 1, &icmp(ICMP_TIME_EXCEEDED, 0, &quoted));
+        assert_eq!(parse_icmp_packet(&pkt, TARGET, 1).unwrap().key, ProbeKey::Tcp(50000));
+
+        // probes to another destination are not ours
+        let quoted = ipv4(ME, ROUTER, 17, &udp_hdr);
+        let pkt = ipv4(ROUTER, ME, 1, &icmp(ICMP_TIME_EXCEEDED, 0, &quoted));
+        assert!(parse_icmp_packet(&pkt, TARGET, 1).is_none());
+    }
+
+    #[test]
+    fn test_parse_unreachable_and_annotations() {
+        let udp_hdr = [0x9c, 0x40, 0x82, 0x9a, 0, 40, 0, 0];
+        let quoted = ipv4(ME, TARGET, 17, &udp_hdr);
+        let pkt = ipv4(TARGET, ME, 1, &icmp(ICMP_DEST_UNREACH, 3, &quoted));
+        let r = parse_icmp_packet(&pkt, TARGET, 1).unwrap();
+        assert_eq!((r.icmp_type, r.code, r.key), (ICMP_DEST_UNREACH, 3, ProbeKey::Udp(33434)));
+        assert_eq!(unreach_annotation(3), None);
+        assert_eq!(unreach_annotation(1), Some("!H"));
+        assert_eq!(unreach_annotation(0), Some("!N"));
+        assert_eq!(unreach_annotation(13), Some("!X"));
+    }
+
+    #[test]
+    fn test_parse_rejects_garbage() {
+        assert!(parse_icmp_packet(&[], TARGET, 1).is_none());
+        assert!(parse_icmp_packet(&[0x45; 10], TARGET, 1).is_none());
+        let truncated = ipv4(ROUTER, ME, 1, &icmp(ICMP_TIME_EXCEEDED, 0, &[0x45, 0, 0]));
+        assert!(parse_icmp_packet(&truncated, TARGET, 1).is_none());
+    }
+}
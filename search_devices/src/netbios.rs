@@ -0,0 +1,257 @@
+// NetBIOS node status (NBSTAT, UDP 137): computer name, workgroup/domain and MAC of
+// Windows hosts that have no PTR record, plus the optional SMB query on 445
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+    sync::{Arc, atomic::{AtomicBool, Ordering}},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use crate::rate::RateLimiter;
+use crate::smb;
+
+/// NetBIOS name service port.
+pub const NBNS_PORT: u16 = 137;
+
+/// Time to wait for a node status answer.
+pub const NBSTAT_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Encode a node status request for the wildcard name "*".
+pub fn build_node_status_request(txid: u16) -> Vec<u8> {
+    let mut msg = txid.to_be_bytes().to_vec();
+    msg.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+    // First-level encoding: each byte of the 16-byte name becomes two letters 'A' + nibble
+    msg.push(0x20);
+    let mut name = [0u8; 16];
+    name[0] = b'*';
+    for b in name {
+        msg.push(b'A' + (b >> 4));
+        msg.push(b'A' + (b & 0x0f));
+    }
+    msg.push(0);
+    msg.extend_from_slice(&[0x00, 0x21, 0x00, 0x01]); // NBSTAT, IN
+    msg
+}
+
+/// One entry of the node's name table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NbName {
+    pub name: String,
+    /// Suffix byte: 0x00 workstation / domain, 0x20 file server, 0x1c domain controllers...
+    pub suffix: u8,
+    pub group: bool,
+}
+
+/// Parsed node status answer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeStatus {
+    pub names: Vec<NbName>,
+    /// Unit ID; None when the node reports all zeros (e.g. Samba)
+    pub mac: Option<String>,
+}
+
+impl NodeStatus {
+    /// Computer name: the unique workstation (or file server) name.
+    pub fn computer_name(&self) -> Option<&str> {
+        [0x00, 0x20].iter().find_map(|s| self.names.iter().find(|n| !n.group && n.suffix == *s)).map(|n| n.name.as_str())
+    }
+
+    /// Workgroup or domain: the group name with suffix 0x00.
+    pub fn workgroup(&self) -> Option<&str> {
+        self.names.iter().find(|n| n.group && n.suffix == 0x00).map(|n| n.name.as_str())
+    }
+
+    /// Text for the Host Info column, e.g. "NB: PC01 / CORP / aa:bb:cc:dd:ee:ff".
+    pub fn summary(&self) -> Option<String> {
+        let name = self.computer_name()?;
+        let mut parts = vec![name.to_string()];
+        parts.extend(self.workgroup().map(str::to_string));
+        parts.extend(self.mac.clone());
+        Some(format!("NB: {}", parts.join(" / ")))
+    }
+}
+
+/// Parse a node status response to `txid`.
+pub fn parse_node_status(msg: &[u8], txid: u16) -> Result<NodeStatus, String> {
+    if msg.len() < 12 || u16::from_be_bytes([msg[0], msg[1]]) != txid {
+        return Err("Not a node status response".to_string());
+    }
+    if msg[2] & 0x80 == 0 || u16::from_be_bytes([msg[6], msg[7]]) == 0 {
+        return Err("No answer in node status response".to_string());
+    }
+    // Skip the owner name (labels or a compression pointer)
+    let mut pos = 12;
+    loop {
+        let len = *msg.get(pos).ok_or("Truncated node status response")? as usize;
+        if len == 0 {
+            pos += 1;
+            break;
+        }
+        if len & 0xc0 == 0xc0 {
+            pos += 2;
+            break;
+        }
+        pos += 1 + len;
+    }
+    // TYPE, CLASS, TTL, RDLENGTH
+    let rtype = msg.get(pos..pos + 2).ok_or("Truncated node status response")?;
+    if rtype != [0x00, 0x21] {
+        return Err("Answer is not NBSTAT".to_string());
+    }
+    pos += 10;
+    let count = *msg.get(pos).ok_or("Truncated node status response")? as usize;
+    pos += 1;
+    let mut status = NodeStatus::default();
+    for _ in 0..count {
+        let entry = msg.get(pos..pos + 18).ok_or("Truncated name table")?;
+        let name = String::from_utf8_lossy(&entry[..15]).trim_end_matches([' ', '\0']).to_string();
+        status.names.push(NbName { name, suffix: entry[15], group: entry[16] & 0x80 != 0 });
+        pos += 18;
+    }
+    if let Some(unit) = msg.get(pos..pos + 6) {
+        if unit.iter().any(|b| *b != 0) {
+            status.mac = Some(unit.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":"));
+        }
+    }
+    Ok(status)
+}
+
+/// Send a node status request to `target` and wait up to `timeout` for the answer.
+/// None when there is no answer or `running` is cleared.
+pub fn query_node_status(target: SocketAddr, timeout: Duration, running: &AtomicBool) -> Option<NodeStatus> {
+    let sock = UdpSocket::bind("0.0.0.0:0").ok()?;
+    sock.set_read_timeout(Some(Duration::from_millis(100))).ok()?;
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    let txid = (nanos >> 8) as u16 ^ std::process::id() as u16;
+    sock.send_to(&build_node_status_request(txid), target).ok()?;
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 1024];
+    while Instant::now() < deadline && running.load(Ordering::SeqCst) {
+        let Ok((n, from)) = sock.recv_from(&mut buf) else { continue };
+        if from.ip() != target.ip() {
+            continue;
+        }
+        if let Ok(status) = parse_node_status(&buf[..n], txid) {
+            return Some(status);
+        }
+    }
+    None
+}
+
+/// Host information queries run for live hosts of the CIDR and IP List tabs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostQuery {
+    pub netbios: bool,
+    pub smb: bool,
+}
+
+impl HostQuery {
+    pub fn enabled(&self) -> bool {
+        self.netbios || self.smb
+    }
+
+    /// Query `ip` and return the Host Info notes (NetBIOS first, then SMB).
+    pub fn lookup(&self, ip: Ipv4Addr, limiter: &RateLimiter, running: &AtomicBool) -> Vec<String> {
+        self.lookup_at(ip, NBNS_PORT, smb::SMB_PORT, limiter, running)
+    }
+
+    fn lookup_at(&self, ip: Ipv4Addr, nbns_port: u16, smb_port: u16, limiter: &RateLimiter, running: &AtomicBool) -> Vec<String> {
+        let mut notes = Vec::new();
+        if self.netbios && limiter.pace(ip, 1, running) {
+            let target = SocketAddr::V4(SocketAddrV4::new(ip, nbns_port));
+            if let Some(note) = query_node_status(target, NBSTAT_TIMEOUT, running).and_then(|s| s.summary()) {
+                notes.push(note);
+            }
+        }
+        if self.smb && limiter.pace(ip, 1, running) {
+            let target = SocketAddr::new(IpAddr::V4(ip), smb_port);
+            if let Ok(info) = smb::query(target, smb::SMB_TIMEOUT, running) {
+                notes.push(info.summary());
+            }
+        }
+        notes
+    }
+
+    /// Run `lookup` in the background, holding an in-flight slot, and pass each note to `on_note`.
+    pub fn spawn(self, ip: Ipv4Addr, limiter: Arc<RateLimiter>, running: Arc<AtomicBool>, on_note: impl Fn(String) + Send + 'static) {
+        thread::spawn(move || {
+            let Some(_permit) = limiter.in_flight(1, &running) else { return };
+            for note in self.lookup(ip, &limiter, &running) {
+                if running.load(Ordering::SeqCst) {
+                    on_note(note);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate::RateLimits;
+
+    fn name_entry(name: &str, suffix: u8, group: bool) -> Vec<u8> {
+        let mut e = format!("{:<15}", name).into_bytes();
+        e.push(suffix);
+        e.extend_from_slice(if group { &[0x84, 0x00] } else { &[0x04, 0x00] });
+        e
+    }
+
+    /// Node status answer as sent by Windows: owner name repeated, three names, MAC, statistics.
+    fn node_status_response(txid: u16, request: &[u8], mac: [u8; 6]) -> Vec<u8> {
+        let mut msg = txid.to_be_bytes().to_vec();
+        msg.extend_from_slice(&[0x84, 0x00, 0, 0, 0, 1, 0, 0, 0, 0]);
+        msg.extend_from_slice(&request[12..46]);
+        msg.extend_from_slice(&[0x00, 0x21, 0x00, 0x01, 0, 0, 0, 0]);
+        let mut rdata = vec![3];
+        rdata.extend(name_entry("PC01", 0x00, false));
+        rdata.extend(name_entry("CORP", 0x00, true));
+        rdata.extend(name_entry("PC01", 0x20, false));
+        rdata.extend_from_slice(&mac);
+        rdata.extend_from_slice(&[0u8; 40]);
+        msg.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        msg.extend(rdata);
+        msg
+    }
+
+    #[test]
+    fn test_node_status_request_and_parse() {
+        let req = build_node_status_request(0x1234);
+        assert_eq!(req.len(), 50);
+        assert_eq!(&req[13..15], b"CK");
+        assert_eq!(&req[15..45], [b'A'; 30]);
+        assert_eq!(&req[46..], [0x00, 0x21, 0x00, 0x01]);
+
+        let status = parse_node_status(&node_status_response(0x1234, &req, [0xaa, 0xbb, 0xcc, 0, 0x11, 0x22]), 0x1234).unwrap();
+        assert_eq!(status.names.len(), 3);
+        assert_eq!(status.computer_name(), Some("PC01"));
+        assert_eq!(status.workgroup(), Some("CORP"));
+        assert_eq!(status.summary().unwrap(), "NB: PC01 / CORP / aa:bb:cc:00:11:22");
+        // Samba reports a zero MAC
+        let status = parse_node_status(&node_status_response(7, &req, [0; 6]), 7).unwrap();
+        assert_eq!(status.summary().unwrap(), "NB: PC01 / CORP");
+        assert!(parse_node_status(&node_status_response(7, &req, [0; 6]), 8).is_err());
+        assert!(parse_node_status(&node_status_response(7, &req, [0; 6])[..60], 7).is_err());
+    }
+
+    #[test]
+    fn test_lookup_against_stand_in() {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = udp.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            while let Ok((n, from)) = udp.recv_from(&mut buf) {
+                let txid = u16::from_be_bytes([buf[0], buf[1]]);
+                let _ = udp.send_to(&node_status_response(txid, &buf[..n], [2, 0, 0, 0, 0, 1]), from);
+            }
+        });
+        let limiter = RateLimiter::new(RateLimits { pps: 0.0, max_in_flight: 0, host_delay_ms: 0, ping_interval_ms: 0 });
+        let running = AtomicBool::new(true);
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let query = HostQuery { netbios: true, smb: true };
+        // Nothing listens for SMB: only the NetBIOS note
+        assert_eq!(query.lookup_at(Ipv4Addr::LOCALHOST, port, closed, &limiter, &running), vec!["NB: PC01 / CORP / 02:00:00:00:00:01".to_string()]);
+        running.store(false, Ordering::SeqCst);
+        assert!(query.lookup_at(Ipv4Addr::LOCALHOST, port, closed, &limiter, &running).is_empty());
+    }
+}
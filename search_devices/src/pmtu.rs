@@ -0,0 +1,432 @@
+// Path MTU discovery: ICMP echo with Don't-Fragment set, binary search on the packet size
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::{
+    io::Read,
+    net::{Ipv4Addr, SocketAddrV4},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+use crate::native_trace::{build_echo_request, checksum, local_ipv4_for};
+use crate::rate::RateLimiter;
+
+/// IPv4 header (no options) plus ICMP echo header: MTU - OVERHEAD = echo payload.
+pub const OVERHEAD: u16 = 28;
+
+/// Smallest MTU every IPv4 link must carry (RFC 791).
+pub const MIN_MTU: u16 = 68;
+
+/// Largest size offered in the tab (jumbo frames).
+pub const MAX_MTU: u16 = 9000;
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_DEST_UNREACH: u8 = 3;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const CODE_FRAG_NEEDED: u8 = 4;
+
+/// Don't-Fragment flag of the IPv4 header.
+const IP_FLAG_DF: u16 = 0x4000;
+
+/// Settings of one discovery run.
+#[derive(Debug, Clone)]
+pub struct PmtuOptions {
+    /// Size tried first and upper bound of the search.
+    pub max_mtu: u16,
+    pub timeout_ms: u32,
+    /// Echo requests sent per size before it counts as lost.
+    pub retries: u32,
+}
+
+impl Default for PmtuOptions {
+    fn default() -> Self {
+        PmtuOptions { max_mtu: 1500, timeout_ms: 1000, retries: 2 }
+    }
+}
+
+/// Result of the probes of one size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProbeOutcome {
+    /// The echo reply came back: the size fits the whole path.
+    Reply { rtt_ms: f64 },
+    /// A router (or the target) returned "fragmentation needed and DF set".
+    FragNeeded { from: Ipv4Addr, next_hop_mtu: Option<u16> },
+    /// The local stack refused to send: larger than the outgoing interface MTU.
+    LocalTooBig,
+    /// Nothing came back within the timeout.
+    NoReply,
+}
+
+impl ProbeOutcome {
+    pub fn passed(&self) -> bool {
+        matches!(self, ProbeOutcome::Reply { .. })
+    }
+}
+
+/// One probed size and what happened to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProbeStep {
+    pub mtu: u16,
+    pub outcome: ProbeOutcome,
+}
+
+impl ProbeStep {
+    pub fn format_line(&self) -> String {
+        let size = format!("MTU {:>4} (payload {:>4})", self.mtu, self.mtu - OVERHEAD);
+        match self.outcome {
+            ProbeOutcome::Reply { rtt_ms } => format!("{}: reply in {:.1} ms", size, rtt_ms),
+            ProbeOutcome::FragNeeded { from, next_hop_mtu: Some(m) } => format!("{}: fragmentation needed from {} (next-hop MTU {})", size, from, m),
+            ProbeOutcome::FragNeeded { from, next_hop_mtu: None } => format!("{}: fragmentation needed from {}", size, from),
+            ProbeOutcome::LocalTooBig => format!("{}: larger than the local interface MTU", size),
+            ProbeOutcome::NoReply => format!("{}: no reply", size),
+        }
+    }
+}
+
+/// Outcome of a whole discovery.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmtuResult {
+    /// Largest size answered, None when even the minimum got no reply.
+    pub mtu: Option<u16>,
+    /// Every probed size in order.
+    pub steps: Vec<ProbeStep>,
+}
+
+impl PmtuResult {
+    /// Senders of fragmentation-needed messages with the next-hop MTU they reported, first seen first.
+    pub fn frag_sources(&self) -> Vec<(Ipv4Addr, Option<u16>)> {
+        let mut sources: Vec<(Ipv4Addr, Option<u16>)> = Vec::new();
+        for step in &self.steps {
+            if let ProbeOutcome::FragNeeded { from, next_hop_mtu } = step.outcome {
+                if !sources.contains(&(from, next_hop_mtu)) {
+                    sources.push((from, next_hop_mtu));
+                }
+            }
+        }
+        sources
+    }
+
+    /// Larger sizes vanished without any ICMP error: a PMTU black hole on the path.
+    pub fn black_hole(&self) -> bool {
+        let Some(mtu) = self.mtu else { return false };
+        self.steps.iter().any(|s| s.mtu > mtu && s.outcome == ProbeOutcome::NoReply)
+    }
+
+    /// Closing report lines.
+    pub fn summary_lines(&self, target: Ipv4Addr) -> Vec<String> {
+        let Some(mtu) = self.mtu else {
+            return vec![format!("[Error] No reply from {} even at {} bytes (host down or ICMP filtered)", target, MIN_MTU)];
+        };
+        let mut lines = vec![format!("[Info] Path MTU to {}: {} bytes (ICMP payload {}, TCP MSS {})", target, mtu, mtu - OVERHEAD, mtu - 40)];
+        let sources = self.frag_sources();
+        for (from, next_hop_mtu) in &sources {
+            let reported = next_hop_mtu.map(|m| m.to_string()).unwrap_or_else(|| "not given".to_string());
+            lines.push(format!("[Info] Fragmentation needed reported by {} (next-hop MTU {})", from, reported));
+        }
+        if self.black_hole() {
+            lines.push(format!("[Error] Packets larger than {} bytes are dropped without an ICMP error: possible PMTU black hole", mtu));
+        } else if sources.is_empty() && self.steps.iter().any(|s| s.mtu > mtu && s.outcome != ProbeOutcome::LocalTooBig) {
+            lines.push("[Info] No fragmentation-needed message received".to_string());
+        }
+        lines
+    }
+}
+
+/// Binary search for the largest size in `min..=max` that `probe` gets through.
+/// `max` is tried first, then `min`; a next-hop MTU from a fragmentation-needed message
+/// is tried next when it lies inside the open range. `probe` returns None to stop,
+/// in which case the search returns None.
+pub fn search(min: u16, max: u16, mut probe: impl FnMut(u16) -> Option<ProbeOutcome>) -> Option<PmtuResult> {
+    let mut steps = Vec::new();
+    let max = max.max(min);
+    let first = probe(max)?;
+    steps.push(ProbeStep { mtu: max, outcome: first });
+    if first.passed() || max == min {
+        return Some(PmtuResult { mtu: first.passed().then_some(max), steps });
+    }
+    let lowest = probe(min)?;
+    steps.push(ProbeStep { mtu: min, outcome: lowest });
+    if !lowest.passed() {
+        return Some(PmtuResult { mtu: None, steps });
+    }
+
+    // lo always passed, hi always failed
+    let (mut lo, mut hi) = (min, max);
+    let mut hint = next_hop_mtu(&first);
+    while hi - lo > 1 {
+        let mid = hint.filter(|m| lo < *m && *m < hi).unwrap_or(lo + (hi - lo) / 2);
+        let outcome = probe(mid)?;
+        steps.push(ProbeStep { mtu: mid, outcome });
+        if outcome.passed() {
+            lo = mid;
+            hint = None;
+        } else {
+            hi = mid;
+            hint = next_hop_mtu(&outcome);
+        }
+    }
+    Some(PmtuResult { mtu: Some(lo), steps })
+}
+
+fn next_hop_mtu(outcome: &ProbeOutcome) -> Option<u16> {
+    match outcome {
+        ProbeOutcome::FragNeeded { next_hop_mtu, .. } => *next_hop_mtu,
+        _ => None,
+    }
+}
+
+/// An ICMP message matched to the echo request in flight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reply {
+    Echo,
+    FragNeeded { from: Ipv4Addr, next_hop_mtu: Option<u16> },
+}
+
+/// Parse a packet read from the raw ICMP socket (IPv4 header included) and match it
+/// against the echo request `seq` sent to `target`.
+fn parse_reply(buf: &[u8], target: Ipv4Addr, ident: u16, seq: u16) -> Option<Reply> {
+    if buf.len() < 20 || buf[0] >> 4 != 4 || buf[9] != 1 { return None; }
+    let ihl = usize::from(buf[0] & 0x0f) * 4;
+    let from = Ipv4Addr::new(buf[12], buf[13], buf[14], buf[15]);
+    let icmp = buf.get(ihl..)?;
+    if icmp.len() < 8 { return None; }
+    match (icmp[0], icmp[1]) {
+        (ICMP_ECHO_REPLY, _) => {
+            (from == target && icmp[4..8] == [ident.to_be_bytes(), seq.to_be_bytes()].concat()[..]).then_some(Reply::Echo)
+        }
+        (ICMP_DEST_UNREACH, CODE_FRAG_NEEDED) => {
+            // Quoted original datagram: IPv4 header + first 8 bytes of the echo request
+            let inner = &icmp[8..];
+            if inner.len() < 20 || inner[0] >> 4 != 4 || inner[9] != 1 { return None; }
+            if Ipv4Addr::new(inner[16], inner[17], inner[18], inner[19]) != target { return None; }
+            let quote = inner.get(usize::from(inner[0] & 0x0f) * 4..)?.get(..8)?;
+            if quote[0] != ICMP_ECHO_REQUEST || quote[4..8] != [ident.to_be_bytes(), seq.to_be_bytes()].concat()[..] { return None; }
+            // RFC 1191: next-hop MTU in the low half of the unused field, 0 from older routers
+            let next_hop_mtu = Some(u16::from_be_bytes([icmp[6], icmp[7]])).filter(|m| *m >= MIN_MTU);
+            Some(Reply::FragNeeded { from, next_hop_mtu })
+        }
+        _ => None,
+    }
+}
+
+/// IPv4 header with DF set, for sockets where the caller supplies the header.
+fn ipv4_header(src: Ipv4Addr, dst: Ipv4Addr, total_len: u16, id: u16) -> [u8; 20] {
+    let mut h = [0u8; 20];
+    h[0] = 0x45;
+    h[2..4].copy_from_slice(&total_len.to_be_bytes());
+    h[4..6].copy_from_slice(&id.to_be_bytes());
+    h[6..8].copy_from_slice(&IP_FLAG_DF.to_be_bytes());
+    h[8] = 64;
+    h[9] = 1;
+    h[12..16].copy_from_slice(&src.octets());
+    h[16..20].copy_from_slice(&dst.octets());
+    let sum = checksum(&h);
+    h[10..12].copy_from_slice(&sum.to_be_bytes());
+    h
+}
+
+#[cfg(unix)]
+fn set_int_option(sock: &Socket, level: libc::c_int, name: libc::c_int, value: libc::c_int) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+    // SAFETY: the pointer and length describe `value`, which outlives the call
+    let rc = unsafe {
+        libc::setsockopt(sock.as_raw_fd(), level, name, &value as *const libc::c_int as *const libc::c_void, std::mem::size_of::<libc::c_int>() as libc::socklen_t)
+    };
+    if rc == 0 { Ok(()) } else { Err(std::io::Error::last_os_error()) }
+}
+
+/// Make the socket send with DF set. Returns true when the caller must build the IPv4 header.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn set_dont_fragment(sock: &Socket) -> std::io::Result<bool> {
+    // PROBE: DF set, and sizes above the cached path MTU still go out
+    set_int_option(sock, libc::IPPROTO_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_PROBE)?;
+    Ok(false)
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+fn set_dont_fragment(sock: &Socket) -> std::io::Result<bool> {
+    set_int_option(sock, libc::IPPROTO_IP, libc::IP_DONTFRAG, 1)?;
+    Ok(false)
+}
+
+#[cfg(windows)]
+fn set_dont_fragment(sock: &Socket) -> std::io::Result<bool> {
+    sock.set_header_included_v4(true)?;
+    Ok(true)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd", windows)))]
+fn set_dont_fragment(_sock: &Socket) -> std::io::Result<bool> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Don't-Fragment not supported on this platform"))
+}
+
+/// Whether a send error means "larger than the outgoing interface MTU".
+fn message_too_long(e: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    { e.raw_os_error() == Some(libc::EMSGSIZE) }
+    #[cfg(not(unix))]
+    { e.raw_os_error() == Some(10040) } // WSAEMSGSIZE
+}
+
+/// Discover the path MTU to `target`, calling `on_step` after every probed size.
+/// Returns None when stopped through `running`. Probes are paced by `limiter`.
+/// Needs raw-socket privileges (administrator / root or CAP_NET_RAW).
+pub fn discover(target: Ipv4Addr, opts: &PmtuOptions, running: &AtomicBool, limiter: &RateLimiter, mut on_step: impl FnMut(&ProbeStep)) -> Result<Option<PmtuResult>, String> {
+    let raw = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))
+        .map_err(|e| format!("Raw ICMP socket unavailable (run as administrator/root or grant CAP_NET_RAW): {}", e))?;
+    let local = local_ipv4_for(target);
+    raw.bind(&SockAddr::from(SocketAddrV4::new(local, 0)))
+        .map_err(|e| format!("Failed to bind ICMP socket: {}", e))?;
+    let header_included = set_dont_fragment(&raw).map_err(|e| format!("Cannot set Don't-Fragment: {}", e))?;
+    raw.set_read_timeout(Some(Duration::from_millis(20))).map_err(|e| e.to_string())?;
+
+    let ident = (std::process::id() & 0xffff) as u16;
+    let timeout = Duration::from_millis(u64::from(opts.timeout_ms.max(1)));
+    let target_sa = SockAddr::from(SocketAddrV4::new(target, 0));
+    let mut seq: u16 = 0;
+    let mut error: Option<String> = None;
+    let mut buf = vec![0u8; usize::from(MAX_MTU) + 100];
+
+    let mut probe = |mtu: u16| -> Option<ProbeOutcome> {
+        for _ in 0..opts.retries.clamp(1, 10) {
+            let _permit = limiter.in_flight(1, running)?;
+            if !limiter.pace(target, 1, running) { return None; }
+            seq = seq.wrapping_add(1);
+            let mut pkt = build_echo_request(ident, seq, usize::from(mtu - OVERHEAD));
+            if header_included {
+                pkt.splice(0..0, ipv4_header(local, target, mtu, seq));
+            }
+            let sent = Instant::now();
+            match raw.send_to(&pkt, &target_sa) {
+                Ok(_) => {}
+                Err(e) if message_too_long(&e) => return Some(ProbeOutcome::LocalTooBig),
+                Err(e) => {
+                    error = Some(format!("Failed to send echo request: {}", e));
+                    return None;
+                }
+            }
+            let deadline = sent + timeout;
+            while Instant::now() < deadline {
+                if !running.load(Ordering::SeqCst) { return None; }
+                let Ok(n) = (&raw).read(&mut buf) else { continue };
+                match parse_reply(&buf[..n], target, ident, seq) {
+                    Some(Reply::Echo) => return Some(ProbeOutcome::Reply { rtt_ms: sent.elapsed().as_secs_f64() * 1000.0 }),
+                    Some(Reply::FragNeeded { from, next_hop_mtu }) => return Some(ProbeOutcome::FragNeeded { from, next_hop_mtu }),
+                    None => {}
+                }
+            }
+        }
+        Some(ProbeOutcome::NoReply)
+    };
+
+    let result = search(MIN_MTU, opts.max_mtu.clamp(MIN_MTU, MAX_MTU), |mtu| {
+        let outcome = probe(mtu)?;
+        on_step(&ProbeStep { mtu, outcome });
+        Some(outcome)
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(result),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ME: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 10);
+    const ROUTER: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const TARGET: Ipv4Addr = Ipv4Addr::new(203, 0, 113, 5);
+
+    /// Path whose bottleneck is `mtu`; `router` reports it unless the hop is a black hole.
+    fn path(mtu: u16, router: Option<Ipv4Addr>) -> impl FnMut(u16) -> Option<ProbeOutcome> {
+        move |size| Some(match (size <= mtu, router) {
+            (true, _) => ProbeOutcome::Reply { rtt_ms: 1.0 },
+            (false, Some(from)) => ProbeOutcome::FragNeeded { from, next_hop_mtu: Some(mtu) },
+            (false, None) => ProbeOutcome::NoReply,
+        })
+    }
+
+    fn packet(src: Ipv4Addr, dst: Ipv4Addr, icmp: &[u8]) -> Vec<u8> {
+        let mut p = ipv4_header(src, dst, 20 + icmp.len() as u16, 1).to_vec();
+        p.extend_from_slice(icmp);
+        p
+    }
+
+    #[test]
+    fn test_search_uses_next_hop_mtu() {
+        let result = search(MIN_MTU, 1500, path(1400, Some(ROUTER))).unwrap();
+        assert_eq!(result.mtu, Some(1400));
+        assert_eq!(result.steps[0], ProbeStep { mtu: 1500, outcome: ProbeOutcome::FragNeeded { from: ROUTER, next_hop_mtu: Some(1400) } });
+        // 1500, 68, then the reported 1400 straight away
+        assert_eq!(result.steps[2].mtu, 1400);
+        assert!(result.steps.len() <= 3 + 7);
+        assert_eq!(result.frag_sources(), vec![(ROUTER, Some(1400))]);
+        assert!(!result.black_hole());
+
+        let full = search(MIN_MTU, 1500, path(1500, None)).unwrap();
+        assert_eq!((full.mtu, full.steps.len()), (Some(1500), 1));
+    }
+
+    #[test]
+    fn test_search_black_hole_and_down() {
+        let result = search(MIN_MTU, 1500, path(1412, None)).unwrap();
+        assert_eq!(result.mtu, Some(1412));
+        assert!(result.frag_sources().is_empty());
+        assert!(result.black_hole());
+        let lines = result.summary_lines(TARGET);
+        assert_eq!(lines[0], "[Info] Path MTU to 203.0.113.5: 1412 bytes (ICMP payload 1384, TCP MSS 1372)");
+        assert!(lines[1].contains("black hole"));
+
+        let down = search(MIN_MTU, 1500, |_| Some(ProbeOutcome::NoReply)).unwrap();
+        assert_eq!(down.mtu, None);
+        assert_eq!(down.steps.len(), 2);
+        assert!(down.summary_lines(TARGET)[0].starts_with("[Error] No reply from 203.0.113.5"));
+
+        // stopped mid-way
+        let (mut left, mut inner) = (3, path(1000, None));
+        assert!(search(MIN_MTU, 1500, |size| { left -= 1; if left > 0 { inner(size) } else { None } }).is_none());
+    }
+
+    #[test]
+    fn test_parse_echo_reply_and_frag_needed() {
+        let mut reply = build_echo_request(0x1234, 9, 16);
+        reply[0] = ICMP_ECHO_REPLY;
+        let pkt = packet(TARGET, ME, &reply);
+        assert_eq!(parse_reply(&pkt, TARGET, 0x1234, 9), Some(Reply::Echo));
+        assert_eq!(parse_reply(&pkt, TARGET, 0x1234, 8), None);
+        assert_eq!(parse_reply(&pkt, ROUTER, 0x1234, 9), None);
+
+        // Router quotes our 1500-byte request and offers 1400
+        let echo = build_echo_request(0x1234, 9, 1472);
+        let mut icmp = vec![ICMP_DEST_UNREACH, CODE_FRAG_NEEDED, 0, 0, 0, 0, 0x05, 0x78];
+        icmp.extend_from_slice(&ipv4_header(ME, TARGET, 1500, 9));
+        icmp.extend_from_slice(&echo[..8]);
+        let pkt = packet(ROUTER, ME, &icmp);
+        assert_eq!(parse_reply(&pkt, TARGET, 0x1234, 9), Some(Reply::FragNeeded { from: ROUTER, next_hop_mtu: Some(1400) }));
+        assert_eq!(parse_reply(&pkt, TARGET, 0x1234, 10), None);
+
+        // Pre-RFC 1191 router: no MTU given
+        icmp[6..8].copy_from_slice(&[0, 0]);
+        let pkt = packet(ROUTER, ME, &icmp);
+        assert_eq!(parse_reply(&pkt, TARGET, 0x1234, 9), Some(Reply::FragNeeded { from: ROUTER, next_hop_mtu: None }));
+        // other unreachable codes are not ours
+        icmp[1] = 1;
+        assert_eq!(parse_reply(&packet(ROUTER, ME, &icmp), TARGET, 0x1234, 9), None);
+        assert_eq!(parse_reply(&[0x45; 10], TARGET, 0x1234, 9), None);
+    }
+
+    #[test]
+    fn test_ipv4_header_and_step_lines() {
+        let h = ipv4_header(ME, TARGET, 1500, 7);
+        assert_eq!(&h[2..4], &1500u16.to_be_bytes());
+        assert_eq!(h[6] & 0x40, 0x40);
+        assert_eq!(checksum(&h), 0);
+
+        let step = ProbeStep { mtu: 1500, outcome: ProbeOutcome::FragNeeded { from: ROUTER, next_hop_mtu: Some(1400) } };
+        assert_eq!(step.format_line(), "MTU 1500 (payload 1472): fragmentation needed from 10.0.0.1 (next-hop MTU 1400)");
+        let step = ProbeStep { mtu: 9000, outcome: ProbeOutcome::LocalTooBig };
+        assert_eq!(step.format_line(), "MTU 9000 (payload 8972): larger than the local interface MTU");
+        let step = ProbeStep { mtu: 1400, outcome: ProbeOutcome::Reply { rtt_ms: 12.34 } };
+        assert_eq!(step.format_line(), "MTU 1400 (payload 1372): reply in 12.3 ms");
+    }
+}
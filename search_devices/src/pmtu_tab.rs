@@ -0,0 +1,103 @@
+use fltk::{
+    prelude::*,
+    frame::Frame,
+    input::{Input, IntInput},
+    button::Button,
+    text::{TextDisplay, TextBuffer},
+    app,
+};
+use std::{
+    net::Ipv4Addr,
+    sync::{Arc, Mutex, atomic::AtomicBool},
+    thread,
+};
+use crate::pmtu::{self, PmtuOptions, MAX_MTU, MIN_MTU};
+use crate::rate::RateLimiter;
+use crate::utils::{resolve_target_ipv4, ScanRun};
+
+/// Build the PMTU tab. Returns the running flag, the output buffer and the display.
+pub fn build_pmtu_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>, limiter: Arc<RateLimiter>) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>) {
+    let defaults = PmtuOptions::default();
+    Frame::new(10, 30, 480, 25, "Target (host or IPv4): ICMP echo with Don't-Fragment set");
+    let mut input = Input::new(10, 70, 200, 30, "");
+    input.set_value("8.8.8.8");
+
+    let mut clear_btn = Button::new(240, 70, 80, 30, "Clear");
+    let mut probe_btn = Button::new(320, 70, 80, 30, "Probe");
+    let mut stop_btn = Button::new(410, 70, 80, 30, "Stop");
+
+    // Options row
+    let _max_label = Frame::new(10, 110, 60, 25, "Max MTU");
+    let mut max_inp = IntInput::new(70, 110, 60, 25, "");
+    max_inp.set_value(&defaults.max_mtu.to_string());
+    let _to_label = Frame::new(140, 110, 100, 25, "Timeout (ms)");
+    let mut to_inp = IntInput::new(240, 110, 70, 25, "");
+    to_inp.set_value(&defaults.timeout_ms.to_string());
+    let _retries_label = Frame::new(320, 110, 60, 25, "Retries");
+    let mut retries_inp = IntInput::new(380, 110, 40, 25, "");
+    retries_inp.set_value(&defaults.retries.to_string());
+
+    let mut display = TextDisplay::new(10, 140, 480, 230, "");
+    let buff = TextBuffer::default();
+    display.set_buffer(buff.clone());
+    let display_ref = Arc::new(Mutex::new(display));
+
+    let run = ScanRun::default();
+
+    // Clear
+    {
+        let mut b = buff.clone();
+        clear_btn.set_callback(move |_| b.set_text(""));
+    }
+
+    // Probe: full size first, then binary search down to the largest size answered
+    {
+        let run = run.clone();
+        let mut b = buff.clone();
+        probe_btn.set_callback(move |_| {
+            if run.is_busy() {
+                return; // already running
+            }
+            let target = input.value().trim().to_string();
+            let Some(ip) = resolve_target_ipv4(&target) else {
+                b.append(&format!("[Error] Cannot resolve target: {}\n", target));
+                return;
+            };
+            let Some(max_mtu) = max_inp.value().parse::<u16>().ok().filter(|m| (MIN_MTU..=MAX_MTU).contains(m)) else {
+                b.append(&format!("[Error] Max MTU must be {}-{}: {}\n", MIN_MTU, MAX_MTU, max_inp.value()));
+                return;
+            };
+            let opts = PmtuOptions {
+                max_mtu,
+                timeout_ms: to_inp.value().parse().ok().filter(|v| (1..=10000).contains(v)).unwrap_or(defaults.timeout_ms),
+                retries: retries_inp.value().parse().ok().filter(|v| (1..=10).contains(v)).unwrap_or(defaults.retries),
+            };
+            b.set_text(&format!("[Info] Path MTU discovery to {} ({}), {} down to {} bytes...\n", target, ip, max_mtu, MIN_MTU));
+
+            let flag_thread = run.start();
+            let run = run.clone();
+            let limiter = limiter.clone();
+            thread::spawn(move || {
+                let send = |line: String| sender.send(("PMTU".to_string(), ip, false, line));
+                match pmtu::discover(ip, &opts, &flag_thread, &limiter, |step| send(step.format_line())) {
+                    Ok(Some(result)) => {
+                        for line in result.summary_lines(ip) {
+                            send(line);
+                        }
+                    }
+                    Ok(None) => send("[Info] PMTU probe stopped".to_string()),
+                    Err(e) => send(format!("[Error] {}", e)),
+                }
+                run.finish();
+            });
+        });
+    }
+
+    // Stop
+    {
+        let run = run.clone();
+        stop_btn.set_callback(move |_| run.stop());
+    }
+
+    (run.busy_flag(), buff, display_ref)
+}
@@ -7,13 +7,14 @@ use fltk::{
     app,
 };
 use std::{
-    net::{Ipv4Addr, SocketAddr, TcpStream},
+    net::Ipv4Addr,
     sync::{Arc, atomic::{AtomicBool, Ordering}, Mutex},
-    time::Duration,
     thread,
 };
-use dns_lookup::lookup_host;
-use crate::utils::parse_ports;
+use crate::{config::Config, services::{parse_port_spec, port_label}, utils::{resolve_target_ipv4, ScanRun}};
+use crate::exclusions::{profile_choice, selected_profile, ExcludeList};
+use crate::rate::RateLimiter;
+use crate::liveness::{tcp_connect, TcpState};
 
 /// Representative common TCP ports to scan.
 const DEFAULT_PORTS: &[u16] = &[
@@ -22,18 +23,30 @@ const DEFAULT_PORTS: &[u16] = &[
     5900, 8080, 8443,
 ];
 
-fn is_tcp_open(ip: Ipv4Addr, port: u16, timeout_ms: u64) -> bool {
-    let addr = SocketAddr::from((ip, port));
-    let timeout = Duration::from_millis(timeout_ms.max(1));
-    TcpStream::connect_timeout(&addr, timeout).is_ok()
+/// Try a TCP connection, giving up at the timeout or as soon as `running` is cleared.
+/// Returns whether the port is open, or None when stopped (the socket is closed at once).
+fn is_tcp_open(ip: Ipv4Addr, port: u16, timeout_ms: u64, running: &AtomicBool) -> Option<bool> {
+    tcp_connect(ip, port, timeout_ms, running).map(|state| state == TcpState::Open)
+}
+
+/// Refuse targets on the exclusion list of the selected profile.
+fn check_excluded(ip: Ipv4Addr, config: &Config, profile: &fltk::menu::Choice) -> Result<(), String> {
+    let excludes = ExcludeList::for_profile(config, selected_profile(profile).as_deref()).map_err(|e| format!("[Error] {}", e))?;
+    match excludes.matches(ip) {
+        Some(rule) => Err(format!("[Info] Skipped {}: excluded by {}", ip, rule.label())),
+        None => Ok(()),
+    }
 }
 
 /// Build the Ports tab UI.
 pub fn build_port_tab(
     sender: app::Sender<(String, Ipv4Addr, bool, String)>,
+    config: Arc<Config>,
+    limiter: Arc<RateLimiter>,
 ) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>) {
     // Widen labels to avoid text clipping on some platforms
-    Frame::new(10, 30, 480, 25, "Target (host or IPv4)");
+    Frame::new(10, 30, 200, 25, "Target (host or IPv4)");
+    let profile = profile_choice(410, 35, 80, 25, &config);
     let mut target_inp = Input::new(10, 70, 200, 30, "");
     target_inp.set_value("127.0.0.1");
 
@@ -41,7 +54,8 @@ pub fn build_port_tab(
     let mut scan_common_btn = Button::new(320, 70, 80, 30, "Common");
     let mut scan_custom_btn = Button::new(410, 70, 80, 30, "Custom");
 
-    let _ports_label = Frame::new(10, 110, 480, 25, "Ports (e.g. 22,80,443 or 8000-8010)");
+    let _ports_label = Frame::new(10, 110, 390, 25, "Ports (e.g. 22,80,443, 8000-8010, ssh,https or top100)");
+    let mut stop_btn = Button::new(410, 105, 80, 30, "Stop");
     let mut ports_inp = Input::new(10, 140, 260, 25, "");
     ports_inp.set_value("22,80,443");
 
@@ -54,24 +68,37 @@ pub fn build_port_tab(
     display.set_buffer(buff.clone());
     let display_ref = Arc::new(Mutex::new(display));
 
-    let running = Arc::new(AtomicBool::new(false));
+    let run = ScanRun::default();
 
-    // Clear
+    // Clear (also stops a running scan)
     {
         let mut b = buff.clone();
-        clear_btn.set_callback(move |_| b.set_text(""));
+        let run = run.clone();
+        clear_btn.set_callback(move |_| {
+            run.stop();
+            b.set_text("");
+        });
+    }
+
+    // Stop: closes the pending connection at once
+    {
+        let run = run.clone();
+        stop_btn.set_callback(move |_| run.stop());
     }
 
     // Scan common ports
     {
         let s = sender.clone();
-        let flag = running.clone();
+        let run = run.clone();
         let mut b = buff.clone();
         let target_inp = target_inp.clone();
         let to_inp = to_inp.clone();
         let display_ref = display_ref.clone();
+        let profile = profile.clone();
+        let limiter = limiter.clone();
+        let config = config.clone();
         scan_common_btn.set_callback(move |_| {
-            if flag.load(Ordering::SeqCst) { return; }
+            if run.is_busy() { return; }
             let target = target_inp.value();
             if target.trim().is_empty() {
                 b.append("[Error] Target is empty.\n");
@@ -88,22 +115,36 @@ pub fn build_port_tab(
                     return;
                 }
             };
+            if let Err(e) = check_excluded(ip, &config, &profile) {
+                b.append(&format!("{}\n", e));
+                return;
+            }
 
             // Header
             b.set_text(&format!("{:<15} {:<7} {:<12} {}\n", "Target", "Result", "Status", "Info"));
+            b.append(&format!("[Info] Rate limit: {}\n", limiter.limits().describe()));
 
-            flag.store(true, Ordering::SeqCst);
-            let flag_th = flag.clone();
+            let flag_th = run.start();
+            let run = run.clone();
             let sender = s.clone();
             let display_ref = display_ref.clone();
+            let limiter = limiter.clone();
             thread::spawn(move || {
+                let total = DEFAULT_PORTS.len();
+                let mut done = 0;
                 for &port in DEFAULT_PORTS {
                     if !flag_th.load(Ordering::SeqCst) { break; }
-                    let open = is_tcp_open(ip, port, timeout_ms);
-                    sender.send(("PORTS".to_string(), ip, open, format!("{}/tcp", port)));
+                    let Some(_permit) = limiter.in_flight(1, &flag_th) else { break };
+                    if !limiter.pace(ip, 1, &flag_th) { break; }
+                    let Some(open) = is_tcp_open(ip, port, timeout_ms, &flag_th) else { break };
+                    done += 1;
+                    sender.send(("PORTS".to_string(), ip, open, port_label(port)));
+                }
+                if !flag_th.load(Ordering::SeqCst) {
+                    sender.send(("PORTS_INFO".to_string(), ip, false, format!("[Info] Scan stopped after {} of {} ports", done, total)));
                 }
                 if let Ok(mut display) = display_ref.lock() { display.redraw(); }
-                flag_th.store(false, Ordering::SeqCst);
+                run.finish();
             });
         });
     }
@@ -111,21 +152,23 @@ pub fn build_port_tab(
     // Scan custom ports
     {
         let s = sender.clone();
-        let flag = running.clone();
+        let run = run.clone();
         let mut b = buff.clone();
         let target_inp = target_inp.clone();
         let ports_inp = ports_inp.clone();
         let to_inp = to_inp.clone();
         let display_ref = display_ref.clone();
+        let profile = profile.clone();
+        let limiter = limiter.clone();
         scan_custom_btn.set_callback(move |_| {
-            if flag.load(Ordering::SeqCst) { return; }
+            if run.is_busy() { return; }
             let target = target_inp.value();
             if target.trim().is_empty() {
                 b.append("[Error] Target is empty.\n");
                 return;
             }
             let ports_str = ports_inp.value();
-            let ports = match parse_ports(&ports_str) {
+            let ports = match parse_port_spec(&ports_str, &config.presets) {
                 Ok(v) if !v.is_empty() => v,
                 Ok(_) => {
                     b.append("[Error] No ports specified.\n");
@@ -146,37 +189,41 @@ pub fn build_port_tab(
                     return;
                 }
             };
+            if let Err(e) = check_excluded(ip, &config, &profile) {
+                b.append(&format!("{}\n", e));
+                return;
+            }
 
             // Header
             b.set_text(&format!("{:<15} {:<7} {:<12} {}\n", "Target", "Result", "Status", "Info"));
+            b.append(&format!("[Info] Rate limit: {}\n", limiter.limits().describe()));
 
-            flag.store(true, Ordering::SeqCst);
-            let flag_th = flag.clone();
+            let flag_th = run.start();
+            let run = run.clone();
             let sender = s.clone();
             let display_ref = display_ref.clone();
+            let limiter = limiter.clone();
             thread::spawn(move || {
+                let total = ports.len();
+                let mut done = 0;
                 for port in ports {
                     if !flag_th.load(Ordering::SeqCst) { break; }
-                    let open = is_tcp_open(ip, port, timeout_ms);
-                    sender.send(("PORTS".to_string(), ip, open, format!("{}/tcp", port)));
+                    let Some(_permit) = limiter.in_flight(1, &flag_th) else { break };
+                    if !limiter.pace(ip, 1, &flag_th) { break; }
+                    let Some(open) = is_tcp_open(ip, port, timeout_ms, &flag_th) else { break };
+                    done += 1;
+                    sender.send(("PORTS".to_string(), ip, open, port_label(port)));
+                }
+                if !flag_th.load(Ordering::SeqCst) {
+                    sender.send(("PORTS_INFO".to_string(), ip, false, format!("[Info] Scan stopped after {} of {} ports", done, total)));
                 }
                 if let Ok(mut display) = display_ref.lock() { display.redraw(); }
-                flag_th.store(false, Ordering::SeqCst);
+                run.finish();
             });
         });
     }
 
-    (running, buff, display_ref)
-}
-
-fn resolve_target_ipv4(target: &str) -> Option<Ipv4Addr> {
-    if let Ok(ip) = target.parse::<Ipv4Addr>() { return Some(ip); }
-    if let Ok(addrs) = lookup_host(target) {
-        for a in addrs {
-            if let std::net::IpAddr::V4(v4) = a { return Some(v4); }
-        }
-    }
-    None
+    (run.busy_flag(), buff, display_ref)
 }
 
 #[cfg(test)]
@@ -187,4 +234,14 @@ mod tests {
     fn test_resolve_target_ipv4_local() {
         assert!(resolve_target_ipv4("127.0.0.1").is_some());
     }
+
+    #[test]
+    fn test_is_tcp_open_local() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let running = AtomicBool::new(true);
+        assert_eq!(is_tcp_open(Ipv4Addr::LOCALHOST, port, 1000, &running), Some(true));
+        drop(listener);
+        assert_eq!(is_tcp_open(Ipv4Addr::LOCALHOST, port, 1000, &running), Some(false));
+    }
 }
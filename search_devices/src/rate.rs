@@ -0,0 +1,231 @@
+// Global rate limiting shared by every scanner: probes per second, probes in flight,
+// a minimum delay between probes to the same host and the ping interval
+
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    net::Ipv4Addr,
+    sync::{Condvar, Mutex, atomic::{AtomicBool, Ordering}},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Window over which the effective rate is measured.
+const RATE_WINDOW: Duration = Duration::from_secs(2);
+/// Longest single sleep, so that Stop is noticed quickly.
+const WAIT_STEP: Duration = Duration::from_millis(50);
+
+/// Limits read from the `[rate]` section of the configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RateLimits {
+    /// Probes per second over all scanners (0 = unlimited).
+    pub pps: f64,
+    /// Probes waiting for an answer at the same time, over all scanners (0 = unlimited).
+    pub max_in_flight: u32,
+    /// Minimum delay between two probes to the same host.
+    pub host_delay_ms: u64,
+    /// Interval between the echo requests of one ping (Count > 1).
+    pub ping_interval_ms: u64,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        RateLimits { pps: 50.0, max_in_flight: 16, host_delay_ms: 0, ping_interval_ms: 1000 }
+    }
+}
+
+impl RateLimits {
+    /// Short description, e.g. "50/s, 16 in flight".
+    pub fn describe(&self) -> String {
+        let pps = if self.pps > 0.0 { format!("{}/s", self.pps) } else { "unlimited".to_string() };
+        let in_flight = if self.max_in_flight > 0 { self.max_in_flight.to_string() } else { "unlimited".to_string() };
+        let mut s = format!("{}, {} in flight", pps, in_flight);
+        if self.host_delay_ms > 0 {
+            s.push_str(&format!(", {} ms per host", self.host_delay_ms));
+        }
+        s
+    }
+}
+
+struct State {
+    /// Earliest time the next probe may be sent
+    next_slot: Instant,
+    in_flight: u32,
+    last_by_host: HashMap<Ipv4Addr, Instant>,
+    /// Send times of recent probes, for the effective rate
+    recent: VecDeque<Instant>,
+}
+
+/// Rate limiter shared by all tabs.
+pub struct RateLimiter {
+    limits: RateLimits,
+    state: Mutex<State>,
+    released: Condvar,
+}
+
+/// In-flight slots held by a probe; released when dropped.
+pub struct Permit<'a> {
+    limiter: &'a RateLimiter,
+    count: u32,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut st) = self.limiter.state.lock() {
+            st.in_flight = st.in_flight.saturating_sub(self.count);
+        }
+        self.limiter.released.notify_all();
+    }
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> RateLimiter {
+        RateLimiter {
+            limits,
+            state: Mutex::new(State { next_slot: Instant::now(), in_flight: 0, last_by_host: HashMap::new(), recent: VecDeque::new() }),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn limits(&self) -> &RateLimits {
+        &self.limits
+    }
+
+    /// Number of probes that may be in flight together out of `wanted`.
+    pub fn max_batch(&self, wanted: u32) -> u32 {
+        if self.limits.max_in_flight == 0 { wanted.max(1) } else { wanted.clamp(1, self.limits.max_in_flight) }
+    }
+
+    /// Wait for `count` in-flight slots (clamped to the limit). None when `running` is cleared.
+    pub fn in_flight(&self, count: u32, running: &AtomicBool) -> Option<Permit<'_>> {
+        let count = self.max_batch(count);
+        let mut st = self.state.lock().ok()?;
+        loop {
+            if !running.load(Ordering::SeqCst) {
+                return None;
+            }
+            if self.limits.max_in_flight == 0 || st.in_flight + count <= self.limits.max_in_flight {
+                st.in_flight += count;
+                return Some(Permit { limiter: self, count });
+            }
+            st = self.released.wait_timeout(st, WAIT_STEP).ok()?.0;
+        }
+    }
+
+    /// Wait until `probes` probes may be sent to `ip`: reserves send slots for the
+    /// probes-per-second limit and honours the per-host delay. False when `running` is cleared.
+    pub fn pace(&self, ip: Ipv4Addr, probes: u32, running: &AtomicBool) -> bool {
+        let start = {
+            let Ok(mut st) = self.state.lock() else { return false };
+            let now = Instant::now();
+            let mut start = st.next_slot.max(now);
+            if self.limits.host_delay_ms > 0 {
+                let delay = Duration::from_millis(self.limits.host_delay_ms);
+                if let Some(last) = st.last_by_host.get(&ip) {
+                    start = start.max(*last + delay);
+                }
+                if st.last_by_host.len() > 4096 {
+                    st.last_by_host.retain(|_, t| *t + delay > now);
+                }
+                st.last_by_host.insert(ip, start);
+            }
+            if self.limits.pps > 0.0 {
+                st.next_slot = start + Duration::from_secs_f64(f64::from(probes.max(1)) / self.limits.pps);
+            }
+            start
+        };
+        while Instant::now() < start {
+            if !running.load(Ordering::SeqCst) {
+                return false;
+            }
+            thread::sleep((start - Instant::now()).min(WAIT_STEP));
+        }
+        if let Ok(mut st) = self.state.lock() {
+            let now = Instant::now();
+            for _ in 0..probes.max(1) {
+                st.recent.push_back(now);
+            }
+            while st.recent.front().is_some_and(|t| now.duration_since(*t) > RATE_WINDOW) {
+                st.recent.pop_front();
+            }
+        }
+        running.load(Ordering::SeqCst)
+    }
+
+    /// Effective rate over the last two seconds and probes in flight,
+    /// or None when nothing was sent recently.
+    pub fn status(&self) -> Option<(f64, u32)> {
+        let st = self.state.lock().ok()?;
+        let now = Instant::now();
+        let sent = st.recent.iter().filter(|t| now.duration_since(**t) <= RATE_WINDOW).count();
+        if sent == 0 && st.in_flight == 0 {
+            return None;
+        }
+        Some((sent as f64 / RATE_WINDOW.as_secs_f64(), st.in_flight))
+    }
+
+    /// Status text for the window title, e.g. "12.5 probes/s, 3 in flight (limit 50/s, 16 in flight)".
+    pub fn status_line(&self) -> Option<String> {
+        let (rate, in_flight) = self.status()?;
+        Some(format!("{:.1} probes/s, {} in flight (limit {})", rate, in_flight, self.limits.describe()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_pps_pacing() {
+        let limiter = RateLimiter::new(RateLimits { pps: 100.0, max_in_flight: 0, host_delay_ms: 0, ping_interval_ms: 0 });
+        let running = AtomicBool::new(true);
+        let started = Instant::now();
+        for i in 0..11 {
+            assert!(limiter.pace(Ipv4Addr::new(10, 0, 0, i), 1, &running));
+        }
+        // 11 probes at 100/s: the last one goes out 100 ms after the first
+        assert!(started.elapsed() >= Duration::from_millis(95));
+        let (rate, in_flight) = limiter.status().unwrap();
+        assert!(rate > 0.0);
+        assert_eq!(in_flight, 0);
+    }
+
+    #[test]
+    fn test_host_delay_and_stop() {
+        let limiter = RateLimiter::new(RateLimits { pps: 0.0, max_in_flight: 0, host_delay_ms: 60, ping_interval_ms: 0 });
+        let running = AtomicBool::new(true);
+        let ip = Ipv4Addr::new(10, 0, 0, 1);
+        let started = Instant::now();
+        assert!(limiter.pace(ip, 1, &running));
+        assert!(limiter.pace(Ipv4Addr::new(10, 0, 0, 2), 1, &running));
+        assert!(started.elapsed() < Duration::from_millis(50));
+        assert!(limiter.pace(ip, 1, &running));
+        assert!(started.elapsed() >= Duration::from_millis(55));
+        running.store(false, Ordering::SeqCst);
+        assert!(!limiter.pace(ip, 1, &running));
+    }
+
+    #[test]
+    fn test_in_flight_limit() {
+        let limiter = Arc::new(RateLimiter::new(RateLimits { pps: 0.0, max_in_flight: 2, host_delay_ms: 0, ping_interval_ms: 0 }));
+        let running = Arc::new(AtomicBool::new(true));
+        assert_eq!(limiter.max_batch(5), 2);
+        let a = limiter.in_flight(1, &running).unwrap();
+        let _b = limiter.in_flight(1, &running).unwrap();
+        assert_eq!(limiter.status().unwrap().1, 2);
+        let (l, r) = (limiter.clone(), running.clone());
+        let waiter = thread::spawn(move || {
+            let started = Instant::now();
+            let _c = l.in_flight(1, &r).unwrap();
+            started.elapsed()
+        });
+        thread::sleep(Duration::from_millis(80));
+        drop(a);
+        assert!(waiter.join().unwrap() >= Duration::from_millis(60));
+        // Stop releases a waiting scanner
+        running.store(false, Ordering::SeqCst);
+        assert!(limiter.in_flight(1, &running).is_none());
+    }
+}
@@ -0,0 +1,199 @@
+// Asynchronous reverse DNS: a small worker pool with a per-lookup timeout and a cache
+// shared by all tabs, so a slow resolver never stalls a sweep.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{mpsc, Arc, Mutex, atomic::{AtomicBool, Ordering}},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Number of lookups resolved concurrently.
+pub const RDNS_WORKERS: usize = 8;
+
+/// Default time to wait for one PTR lookup.
+pub const DEFAULT_DNS_TIMEOUT_MS: u64 = 1000;
+
+/// Poll step while waiting for a lookup, so that a stopped scan is noticed quickly.
+const WAIT_STEP: Duration = Duration::from_millis(20);
+
+/// How long a resolved name (or a definite "no name") stays cached.
+const CACHE_TTL: Duration = Duration::from_secs(600);
+
+type Resolver = Arc<dyn Fn(IpAddr) -> Option<String> + Send + Sync>;
+type Callback = Box<dyn FnOnce(Option<String>) + Send>;
+
+struct Job {
+    ip: IpAddr,
+    timeout: Duration,
+    /// Scan flag; the job is abandoned once it is cleared
+    running: Arc<AtomicBool>,
+    on_done: Callback,
+}
+
+type Cache = Arc<Mutex<HashMap<IpAddr, (Option<String>, Instant)>>>;
+
+/// Reverse DNS resolver pool with a shared cache.
+pub struct RdnsPool {
+    cache: Cache,
+    jobs: Mutex<mpsc::Sender<Job>>,
+}
+
+impl RdnsPool {
+    /// Pool backed by the system resolver.
+    pub fn new() -> RdnsPool {
+        RdnsPool::with_resolver(RDNS_WORKERS, Arc::new(system_lookup))
+    }
+
+    fn with_resolver(workers: usize, resolver: Resolver) -> RdnsPool {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        let cache: Cache = Arc::new(Mutex::new(HashMap::new()));
+        for _ in 0..workers.max(1) {
+            let rx = rx.clone();
+            let cache = cache.clone();
+            let resolver = resolver.clone();
+            thread::spawn(move || loop {
+                let job = match rx.lock() {
+                    Ok(r) => match r.recv() { Ok(j) => j, Err(_) => return },
+                    Err(_) => return,
+                };
+                // Lookups of a stopped scan are dropped without calling back
+                if !job.running.load(Ordering::SeqCst) { continue; }
+                let name = lookup_with_timeout(job.ip, job.timeout, &job.running, &resolver, &cache);
+                if job.running.load(Ordering::SeqCst) {
+                    (job.on_done)(name);
+                }
+            });
+        }
+        RdnsPool { cache, jobs: Mutex::new(tx) }
+    }
+
+    /// Cached result for `ip`: `Some(name)` when known (the name itself may be None).
+    pub fn cached(&self, ip: IpAddr) -> Option<Option<String>> {
+        let cache = self.cache.lock().ok()?;
+        cache.get(&ip).filter(|(_, at)| at.elapsed() < CACHE_TTL).map(|(name, _)| name.clone())
+    }
+
+    /// Resolve `ip` in the background and call `on_done` with the host name
+    /// (None on no PTR record or timeout). Cached results are returned at once.
+    /// Once `running` is cleared, pending lookups are abandoned and `on_done` is not called.
+    pub fn resolve_async(&self, ip: IpAddr, timeout_ms: u64, running: &Arc<AtomicBool>, on_done: impl FnOnce(Option<String>) + Send + 'static) {
+        if let Some(name) = self.cached(ip) {
+            on_done(name);
+            return;
+        }
+        let job = Job { ip, timeout: Duration::from_millis(timeout_ms.max(1)), running: running.clone(), on_done: Box::new(on_done) };
+        if let Ok(tx) = self.jobs.lock() {
+            let _ = tx.send(job);
+        }
+    }
+}
+
+impl Default for RdnsPool {
+    fn default() -> Self {
+        RdnsPool::new()
+    }
+}
+
+/// PTR lookup through the OS resolver. A numeric answer means there is no name.
+fn system_lookup(ip: IpAddr) -> Option<String> {
+    dns_lookup::lookup_addr(&ip).ok().filter(|h| !h.is_empty() && h.parse::<IpAddr>().ok() != Some(ip))
+}
+
+/// Run the resolver on a helper thread and wait at most `timeout`, or until `running`
+/// is cleared. A lookup that is given up keeps running and still fills the cache when it completes.
+fn lookup_with_timeout(ip: IpAddr, timeout: Duration, running: &AtomicBool, resolver: &Resolver, cache: &Cache) -> Option<String> {
+    let (tx, rx) = mpsc::channel();
+    let resolver = resolver.clone();
+    let cache = cache.clone();
+    thread::spawn(move || {
+        let name = resolver(ip);
+        if let Ok(mut c) = cache.lock() {
+            c.insert(ip, (name.clone(), Instant::now()));
+        }
+        let _ = tx.send(name);
+    });
+    let deadline = Instant::now() + timeout;
+    loop {
+        let now = Instant::now();
+        if now >= deadline || !running.load(Ordering::SeqCst) {
+            return None;
+        }
+        match rx.recv_timeout((deadline - now).min(WAIT_STEP)) {
+            Ok(name) => return name,
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn ip(s: &str) -> IpAddr { s.parse().unwrap() }
+
+    #[test]
+    fn test_resolve_and_cache() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let c = calls.clone();
+        let pool = RdnsPool::with_resolver(2, Arc::new(move |ip: IpAddr| {
+            c.fetch_add(1, Ordering::SeqCst);
+            if ip == "10.0.0.1".parse::<IpAddr>().unwrap() { Some("gw.lan".to_string()) } else { None }
+        }));
+        let running = Arc::new(AtomicBool::new(true));
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..2 {
+            let tx = tx.clone();
+            pool.resolve_async(ip("10.0.0.1"), 1000, &running, move |n| tx.send(n).unwrap());
+            assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap().as_deref(), Some("gw.lan"));
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1, "second lookup comes from the cache");
+        let tx2 = tx.clone();
+        pool.resolve_async(ip("10.0.0.2"), 1000, &running, move |n| tx2.send(n).unwrap());
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), None);
+        assert_eq!(pool.cached(ip("10.0.0.2")), Some(None));
+        assert_eq!(pool.cached(ip("10.0.0.3")), None);
+    }
+
+    #[test]
+    fn test_timeout_fills_cache_later() {
+        let pool = RdnsPool::with_resolver(1, Arc::new(|_| {
+            thread::sleep(Duration::from_millis(200));
+            Some("slow.example".to_string())
+        }));
+        let (tx, rx) = mpsc::channel();
+        let started = Instant::now();
+        pool.resolve_async(ip("192.0.2.1"), 20, &Arc::new(AtomicBool::new(true)), move |n| tx.send(n).unwrap());
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), None);
+        assert!(started.elapsed() < Duration::from_millis(150));
+        thread::sleep(Duration::from_millis(400));
+        assert_eq!(pool.cached(ip("192.0.2.1")), Some(Some("slow.example".to_string())));
+    }
+    #[test]
+    fn test_stop_abandons_lookups() {
+        let pool = RdnsPool::with_resolver(1, Arc::new(|_| {
+            thread::sleep(Duration::from_millis(300));
+            Some("slow.example".to_string())
+        }));
+        let running = Arc::new(AtomicBool::new(true));
+        let (tx, rx) = mpsc::channel();
+        for last in 1..=3u8 {
+            let tx = tx.clone();
+            pool.resolve_async(IpAddr::from([192, 0, 2, last]), 5000, &running, move |n| tx.send(n).unwrap());
+        }
+        thread::sleep(Duration::from_millis(50));
+        let stopped = Instant::now();
+        running.store(false, Ordering::SeqCst);
+        // Neither the lookup in progress nor the queued ones call back
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+        assert!(stopped.elapsed() < Duration::from_millis(300));
+        // The worker is free again for the next scan
+        let again = Arc::new(AtomicBool::new(true));
+        pool.resolve_async(ip("192.0.2.9"), 5000, &again, move |n| tx.send(n).unwrap());
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap().as_deref(), Some("slow.example"));
+    }
+}
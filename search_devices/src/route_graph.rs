@@ -0,0 +1,475 @@
+// Route graph: lays out one or more traces as a node/edge diagram, draws it with FLTK
+// and exports it as SVG or PNG. Hops answered by the same router are merged, so a
+// shared upstream path shows as a common trunk.
+
+use fltk::{
+    prelude::*,
+    button::Button,
+    draw,
+    draw::LineStyle,
+    enums::{Align, Color, Font, FrameType},
+    frame::Frame,
+    group::{Group, Scroll},
+    surface::ImageSurface,
+};
+use std::{
+    collections::HashMap,
+    io::Write,
+    sync::{Arc, Mutex},
+};
+use crate::hops::HopRecord;
+use crate::utils::choose_save_path;
+
+const MARGIN: i32 = 20;
+const LEGEND_ROW_H: i32 = 18;
+const ROW_H: i32 = 50;
+const LANE_W: i32 = 170;
+const NODE_R: i32 = 8;
+
+/// Edge colors, one per overlaid trace.
+const TRACE_COLORS: &[(u8, u8, u8)] = &[
+    (31, 119, 180), (148, 103, 189), (23, 190, 207), (227, 119, 194),
+    (140, 86, 75), (188, 189, 34), (127, 127, 127), (255, 127, 14),
+];
+
+/// Color of an edge shared by several traces.
+const TRUNK_COLOR: (u8, u8, u8) = (40, 40, 40);
+
+/// Traces shown in the graph, keyed by target. Tracing the same target again replaces it.
+#[derive(Debug, Clone, Default)]
+pub struct RouteGraph {
+    traces: Vec<(String, Vec<HopRecord>)>,
+}
+
+impl RouteGraph {
+    /// Add a trace, replacing an earlier trace to the same target.
+    pub fn set_trace(&mut self, target: &str, hops: &[HopRecord]) {
+        match self.traces.iter_mut().find(|(t, _)| t == target) {
+            Some(entry) => entry.1 = hops.to_vec(),
+            None => self.traces.push((target.to_string(), hops.to_vec())),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.traces.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.traces.is_empty()
+    }
+
+    pub fn layout(&self) -> GraphLayout {
+        layout(&self.traces)
+    }
+}
+
+/// A router (or an unanswered hop) placed in the diagram.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphNode {
+    /// Responder address, or "*" for a hop without answers
+    pub label: String,
+    pub hop: u32,
+    /// Mean RTT over all answered probes
+    pub rtt: Option<f64>,
+    pub loss_pct: f64,
+    /// Indices of the traces passing through this node
+    pub traces: Vec<usize>,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    pub traces: Vec<usize>,
+}
+
+/// Positioned nodes and edges, in diagram coordinates (origin at the top left).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GraphLayout {
+    pub targets: Vec<String>,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Lay out traces top to bottom by hop number, one lane per trace. A node shared by
+/// several traces sits between their lanes.
+pub fn layout(traces: &[(String, Vec<HopRecord>)]) -> GraphLayout {
+    let mut nodes: Vec<GraphNode> = Vec::new();
+    let mut probes: Vec<(f64, u32, u32)> = Vec::new(); // (rtt sum, answered, sent)
+    let mut by_key: HashMap<String, usize> = HashMap::new();
+    let mut edges: Vec<GraphEdge> = Vec::new();
+    let mut max_hop = 0;
+
+    for (t, (_, hops)) in traces.iter().enumerate() {
+        let mut sorted: Vec<&HopRecord> = hops.iter().collect();
+        sorted.sort_by_key(|h| h.hop);
+        let mut prev: Option<usize> = None;
+        for rec in sorted {
+            max_hop = max_hop.max(rec.hop);
+            let (key, label) = match rec.responders.first() {
+                Some(r) => (r.addr.to_string(), r.addr.to_string()),
+                None => (format!("*{}:{}", t, rec.hop), "*".to_string()),
+            };
+            let idx = *by_key.entry(key).or_insert_with(|| {
+                nodes.push(GraphNode { label, hop: rec.hop, rtt: None, loss_pct: 0.0, traces: Vec::new(), x: 0, y: 0 });
+                probes.push((0.0, 0, 0));
+                nodes.len() - 1
+            });
+            let node = &mut nodes[idx];
+            node.hop = node.hop.min(rec.hop);
+            if !node.traces.contains(&t) { node.traces.push(t); }
+            let p = &mut probes[idx];
+            for rtt in &rec.rtts {
+                p.2 += 1;
+                if let Some(v) = rtt { p.0 += v; p.1 += 1; }
+            }
+            if let Some(from) = prev.filter(|from| *from != idx) {
+                match edges.iter_mut().find(|e| e.from == from && e.to == idx) {
+                    Some(e) => { if !e.traces.contains(&t) { e.traces.push(t); } }
+                    None => edges.push(GraphEdge { from, to: idx, traces: vec![t] }),
+                }
+            }
+            prev = Some(idx);
+        }
+    }
+
+    let top = MARGIN + LEGEND_ROW_H * traces.len() as i32 + 10;
+    for (node, (sum, answered, sent)) in nodes.iter_mut().zip(probes) {
+        node.rtt = if answered > 0 { Some(sum / f64::from(answered)) } else { None };
+        node.loss_pct = if sent > 0 { f64::from(sent - answered) * 100.0 / f64::from(sent) } else { 100.0 };
+        let lane = node.traces.iter().sum::<usize>() as f64 / node.traces.len() as f64;
+        node.x = MARGIN + (lane * f64::from(LANE_W)) as i32 + LANE_W / 2;
+        node.y = top + (node.hop.max(1) as i32 - 1) * ROW_H + NODE_R;
+    }
+
+    GraphLayout {
+        targets: traces.iter().map(|(t, _)| t.clone()).collect(),
+        nodes,
+        edges,
+        width: (MARGIN * 2 + LANE_W * traces.len().max(1) as i32).max(300),
+        height: top + max_hop as i32 * ROW_H + MARGIN,
+    }
+}
+
+/// Node fill color by packet loss: green (none), yellow (some), orange (heavy), red (all lost).
+pub fn loss_color(loss_pct: f64) -> (u8, u8, u8) {
+    if loss_pct <= 0.0 { (46, 160, 67) }
+    else if loss_pct < 50.0 { (230, 190, 0) }
+    else if loss_pct < 100.0 { (240, 120, 0) }
+    else { (210, 40, 40) }
+}
+
+fn edge_color(edge: &GraphEdge) -> (u8, u8, u8) {
+    if edge.traces.len() > 1 { TRUNK_COLOR } else { TRACE_COLORS[edge.traces[0] % TRACE_COLORS.len()] }
+}
+
+/// RTT and loss text shown under a node, e.g. "12.3 ms, 33% loss".
+fn node_caption(node: &GraphNode) -> String {
+    let rtt = node.rtt.map(|v| format!("{:.1} ms", v)).unwrap_or_else(|| "timeout".to_string());
+    if node.loss_pct > 0.0 && node.rtt.is_some() {
+        format!("{}, {:.0}% loss", rtt, node.loss_pct)
+    } else {
+        rtt
+    }
+}
+
+fn rgb((r, g, b): (u8, u8, u8)) -> Color {
+    Color::from_rgb(r, g, b)
+}
+
+/// Draw the diagram with FLTK primitives, offset by (ox, oy).
+pub fn draw_layout(layout: &GraphLayout, ox: i32, oy: i32) {
+    draw::draw_rect_fill(ox, oy, layout.width, layout.height, Color::White);
+    draw::set_font(Font::Helvetica, 12);
+    for (i, target) in layout.targets.iter().enumerate() {
+        let y = oy + MARGIN + LEGEND_ROW_H * i as i32;
+        draw::set_draw_color(rgb(TRACE_COLORS[i % TRACE_COLORS.len()]));
+        draw::set_line_style(LineStyle::Solid, 3);
+        draw::draw_line(ox + MARGIN, y + 7, ox + MARGIN + 24, y + 7);
+        draw::set_draw_color(Color::Black);
+        draw::draw_text2(target, ox + MARGIN + 30, y, 300, 14, Align::Left | Align::Inside);
+    }
+    for edge in &layout.edges {
+        let (a, b) = (&layout.nodes[edge.from], &layout.nodes[edge.to]);
+        draw::set_draw_color(rgb(edge_color(edge)));
+        draw::set_line_style(LineStyle::Solid, if edge.traces.len() > 1 { 4 } else { 2 });
+        draw::draw_line(ox + a.x, oy + a.y, ox + b.x, oy + b.y);
+    }
+    draw::set_line_style(LineStyle::Solid, 0);
+    for node in &layout.nodes {
+        let (x, y) = (ox + node.x, oy + node.y);
+        draw::set_draw_color(rgb(loss_color(node.loss_pct)));
+        draw::draw_pie(x - NODE_R, y - NODE_R, NODE_R * 2, NODE_R * 2, 0.0, 360.0);
+        draw::set_draw_color(Color::Black);
+        draw::draw_arc(x - NODE_R, y - NODE_R, NODE_R * 2, NODE_R * 2, 0.0, 360.0);
+        draw::set_font(Font::Helvetica, 11);
+        draw::draw_text2(&format!("{} {}", node.hop, node.label), x + NODE_R + 4, y - 13, LANE_W, 13, Align::Left | Align::Inside);
+        draw::set_draw_color(Color::from_rgb(90, 90, 90));
+        draw::draw_text2(&node_caption(node), x + NODE_R + 4, y, LANE_W, 13, Align::Left | Align::Inside);
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn svg_rgb((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Render the diagram as a standalone SVG document.
+pub fn to_svg(layout: &GraphLayout) -> String {
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" font-family=\"Helvetica, Arial, sans-serif\">\n\
+         <rect width=\"{w}\" height=\"{h}\" fill=\"#ffffff\"/>\n",
+        w = layout.width, h = layout.height
+    );
+    for (i, target) in layout.targets.iter().enumerate() {
+        let y = MARGIN + LEGEND_ROW_H * i as i32;
+        out.push_str(&format!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"3\"/>\n<text x=\"{}\" y=\"{}\" font-size=\"12\">{}</text>\n",
+            MARGIN, y + 7, MARGIN + 24, y + 7, svg_rgb(TRACE_COLORS[i % TRACE_COLORS.len()]),
+            MARGIN + 30, y + 11, xml_escape(target)
+        ));
+    }
+    for edge in &layout.edges {
+        let (a, b) = (&layout.nodes[edge.from], &layout.nodes[edge.to]);
+        out.push_str(&format!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>\n",
+            a.x, a.y, b.x, b.y, svg_rgb(edge_color(edge)), if edge.traces.len() > 1 { 4 } else { 2 }
+        ));
+    }
+    for node in &layout.nodes {
+        out.push_str(&format!(
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\" stroke=\"#000000\"/>\n\
+             <text x=\"{tx}\" y=\"{}\" font-size=\"11\">{} {}</text>\n\
+             <text x=\"{tx}\" y=\"{}\" font-size=\"11\" fill=\"#5a5a5a\">{}</text>\n",
+            node.x, node.y, NODE_R, svg_rgb(loss_color(node.loss_pct)),
+            node.y - 3, node.hop, xml_escape(&node.label),
+            node.y + 10, xml_escape(&node_caption(node)),
+            tx = node.x + NODE_R + 4
+        ));
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+/// Encode 8-bit RGB pixels (row-major, 3 bytes per pixel) as a PNG file.
+pub fn encode_png(width: u32, height: u32, rgb: &[u8]) -> Result<Vec<u8>, String> {
+    let stride = width as usize * 3;
+    if rgb.len() != stride * height as usize {
+        return Err("Image data does not match its size".to_string());
+    }
+    // Each scanline is prefixed with filter type 0 (none)
+    let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+    for row in rgb.chunks(stride.max(1)) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut z = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    z.write_all(&raw).map_err(|e| e.to_string())?;
+    let idat = z.finish().map_err(|e| e.to_string())?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]); // 8-bit, truecolor, deflate, no filter, no interlace
+
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+    for (kind, data) in [(b"IHDR", &ihdr[..]), (b"IDAT", &idat[..]), (b"IEND", &[][..])] {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        let mut crc = flate2::Crc::new();
+        crc.update(kind);
+        crc.update(data);
+        png.extend_from_slice(&crc.sum().to_be_bytes());
+    }
+    Ok(png)
+}
+
+/// Render the diagram off-screen and encode it as PNG.
+fn render_png(layout: &GraphLayout) -> Result<Vec<u8>, String> {
+    let surf = ImageSurface::new(layout.width, layout.height, false);
+    ImageSurface::push_current(&surf);
+    draw_layout(layout, 0, 0);
+    ImageSurface::pop_current();
+    let img = surf.image().ok_or("Failed to render the graph")?;
+    let img = img.convert(fltk::enums::ColorDepth::Rgb8).map_err(|e| e.to_string())?;
+    encode_png(img.data_w() as u32, img.data_h() as u32, &img.to_rgb_data())
+}
+
+/// Build the route graph panel in the current group: a scrolled diagram with Clear and
+/// Export below it. It redraws with the shared graph on every UI refresh.
+pub fn build_graph_panel(x: i32, y: i32, w: i32, h: i32, graph: Arc<Mutex<RouteGraph>>) -> Group {
+    let panel = Group::new(x, y, w, h, "");
+    let scroll = Scroll::new(x, y, w, h - 30, "");
+    let mut canvas = Frame::new(x, y, w - 20, h - 50, "");
+    canvas.set_frame(FrameType::NoBox);
+    scroll.end();
+    let mut clear_btn = Button::new(x, y + h - 25, 70, 25, "Clear");
+    let mut export_btn = Button::new(x + w - 80, y + h - 25, 80, 25, "Export");
+    let mut status = Frame::new(x + 75, y + h - 25, w - 160, 25, "");
+    status.set_align(Align::Left | Align::Inside);
+    panel.end();
+
+    {
+        let graph = graph.clone();
+        canvas.draw(move |f| {
+            let layout = graph.lock().map(|g| g.layout()).unwrap_or_default();
+            // Grow the canvas so the scroll area covers the whole diagram
+            if f.w() != layout.width || f.h() != layout.height {
+                f.resize(f.x(), f.y(), layout.width, layout.height);
+                if let Some(mut p) = f.parent() { p.redraw(); }
+                return;
+            }
+            draw::push_clip(f.x(), f.y(), f.w(), f.h());
+            draw_layout(&layout, f.x(), f.y());
+            draw::pop_clip();
+        });
+    }
+
+    {
+        let graph = graph.clone();
+        let mut scroll = scroll.clone();
+        let mut status = status.clone();
+        clear_btn.set_callback(move |_| {
+            if let Ok(mut g) = graph.lock() { g.clear(); }
+            status.set_label("");
+            scroll.redraw();
+        });
+    }
+
+    {
+        let graph = graph.clone();
+        let mut status = status.clone();
+        export_btn.set_callback(move |_| {
+            let layout = match graph.lock() {
+                Ok(g) if !g.is_empty() => g.layout(),
+                _ => { status.set_label("No traces to export."); return; }
+            };
+            let Some(path) = choose_save_path("Export route graph", "SVG\t*.svg\nPNG\t*.png", "route.svg") else { return };
+            let is_png = path.extension().map(|e| e.eq_ignore_ascii_case("png")).unwrap_or(false);
+            let data = if is_png { render_png(&layout) } else { Ok(to_svg(&layout).into_bytes()) };
+            let result = data.and_then(|d| std::fs::write(&path, d).map_err(|e| e.to_string()));
+            match result {
+                Ok(_) => status.set_label(&format!("Exported to {}", path.display())),
+                Err(e) => status.set_label(&format!("Export failed: {}", e)),
+            }
+        });
+    }
+
+    panel
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::IpAddr;
+
+    fn hop(n: u32, addr: Option<&str>, rtts: Vec<Option<f64>>) -> HopRecord {
+        let mut rec = HopRecord::new(n);
+        if let Some(a) = addr {
+            rec.add_responder(a.parse::<IpAddr>().unwrap(), None);
+        }
+        rec.rtts = rtts;
+        rec
+    }
+
+    fn two_traces() -> Vec<(String, Vec<HopRecord>)> {
+        vec![
+            ("8.8.8.8".to_string(), vec![
+                hop(1, Some("192.168.0.1"), vec![Some(1.0), Some(3.0)]),
+                hop(2, Some("10.0.0.1"), vec![Some(5.0), None]),
+                hop(3, None, vec![None, None]),
+                hop(4, Some("8.8.8.8"), vec![Some(12.0), Some(12.0)]),
+            ]),
+            ("1.1.1.1".to_string(), vec![
+                hop(1, Some("192.168.0.1"), vec![Some(2.0)]),
+                hop(2, Some("10.0.0.1"), vec![Some(6.0)]),
+                hop(3, Some("1.1.1.1"), vec![Some(9.0)]),
+            ]),
+        ]
+    }
+
+    #[test]
+    fn test_layout_shared_trunk() {
+        let l = layout(&two_traces());
+        assert_eq!(l.targets, vec!["8.8.8.8", "1.1.1.1"]);
+        // 192.168.0.1 and 10.0.0.1 are shared, "*" is per trace
+        assert_eq!(l.nodes.len(), 5);
+        let gw = &l.nodes[0];
+        assert_eq!((gw.label.as_str(), gw.traces.clone()), ("192.168.0.1", vec![0, 1]));
+        assert_eq!(gw.rtt, Some(2.0));
+        assert_eq!(gw.loss_pct, 0.0);
+        assert_eq!(l.nodes[1].loss_pct, 100.0 / 3.0);
+        assert_eq!(l.nodes[2].label, "*");
+        assert_eq!(l.nodes[2].loss_pct, 100.0);
+        // shared nodes sit between the two lanes, on the row of their hop
+        assert!(l.nodes[3].x < gw.x && gw.x < l.nodes[4].x);
+        assert!(l.nodes[0].y < l.nodes[1].y && l.nodes[1].y < l.nodes[2].y);
+        // the trunk edge is used by both traces
+        let trunk = l.edges.iter().find(|e| e.from == 0 && e.to == 1).unwrap();
+        assert_eq!(trunk.traces, vec![0, 1]);
+        assert_eq!(l.edges.len(), 4);
+        assert!(l.width >= 2 * LANE_W && l.height > l.nodes[3].y);
+    }
+
+    #[test]
+    fn test_route_graph_replaces_same_target() {
+        let mut g = RouteGraph::default();
+        let traces = two_traces();
+        g.set_trace(&traces[0].0, &traces[0].1);
+        g.set_trace(&traces[1].0, &traces[1].1);
+        g.set_trace(&traces[0].0, &traces[0].1[..1]);
+        let l = g.layout();
+        assert_eq!(l.targets.len(), 2);
+        assert_eq!(l.nodes.len(), 3);
+        g.clear();
+        assert!(g.is_empty());
+    }
+
+    #[test]
+    fn test_loss_colors() {
+        assert_eq!(loss_color(0.0), (46, 160, 67));
+        assert_eq!(loss_color(10.0), (230, 190, 0));
+        assert_eq!(loss_color(60.0), (240, 120, 0));
+        assert_eq!(loss_color(100.0), (210, 40, 40));
+    }
+
+    #[test]
+    fn test_svg_export() {
+        let mut traces = two_traces();
+        traces[1].0 = "a<b>".to_string();
+        let svg = to_svg(&layout(&traces));
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<circle").count(), 5);
+        assert!(svg.contains("a&lt;b&gt;"));
+        assert!(svg.contains("2.0 ms"));
+        assert!(svg.contains("5.5 ms, 33% loss"));
+        assert!(svg.contains("timeout"));
+    }
+
+    #[test]
+    fn test_encode_png() {
+        let pixels: Vec<u8> = (0..2 * 2 * 3).map(|i| i as u8).collect();
+        let png = encode_png(2, 2, &pixels).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), 2);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+        // IDAT holds the filtered scanlines
+        let idat_len = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let mut raw = Vec::new();
+        flate2::read::ZlibDecoder::new(&png[41..41 + idat_len]).read_to_end(&mut raw).unwrap();
+        assert_eq!(raw, vec![0, 0, 1, 2, 3, 4, 5, 0, 6, 7, 8, 9, 10, 11]);
+        assert!(encode_png(3, 3, &pixels).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+// Port/service-name catalog and named port presets
+
+use std::{collections::{BTreeMap, HashMap, HashSet}, sync::OnceLock};
+use crate::utils::parse_ports;
+
+/// Bundled catalog in /etc/services format.
+const SERVICES_TXT: &str = include_str!("../data/services.txt");
+
+/// The 100 most frequently open TCP ports of the nmap-services ranking, most common first.
+const TOP100_PORTS: &[u16] = &[
+    80, 23, 443, 21, 22, 25, 3389, 110, 445, 139, 143, 53, 135, 3306, 8080, 1723, 111, 995,
+    993, 5900, 1025, 587, 8888, 199, 1720, 465, 548, 113, 81, 6001, 10000, 514, 5060, 179,
+    1026, 2000, 8443, 8000, 32768, 554, 26, 1433, 49152, 2001, 515, 8008, 49154, 1027, 5666,
+    646, 5000, 5631, 631, 49153, 8081, 2049, 88, 79, 5800, 106, 2121, 1110, 49155, 6000, 513,
+    990, 5357, 427, 49156, 543, 544, 5101, 144, 7, 389, 8009, 3128, 444, 9999, 5009, 7070,
+    5190, 3000, 5432, 1900, 3986, 13, 1029, 9, 5051, 6646, 49157, 1028, 873, 1755, 2717, 4899,
+    9100, 119, 37,
+];
+
+/// Windows / Active Directory services.
+const WINDOWS_PORTS: &[u16] = &[
+    53, 88, 135, 137, 139, 389, 445, 464, 636, 3268, 3269, 3389, 5985, 5986, 9389,
+];
+
+/// Network printers.
+const PRINTER_PORTS: &[u16] = &[80, 443, 515, 631, 9100];
+
+/// Industrial control protocols (S7, Modbus, EtherNet/IP, BACnet).
+const INDUSTRIAL_PORTS: &[u16] = &[102, 502, 44818, 47808];
+
+/// Names of the built-in presets, in display order.
+pub const BUILTIN_PRESETS: &[&str] = &["top100", "top1000", "catalog1000", "windows", "printers", "industrial"];
+
+struct Catalog {
+    by_port: HashMap<u16, String>,
+    by_name: HashMap<String, u16>,
+}
+
+/// Parse /etc/services-style text into a catalog.
+fn parse_catalog(text: &str) -> Catalog {
+    let mut by_port = HashMap::new();
+    let mut by_name = HashMap::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(port_proto)) = (fields.next(), fields.next()) else { continue };
+        let Some((port, "tcp")) = port_proto.split_once('/') else { continue };
+        let Ok(port) = port.parse::<u16>() else { continue };
+        by_port.entry(port).or_insert_with(|| name.to_string());
+        for n in std::iter::once(name).chain(fields) {
+            by_name.entry(n.to_ascii_lowercase()).or_insert(port);
+        }
+    }
+    Catalog { by_port, by_name }
+}
+
+fn catalog() -> &'static Catalog {
+    static CATALOG: OnceLock<Catalog> = OnceLock::new();
+    CATALOG.get_or_init(|| parse_catalog(SERVICES_TXT))
+}
+
+/// Service name registered for a TCP port, if any.
+pub fn service_name(port: u16) -> Option<&'static str> {
+    catalog().by_port.get(&port).map(String::as_str)
+}
+
+/// Port number for a service name or alias (case-insensitive).
+pub fn port_by_name(name: &str) -> Option<u16> {
+    catalog().by_name.get(&name.to_ascii_lowercase()).copied()
+}
+
+/// Format a port for result lines, e.g. "22/tcp ssh".
+pub fn port_label(port: u16) -> String {
+    match service_name(port) {
+        Some(name) => format!("{}/tcp {}", port, name),
+        None => format!("{}/tcp", port),
+    }
+}
+
+/// Ports of a built-in preset.
+///
+/// `catalog1000` is `top100` followed by the other catalog ports in numeric order and
+/// then the remaining well-known ports, capped at 1000 entries. `top1000` is kept as
+/// an alias of it; only its first 100 ports follow the frequency ranking.
+pub fn builtin_preset(name: &str) -> Option<Vec<u16>> {
+    let ports = match name.to_ascii_lowercase().as_str() {
+        "top100" => TOP100_PORTS.to_vec(),
+        "catalog1000" | "top1000" => {
+            let mut seen = HashSet::new();
+            let mut catalog_ports: Vec<u16> = catalog().by_port.keys().copied().collect();
+            catalog_ports.sort_unstable();
+            TOP100_PORTS.iter().copied()
+                .chain(catalog_ports)
+                .chain(1..=1024)
+                .filter(|p| seen.insert(*p))
+                .take(1000)
+                .collect()
+        }
+        "windows" => WINDOWS_PORTS.to_vec(),
+        "printers" => PRINTER_PORTS.to_vec(),
+        "industrial" => INDUSTRIAL_PORTS.to_vec(),
+        _ => return None,
+    };
+    Some(ports)
+}
+
+/// Parse a port specification that may mix numbers, ranges, service names and presets,
+/// e.g. "ssh,https,8000-8010,industrial". User presets come from the config file and
+/// may themselves use numbers, ranges, service names and built-in presets.
+/// Returns a deduplicated list in input order.
+pub fn parse_port_spec(s: &str, user_presets: &BTreeMap<String, String>) -> Result<Vec<u16>, String> {
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for raw in s.split(',') {
+        let token = raw.trim();
+        if token.is_empty() { continue; }
+        let ports = match user_presets.get(token) {
+            Some(spec) => parse_port_spec(spec, &BTreeMap::new())
+                .map_err(|e| format!("{} (preset '{}')", e, token))?,
+            None => resolve_token(token)?,
+        };
+        for p in ports {
+            if seen.insert(p) { out.push(p); }
+        }
+    }
+    Ok(out)
+}
+
+/// Resolve a single token: number, numeric range, built-in preset or service name.
+fn resolve_token(token: &str) -> Result<Vec<u16>, String> {
+    let is_numeric = |t: &str| !t.is_empty() && t.chars().all(|c| c.is_ascii_digit() || c == '-' || c.is_whitespace());
+    if is_numeric(token) {
+        return parse_ports(token);
+    }
+    if let Some(ports) = builtin_preset(token) {
+        return Ok(ports);
+    }
+    port_by_name(token)
+        .map(|p| vec![p])
+        .ok_or_else(|| format!("Unknown port or service: {} (presets: {})", token, BUILTIN_PRESETS.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_lookup() {
+        assert_eq!(service_name(22), Some("ssh"));
+        assert_eq!(service_name(443), Some("https"));
+        assert_eq!(port_by_name("rdp"), Some(3389));
+        assert_eq!(port_by_name("HTTP"), Some(80));
+        assert_eq!(port_by_name("www"), Some(80));
+        assert_eq!(port_label(22), "22/tcp ssh");
+        assert_eq!(port_label(1), "1/tcp tcpmux");
+        assert_eq!(port_label(65000), "65000/tcp");
+    }
+
+    #[test]
+    fn test_builtin_presets() {
+        assert_eq!(builtin_preset("industrial").unwrap(), vec![102, 502, 44818, 47808]);
+        assert_eq!(builtin_preset("top100").unwrap().len(), 100);
+        let catalog1000 = builtin_preset("catalog1000").unwrap();
+        assert_eq!(catalog1000.len(), 1000);
+        assert_eq!(catalog1000.iter().collect::<HashSet<_>>().len(), 1000);
+        assert!(catalog1000.starts_with(TOP100_PORTS));
+        assert_eq!(builtin_preset("top1000").unwrap(), catalog1000);
+        assert!(builtin_preset("top100").unwrap().starts_with(&[80, 23, 443]));
+        for name in BUILTIN_PRESETS {
+            assert!(builtin_preset(name).is_some());
+        }
+        assert!(builtin_preset("nope").is_none());
+    }
+
+    #[test]
+    fn test_parse_port_spec_names_and_ranges() {
+        let none = BTreeMap::new();
+        assert_eq!(parse_port_spec("ssh,http,https,rdp", &none).unwrap(), vec![22, 80, 443, 3389]);
+        assert_eq!(parse_port_spec("22, 8000-8002, http", &none).unwrap(), vec![22, 8000, 8001, 8002, 80]);
+        assert_eq!(parse_port_spec("ftp-data,ssh,22", &none).unwrap(), vec![20, 22]);
+        assert_eq!(parse_port_spec("printers,https", &none).unwrap(), vec![80, 443, 515, 631, 9100]);
+    }
+
+    #[test]
+    fn test_parse_port_spec_user_presets() {
+        let mut presets = BTreeMap::new();
+        presets.insert("web".to_string(), "http,https,8080".to_string());
+        presets.insert("plant".to_string(), "industrial,ssh".to_string());
+        assert_eq!(parse_port_spec("web", &presets).unwrap(), vec![80, 443, 8080]);
+        assert_eq!(parse_port_spec("plant,web", &presets).unwrap(), vec![102, 502, 44818, 47808, 22, 80, 443, 8080]);
+        presets.insert("broken".to_string(), "bogus".to_string());
+        assert!(parse_port_spec("broken", &presets).is_err());
+    }
+
+    #[test]
+    fn test_parse_port_spec_invalid() {
+        let none = BTreeMap::new();
+        assert!(parse_port_spec("nosuchservice", &none).is_err());
+        assert!(parse_port_spec("10-5", &none).is_err());
+        assert!(parse_port_spec("65536", &none).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+// SMB2 host information (TCP 445): the negotiated dialect and signing mode from NEGOTIATE,
+// then the OS version and names from the NTLM challenge of an anonymous SESSION_SETUP.
+// No session is established.
+
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// SMB over TCP (direct hosting) port.
+pub const SMB_PORT: u16 = 445;
+
+/// Timeout for the connection and each answer.
+pub const SMB_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Dialects offered in NEGOTIATE, oldest first.
+const DIALECTS: [u16; 5] = [0x0202, 0x0210, 0x0300, 0x0302, 0x0311];
+
+const STATUS_SUCCESS: u32 = 0;
+const STATUS_MORE_PROCESSING_REQUIRED: u32 = 0xc000_0016;
+
+const NTLMSSP: &[u8] = b"NTLMSSP\0";
+
+/// Dialect name, e.g. 0x0311 -> "3.1.1".
+pub fn dialect_name(dialect: u16) -> String {
+    match dialect {
+        0x0202 => "2.0.2".to_string(),
+        0x0210 => "2.1".to_string(),
+        0x0300 => "3.0".to_string(),
+        0x0302 => "3.0.2".to_string(),
+        0x0311 => "3.1.1".to_string(),
+        d => format!("0x{:04x}", d),
+    }
+}
+
+/// Information gathered from the server.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmbInfo {
+    pub dialect: u16,
+    pub signing_required: bool,
+    /// Windows version from the NTLM challenge, e.g. (10, 0, 19041)
+    pub os_version: Option<(u8, u8, u16)>,
+    pub nb_computer: Option<String>,
+    pub nb_domain: Option<String>,
+    pub dns_computer: Option<String>,
+}
+
+impl SmbInfo {
+    /// Text for the Host Info column, e.g. "SMB 3.1.1 signing required, OS 10.0.19041, pc01.corp.example".
+    pub fn summary(&self) -> String {
+        let mut s = format!("SMB {}", dialect_name(self.dialect));
+        if self.signing_required {
+            s.push_str(" signing required");
+        }
+        if let Some((major, minor, build)) = self.os_version {
+            s.push_str(&format!(", OS {}.{}.{}", major, minor, build));
+        }
+        if let Some(name) = self.dns_computer.as_ref().or(self.nb_computer.as_ref()) {
+            s.push_str(&format!(", {}", name));
+        }
+        s
+    }
+}
+
+/// 64-byte SMB2 header for a request.
+fn header(command: u16, message_id: u64) -> Vec<u8> {
+    let mut h = Vec::with_capacity(64);
+    h.extend_from_slice(b"\xfeSMB");
+    h.extend_from_slice(&64u16.to_le_bytes()); // StructureSize
+    h.extend_from_slice(&0u16.to_le_bytes()); // CreditCharge
+    h.extend_from_slice(&0u32.to_le_bytes()); // Status
+    h.extend_from_slice(&command.to_le_bytes());
+    h.extend_from_slice(&1u16.to_le_bytes()); // CreditRequest
+    h.extend_from_slice(&0u32.to_le_bytes()); // Flags
+    h.extend_from_slice(&0u32.to_le_bytes()); // NextCommand
+    h.extend_from_slice(&message_id.to_le_bytes());
+    h.extend_from_slice(&[0u8; 4 + 4 + 8 + 16]); // Reserved, TreeId, SessionId, Signature
+    h
+}
+
+/// Add the 4-byte direct hosting header (zero, 24-bit length).
+fn frame(msg: Vec<u8>) -> Vec<u8> {
+    let len = msg.len() as u32;
+    let mut out = vec![0, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+    out.extend(msg);
+    out
+}
+
+/// NEGOTIATE request offering 2.0.2 to 3.1.1 (with the preauth integrity context 3.1.1 requires).
+pub fn build_negotiate(client_guid: [u8; 16], salt: [u8; 32]) -> Vec<u8> {
+    let mut msg = header(0, 0);
+    msg.extend_from_slice(&36u16.to_le_bytes());
+    msg.extend_from_slice(&(DIALECTS.len() as u16).to_le_bytes());
+    msg.extend_from_slice(&1u16.to_le_bytes()); // SecurityMode: signing enabled
+    msg.extend_from_slice(&0u16.to_le_bytes());
+    msg.extend_from_slice(&0u32.to_le_bytes()); // Capabilities
+    msg.extend_from_slice(&client_guid);
+    // NegotiateContextOffset / Count (ClientStartTime before 3.1.1), patched below
+    let ctx_field = msg.len();
+    msg.extend_from_slice(&[0u8; 8]);
+    for d in DIALECTS {
+        msg.extend_from_slice(&d.to_le_bytes());
+    }
+    while !msg.len().is_multiple_of(8) {
+        msg.push(0);
+    }
+    let ctx_offset = msg.len() as u32;
+    msg[ctx_field..ctx_field + 4].copy_from_slice(&ctx_offset.to_le_bytes());
+    msg[ctx_field + 4..ctx_field + 6].copy_from_slice(&1u16.to_le_bytes());
+    // SMB2_PREAUTH_INTEGRITY_CAPABILITIES: one algorithm (SHA-512) and a salt
+    msg.extend_from_slice(&1u16.to_le_bytes());
+    msg.extend_from_slice(&(6 + salt.len() as u16).to_le_bytes());
+    msg.extend_from_slice(&0u32.to_le_bytes());
+    msg.extend_from_slice(&1u16.to_le_bytes());
+    msg.extend_from_slice(&(salt.len() as u16).to_le_bytes());
+    msg.extend_from_slice(&1u16.to_le_bytes());
+    msg.extend_from_slice(&salt);
+    frame(msg)
+}
+
+/// NTLMSSP NEGOTIATE_MESSAGE asking for the target info and the version.
+fn ntlm_negotiate() -> Vec<u8> {
+    let mut m = NTLMSSP.to_vec();
+    m.extend_from_slice(&1u32.to_le_bytes());
+    // UNICODE, OEM, REQUEST_TARGET, NTLM, ALWAYS_SIGN, EXTENDED_SESSIONSECURITY, TARGET_INFO, VERSION, 128, 56
+    m.extend_from_slice(&0xa288_8207u32.to_le_bytes());
+    m.extend_from_slice(&[0u8; 16]); // DomainNameFields, WorkstationFields
+    m.extend_from_slice(&[0u8; 8]); // Version
+    m
+}
+
+/// SESSION_SETUP request carrying a raw NTLMSSP negotiate.
+pub fn build_session_setup() -> Vec<u8> {
+    let token = ntlm_negotiate();
+    let mut msg = header(1, 1);
+    msg.extend_from_slice(&25u16.to_le_bytes());
+    msg.push(0); // Flags
+    msg.push(1); // SecurityMode: signing enabled
+    msg.extend_from_slice(&0u32.to_le_bytes()); // Capabilities
+    msg.extend_from_slice(&0u32.to_le_bytes()); // Channel
+    msg.extend_from_slice(&(64u16 + 24).to_le_bytes());
+    msg.extend_from_slice(&(token.len() as u16).to_le_bytes());
+    msg.extend_from_slice(&0u64.to_le_bytes()); // PreviousSessionId
+    msg.extend(token);
+    frame(msg)
+}
+
+fn u16_at(b: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(b.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn u32_at(b: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(b.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// Check the SMB2 header of a response; returns its status.
+fn response_status(msg: &[u8], command: u16) -> Result<u32, String> {
+    if msg.len() < 64 || &msg[..4] != b"\xfeSMB" {
+        return Err("Not an SMB2 response".to_string());
+    }
+    if u16_at(msg, 12) != Some(command) {
+        return Err("Unexpected SMB2 command in response".to_string());
+    }
+    Ok(u32_at(msg, 8).unwrap_or(0))
+}
+
+/// Dialect and "signing required" from a NEGOTIATE response (without the 4-byte frame header).
+pub fn parse_negotiate(msg: &[u8]) -> Result<(u16, bool), String> {
+    let status = response_status(msg, 0)?;
+    if status != STATUS_SUCCESS {
+        return Err(format!("NEGOTIATE failed (status 0x{:08x})", status));
+    }
+    let security_mode = u16_at(msg, 66).ok_or("Truncated NEGOTIATE response")?;
+    let dialect = u16_at(msg, 68).ok_or("Truncated NEGOTIATE response")?;
+    Ok((dialect, security_mode & 0x0002 != 0))
+}
+
+fn utf16(b: &[u8]) -> String {
+    let units: Vec<u16> = b.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Apply the fields of an NTLMSSP CHALLENGE_MESSAGE found in a SESSION_SETUP response
+/// (raw or wrapped in SPNEGO) to `info`.
+pub fn parse_session_setup(msg: &[u8], info: &mut SmbInfo) -> Result<(), String> {
+    let status = response_status(msg, 1)?;
+    if status != STATUS_MORE_PROCESSING_REQUIRED {
+        return Err(format!("SESSION_SETUP status 0x{:08x}", status));
+    }
+    let start = msg.windows(NTLMSSP.len()).position(|w| w == NTLMSSP).ok_or("No NTLM challenge")?;
+    let ntlm = &msg[start..];
+    if u32_at(ntlm, 8) != Some(2) {
+        return Err("Not an NTLM challenge".to_string());
+    }
+    let flags = u32_at(ntlm, 20).ok_or("Truncated NTLM challenge")?;
+    // NTLMSSP_NEGOTIATE_VERSION
+    if flags & 0x0200_0000 != 0 {
+        if let (Some(&major), Some(&minor), Some(build)) = (ntlm.get(48), ntlm.get(49), u16_at(ntlm, 50)) {
+            info.os_version = Some((major, minor, build));
+        }
+    }
+    // Target info AV pairs
+    let len = u16_at(ntlm, 40).ok_or("Truncated NTLM challenge")? as usize;
+    let offset = u32_at(ntlm, 44).ok_or("Truncated NTLM challenge")? as usize;
+    let Some(mut av) = ntlm.get(offset..offset + len) else { return Ok(()) };
+    while av.len() >= 4 {
+        let id = u16::from_le_bytes([av[0], av[1]]);
+        let vlen = u16::from_le_bytes([av[2], av[3]]) as usize;
+        let Some(value) = av.get(4..4 + vlen) else { break };
+        match id {
+            0 => break,
+            1 => info.nb_computer = Some(utf16(value)),
+            2 => info.nb_domain = Some(utf16(value)),
+            3 => info.dns_computer = Some(utf16(value)),
+            _ => {}
+        }
+        av = &av[4 + vlen..];
+    }
+    Ok(())
+}
+
+/// Read one framed message.
+fn read_message(stream: &mut TcpStream) -> Result<Vec<u8>, String> {
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).map_err(|e| e.to_string())?;
+    let len = u32::from_be_bytes([0, head[1], head[2], head[3]]) as usize;
+    if len > 1 << 16 {
+        return Err("SMB response too large".to_string());
+    }
+    let mut msg = vec![0u8; len];
+    stream.read_exact(&mut msg).map_err(|e| e.to_string())?;
+    Ok(msg)
+}
+
+/// Query `target`: NEGOTIATE, then an anonymous SESSION_SETUP for the NTLM challenge.
+/// A failed SESSION_SETUP still returns the negotiated dialect.
+pub fn query(target: SocketAddr, timeout: Duration, running: &AtomicBool) -> Result<SmbInfo, String> {
+    let mut stream = TcpStream::connect_timeout(&target, timeout).map_err(|e| format!("{}: {}", target, e))?;
+    stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    let mut guid = [0u8; 16];
+    guid.copy_from_slice(&nanos.to_le_bytes());
+    let mut salt = [0u8; 32];
+    salt[..16].copy_from_slice(&guid);
+    salt[16..].copy_from_slice(&(nanos ^ u128::from(std::process::id())).to_be_bytes());
+    stream.write_all(&build_negotiate(guid, salt)).map_err(|e| e.to_string())?;
+    let (dialect, signing_required) = parse_negotiate(&read_message(&mut stream)?)?;
+    let mut info = SmbInfo { dialect, signing_required, ..SmbInfo::default() };
+    if !running.load(Ordering::SeqCst) {
+        return Ok(info);
+    }
+    if stream.write_all(&build_session_setup()).is_ok() {
+        if let Ok(msg) = read_message(&mut stream) {
+            let _ = parse_session_setup(&msg, &mut info);
+        }
+    }
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+
+    fn response_header(command: u16, status: u32) -> Vec<u8> {
+        let mut h = header(command, 0);
+        h[8..12].copy_from_slice(&status.to_le_bytes());
+        h[16..20].copy_from_slice(&1u32.to_le_bytes()); // SERVER_TO_REDIR
+        h
+    }
+
+    fn negotiate_response(dialect: u16, security_mode: u16) -> Vec<u8> {
+        let mut m = response_header(0, STATUS_SUCCESS);
+        m.extend_from_slice(&65u16.to_le_bytes());
+        m.extend_from_slice(&security_mode.to_le_bytes());
+        m.extend_from_slice(&dialect.to_le_bytes());
+        m.extend_from_slice(&[0u8; 58]);
+        m
+    }
+
+    /// SESSION_SETUP response with an NTLM challenge from Windows 10 build 19041.
+    fn session_setup_response() -> Vec<u8> {
+        let mut av = Vec::new();
+        for (id, value) in [(2u16, "CORP"), (1, "PC01"), (3, "pc01.corp.example")] {
+            let v: Vec<u8> = value.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+            av.extend_from_slice(&id.to_le_bytes());
+            av.extend_from_slice(&(v.len() as u16).to_le_bytes());
+            av.extend(v);
+        }
+        av.extend_from_slice(&[0, 0, 0, 0]);
+        let mut ntlm = NTLMSSP.to_vec();
+        ntlm.extend_from_slice(&2u32.to_le_bytes());
+        ntlm.extend_from_slice(&[0u8; 8]); // TargetNameFields
+        ntlm.extend_from_slice(&0xe289_8215u32.to_le_bytes());
+        ntlm.extend_from_slice(&[0x11; 8]); // ServerChallenge
+        ntlm.extend_from_slice(&[0u8; 8]);
+        ntlm.extend_from_slice(&(av.len() as u16).to_le_bytes());
+        ntlm.extend_from_slice(&(av.len() as u16).to_le_bytes());
+        ntlm.extend_from_slice(&56u32.to_le_bytes());
+        ntlm.extend_from_slice(&[10, 0, 0x61, 0x4a, 0, 0, 0, 15]);
+        ntlm.extend(av);
+        let mut m = response_header(1, STATUS_MORE_PROCESSING_REQUIRED);
+        m.extend_from_slice(&9u16.to_le_bytes());
+        m.extend_from_slice(&0u16.to_le_bytes());
+        m.extend_from_slice(&72u16.to_le_bytes());
+        m.extend_from_slice(&(ntlm.len() as u16).to_le_bytes());
+        m.extend(ntlm);
+        m
+    }
+
+    #[test]
+    fn test_requests_and_parse() {
+        let neg = build_negotiate([1; 16], [2; 32]);
+        assert_eq!(u32::from_be_bytes([0, neg[1], neg[2], neg[3]]) as usize, neg.len() - 4);
+        let msg = &neg[4..];
+        assert_eq!(&msg[..4], b"\xfeSMB");
+        assert_eq!(u16_at(msg, 66), Some(5));
+        // Negotiate context at an 8-byte aligned offset
+        let ctx = u32_at(msg, 64 + 28).unwrap() as usize;
+        assert_eq!(ctx % 8, 0);
+        assert_eq!(u16_at(msg, ctx), Some(1));
+        assert_eq!(u16_at(msg, 64 + 36 + 8), Some(0x0311));
+
+        assert_eq!(parse_negotiate(&negotiate_response(0x0311, 0x0003)), Ok((0x0311, true)));
+        assert_eq!(parse_negotiate(&negotiate_response(0x0210, 0x0001)), Ok((0x0210, false)));
+        assert!(parse_negotiate(&session_setup_response()).is_err());
+
+        let mut info = SmbInfo { dialect: 0x0311, signing_required: true, ..SmbInfo::default() };
+        parse_session_setup(&session_setup_response(), &mut info).unwrap();
+        assert_eq!(info.os_version, Some((10, 0, 19041)));
+        assert_eq!(info.nb_computer.as_deref(), Some("PC01"));
+        assert_eq!(info.nb_domain.as_deref(), Some("CORP"));
+        assert_eq!(info.summary(), "SMB 3.1.1 signing required, OS 10.0.19041, pc01.corp.example");
+        assert_eq!(SmbInfo { dialect: 0x0302, ..SmbInfo::default() }.summary(), "SMB 3.0.2");
+    }
+
+    #[test]
+    fn test_query_against_stand_in() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let Ok((mut s, _)) = listener.accept() else { return };
+            for reply in [negotiate_response(0x0302, 0x0001), session_setup_response()] {
+                let Ok(req) = read_message(&mut s) else { return };
+                assert_eq!(&req[..4], b"\xfeSMB");
+                let _ = s.write_all(&frame(reply));
+            }
+        });
+        let running = AtomicBool::new(true);
+        let info = query(addr, Duration::from_secs(2), &running).unwrap();
+        assert_eq!(info.summary(), "SMB 3.0.2, OS 10.0.19041, pc01.corp.example");
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        assert!(query(closed, Duration::from_secs(1), &running).is_err());
+    }
+}
@@ -0,0 +1,1359 @@
+// SNMP v1/v2c/v3 client: GET / GETNEXT over UDP with a minimal BER codec, USM
+// authentication (HMAC-MD5-96 / HMAC-SHA-96) and privacy (DES-CBC / AES-128-CFB)
+
+use aes::Aes128;
+use cbc::cipher::{block_padding::NoPadding, AsyncStreamCipher, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use hmac::{digest::Digest, Hmac, Mac};
+use md5::Md5;
+use serde::Deserialize;
+use sha1::Sha1;
+use std::{
+    fmt,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use crate::rate::RateLimiter;
+
+/// SNMP agent port.
+pub const SNMP_PORT: u16 = 161;
+
+/// System group objects, in display order.
+pub const SYS_DESCR: &str = "1.3.6.1.2.1.1.1.0";
+pub const SYS_OBJECT_ID: &str = "1.3.6.1.2.1.1.2.0";
+pub const SYS_UPTIME: &str = "1.3.6.1.2.1.1.3.0";
+pub const SYS_CONTACT: &str = "1.3.6.1.2.1.1.4.0";
+pub const SYS_NAME: &str = "1.3.6.1.2.1.1.5.0";
+pub const SYS_LOCATION: &str = "1.3.6.1.2.1.1.6.0";
+
+/// Interface table columns (ifTable / ifXTable).
+const IF_DESCR: &str = "1.3.6.1.2.1.2.2.1.2";
+const IF_TYPE: &str = "1.3.6.1.2.1.2.2.1.3";
+const IF_MTU: &str = "1.3.6.1.2.1.2.2.1.4";
+const IF_SPEED: &str = "1.3.6.1.2.1.2.2.1.5";
+const IF_PHYS_ADDRESS: &str = "1.3.6.1.2.1.2.2.1.6";
+const IF_ADMIN_STATUS: &str = "1.3.6.1.2.1.2.2.1.7";
+const IF_OPER_STATUS: &str = "1.3.6.1.2.1.2.2.1.8";
+const IF_NAME: &str = "1.3.6.1.2.1.31.1.1.1.1";
+const IF_HIGH_SPEED: &str = "1.3.6.1.2.1.31.1.1.1.15";
+const IF_ALIAS: &str = "1.3.6.1.2.1.31.1.1.1.18";
+
+/// Upper bound of objects returned by one walk.
+const MAX_WALK: usize = 10_000;
+
+const PDU_GET: u8 = 0xa0;
+const PDU_GET_NEXT: u8 = 0xa1;
+const PDU_RESPONSE: u8 = 0xa2;
+const PDU_REPORT: u8 = 0xa8;
+
+const FLAG_AUTH: u8 = 0x01;
+const FLAG_PRIV: u8 = 0x02;
+const FLAG_REPORTABLE: u8 = 0x04;
+
+/// usmStats counters sent in Report PDUs.
+const USM_STATS: [(&str, &str); 6] = [
+    ("1.3.6.1.6.3.15.1.1.1.0", "unsupported security level"),
+    ("1.3.6.1.6.3.15.1.1.2.0", "not in time window"),
+    ("1.3.6.1.6.3.15.1.1.3.0", "unknown user name"),
+    ("1.3.6.1.6.3.15.1.1.4.0", "unknown engine ID"),
+    ("1.3.6.1.6.3.15.1.1.5.0", "wrong digest (check the auth password)"),
+    ("1.3.6.1.6.3.15.1.1.6.0", "decryption error (check the privacy password)"),
+];
+
+/// Credentials and timing read from the `[snmp]` section, or from `[profiles.<name>.snmp]`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SnmpConfig {
+    /// "1", "2c" or "3".
+    pub version: String,
+    /// Communities tried in order (v1/v2c).
+    pub communities: Vec<String>,
+    pub port: u16,
+    pub timeout_ms: u64,
+    pub retries: u32,
+    /// v3 user name.
+    pub user: String,
+    /// "none", "md5" or "sha".
+    pub auth_protocol: String,
+    pub auth_password: String,
+    /// "none", "des" or "aes".
+    pub priv_protocol: String,
+    pub priv_password: String,
+    /// v3 context name.
+    pub context: String,
+}
+
+impl Default for SnmpConfig {
+    fn default() -> Self {
+        SnmpConfig {
+            version: "2c".to_string(),
+            communities: vec!["public".to_string()],
+            port: SNMP_PORT,
+            timeout_ms: 1000,
+            retries: 1,
+            user: String::new(),
+            auth_protocol: "none".to_string(),
+            auth_password: String::new(),
+            priv_protocol: "none".to_string(),
+            priv_password: String::new(),
+            context: String::new(),
+        }
+    }
+}
+
+impl SnmpConfig {
+    /// Short description, e.g. "v2c, 2 communities" or "v3 user monitor (sha/aes)".
+    pub fn describe(&self) -> String {
+        match self.version.as_str() {
+            "3" => format!("v3 user {} ({}/{})", self.user, self.auth_protocol, self.priv_protocol),
+            v => format!("v{}, {} communit{}", v, self.communities.len(), if self.communities.len() == 1 { "y" } else { "ies" }),
+        }
+    }
+}
+
+// ---- BER ----------------------------------------------------------------
+
+fn push_len(out: &mut Vec<u8>, len: usize) {
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = (len as u32).to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (4 - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    push_len(&mut out, content.len());
+    out.extend_from_slice(content);
+    out
+}
+
+fn int_bytes(v: i64) -> Vec<u8> {
+    let bytes = v.to_be_bytes();
+    let mut start = 0;
+    // Drop redundant sign bytes
+    while start < 7 && ((bytes[start] == 0 && bytes[start + 1] & 0x80 == 0) || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0)) {
+        start += 1;
+    }
+    bytes[start..].to_vec()
+}
+
+fn uint_bytes(v: u64) -> Vec<u8> {
+    let mut out = v.to_be_bytes().iter().copied().skip_while(|b| *b == 0).collect::<Vec<u8>>();
+    if out.first().is_none_or(|b| b & 0x80 != 0) {
+        out.insert(0, 0);
+    }
+    out
+}
+
+fn int_value(b: &[u8]) -> i64 {
+    let mut v: i64 = if b.first().is_some_and(|x| x & 0x80 != 0) { -1 } else { 0 };
+    for x in b.iter().take(8) {
+        v = (v << 8) | i64::from(*x);
+    }
+    v
+}
+
+fn uint_value(b: &[u8]) -> u64 {
+    b.iter().take(9).fold(0u64, |v, x| (v << 8) | u64::from(*x))
+}
+
+/// Numeric arcs of a dotted OID ("1.3.6.1..." or ".1.3.6.1...").
+pub fn oid_parts(oid: &str) -> Result<Vec<u32>, String> {
+    let parts: Result<Vec<u32>, _> = oid.trim().trim_start_matches('.').split('.').map(str::parse).collect();
+    match parts {
+        Ok(p) if p.len() >= 2 && p[0] <= 2 => Ok(p),
+        _ => Err(format!("Invalid OID: {}", oid)),
+    }
+}
+
+fn encode_oid(oid: &str) -> Result<Vec<u8>, String> {
+    let parts = oid_parts(oid)?;
+    let mut content = Vec::new();
+    let mut arcs = vec![parts[0] * 40 + parts[1]];
+    arcs.extend_from_slice(&parts[2..]);
+    for arc in arcs {
+        let mut groups = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            groups.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        content.extend(groups.iter().rev());
+    }
+    Ok(tlv(0x06, &content))
+}
+
+fn decode_oid(b: &[u8]) -> String {
+    let mut arcs = Vec::new();
+    let mut cur: u32 = 0;
+    for x in b {
+        cur = (cur << 7) | u32::from(x & 0x7f);
+        if x & 0x80 == 0 {
+            arcs.push(cur);
+            cur = 0;
+        }
+    }
+    let Some(first) = arcs.first().copied() else { return String::new() };
+    let (a, b) = if first < 80 { (first / 40, first % 40) } else { (2, first - 80) };
+    let mut s = format!("{}.{}", a, b);
+    for arc in &arcs[1..] {
+        s.push_str(&format!(".{}", arc));
+    }
+    s
+}
+
+/// Cursor over consecutive TLVs between `pos` and `end` of `buf`.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    end: usize,
+}
+
+/// A TLV found by `Reader`: tag and content bounds in the whole buffer.
+#[derive(Clone, Copy)]
+struct Tlv {
+    tag: u8,
+    /// Offset of the tag octet
+    at: usize,
+    start: usize,
+    end: usize,
+}
+
+const TRUNCATED: &str = "Truncated SNMP message";
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Reader<'a> {
+        Reader { buf, pos: 0, end: buf.len() }
+    }
+
+    fn inside(&self, t: Tlv) -> Reader<'a> {
+        Reader { buf: self.buf, pos: t.start, end: t.end }
+    }
+
+    fn content(&self, t: Tlv) -> &'a [u8] {
+        &self.buf[t.start..t.end]
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.end
+    }
+
+    fn next(&mut self) -> Result<Tlv, String> {
+        let tag = *self.buf.get(self.pos).filter(|_| self.pos < self.end).ok_or(TRUNCATED)?;
+        let first = *self.buf.get(self.pos + 1).ok_or(TRUNCATED)?;
+        let mut start = self.pos + 2;
+        let len = if first < 0x80 {
+            first as usize
+        } else {
+            let n = (first & 0x7f) as usize;
+            if n == 0 || n > 3 {
+                return Err("Unsupported BER length".to_string());
+            }
+            let bytes = self.buf.get(start..start + n).ok_or(TRUNCATED)?;
+            start += n;
+            bytes.iter().fold(0usize, |v, b| (v << 8) | *b as usize)
+        };
+        let end = start + len;
+        if end > self.end {
+            return Err(TRUNCATED.to_string());
+        }
+        let at = self.pos;
+        self.pos = end;
+        Ok(Tlv { tag, at, start, end })
+    }
+
+    fn expect(&mut self, tag: u8) -> Result<Tlv, String> {
+        let t = self.next()?;
+        if t.tag != tag {
+            return Err(format!("Unexpected BER tag 0x{:02x} (expected 0x{:02x})", t.tag, tag));
+        }
+        Ok(t)
+    }
+
+    fn int(&mut self) -> Result<i64, String> {
+        let t = self.expect(0x02)?;
+        Ok(int_value(self.content(t)))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, String> {
+        let t = self.expect(0x04)?;
+        Ok(self.content(t).to_vec())
+    }
+}
+
+// ---- Values and PDUs ------------------------------------------------------
+
+/// Value of a variable binding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnmpValue {
+    Int(i64),
+    Str(Vec<u8>),
+    Oid(String),
+    IpAddress(Ipv4Addr),
+    Counter32(u32),
+    Gauge32(u32),
+    TimeTicks(u32),
+    Counter64(u64),
+    Null,
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+    Other(u8, Vec<u8>),
+}
+
+impl SnmpValue {
+    fn decode(tag: u8, b: &[u8]) -> SnmpValue {
+        match tag {
+            0x02 => SnmpValue::Int(int_value(b)),
+            0x04 => SnmpValue::Str(b.to_vec()),
+            0x05 => SnmpValue::Null,
+            0x06 => SnmpValue::Oid(decode_oid(b)),
+            0x40 if b.len() == 4 => SnmpValue::IpAddress(Ipv4Addr::new(b[0], b[1], b[2], b[3])),
+            0x41 => SnmpValue::Counter32(uint_value(b) as u32),
+            0x42 => SnmpValue::Gauge32(uint_value(b) as u32),
+            0x43 => SnmpValue::TimeTicks(uint_value(b) as u32),
+            0x46 => SnmpValue::Counter64(uint_value(b)),
+            0x80 => SnmpValue::NoSuchObject,
+            0x81 => SnmpValue::NoSuchInstance,
+            0x82 => SnmpValue::EndOfMibView,
+            t => SnmpValue::Other(t, b.to_vec()),
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, String> {
+        Ok(match self {
+            SnmpValue::Int(v) => tlv(0x02, &int_bytes(*v)),
+            SnmpValue::Str(b) => tlv(0x04, b),
+            SnmpValue::Oid(o) => encode_oid(o)?,
+            SnmpValue::IpAddress(a) => tlv(0x40, &a.octets()),
+            SnmpValue::Counter32(v) => tlv(0x41, &uint_bytes(u64::from(*v))),
+            SnmpValue::Gauge32(v) => tlv(0x42, &uint_bytes(u64::from(*v))),
+            SnmpValue::TimeTicks(v) => tlv(0x43, &uint_bytes(u64::from(*v))),
+            SnmpValue::Counter64(v) => tlv(0x46, &uint_bytes(*v)),
+            SnmpValue::Null => tlv(0x05, &[]),
+            SnmpValue::NoSuchObject => tlv(0x80, &[]),
+            SnmpValue::NoSuchInstance => tlv(0x81, &[]),
+            SnmpValue::EndOfMibView => tlv(0x82, &[]),
+            SnmpValue::Other(t, b) => tlv(*t, b),
+        })
+    }
+
+    /// True for noSuchObject / noSuchInstance / endOfMibView.
+    pub fn is_exception(&self) -> bool {
+        matches!(self, SnmpValue::NoSuchObject | SnmpValue::NoSuchInstance | SnmpValue::EndOfMibView)
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            SnmpValue::Int(v) => Some(*v),
+            SnmpValue::Counter32(v) | SnmpValue::Gauge32(v) | SnmpValue::TimeTicks(v) => Some(i64::from(*v)),
+            SnmpValue::Counter64(v) => i64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Uptime in days, hours, minutes and seconds, e.g. "12d 03:04:05".
+pub fn format_ticks(ticks: u32) -> String {
+    let secs = ticks / 100;
+    format!("{}d {:02}:{:02}:{:02}", secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60)
+}
+
+fn hex_bytes(b: &[u8]) -> String {
+    b.iter().map(|x| format!("{:02x}", x)).collect::<Vec<_>>().join(":")
+}
+
+impl fmt::Display for SnmpValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnmpValue::Int(v) => write!(f, "{}", v),
+            SnmpValue::Str(b) => {
+                let printable = b.iter().all(|c| (0x20..0x7f).contains(c) || matches!(c, b'\r' | b'\n' | b'\t'))
+                    || std::str::from_utf8(b).is_ok_and(|s| !s.chars().any(|c| c.is_control() && !c.is_whitespace()));
+                if printable { write!(f, "{}", String::from_utf8_lossy(b).trim_end_matches('\0')) } else { write!(f, "{}", hex_bytes(b)) }
+            }
+            SnmpValue::Oid(o) => write!(f, "{}", o),
+            SnmpValue::IpAddress(a) => write!(f, "{}", a),
+            SnmpValue::Counter32(v) | SnmpValue::Gauge32(v) => write!(f, "{}", v),
+            SnmpValue::TimeTicks(v) => write!(f, "{}", format_ticks(*v)),
+            SnmpValue::Counter64(v) => write!(f, "{}", v),
+            SnmpValue::Null => write!(f, ""),
+            SnmpValue::NoSuchObject => write!(f, "noSuchObject"),
+            SnmpValue::NoSuchInstance => write!(f, "noSuchInstance"),
+            SnmpValue::EndOfMibView => write!(f, "endOfMibView"),
+            SnmpValue::Other(t, b) => write!(f, "[0x{:02x}] {}", t, hex_bytes(b)),
+        }
+    }
+}
+
+/// A request or response PDU.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pdu {
+    pub tag: u8,
+    pub request_id: i32,
+    pub error_status: i64,
+    pub error_index: i64,
+    pub varbinds: Vec<(String, SnmpValue)>,
+}
+
+impl Pdu {
+    fn encode(&self) -> Result<Vec<u8>, String> {
+        let mut vbs = Vec::new();
+        for (oid, value) in &self.varbinds {
+            let mut vb = encode_oid(oid)?;
+            vb.extend(value.encode()?);
+            vbs.extend(tlv(0x30, &vb));
+        }
+        let mut content = tlv(0x02, &int_bytes(i64::from(self.request_id)));
+        content.extend(tlv(0x02, &int_bytes(self.error_status)));
+        content.extend(tlv(0x02, &int_bytes(self.error_index)));
+        content.extend(tlv(0x30, &vbs));
+        Ok(tlv(self.tag, &content))
+    }
+
+    fn decode(r: &mut Reader) -> Result<Pdu, String> {
+        let t = r.next()?;
+        if !(0xa0..=0xa8).contains(&t.tag) {
+            return Err(format!("Unexpected PDU type 0x{:02x}", t.tag));
+        }
+        let mut p = r.inside(t);
+        let request_id = p.int()? as i32;
+        let error_status = p.int()?;
+        let error_index = p.int()?;
+        let list = p.expect(0x30)?;
+        let mut vbs = p.inside(list);
+        let mut varbinds = Vec::new();
+        while !vbs.at_end() {
+            let vb = vbs.expect(0x30)?;
+            let mut v = vbs.inside(vb);
+            let oid = v.expect(0x06)?;
+            let value = v.next()?;
+            varbinds.push((decode_oid(v.content(oid)), SnmpValue::decode(value.tag, v.content(value))));
+        }
+        Ok(Pdu { tag: t.tag, request_id, error_status, error_index, varbinds })
+    }
+}
+
+/// Name of a PDU error status.
+fn error_name(status: i64) -> &'static str {
+    match status {
+        1 => "tooBig",
+        2 => "noSuchName",
+        3 => "badValue",
+        4 => "readOnly",
+        5 => "genErr",
+        6 => "noAccess",
+        16 => "authorizationError",
+        _ => "error",
+    }
+}
+
+/// v1 / v2c message: version, community and PDU.
+fn encode_community(version: i64, community: &str, pdu: &Pdu) -> Result<Vec<u8>, String> {
+    let mut content = tlv(0x02, &int_bytes(version));
+    content.extend(tlv(0x04, community.as_bytes()));
+    content.extend(pdu.encode()?);
+    Ok(tlv(0x30, &content))
+}
+
+fn decode_community(msg: &[u8]) -> Result<(i64, Vec<u8>, Pdu), String> {
+    let mut r = Reader::new(msg);
+    let seq = r.expect(0x30)?;
+    let mut m = r.inside(seq);
+    let version = m.int()?;
+    let community = m.bytes()?;
+    Ok((version, community, Pdu::decode(&mut m)?))
+}
+
+// ---- SNMPv3 / USM -----------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthProtocol {
+    None,
+    Md5,
+    Sha,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivProtocol {
+    None,
+    Des,
+    Aes,
+}
+
+/// Message header and USM security parameters of a v3 message.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct V3Header {
+    msg_id: i32,
+    flags: u8,
+    engine_id: Vec<u8>,
+    boots: u32,
+    time: u32,
+    user: Vec<u8>,
+    auth_params: Vec<u8>,
+    priv_params: Vec<u8>,
+}
+
+/// Decoded v3 message: header, offset of the authentication parameters and the
+/// scoped PDU (plain) or its ciphertext (when the privacy flag is set).
+struct V3Message {
+    header: V3Header,
+    auth_offset: usize,
+    data: Vec<u8>,
+}
+
+fn encode_v3(h: &V3Header, data: &[u8]) -> Vec<u8> {
+    let mut global = tlv(0x02, &int_bytes(i64::from(h.msg_id)));
+    global.extend(tlv(0x02, &int_bytes(65507)));
+    global.extend(tlv(0x04, &[h.flags]));
+    global.extend(tlv(0x02, &[3])); // USM
+    let mut usm = tlv(0x04, &h.engine_id);
+    usm.extend(tlv(0x02, &int_bytes(i64::from(h.boots))));
+    usm.extend(tlv(0x02, &int_bytes(i64::from(h.time))));
+    usm.extend(tlv(0x04, &h.user));
+    usm.extend(tlv(0x04, &h.auth_params));
+    usm.extend(tlv(0x04, &h.priv_params));
+    let mut content = tlv(0x02, &[3]);
+    content.extend(tlv(0x30, &global));
+    content.extend(tlv(0x04, &tlv(0x30, &usm)));
+    if h.flags & FLAG_PRIV != 0 {
+        content.extend(tlv(0x04, data));
+    } else {
+        content.extend_from_slice(data);
+    }
+    tlv(0x30, &content)
+}
+
+fn decode_v3(msg: &[u8]) -> Result<V3Message, String> {
+    let mut r = Reader::new(msg);
+    let seq = r.expect(0x30)?;
+    let mut m = r.inside(seq);
+    if m.int()? != 3 {
+        return Err("Not an SNMPv3 message".to_string());
+    }
+    let global = m.expect(0x30)?;
+    let mut g = m.inside(global);
+    let msg_id = g.int()? as i32;
+    let _max_size = g.int()?;
+    let flags = g.bytes()?.first().copied().unwrap_or(0);
+    let sec_params = m.expect(0x04)?;
+    let mut sp = m.inside(sec_params);
+    let usm = sp.expect(0x30)?;
+    let mut u = sp.inside(usm);
+    let engine_id = u.bytes()?;
+    let boots = u.int()? as u32;
+    let time = u.int()? as u32;
+    let user = u.bytes()?;
+    let auth = u.expect(0x04)?;
+    let priv_params = u.bytes()?;
+    let data_tlv = m.next()?;
+    let data = if flags & FLAG_PRIV != 0 { m.content(data_tlv).to_vec() } else { msg[data_tlv.at..data_tlv.end].to_vec() };
+    let header = V3Header { msg_id, flags, engine_id, boots, time, user, auth_params: msg[auth.start..auth.end].to_vec(), priv_params };
+    Ok(V3Message { header, auth_offset: auth.start, data })
+}
+
+fn encode_scoped(context_engine: &[u8], context: &str, pdu: &Pdu) -> Result<Vec<u8>, String> {
+    let mut content = tlv(0x04, context_engine);
+    content.extend(tlv(0x04, context.as_bytes()));
+    content.extend(pdu.encode()?);
+    Ok(tlv(0x30, &content))
+}
+
+fn decode_scoped(data: &[u8]) -> Result<Pdu, String> {
+    let mut r = Reader::new(data);
+    let seq = r.expect(0x30)?;
+    let mut s = r.inside(seq);
+    let _engine = s.bytes()?;
+    let _context = s.bytes()?;
+    Pdu::decode(&mut s)
+}
+
+/// RFC 3414 password-to-key: hash 1 MiB of the repeated password.
+fn password_to_key(auth: AuthProtocol, password: &str) -> Vec<u8> {
+    fn expand<D: Digest>(password: &[u8]) -> Vec<u8> {
+        let mut d = D::new();
+        let mut block = [0u8; 64];
+        let mut idx = 0;
+        for _ in 0..(1_048_576 / 64) {
+            for b in block.iter_mut() {
+                *b = password[idx % password.len()];
+                idx += 1;
+            }
+            d.update(block);
+        }
+        d.finalize().to_vec()
+    }
+    if password.is_empty() {
+        return Vec::new();
+    }
+    match auth {
+        AuthProtocol::Md5 => expand::<Md5>(password.as_bytes()),
+        AuthProtocol::Sha => expand::<Sha1>(password.as_bytes()),
+        AuthProtocol::None => Vec::new(),
+    }
+}
+
+/// Localize a key to an engine: H(Ku || engineID || Ku).
+fn localize_key(auth: AuthProtocol, ku: &[u8], engine_id: &[u8]) -> Vec<u8> {
+    let data = [ku, engine_id, ku].concat();
+    match auth {
+        AuthProtocol::Md5 => Md5::digest(&data).to_vec(),
+        AuthProtocol::Sha => Sha1::digest(&data).to_vec(),
+        AuthProtocol::None => Vec::new(),
+    }
+}
+
+/// Localized USM keys for one engine.
+struct UsmKeys {
+    auth: AuthProtocol,
+    privacy: PrivProtocol,
+    auth_key: Vec<u8>,
+    priv_key: Vec<u8>,
+}
+
+impl UsmKeys {
+    fn new(auth: AuthProtocol, auth_password: &str, privacy: PrivProtocol, priv_password: &str, engine_id: &[u8]) -> UsmKeys {
+        UsmKeys {
+            auth,
+            privacy,
+            auth_key: localize_key(auth, &password_to_key(auth, auth_password), engine_id),
+            priv_key: localize_key(auth, &password_to_key(auth, priv_password), engine_id),
+        }
+    }
+
+    fn flags(&self) -> u8 {
+        let mut f = 0;
+        if self.auth != AuthProtocol::None {
+            f |= FLAG_AUTH;
+            if self.privacy != PrivProtocol::None {
+                f |= FLAG_PRIV;
+            }
+        }
+        f
+    }
+
+    /// HMAC-96 of a whole message whose authentication parameters are zeroed.
+    fn mac(&self, msg: &[u8]) -> Vec<u8> {
+        let full = match self.auth {
+            AuthProtocol::Md5 => {
+                let mut m = <Hmac<Md5> as Mac>::new_from_slice(&self.auth_key).expect("HMAC accepts any key length");
+                m.update(msg);
+                m.finalize().into_bytes().to_vec()
+            }
+            AuthProtocol::Sha => {
+                let mut m = <Hmac<Sha1> as Mac>::new_from_slice(&self.auth_key).expect("HMAC accepts any key length");
+                m.update(msg);
+                m.finalize().into_bytes().to_vec()
+            }
+            AuthProtocol::None => return Vec::new(),
+        };
+        full[..12].to_vec()
+    }
+
+    /// Fill in the authentication parameters of an encoded message.
+    fn sign(&self, msg: &mut [u8]) -> Result<(), String> {
+        let offset = decode_v3(msg)?.auth_offset;
+        let mac = self.mac(msg);
+        msg[offset..offset + 12].copy_from_slice(&mac);
+        Ok(())
+    }
+
+    /// Check the authentication parameters of a received message.
+    fn verify(&self, msg: &[u8]) -> bool {
+        let Ok(m) = decode_v3(msg) else { return false };
+        if m.header.auth_params.len() != 12 {
+            return false;
+        }
+        let mut zeroed = msg.to_vec();
+        zeroed[m.auth_offset..m.auth_offset + 12].fill(0);
+        self.mac(&zeroed) == m.header.auth_params
+    }
+
+    /// Encrypt a scoped PDU; returns the ciphertext and the privacy parameters (salt).
+    fn encrypt(&self, boots: u32, time: u32, salt: u64, plain: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+        match self.privacy {
+            PrivProtocol::Des => {
+                let salt = [boots.to_be_bytes(), (salt as u32).to_be_bytes()].concat();
+                let iv: Vec<u8> = self.priv_key[8..16].iter().zip(&salt).map(|(a, b)| a ^ b).collect();
+                let mut buf = plain.to_vec();
+                buf.resize(plain.len().div_ceil(8) * 8, 0);
+                let len = buf.len();
+                cbc::Encryptor::<des::Des>::new_from_slices(&self.priv_key[..8], &iv)
+                    .map_err(|e| e.to_string())?
+                    .encrypt_padded_mut::<NoPadding>(&mut buf, len)
+                    .map_err(|e| e.to_string())?;
+                Ok((buf, salt))
+            }
+            PrivProtocol::Aes => {
+                let salt = salt.to_be_bytes().to_vec();
+                let iv = [&boots.to_be_bytes()[..], &time.to_be_bytes(), &salt].concat();
+                let mut buf = plain.to_vec();
+                cfb_mode::Encryptor::<Aes128>::new_from_slices(&self.priv_key[..16], &iv).map_err(|e| e.to_string())?.encrypt(&mut buf);
+                Ok((buf, salt))
+            }
+            PrivProtocol::None => Ok((plain.to_vec(), Vec::new())),
+        }
+    }
+
+    fn decrypt(&self, boots: u32, time: u32, priv_params: &[u8], data: &[u8]) -> Result<Vec<u8>, String> {
+        if priv_params.len() != 8 {
+            return Err("Invalid privacy parameters".to_string());
+        }
+        let mut buf = data.to_vec();
+        match self.privacy {
+            PrivProtocol::Des => {
+                if !buf.len().is_multiple_of(8) {
+                    return Err("Invalid DES ciphertext length".to_string());
+                }
+                let iv: Vec<u8> = self.priv_key[8..16].iter().zip(priv_params).map(|(a, b)| a ^ b).collect();
+                cbc::Decryptor::<des::Des>::new_from_slices(&self.priv_key[..8], &iv)
+                    .map_err(|e| e.to_string())?
+                    .decrypt_padded_mut::<NoPadding>(&mut buf)
+                    .map_err(|e| e.to_string())?;
+            }
+            PrivProtocol::Aes => {
+                let iv = [&boots.to_be_bytes()[..], &time.to_be_bytes(), priv_params].concat();
+                cfb_mode::Decryptor::<Aes128>::new_from_slices(&self.priv_key[..16], &iv).map_err(|e| e.to_string())?.decrypt(&mut buf);
+            }
+            PrivProtocol::None => {}
+        }
+        Ok(buf)
+    }
+}
+
+fn parse_auth(s: &str) -> Result<AuthProtocol, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "" | "none" => Ok(AuthProtocol::None),
+        "md5" => Ok(AuthProtocol::Md5),
+        "sha" | "sha1" => Ok(AuthProtocol::Sha),
+        other => Err(format!("Unsupported SNMPv3 auth protocol: {}", other)),
+    }
+}
+
+fn parse_priv(s: &str) -> Result<PrivProtocol, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "" | "none" => Ok(PrivProtocol::None),
+        "des" => Ok(PrivProtocol::Des),
+        "aes" | "aes128" => Ok(PrivProtocol::Aes),
+        other => Err(format!("Unsupported SNMPv3 privacy protocol: {}", other)),
+    }
+}
+
+/// Engine parameters learnt by discovery, with the local time they were received.
+struct Engine {
+    id: Vec<u8>,
+    boots: u32,
+    time: u32,
+    at: Instant,
+    keys: UsmKeys,
+}
+
+impl Engine {
+    fn time_now(&self) -> u32 {
+        self.time.saturating_add(self.at.elapsed().as_secs() as u32)
+    }
+}
+
+enum Security {
+    Community { version: i64, community: String },
+    Usm { user: String, context: String, auth: AuthProtocol, privacy: PrivProtocol, engine: Option<Engine> },
+}
+
+// ---- Client -----------------------------------------------------------------
+
+/// SNMP session with one agent.
+pub struct SnmpClient {
+    sock: UdpSocket,
+    target: SocketAddr,
+    timeout: Duration,
+    retries: u32,
+    security: Security,
+    next_id: i32,
+    salt: u64,
+}
+
+impl SnmpClient {
+    /// Open a session to `ip` with the given settings. For v1/v2c the communities are tried
+    /// in order (the first that answers is kept); for v3 the engine is discovered.
+    pub fn connect(ip: Ipv4Addr, cfg: &SnmpConfig, running: &AtomicBool) -> Result<SnmpClient, String> {
+        SnmpClient::connect_to(SocketAddr::from((ip, cfg.port)), cfg, running)
+    }
+
+    fn connect_to(target: SocketAddr, cfg: &SnmpConfig, running: &AtomicBool) -> Result<SnmpClient, String> {
+        let sock = UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("Failed to open UDP socket: {}", e))?;
+        sock.set_read_timeout(Some(Duration::from_millis(50))).map_err(|e| e.to_string())?;
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0) ^ u64::from(std::process::id());
+        let mut client = SnmpClient {
+            sock,
+            target,
+            timeout: Duration::from_millis(cfg.timeout_ms.max(100)),
+            retries: cfg.retries,
+            security: Security::Community { version: 1, community: String::new() },
+            next_id: (seed & 0x3fff_ffff) as i32,
+            salt: seed,
+        };
+        match cfg.version.trim() {
+            "1" | "2c" | "2" => {
+                let version = if cfg.version.trim() == "1" { 0 } else { 1 };
+                if cfg.communities.is_empty() {
+                    return Err("No SNMP community configured".to_string());
+                }
+                let mut last = String::new();
+                for community in &cfg.communities {
+                    client.security = Security::Community { version, community: community.clone() };
+                    match client.get(&[SYS_OBJECT_ID], running) {
+                        Ok(_) => return Ok(client),
+                        Err(e) => last = e,
+                    }
+                    if !running.load(Ordering::SeqCst) {
+                        break;
+                    }
+                }
+                Err(last)
+            }
+            "3" => {
+                let auth = parse_auth(&cfg.auth_protocol)?;
+                let privacy = parse_priv(&cfg.priv_protocol)?;
+                if privacy != PrivProtocol::None && auth == AuthProtocol::None {
+                    return Err("SNMPv3 privacy requires an auth protocol".to_string());
+                }
+                if cfg.user.is_empty() {
+                    return Err("No SNMPv3 user configured".to_string());
+                }
+                client.security = Security::Usm { user: cfg.user.clone(), context: cfg.context.clone(), auth, privacy, engine: None };
+                client.discover(cfg, running)?;
+                Ok(client)
+            }
+            v => Err(format!("Unsupported SNMP version: {}", v)),
+        }
+    }
+
+    /// Learn the engine ID, boots and time from the Report to an empty request.
+    fn discover(&mut self, cfg: &SnmpConfig, running: &AtomicBool) -> Result<(), String> {
+        let id = self.take_id();
+        let pdu = Pdu { tag: PDU_GET, request_id: id, error_status: 0, error_index: 0, varbinds: Vec::new() };
+        let header = V3Header { msg_id: id, flags: FLAG_REPORTABLE, ..V3Header::default() };
+        let msg = encode_v3(&header, &encode_scoped(&[], "", &pdu)?);
+        let reply = self.exchange(&msg, running, |m| decode_v3(m).is_ok_and(|v| v.header.msg_id == id))?;
+        let h = decode_v3(&reply)?.header;
+        if h.engine_id.is_empty() {
+            return Err("SNMPv3 engine discovery failed".to_string());
+        }
+        if let Security::Usm { auth, privacy, engine, .. } = &mut self.security {
+            let keys = UsmKeys::new(*auth, &cfg.auth_password, *privacy, &cfg.priv_password, &h.engine_id);
+            *engine = Some(Engine { id: h.engine_id, boots: h.boots, time: h.time, at: Instant::now(), keys });
+        }
+        Ok(())
+    }
+
+    fn take_id(&mut self) -> i32 {
+        self.next_id = self.next_id.wrapping_add(1) & 0x7fff_ffff;
+        self.next_id
+    }
+
+    /// Send `msg` and wait for a datagram from the agent accepted by `matches`, with retries.
+    fn exchange(&self, msg: &[u8], running: &AtomicBool, matches: impl Fn(&[u8]) -> bool) -> Result<Vec<u8>, String> {
+        let mut buf = vec![0u8; 65535];
+        for _ in 0..=self.retries {
+            self.sock.send_to(msg, self.target).map_err(|e| format!("{}: {}", self.target, e))?;
+            let deadline = Instant::now() + self.timeout;
+            while Instant::now() < deadline {
+                if !running.load(Ordering::SeqCst) {
+                    return Err("Stopped".to_string());
+                }
+                let Ok((n, from)) = self.sock.recv_from(&mut buf) else { continue };
+                if from.ip() == self.target.ip() && matches(&buf[..n]) {
+                    return Ok(buf[..n].to_vec());
+                }
+            }
+        }
+        Err(format!("{}: no SNMP response", self.target.ip()))
+    }
+
+    /// Send one PDU and return the response PDU.
+    fn request(&mut self, tag: u8, oids: &[&str], running: &AtomicBool) -> Result<Pdu, String> {
+        let id = self.take_id();
+        let varbinds = oids.iter().map(|o| (o.to_string(), SnmpValue::Null)).collect();
+        let pdu = Pdu { tag, request_id: id, error_status: 0, error_index: 0, varbinds };
+        match &self.security {
+            Security::Community { version, community } => {
+                let msg = encode_community(*version, community, &pdu)?;
+                let reply = self.exchange(&msg, running, |m| decode_community(m).is_ok_and(|(_, _, p)| p.request_id == id))?;
+                Ok(decode_community(&reply)?.2)
+            }
+            Security::Usm { .. } => {
+                // A "not in time window" report refreshes boots/time; retry once
+                match self.request_v3(&pdu, running)? {
+                    Ok(p) => Ok(p),
+                    Err(report) if report == USM_STATS[1].1 => {
+                        let mut pdu = pdu;
+                        pdu.request_id = self.take_id();
+                        self.request_v3(&pdu, running)?.map_err(|r| format!("{}: {}", self.target.ip(), r))
+                    }
+                    Err(report) => Err(format!("{}: {}", self.target.ip(), report)),
+                }
+            }
+        }
+    }
+
+    /// One v3 exchange. The inner error is the reason given by a Report PDU.
+    fn request_v3(&mut self, pdu: &Pdu, running: &AtomicBool) -> Result<Result<Pdu, String>, String> {
+        self.salt = self.salt.wrapping_add(1);
+        let salt = self.salt;
+        let Security::Usm { user, context, engine: Some(engine), .. } = &self.security else {
+            return Err("SNMPv3 engine not discovered".to_string());
+        };
+        let keys = &engine.keys;
+        let time = engine.time_now();
+        let scoped = encode_scoped(&engine.id, context, pdu)?;
+        let flags = keys.flags() | FLAG_REPORTABLE;
+        let (data, priv_params) = if flags & FLAG_PRIV != 0 { keys.encrypt(engine.boots, time, salt, &scoped)? } else { (scoped, Vec::new()) };
+        let auth_params = if flags & FLAG_AUTH != 0 { vec![0u8; 12] } else { Vec::new() };
+        let header = V3Header {
+            msg_id: pdu.request_id,
+            flags,
+            engine_id: engine.id.clone(),
+            boots: engine.boots,
+            time,
+            user: user.as_bytes().to_vec(),
+            auth_params,
+            priv_params,
+        };
+        let mut msg = encode_v3(&header, &data);
+        if flags & FLAG_AUTH != 0 {
+            keys.sign(&mut msg)?;
+        }
+        let id = pdu.request_id;
+        let reply = self.exchange(&msg, running, |m| decode_v3(m).is_ok_and(|v| v.header.msg_id == id))?;
+        let m = decode_v3(&reply)?;
+        let authed = m.header.flags & FLAG_AUTH != 0;
+        if authed && !engine.keys.verify(&reply) {
+            return Err(format!("{}: response failed authentication", self.target.ip()));
+        }
+        let scoped = if m.header.flags & FLAG_PRIV != 0 {
+            engine.keys.decrypt(m.header.boots, m.header.time, &m.header.priv_params, &m.data)?
+        } else {
+            m.data
+        };
+        let reply_pdu = decode_scoped(&scoped)?;
+        // Only reports may come back below the requested level (e.g. unknown engine ID);
+        // a plain response must be at least as protected as the request
+        let wanted = keys.flags();
+        if reply_pdu.tag != PDU_REPORT && m.header.flags & wanted != wanted {
+            return Err(format!("{}: response below the requested security level", self.target.ip()));
+        }
+        if reply_pdu.tag == PDU_REPORT {
+            let oid = reply_pdu.varbinds.first().map(|(o, _)| o.as_str()).unwrap_or("");
+            let reason = USM_STATS.iter().find(|(o, _)| *o == oid).map(|(_, r)| r.to_string()).unwrap_or_else(|| format!("report {}", oid));
+            if authed {
+                // Authenticated reports carry the current boots/time
+                if let Security::Usm { engine: Some(engine), .. } = &mut self.security {
+                    engine.boots = m.header.boots;
+                    engine.time = m.header.time;
+                    engine.at = Instant::now();
+                }
+            }
+            return Ok(Err(reason));
+        }
+        Ok(Ok(reply_pdu))
+    }
+
+    fn check(&self, pdu: Pdu) -> Result<Vec<(String, SnmpValue)>, String> {
+        if pdu.tag != PDU_RESPONSE {
+            return Err(format!("{}: unexpected PDU 0x{:02x}", self.target.ip(), pdu.tag));
+        }
+        if pdu.error_status != 0 {
+            return Err(format!("{}: {} (index {})", self.target.ip(), error_name(pdu.error_status), pdu.error_index));
+        }
+        Ok(pdu.varbinds)
+    }
+
+    /// GET several objects.
+    pub fn get(&mut self, oids: &[&str], running: &AtomicBool) -> Result<Vec<(String, SnmpValue)>, String> {
+        let pdu = self.request(PDU_GET, oids, running)?;
+        self.check(pdu)
+    }
+
+    /// Every object under `root`, by repeated GETNEXT.
+    pub fn walk(&mut self, root: &str, limiter: &RateLimiter, ip: Ipv4Addr, running: &AtomicBool) -> Result<Vec<(String, SnmpValue)>, String> {
+        let root_parts = oid_parts(root)?;
+        let mut out = Vec::new();
+        let mut cur = root.to_string();
+        while out.len() < MAX_WALK {
+            if !limiter.pace(ip, 1, running) {
+                return Err("Stopped".to_string());
+            }
+            let pdu = self.request(PDU_GET_NEXT, &[&cur], running)?;
+            // v1 signals the end of the MIB with noSuchName
+            if pdu.error_status == 2 {
+                break;
+            }
+            let Some((oid, value)) = self.check(pdu)?.into_iter().next() else { break };
+            let parts = oid_parts(&oid)?;
+            if value == SnmpValue::EndOfMibView || !parts.starts_with(&root_parts) || parts <= oid_parts(&cur)? {
+                break;
+            }
+            cur = oid.clone();
+            out.push((oid, value));
+        }
+        Ok(out)
+    }
+}
+
+// ---- System group and interfaces -------------------------------------------
+
+/// System group of a device.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemInfo {
+    pub name: String,
+    pub descr: String,
+    pub object_id: String,
+    pub uptime: String,
+    pub contact: String,
+    pub location: String,
+}
+
+impl SystemInfo {
+    /// Result lines for the SNMP tab.
+    pub fn format_lines(&self, ip: Ipv4Addr) -> Vec<String> {
+        let mut lines = vec![format!("{}  {}", ip, if self.name.is_empty() { "(no sysName)" } else { &self.name })];
+        if !self.descr.is_empty() {
+            lines.push(format!("    Descr: {}", self.descr.lines().next().unwrap_or("").trim()));
+        }
+        lines.push(format!("    ObjectID: {}  Uptime: {}", self.object_id, self.uptime));
+        if !self.contact.is_empty() || !self.location.is_empty() {
+            lines.push(format!("    Contact: {}  Location: {}", self.contact, self.location));
+        }
+        lines
+    }
+
+    /// Text appended to scan result rows, e.g. "SNMP: sw-core-01 (Cisco IOS Software...)".
+    pub fn merge_label(&self) -> String {
+        let descr: String = self.descr.lines().next().unwrap_or("").trim().chars().take(40).collect();
+        match (self.name.is_empty(), descr.is_empty()) {
+            (false, false) => format!("SNMP: {} ({})", self.name, descr),
+            (false, true) => format!("SNMP: {}", self.name),
+            _ => format!("SNMP: {}", descr),
+        }
+    }
+}
+
+/// GET the system group.
+pub fn query_system(client: &mut SnmpClient, running: &AtomicBool) -> Result<SystemInfo, String> {
+    let vbs = client.get(&[SYS_DESCR, SYS_OBJECT_ID, SYS_UPTIME, SYS_CONTACT, SYS_NAME, SYS_LOCATION], running)?;
+    let get = |oid: &str| vbs.iter().find(|(o, v)| o == oid && !v.is_exception()).map(|(_, v)| v.to_string()).unwrap_or_default();
+    Ok(SystemInfo {
+        name: get(SYS_NAME),
+        descr: get(SYS_DESCR),
+        object_id: get(SYS_OBJECT_ID),
+        uptime: get(SYS_UPTIME),
+        contact: get(SYS_CONTACT),
+        location: get(SYS_LOCATION),
+    })
+}
+
+/// One row of the interface table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Interface {
+    pub index: u32,
+    pub name: String,
+    pub descr: String,
+    pub alias: String,
+    pub if_type: i64,
+    pub mtu: i64,
+    /// Mbit/s
+    pub speed_mbps: u64,
+    pub mac: String,
+    pub admin: i64,
+    pub oper: i64,
+}
+
+fn status_name(v: i64) -> &'static str {
+    match v {
+        1 => "up",
+        2 => "down",
+        3 => "testing",
+        5 => "dormant",
+        6 => "notPresent",
+        7 => "lowerLayerDown",
+        _ => "unknown",
+    }
+}
+
+fn format_speed(mbps: u64) -> String {
+    match mbps {
+        0 => "-".to_string(),
+        m if m >= 1000 && m.is_multiple_of(1000) => format!("{}G", m / 1000),
+        m => format!("{}M", m),
+    }
+}
+
+/// Walk ifTable / ifXTable and join the columns by ifIndex.
+pub fn walk_interfaces(client: &mut SnmpClient, ip: Ipv4Addr, limiter: &RateLimiter, running: &AtomicBool) -> Result<Vec<Interface>, String> {
+    let mut rows: std::collections::BTreeMap<u32, Interface> = std::collections::BTreeMap::new();
+    for column in [IF_DESCR, IF_TYPE, IF_MTU, IF_SPEED, IF_PHYS_ADDRESS, IF_ADMIN_STATUS, IF_OPER_STATUS, IF_NAME, IF_HIGH_SPEED, IF_ALIAS] {
+        // ifXTable is missing on old agents
+        let values = match client.walk(column, limiter, ip, running) {
+            Ok(v) => v,
+            Err(_) if column.starts_with("1.3.6.1.2.1.31.") && running.load(Ordering::SeqCst) => continue,
+            Err(e) => return Err(e),
+        };
+        for (oid, value) in values {
+            let Some(index) = oid.rsplit('.').next().and_then(|s| s.parse::<u32>().ok()) else { continue };
+            let row = rows.entry(index).or_insert_with(|| Interface { index, ..Interface::default() });
+            match column {
+                IF_DESCR => row.descr = value.to_string(),
+                IF_TYPE => row.if_type = value.as_int().unwrap_or(0),
+                IF_MTU => row.mtu = value.as_int().unwrap_or(0),
+                // Replaced by ifHighSpeed (walked later) above 4 Gbit/s
+                IF_SPEED => row.speed_mbps = value.as_int().unwrap_or(0).max(0) as u64 / 1_000_000,
+                IF_PHYS_ADDRESS => {
+                    if let SnmpValue::Str(b) = &value {
+                        row.mac = hex_bytes(b);
+                    }
+                }
+                IF_ADMIN_STATUS => row.admin = value.as_int().unwrap_or(0),
+                IF_OPER_STATUS => row.oper = value.as_int().unwrap_or(0),
+                IF_NAME => row.name = value.to_string(),
+                IF_HIGH_SPEED => {
+                    let high = value.as_int().unwrap_or(0).max(0) as u64;
+                    if high > 0 {
+                        row.speed_mbps = high;
+                    }
+                }
+                IF_ALIAS => row.alias = value.to_string(),
+                _ => {}
+            }
+        }
+    }
+    Ok(rows.into_values().collect())
+}
+
+/// Table lines for the SNMP tab.
+pub fn format_interfaces(ifs: &[Interface]) -> Vec<String> {
+    let mut lines = vec![format!("{:<6} {:<16} {:<6} {:<6} {:<6} {:<17} {}", "Index", "Name", "Oper", "Admin", "Speed", "MAC", "Alias / Descr")];
+    for i in ifs {
+        let name = if i.name.is_empty() { &i.descr } else { &i.name };
+        let note = if !i.alias.is_empty() { &i.alias } else if i.name.is_empty() { "" } else { &i.descr };
+        lines.push(format!("{:<6} {:<16} {:<6} {:<6} {:<6} {:<17} {}", i.index, name, status_name(i.oper), status_name(i.admin),
+            format_speed(i.speed_mbps), if i.mac.is_empty() { "-" } else { &i.mac }, note).trim_end().to_string());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate::RateLimits;
+    use std::{collections::BTreeMap, sync::Arc, thread};
+
+    fn unlimited() -> RateLimiter {
+        RateLimiter::new(RateLimits { pps: 0.0, max_in_flight: 0, host_delay_ms: 0, ping_interval_ms: 0 })
+    }
+
+    fn mib() -> BTreeMap<Vec<u32>, (String, SnmpValue)> {
+        let mut m = BTreeMap::new();
+        let mut add = |oid: &str, v: SnmpValue| {
+            m.insert(oid_parts(oid).unwrap(), (oid.to_string(), v));
+        };
+        add(SYS_DESCR, SnmpValue::Str(b"Test Switch 24-port\r\nVersion 1.2".to_vec()));
+        add(SYS_OBJECT_ID, SnmpValue::Oid("1.3.6.1.4.1.99999.1".to_string()));
+        add(SYS_UPTIME, SnmpValue::TimeTicks(123_456_789));
+        add(SYS_CONTACT, SnmpValue::Str(b"noc@example.com".to_vec()));
+        add(SYS_NAME, SnmpValue::Str(b"sw-test-01".to_vec()));
+        add(SYS_LOCATION, SnmpValue::Str(b"Rack 3".to_vec()));
+        for (idx, name, oper, mac) in [(1u32, "Gi1/0/1", 1, [0, 0x11, 0x22, 0x33, 0x44, 0x55]), (2, "Gi1/0/2", 2, [0, 0x11, 0x22, 0x33, 0x44, 0x56])] {
+            add(&format!("{}.{}", IF_DESCR, idx), SnmpValue::Str(format!("GigabitEthernet1/0/{}", idx).into_bytes()));
+            add(&format!("{}.{}", IF_TYPE, idx), SnmpValue::Int(6));
+            add(&format!("{}.{}", IF_SPEED, idx), SnmpValue::Gauge32(1_000_000_000));
+            add(&format!("{}.{}", IF_PHYS_ADDRESS, idx), SnmpValue::Str(mac.to_vec()));
+            add(&format!("{}.{}", IF_ADMIN_STATUS, idx), SnmpValue::Int(1));
+            add(&format!("{}.{}", IF_OPER_STATUS, idx), SnmpValue::Int(oper));
+            add(&format!("{}.{}", IF_NAME, idx), SnmpValue::Str(name.as_bytes().to_vec()));
+        }
+        add(&format!("{}.1", IF_ALIAS), SnmpValue::Str(b"uplink".to_vec()));
+        m
+    }
+
+    /// Answer a request PDU from the stand-in MIB.
+    fn answer(mib: &BTreeMap<Vec<u32>, (String, SnmpValue)>, req: &Pdu, v1: bool) -> Pdu {
+        let mut resp = Pdu { tag: PDU_RESPONSE, request_id: req.request_id, error_status: 0, error_index: 0, varbinds: Vec::new() };
+        for (i, (oid, _)) in req.varbinds.iter().enumerate() {
+            let key = oid_parts(oid).unwrap();
+            let found = if req.tag == PDU_GET_NEXT {
+                mib.range((std::ops::Bound::Excluded(key), std::ops::Bound::Unbounded)).next().map(|(_, v)| v.clone())
+            } else {
+                mib.get(&key).cloned()
+            };
+            match found {
+                Some(vb) => resp.varbinds.push(vb),
+                None if v1 => {
+                    resp.error_status = 2;
+                    resp.error_index = i as i64 + 1;
+                    resp.varbinds.push((oid.clone(), SnmpValue::Null));
+                }
+                None => resp.varbinds.push((oid.clone(), if req.tag == PDU_GET_NEXT { SnmpValue::EndOfMibView } else { SnmpValue::NoSuchObject })),
+            }
+        }
+        resp
+    }
+
+    /// Stand-in agent for v1/v2c (community "secret") and v3 (user "monitor").
+    fn spawn_agent(auth: AuthProtocol, privacy: PrivProtocol) -> SocketAddr {
+        spawn_agent_with(auth, privacy, None)
+    }
+
+    /// Stand-in agent whose v3 responses carry `reply_flags` instead of the user's level.
+    fn spawn_agent_with(auth: AuthProtocol, privacy: PrivProtocol, reply_flags: Option<u8>) -> SocketAddr {
+        let sock = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = sock.local_addr().unwrap();
+        let engine_id = b"\x80\x00\x1f\x88\x04agent".to_vec();
+        let keys = UsmKeys::new(auth, "authpass123", privacy, "privpass123", &engine_id);
+        thread::spawn(move || {
+            let mib = mib();
+            let mut buf = [0u8; 4096];
+            let mut salt = 0u64;
+            while let Ok((n, from)) = sock.recv_from(&mut buf) {
+                let msg = &buf[..n];
+                if let Ok((version, community, req)) = decode_community(msg) {
+                    if community == b"secret" {
+                        let _ = sock.send_to(&encode_community(version, "secret", &answer(&mib, &req, version == 0)).unwrap(), from);
+                    }
+                    continue;
+                }
+                let Ok(m) = decode_v3(msg) else { continue };
+                let (boots, time) = (7, 1000);
+                let mut header = V3Header { msg_id: m.header.msg_id, engine_id: engine_id.clone(), boots, time, user: m.header.user.clone(), ..V3Header::default() };
+                if m.header.engine_id.is_empty() {
+                    // Discovery: unauthenticated report
+                    let report = Pdu { tag: PDU_REPORT, request_id: 0, error_status: 0, error_index: 0,
+                        varbinds: vec![(USM_STATS[3].0.to_string(), SnmpValue::Counter32(1))] };
+                    let _ = sock.send_to(&encode_v3(&header, &encode_scoped(&engine_id, "", &report).unwrap()), from);
+                    continue;
+                }
+                assert!(keys.verify(msg), "request failed authentication");
+                let scoped = if m.header.flags & FLAG_PRIV != 0 {
+                    keys.decrypt(m.header.boots, m.header.time, &m.header.priv_params, &m.data).unwrap()
+                } else {
+                    m.data.clone()
+                };
+                let resp = answer(&mib, &decode_scoped(&scoped).unwrap(), false);
+                let scoped = encode_scoped(&engine_id, "", &resp).unwrap();
+                header.flags = reply_flags.unwrap_or(keys.flags());
+                salt += 1;
+                if header.flags & FLAG_PRIV != 0 {
+                    let (data, priv_params) = keys.encrypt(boots, time, salt, &scoped).unwrap();
+                    header.priv_params = priv_params;
+                    let mut out = encode_v3(&V3Header { auth_params: vec![0; 12], ..header }, &data);
+                    keys.sign(&mut out).unwrap();
+                    let _ = sock.send_to(&out, from);
+                    continue;
+                }
+                if header.flags & FLAG_AUTH != 0 {
+                    header.auth_params = vec![0; 12];
+                }
+                let mut out = encode_v3(&header, &scoped);
+                if header.flags & FLAG_AUTH != 0 {
+                    keys.sign(&mut out).unwrap();
+                }
+                let _ = sock.send_to(&out, from);
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_ber_roundtrip() {
+        for v in [0i64, 127, 128, -1, -129, 65535, i64::from(i32::MAX)] {
+            assert_eq!(int_value(&int_bytes(v)), v);
+        }
+        assert_eq!(int_bytes(128), vec![0x00, 0x80]);
+        assert_eq!(uint_bytes(0xffff_ffff), vec![0x00, 0xff, 0xff, 0xff, 0xff]);
+        let oid = encode_oid("1.3.6.1.4.1.311.21.20").unwrap();
+        assert_eq!(oid, vec![0x06, 0x09, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x15, 0x14]);
+        assert_eq!(decode_oid(&oid[2..]), "1.3.6.1.4.1.311.21.20");
+        assert!(encode_oid("1.3.x").is_err());
+        let long = vec![b'a'; 300];
+        let t = tlv(0x04, &long);
+        assert_eq!(&t[..4], &[0x04, 0x82, 0x01, 0x2c]);
+        let pdu = Pdu { tag: PDU_RESPONSE, request_id: 42, error_status: 0, error_index: 0,
+            varbinds: vec![(SYS_UPTIME.to_string(), SnmpValue::TimeTicks(8_640_000)), (SYS_NAME.to_string(), SnmpValue::Str(long))] };
+        let (version, community, decoded) = decode_community(&encode_community(1, "public", &pdu).unwrap()).unwrap();
+        assert_eq!((version, community.as_slice()), (1, &b"public"[..]));
+        assert_eq!(decoded, pdu);
+        assert_eq!(SnmpValue::TimeTicks(8_640_000).to_string(), "1d 00:00:00");
+        assert_eq!(SnmpValue::Str(vec![0, 0x1b, 0xff]).to_string(), "00:1b:ff");
+    }
+
+    #[test]
+    fn test_key_localization() {
+        // RFC 3414 A.3.1 / A.3.2
+        let engine = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        let md5 = localize_key(AuthProtocol::Md5, &password_to_key(AuthProtocol::Md5, "maplesyrup"), &engine);
+        assert_eq!(hex_bytes(&md5), "52:6f:5e:ed:9f:cc:e2:6f:89:64:c2:93:07:87:d8:2b");
+        let sha = localize_key(AuthProtocol::Sha, &password_to_key(AuthProtocol::Sha, "maplesyrup"), &engine);
+        assert_eq!(hex_bytes(&sha), "66:95:fe:bc:92:88:e3:62:82:23:5f:c7:15:1f:12:84:97:b3:8f:3f");
+    }
+
+    #[test]
+    fn test_v2c_against_stand_in() {
+        let addr = spawn_agent(AuthProtocol::None, PrivProtocol::None);
+        let running = AtomicBool::new(true);
+        let cfg = SnmpConfig { communities: vec!["wrong".to_string(), "secret".to_string()], timeout_ms: 200, retries: 0, ..SnmpConfig::default() };
+        let mut client = SnmpClient::connect_to(addr, &cfg, &running).unwrap();
+        let info = query_system(&mut client, &running).unwrap();
+        assert_eq!(info.name, "sw-test-01");
+        assert_eq!(info.uptime, "14d 06:56:07");
+        assert_eq!(info.merge_label(), "SNMP: sw-test-01 (Test Switch 24-port)");
+        assert_eq!(info.format_lines(Ipv4Addr::LOCALHOST)[1], "    Descr: Test Switch 24-port");
+
+        let ifs = walk_interfaces(&mut client, Ipv4Addr::LOCALHOST, &unlimited(), &running).unwrap();
+        assert_eq!(ifs.len(), 2);
+        assert_eq!(ifs[0].mac, "00:11:22:33:44:55");
+        let lines = format_interfaces(&ifs);
+        assert_eq!(lines[1], format!("{:<6} {:<16} {:<6} {:<6} {:<6} {:<17} {}", 1, "Gi1/0/1", "up", "up", "1G", "00:11:22:33:44:55", "uplink"));
+        assert!(lines[2].contains("down"));
+
+        // v1 walks end with noSuchName
+        let cfg = SnmpConfig { version: "1".to_string(), communities: vec!["secret".to_string()], timeout_ms: 200, retries: 0, ..SnmpConfig::default() };
+        let mut client = SnmpClient::connect_to(addr, &cfg, &running).unwrap();
+        assert_eq!(client.walk(IF_ALIAS, &unlimited(), Ipv4Addr::LOCALHOST, &running).unwrap().len(), 1);
+        let cfg = SnmpConfig { communities: vec!["wrong".to_string()], timeout_ms: 100, retries: 0, ..SnmpConfig::default() };
+        assert!(SnmpClient::connect_to(addr, &cfg, &running).is_err());
+    }
+
+    #[test]
+    fn test_v3_against_stand_in() {
+        let running = Arc::new(AtomicBool::new(true));
+        for (auth, privacy, a, p) in [(AuthProtocol::Sha, PrivProtocol::Aes, "sha", "aes"), (AuthProtocol::Md5, PrivProtocol::Des, "md5", "des"), (AuthProtocol::Sha, PrivProtocol::None, "sha", "none")] {
+            let addr = spawn_agent(auth, privacy);
+            let cfg = SnmpConfig {
+                version: "3".to_string(), user: "monitor".to_string(), timeout_ms: 300, retries: 0,
+                auth_protocol: a.to_string(), auth_password: "authpass123".to_string(),
+                priv_protocol: p.to_string(), priv_password: "privpass123".to_string(), ..SnmpConfig::default()
+            };
+            let mut client = SnmpClient::connect_to(addr, &cfg, &running).unwrap();
+            let info = query_system(&mut client, &running).unwrap();
+            assert_eq!(info.location, "Rack 3", "{}/{}", a, p);
+            let ifs = walk_interfaces(&mut client, Ipv4Addr::LOCALHOST, &unlimited(), &running).unwrap();
+            assert_eq!(ifs[1].name, "Gi1/0/2");
+        }
+        // Spoofed replies: unauthenticated, or authenticated but not encrypted for authPriv
+        for (privacy, p, flags) in [(PrivProtocol::None, "none", 0), (PrivProtocol::Aes, "aes", FLAG_AUTH)] {
+            let addr = spawn_agent_with(AuthProtocol::Sha, privacy, Some(flags));
+            let cfg = SnmpConfig {
+                version: "3".to_string(), user: "monitor".to_string(), timeout_ms: 300, retries: 0,
+                auth_protocol: "sha".to_string(), auth_password: "authpass123".to_string(),
+                priv_protocol: p.to_string(), priv_password: "privpass123".to_string(), ..SnmpConfig::default()
+            };
+            let err = SnmpClient::connect_to(addr, &cfg, &running).and_then(|mut c| query_system(&mut c, &running)).unwrap_err();
+            assert!(err.contains("below the requested security level"), "{}: {}", p, err);
+        }
+        let cfg = SnmpConfig { version: "3".to_string(), user: "monitor".to_string(), priv_protocol: "aes".to_string(), ..SnmpConfig::default() };
+        assert!(matches!(SnmpClient::connect_to(SocketAddr::from((Ipv4Addr::LOCALHOST, 9)), &cfg, &running), Err(e) if e.contains("requires an auth")));
+    }
+}
@@ -0,0 +1,195 @@
+use fltk::{
+    prelude::*,
+    frame::Frame,
+    input::Input,
+    button::{Button, CheckButton},
+    text::{TextDisplay, TextBuffer},
+    app,
+};
+use std::{
+    collections::VecDeque,
+    net::Ipv4Addr,
+    sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}},
+    thread,
+};
+use crate::config::Config;
+use crate::exclusions::{profile_choice, selected_profile, ExcludeList};
+use crate::rate::RateLimiter;
+use crate::snmp::{self, SnmpClient, SnmpConfig};
+use crate::utils::{alive_rows, ScanRun};
+
+/// Line sent to the SNMP tab.
+fn send_line(sender: app::Sender<(String, Ipv4Addr, bool, String)>, line: String) {
+    sender.send(("SNMP".to_string(), Ipv4Addr::UNSPECIFIED, false, line));
+}
+
+/// Credentials and exclusions of the selected profile, or an error line.
+fn load_profile(config: &Config, profile: Option<&str>) -> Result<(SnmpConfig, ExcludeList), String> {
+    let excludes = ExcludeList::for_profile(config, profile).map_err(|e| format!("[Error] {}", e))?;
+    Ok((config.snmp_for(profile).clone(), excludes))
+}
+
+/// Build the SNMP tab. `cidr_buff` and `list_buff` are the CIDR and IP List results
+/// whose alive hosts are queried by "Sweep alive hosts".
+pub fn build_snmp_tab(
+    sender: app::Sender<(String, Ipv4Addr, bool, String)>,
+    config: Arc<Config>,
+    limiter: Arc<RateLimiter>,
+    cidr_buff: TextBuffer,
+    list_buff: TextBuffer,
+) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>) {
+    Frame::new(10, 30, 480, 25, "Device IP (Query / Interfaces)");
+    let input = Input::new(10, 70, 200, 30, "");
+
+    let mut clear_btn = Button::new(240, 70, 80, 30, "Clear");
+    let mut query_btn = Button::new(320, 70, 80, 30, "Query");
+    let mut stop_btn = Button::new(410, 70, 80, 30, "Stop");
+
+    let profile = profile_choice(60, 105, 150, 25, &config);
+    let mut if_btn = Button::new(240, 105, 80, 25, "Interfaces");
+    let mut sweep_btn = Button::new(330, 105, 160, 25, "Sweep alive hosts");
+    let mut merge_cb = CheckButton::new(240, 135, 250, 25, "Merge into CIDR / IP List results");
+    merge_cb.set_value(true);
+
+    let mut display = TextDisplay::new(10, 165, 480, 205, "");
+    let buff = TextBuffer::default();
+    display.set_buffer(buff.clone());
+    let display_ref = Arc::new(Mutex::new(display));
+
+    let run = ScanRun::default();
+
+    // Clear
+    {
+        let mut b = buff.clone();
+        clear_btn.set_callback(move |_| b.set_text(""));
+    }
+
+    // Query / Interfaces: one device
+    for (btn, interfaces) in [(&mut query_btn, false), (&mut if_btn, true)] {
+        let inp = input.clone();
+        let profile = profile.clone();
+        let merge_cb = merge_cb.clone();
+        let config = config.clone();
+        let limiter = limiter.clone();
+        let run = run.clone();
+        let mut b = buff.clone();
+        btn.set_callback(move |_| {
+            if run.is_busy() {
+                return; // already running
+            }
+            let Ok(ip) = inp.value().trim().parse::<Ipv4Addr>() else {
+                b.append(&format!("[Error] Invalid IP address: {}\n", inp.value().trim()));
+                return;
+            };
+            let (cfg, excludes) = match load_profile(&config, selected_profile(&profile).as_deref()) {
+                Ok(p) => p,
+                Err(e) => { b.append(&format!("{}\n", e)); return; }
+            };
+            if let Some(rule) = excludes.matches(ip) {
+                b.append(&format!("[Error] {} is excluded: {}\n", ip, rule.label()));
+                return;
+            }
+            let merge = merge_cb.value();
+            b.set_text(&format!("[Info] SNMP {} -> {}\n", cfg.describe(), ip));
+
+            let flag_thread = run.start();
+            let run = run.clone();
+            let limiter = limiter.clone();
+            thread::spawn(move || {
+                let result = limiter.pace(ip, 1, &flag_thread).then_some(()).ok_or_else(|| "Stopped".to_string())
+                    .and_then(|_| SnmpClient::connect(ip, &cfg, &flag_thread))
+                    .and_then(|mut client| {
+                        if interfaces {
+                            let ifs = snmp::walk_interfaces(&mut client, ip, &limiter, &flag_thread)?;
+                            Ok(snmp::format_interfaces(&ifs).into_iter().chain([format!("[Info] {} interface(s)", ifs.len())]).collect())
+                        } else {
+                            let info = snmp::query_system(&mut client, &flag_thread)?;
+                            if merge {
+                                sender.send(("SNMP_MERGE".to_string(), ip, true, info.merge_label()));
+                            }
+                            Ok(info.format_lines(ip))
+                        }
+                    });
+                match result {
+                    Ok(lines) => lines.into_iter().for_each(|l| send_line(sender, l)),
+                    Err(e) => send_line(sender, format!("[Error] {}", e)),
+                }
+                run.finish();
+            });
+        });
+    }
+
+    // Sweep: system group of every alive host in the CIDR / IP List results
+    {
+        let profile = profile.clone();
+        let merge_cb = merge_cb.clone();
+        let run = run.clone();
+        let mut b = buff.clone();
+        sweep_btn.set_callback(move |_| {
+            if run.is_busy() {
+                return; // already running
+            }
+            let mut hosts = alive_rows(&cidr_buff.text());
+            for ip in alive_rows(&list_buff.text()) {
+                if !hosts.contains(&ip) {
+                    hosts.push(ip);
+                }
+            }
+            let (cfg, excludes) = match load_profile(&config, selected_profile(&profile).as_deref()) {
+                Ok(p) => p,
+                Err(e) => { b.append(&format!("{}\n", e)); return; }
+            };
+            hosts.retain(|ip| excludes.matches(*ip).is_none());
+            if hosts.is_empty() {
+                b.append("[Error] No alive hosts in the CIDR / IP List results (run a sweep first)\n");
+                return;
+            }
+            let merge = merge_cb.value();
+            let total = hosts.len();
+            b.set_text(&format!("[Info] SNMP {} -> {} alive host(s)\n", cfg.describe(), total));
+
+            let flag_thread = run.start();
+            let run = run.clone();
+            let limiter = limiter.clone();
+            thread::spawn(move || {
+                let queue = Arc::new(Mutex::new(VecDeque::from(hosts)));
+                let answered = Arc::new(Mutex::new(0usize));
+                let workers: Vec<_> = (0..limiter.max_batch(8).min(total as u32)).map(|_| {
+                    let (queue, answered, flag, limiter, cfg) = (queue.clone(), answered.clone(), flag_thread.clone(), limiter.clone(), cfg.clone());
+                    thread::spawn(move || loop {
+                        let Some(ip) = queue.lock().ok().and_then(|mut q| q.pop_front()) else { return };
+                        let Some(_permit) = limiter.in_flight(1, &flag) else { return };
+                        if !limiter.pace(ip, 1, &flag) { return }
+                        // Hosts without an agent (or with other credentials) are skipped silently
+                        let Ok(info) = SnmpClient::connect(ip, &cfg, &flag).and_then(|mut c| snmp::query_system(&mut c, &flag)) else { continue };
+                        if let Ok(mut n) = answered.lock() { *n += 1; }
+                        for line in info.format_lines(ip) {
+                            send_line(sender, line);
+                        }
+                        if merge {
+                            sender.send(("SNMP_MERGE".to_string(), ip, true, info.merge_label()));
+                        }
+                    })
+                }).collect();
+                for w in workers {
+                    let _ = w.join();
+                }
+                let answered = answered.lock().map(|n| *n).unwrap_or(0);
+                if flag_thread.load(Ordering::SeqCst) {
+                    send_line(sender, format!("[Info] {} of {} host(s) answered", answered, total));
+                } else {
+                    send_line(sender, format!("[Info] Sweep stopped, {} host(s) answered", answered));
+                }
+                run.finish();
+            });
+        });
+    }
+
+    // Stop
+    {
+        let run = run.clone();
+        stop_btn.set_callback(move |_| run.stop());
+    }
+
+    (run.busy_flag(), buff, display_ref)
+}
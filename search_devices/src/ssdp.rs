@@ -0,0 +1,312 @@
+// SSDP / UPnP discovery: M-SEARCH on the local segment, then the device description
+// XML behind each responder's LOCATION
+
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+/// SSDP multicast group and port.
+pub const SSDP_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 1900);
+
+/// Search target matching every device and service.
+pub const ST_ALL: &str = "ssdp:all";
+
+/// Encode an M-SEARCH request. `mx` is the longest delay (seconds) a device may wait before answering.
+pub fn build_msearch(st: &str, mx: u32) -> String {
+    format!("M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: {}\r\nST: {}\r\n\r\n", mx.clamp(1, 5), st)
+}
+
+/// Headers of an M-SEARCH response.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SsdpResponse {
+    pub location: String,
+    pub server: String,
+    pub st: String,
+    pub usn: String,
+}
+
+/// Parse an M-SEARCH response; None when it is not a 200 answer with a LOCATION.
+pub fn parse_ssdp_response(text: &str) -> Option<SsdpResponse> {
+    let mut lines = text.lines();
+    let status = lines.next()?;
+    if !status.starts_with("HTTP/1.") || status.split_whitespace().nth(1) != Some("200") {
+        return None;
+    }
+    let mut resp = SsdpResponse::default();
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else { continue };
+        let value = value.trim().to_string();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "location" => resp.location = value,
+            "server" => resp.server = value,
+            "st" => resp.st = value,
+            "usn" => resp.usn = value,
+            _ => {}
+        }
+    }
+    if resp.location.is_empty() { None } else { Some(resp) }
+}
+
+/// Fields of a UPnP device description (root device).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub device_type: String,
+    pub friendly_name: String,
+    pub manufacturer: String,
+    pub model_name: String,
+    pub model_number: String,
+    pub serial_number: String,
+}
+
+impl DeviceInfo {
+    /// Text appended to scan result rows, e.g. "UPnP: Living Room TV (Samsung UE55)".
+    pub fn merge_label(&self) -> String {
+        let model: Vec<&str> = [self.manufacturer.as_str(), self.model_name.as_str(), self.model_number.as_str()]
+            .into_iter().filter(|s| !s.is_empty()).collect();
+        let name = if self.friendly_name.is_empty() { "?" } else { &self.friendly_name };
+        if model.is_empty() { format!("UPnP: {}", name) } else { format!("UPnP: {} ({})", name, model.join(" ")) }
+    }
+}
+
+/// Result lines for one responder: address and name, then model, serial, type and LOCATION.
+pub fn format_device(ip: Ipv4Addr, resp: &SsdpResponse, info: Option<&DeviceInfo>) -> Vec<String> {
+    let Some(info) = info else {
+        return vec![format!("{}  (no description)", ip), format!("    {}  {}", resp.location, resp.server).trim_end().to_string()];
+    };
+    let mut lines = vec![format!("{}  {}", ip, if info.friendly_name.is_empty() { "?" } else { &info.friendly_name })];
+    let model: Vec<&str> = [info.manufacturer.as_str(), info.model_name.as_str(), info.model_number.as_str()]
+        .into_iter().filter(|s| !s.is_empty()).collect();
+    let mut detail = model.join(" ");
+    if !info.serial_number.is_empty() {
+        detail = format!("{}  serial {}", detail, info.serial_number).trim_start().to_string();
+    }
+    if !detail.is_empty() {
+        lines.push(format!("    {}", detail));
+    }
+    if !info.device_type.is_empty() {
+        lines.push(format!("    {}", info.device_type));
+    }
+    lines.push(format!("    {}", resp.location));
+    lines
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+/// Text of the first `<tag>` element (namespace prefixes are ignored).
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let mut rest = xml;
+    while let Some(i) = rest.find('<') {
+        rest = &rest[i + 1..];
+        let end = rest.find('>')?;
+        let name = rest[..end].split_whitespace().next().unwrap_or("");
+        let local = name.rsplit(':').next().unwrap_or(name);
+        if local == tag && !rest[..end].ends_with('/') {
+            let body = &rest[end + 1..];
+            let close = body.find("</")?;
+            return Some(xml_unescape(body[..close].trim()));
+        }
+        rest = &rest[end + 1..];
+    }
+    None
+}
+
+/// Parse the root device of a description document.
+pub fn parse_device_description(xml: &str) -> Option<DeviceInfo> {
+    // The root device's fields come before any nested <deviceList>
+    let start = xml.find("<device>").or_else(|| xml.find("<device "))?;
+    let device = &xml[start..];
+    let device = device.find("<deviceList").map(|i| &device[..i]).unwrap_or(device);
+    let get = |tag: &str| xml_text(device, tag).unwrap_or_default();
+    Some(DeviceInfo {
+        device_type: get("deviceType"),
+        friendly_name: get("friendlyName"),
+        manufacturer: get("manufacturer"),
+        model_name: get("modelName"),
+        model_number: get("modelNumber"),
+        serial_number: get("serialNumber"),
+    })
+}
+
+/// Split "http://host:port/path" into the socket address, Host header and path.
+fn parse_http_url(url: &str) -> Result<(SocketAddr, String, String), String> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| format!("Unsupported LOCATION: {}", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let with_port = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+    let addr = with_port.to_socket_addrs().ok().and_then(|mut a| a.next()).ok_or_else(|| format!("Invalid LOCATION: {}", url))?;
+    Ok((addr, authority.to_string(), path.to_string()))
+}
+
+/// Decode a chunked transfer-encoded body.
+fn decode_chunked(body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while let Some(eol) = body[pos..].windows(2).position(|w| w == b"\r\n") {
+        let size_line = String::from_utf8_lossy(&body[pos..pos + eol]).to_string();
+        let size = usize::from_str_radix(size_line.split(';').next().unwrap_or("").trim(), 16).unwrap_or(0);
+        pos += eol + 2;
+        if size == 0 || pos + size > body.len() {
+            break;
+        }
+        out.extend_from_slice(&body[pos..pos + size]);
+        pos += size + 2;
+        if pos >= body.len() { break; }
+    }
+    out
+}
+
+/// Fetch a device description over plain HTTP.
+pub fn fetch_description(url: &str, timeout: Duration) -> Result<String, String> {
+    let (addr, host, path) = parse_http_url(url)?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout).map_err(|e| format!("{}: {}", url, e))?;
+    stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    let req = format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nUser-Agent: search_devices UPnP/1.0\r\n\r\n", path, host);
+    stream.write_all(req.as_bytes()).map_err(|e| format!("{}: {}", url, e))?;
+    let mut raw = Vec::new();
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 4096];
+    while Instant::now() < deadline && raw.len() < 1 << 20 {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => raw.extend_from_slice(&buf[..n]),
+            Err(_) => break,
+        }
+    }
+    let split = raw.windows(4).position(|w| w == b"\r\n\r\n").ok_or_else(|| format!("{}: no HTTP response", url))?;
+    let head = String::from_utf8_lossy(&raw[..split]).to_ascii_lowercase();
+    if head.split_whitespace().nth(1) != Some("200") {
+        return Err(format!("{}: {}", url, head.lines().next().unwrap_or("")));
+    }
+    let body = &raw[split + 4..];
+    let body = if head.contains("transfer-encoding: chunked") { decode_chunked(body) } else { body.to_vec() };
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Send M-SEARCH to `target` (normally `SSDP_ADDR`) and collect answers for `wait`.
+/// Responses are deduplicated by LOCATION. Stops early when `running` is cleared.
+pub fn search(target: SocketAddr, st: &str, wait: Duration, running: &AtomicBool) -> Result<Vec<(Ipv4Addr, SsdpResponse)>, String> {
+    let sock = UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("Failed to open UDP socket: {}", e))?;
+    sock.set_read_timeout(Some(Duration::from_millis(100))).map_err(|e| e.to_string())?;
+    if target.ip().is_multicast() {
+        let _ = sock.set_multicast_ttl_v4(2);
+    }
+    let mx = (wait.as_secs() as u32).max(1);
+    let msg = build_msearch(st, mx);
+    // UDP is lossy: send the request twice
+    for _ in 0..2 {
+        sock.send_to(msg.as_bytes(), target).map_err(|e| format!("Failed to send M-SEARCH: {}", e))?;
+    }
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    let deadline = Instant::now() + wait;
+    let mut buf = [0u8; 2048];
+    while Instant::now() < deadline && running.load(Ordering::SeqCst) {
+        let Ok((n, from)) = sock.recv_from(&mut buf) else { continue };
+        let IpAddr::V4(from) = from.ip() else { continue };
+        if let Some(resp) = parse_ssdp_response(&String::from_utf8_lossy(&buf[..n])) {
+            if seen.insert(resp.location.clone()) {
+                out.push((from, resp));
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+
+    const DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+  <specVersion><major>1</major><minor>0</minor></specVersion>
+  <device>
+    <deviceType>urn:schemas-upnp-org:device:InternetGatewayDevice:1</deviceType>
+    <friendlyName>Home Router &amp; AP</friendlyName>
+    <manufacturer>ACME</manufacturer>
+    <modelName>RT-1000</modelName>
+    <modelNumber>v2</modelNumber>
+    <serialNumber>SN123456</serialNumber>
+    <deviceList>
+      <device>
+        <deviceType>urn:schemas-upnp-org:device:WANDevice:1</deviceType>
+        <friendlyName>WAN Device</friendlyName>
+      </device>
+    </deviceList>
+  </device>
+</root>"#;
+
+    #[test]
+    fn test_msearch_and_response() {
+        let m = build_msearch(ST_ALL, 2);
+        assert!(m.starts_with("M-SEARCH * HTTP/1.1\r\n"));
+        assert!(m.contains("MAN: \"ssdp:discover\"\r\nMX: 2\r\nST: ssdp:all\r\n\r\n"));
+        let resp = parse_ssdp_response("HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nLocation: http://192.168.1.1:1900/igd.xml\r\nSERVER: Linux UPnP/1.0\r\nST: upnp:rootdevice\r\nUSN: uuid:abc::upnp:rootdevice\r\n\r\n").unwrap();
+        assert_eq!(resp.location, "http://192.168.1.1:1900/igd.xml");
+        assert_eq!(resp.server, "Linux UPnP/1.0");
+        assert_eq!(resp.st, "upnp:rootdevice");
+        assert!(parse_ssdp_response("NOTIFY * HTTP/1.1\r\nLOCATION: http://x/\r\n\r\n").is_none());
+        assert!(parse_ssdp_response("HTTP/1.1 200 OK\r\nST: x\r\n\r\n").is_none());
+    }
+
+    #[test]
+    fn test_device_description() {
+        let info = parse_device_description(DESCRIPTION).unwrap();
+        assert_eq!(info.friendly_name, "Home Router & AP");
+        assert_eq!(info.manufacturer, "ACME");
+        assert_eq!(info.model_name, "RT-1000");
+        assert_eq!(info.serial_number, "SN123456");
+        assert_eq!(info.device_type, "urn:schemas-upnp-org:device:InternetGatewayDevice:1");
+        assert_eq!(info.merge_label(), "UPnP: Home Router & AP (ACME RT-1000 v2)");
+        let resp = SsdpResponse { location: "http://192.168.1.1:1900/igd.xml".to_string(), ..SsdpResponse::default() };
+        assert_eq!(format_device(Ipv4Addr::new(192, 168, 1, 1), &resp, Some(&info))[..2],
+            ["192.168.1.1  Home Router & AP".to_string(), "    ACME RT-1000 v2  serial SN123456".to_string()]);
+        assert!(parse_device_description("<html></html>").is_none());
+        assert_eq!(decode_chunked(b"5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n"), b"hello world");
+    }
+
+    #[test]
+    fn test_search_and_fetch_against_stand_in() {
+        // Description server: chunked response
+        let http = TcpListener::bind("127.0.0.1:0").unwrap();
+        let http_port = http.local_addr().unwrap().port();
+        thread::spawn(move || {
+            if let Ok((mut s, _)) = http.accept() {
+                let mut req = [0u8; 1024];
+                let _ = s.read(&mut req);
+                let body = DESCRIPTION.as_bytes();
+                let (a, b) = body.split_at(100);
+                let reply = format!("HTTP/1.1 200 OK\r\nContent-Type: text/xml\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+                    a.len(), String::from_utf8_lossy(a), b.len(), String::from_utf8_lossy(b));
+                let _ = s.write_all(reply.as_bytes());
+            }
+        });
+        // SSDP responder answering every M-SEARCH (sent twice, reported once)
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let udp_addr = udp.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buf = [0u8; 1024];
+            while let Ok((n, from)) = udp.recv_from(&mut buf) {
+                if String::from_utf8_lossy(&buf[..n]).starts_with("M-SEARCH") {
+                    let reply = format!("HTTP/1.1 200 OK\r\nLOCATION: http://127.0.0.1:{}/desc.xml\r\nST: upnp:rootdevice\r\nUSN: uuid:1\r\n\r\n", http_port);
+                    let _ = udp.send_to(reply.as_bytes(), from);
+                }
+            }
+        });
+        let running = AtomicBool::new(true);
+        let found = search(udp_addr, ST_ALL, Duration::from_millis(400), &running).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, Ipv4Addr::LOCALHOST);
+        let xml = fetch_description(&found[0].1.location, Duration::from_secs(2)).unwrap();
+        assert_eq!(parse_device_description(&xml).unwrap().model_name, "RT-1000");
+        assert!(fetch_description("https://127.0.0.1/desc.xml", Duration::from_secs(1)).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use fltk::{
+    prelude::*,
+    frame::Frame,
+    input::{Input, IntInput},
+    button::{Button, CheckButton},
+    text::{TextDisplay, TextBuffer},
+    app,
+};
+use std::{
+    net::Ipv4Addr,
+    sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}},
+    thread,
+    time::Duration,
+};
+use crate::ssdp::{self, SSDP_ADDR, ST_ALL};
+use crate::utils::ScanRun;
+
+/// Timeout for fetching one device description.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Build the SSDP tab. Returns the running flag, the output buffer and the display.
+pub fn build_ssdp_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>) {
+    Frame::new(10, 30, 480, 25, "Search target (ssdp:all, upnp:rootdevice, urn:...)");
+    let mut input = Input::new(10, 70, 200, 30, "");
+    input.set_value(ST_ALL);
+
+    let mut clear_btn = Button::new(240, 70, 80, 30, "Clear");
+    let mut search_btn = Button::new(320, 70, 80, 30, "Search");
+    let mut stop_btn = Button::new(410, 70, 80, 30, "Stop");
+
+    let _wait_label = Frame::new(10, 110, 80, 25, "Wait (s)");
+    let mut wait_inp = IntInput::new(90, 110, 70, 25, "");
+    wait_inp.set_value("3");
+    let mut merge_cb = CheckButton::new(240, 110, 250, 25, "Merge into CIDR / IP List results");
+    merge_cb.set_value(true);
+
+    let mut display = TextDisplay::new(10, 140, 480, 230, "");
+    let buff = TextBuffer::default();
+    display.set_buffer(buff.clone());
+    let display_ref = Arc::new(Mutex::new(display));
+
+    let run = ScanRun::default();
+
+    // Clear
+    {
+        let mut b = buff.clone();
+        clear_btn.set_callback(move |_| b.set_text(""));
+    }
+
+    // Search: M-SEARCH, then fetch each LOCATION
+    {
+        let inp = input.clone();
+        let wait_inp = wait_inp.clone();
+        let merge_cb = merge_cb.clone();
+        let run = run.clone();
+        let mut b = buff.clone();
+        search_btn.set_callback(move |_| {
+            if run.is_busy() {
+                return; // already running
+            }
+            let st = match inp.value().trim() {
+                "" => ST_ALL.to_string(),
+                s => s.to_string(),
+            };
+            let wait = Duration::from_secs(wait_inp.value().parse().ok().filter(|v| (1..=10).contains(v)).unwrap_or(3));
+            let merge = merge_cb.value();
+            b.set_text(&format!("[Info] M-SEARCH {} for {} s...\n", st, wait.as_secs()));
+
+            let flag_thread = run.start();
+            let run = run.clone();
+            thread::spawn(move || {
+                let send = |line: String| sender.send(("SSDP".to_string(), Ipv4Addr::UNSPECIFIED, false, line));
+                match ssdp::search(SSDP_ADDR, &st, wait, &flag_thread) {
+                    Ok(found) => {
+                        let mut described = 0;
+                        for (ip, resp) in &found {
+                            if !flag_thread.load(Ordering::SeqCst) {
+                                break;
+                            }
+                            let info = match ssdp::fetch_description(&resp.location, FETCH_TIMEOUT) {
+                                Ok(xml) => ssdp::parse_device_description(&xml),
+                                Err(e) => {
+                                    send(format!("[Error] {}", e));
+                                    None
+                                }
+                            };
+                            for line in ssdp::format_device(*ip, resp, info.as_ref()) {
+                                send(line);
+                            }
+                            if let Some(info) = info {
+                                described += 1;
+                                if merge {
+                                    sender.send(("SSDP_MERGE".to_string(), *ip, true, info.merge_label()));
+                                }
+                            }
+                        }
+                        send(format!("[Info] {} device(s) answered, {} described", found.len(), described));
+                    }
+                    Err(e) => send(format!("[Error] {}", e)),
+                }
+                run.finish();
+            });
+        });
+    }
+
+    // Stop
+    {
+        let run = run.clone();
+        stop_btn.set_callback(move |_| run.stop());
+    }
+
+    (run.busy_flag(), buff, display_ref)
+}
@@ -0,0 +1,362 @@
+// Import scan targets from files: plain text lists, a CSV column, hosts files,
+// nmap -oX XML and this tool's own JSON exports
+
+use serde_json::Value;
+use std::{collections::HashSet, net::Ipv4Addr, path::Path};
+use crate::targets::{parse_target, split_target_tokens};
+
+/// Supported input formats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// One target per line (or separated by commas/spaces), `#` comments
+    Text,
+    /// One column of a CSV file, by header name or 1-based index
+    Csv { column: String },
+    /// `/etc/hosts` format: address followed by names
+    Hosts,
+    /// nmap `-oX` output
+    NmapXml,
+    /// JSON exported by this tool (hop lists, MTR reports) or any JSON with address fields
+    Json,
+}
+
+/// A target found in the file, with an optional note (e.g. the host name from a hosts file).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedTarget {
+    pub token: String,
+    pub note: Option<String>,
+}
+
+/// Targets found in a file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportResult {
+    pub targets: Vec<ImportedTarget>,
+    /// Entries that did not parse as a target
+    pub invalid: Vec<String>,
+    /// Entries dropped because they were already listed
+    pub duplicates: usize,
+}
+
+impl ImportResult {
+    fn push(&mut self, seen: &mut HashSet<String>, token: &str, note: Option<String>) {
+        let token = token.trim();
+        if token.is_empty() { return; }
+        if parse_target(token).is_err() {
+            self.invalid.push(token.to_string());
+            return;
+        }
+        if !seen.insert(token.to_ascii_lowercase()) {
+            self.duplicates += 1;
+            return;
+        }
+        self.targets.push(ImportedTarget { token: token.to_string(), note: note.filter(|n| !n.is_empty()) });
+    }
+
+    /// Drop targets already present in `existing` (list text), counting them as duplicates.
+    pub fn drop_existing(&mut self, existing: &str) {
+        let have: HashSet<String> = split_target_tokens(existing).iter().map(|t| t.to_ascii_lowercase()).collect();
+        let before = self.targets.len();
+        self.targets.retain(|t| !have.contains(&t.token.to_ascii_lowercase()));
+        self.duplicates += before - self.targets.len();
+    }
+
+    /// Text for the IP List input: one target per line, notes as `#` comments.
+    pub fn to_list_text(&self) -> String {
+        self.targets.iter()
+            .map(|t| match &t.note {
+                Some(n) => format!("{}  # {}\n", t.token, n),
+                None => format!("{}\n", t.token),
+            })
+            .collect()
+    }
+
+    /// One-line summary for the import preview.
+    pub fn summary(&self) -> String {
+        format!("{} valid targets, {} duplicates removed, {} invalid entries", self.targets.len(), self.duplicates, self.invalid.len())
+    }
+}
+
+/// Guess the format from the file name and content. CSV files still need a column.
+pub fn detect_format(path: &Path, content: &str) -> ImportFormat {
+    let ext = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    let head = content.trim_start();
+    if ext == "xml" || head.starts_with("<?xml") || head.contains("<nmaprun") {
+        return ImportFormat::NmapXml;
+    }
+    if ext == "json" || head.starts_with('{') || head.starts_with('[') {
+        return ImportFormat::Json;
+    }
+    if ext == "csv" {
+        return ImportFormat::Csv { column: default_csv_column(content) };
+    }
+    if looks_like_hosts(content) {
+        return ImportFormat::Hosts;
+    }
+    ImportFormat::Text
+}
+
+/// Header of the first column that looks like an address column, or "1".
+pub fn default_csv_column(content: &str) -> String {
+    let Some(first) = content.lines().next() else { return "1".to_string() };
+    for cell in split_csv_line(first) {
+        let c = cell.trim().to_ascii_lowercase();
+        if ["ip", "ip address", "ipaddress", "address", "addr", "host", "hostname", "target"].contains(&c.as_str()) {
+            return cell.trim().to_string();
+        }
+    }
+    "1".to_string()
+}
+
+fn looks_like_hosts(content: &str) -> bool {
+    let mut entries = 0;
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() { continue; }
+        let mut fields = line.split_whitespace();
+        let addr_ok = fields.next().map(|a| a.parse::<std::net::IpAddr>().is_ok()).unwrap_or(false);
+        let name_ok = fields.next().map(|n| n.chars().any(|c| c.is_ascii_alphabetic())).unwrap_or(false);
+        if !(addr_ok && name_ok) { return false; }
+        entries += 1;
+    }
+    entries > 0
+}
+
+/// Extract targets from file content.
+pub fn import_targets(content: &str, format: &ImportFormat) -> Result<ImportResult, String> {
+    let mut result = ImportResult::default();
+    let mut seen = HashSet::new();
+    match format {
+        ImportFormat::Text => {
+            for token in split_target_tokens(content) {
+                result.push(&mut seen, &token, None);
+            }
+        }
+        ImportFormat::Csv { column } => {
+            let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+            let Some(header) = lines.next() else { return Ok(result) };
+            let header = split_csv_line(header);
+            let (idx, has_header) = match column.trim().parse::<usize>() {
+                Ok(n) if n >= 1 => {
+                    // A first row whose cell is not a target is a header
+                    let first_is_target = header.get(n - 1).map(|c| parse_target(c.trim()).is_ok()).unwrap_or(false);
+                    (n - 1, !first_is_target)
+                }
+                _ => {
+                    let pos = header.iter().position(|h| h.trim().eq_ignore_ascii_case(column.trim()))
+                        .ok_or_else(|| format!("CSV column not found: {}", column))?;
+                    (pos, true)
+                }
+            };
+            let rows = if has_header { None } else { Some(header) }.into_iter()
+                .chain(lines.map(split_csv_line));
+            for row in rows {
+                if let Some(cell) = row.get(idx) {
+                    result.push(&mut seen, cell, None);
+                }
+            }
+        }
+        ImportFormat::Hosts => {
+            for line in content.lines() {
+                let line = line.split('#').next().unwrap_or("");
+                let mut fields = line.split_whitespace();
+                let Some(addr) = fields.next() else { continue };
+                let Ok(ip) = addr.parse::<Ipv4Addr>() else { continue }; // IPv6 entries are skipped
+                if ip.is_loopback() || ip.is_unspecified() || ip.is_broadcast() { continue; }
+                let names: Vec<&str> = fields.collect();
+                result.push(&mut seen, addr, Some(names.join(" ")));
+            }
+        }
+        ImportFormat::NmapXml => {
+            if !content.contains("<nmaprun") {
+                return Err("Not an nmap XML file".to_string());
+            }
+            for chunk in content.split("</host>") {
+                let Some(start) = ["<host>", "<host "].iter().filter_map(|t| chunk.rfind(t)).max() else { continue };
+                let host = &chunk[start..];
+                let addr = xml_tags(host, "address").into_iter()
+                    .find(|t| xml_attr(t, "addrtype").as_deref() == Some("ipv4"))
+                    .and_then(|t| xml_attr(t, "addr"));
+                let name = xml_tags(host, "hostname").into_iter().find_map(|t| xml_attr(t, "name"));
+                if let Some(addr) = addr {
+                    result.push(&mut seen, &addr, name);
+                }
+            }
+        }
+        ImportFormat::Json => {
+            let value: Value = serde_json::from_str(content).map_err(|e| format!("Invalid JSON: {}", e))?;
+            let mut found = Vec::new();
+            collect_json_targets(&value, true, &mut found);
+            for (token, note) in found {
+                result.push(&mut seen, &token, note);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Split one CSV line, honouring double quotes.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cur = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => { cur.push('"'); chars.next(); }
+            '"' => quoted = !quoted,
+            ',' if !quoted => cells.push(std::mem::take(&mut cur)),
+            _ => cur.push(c),
+        }
+    }
+    cells.push(cur);
+    cells
+}
+
+/// Start tags named `name` in an XML fragment (attribute text only).
+fn xml_tags<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let open = format!("<{} ", name);
+    xml.match_indices(open.as_str())
+        .filter_map(|(i, m)| {
+            let rest = &xml[i + m.len()..];
+            rest.find('>').map(|end| &rest[..end])
+        })
+        .collect()
+}
+
+/// Value of attribute `name` in a start tag.
+fn xml_attr(tag: &str, name: &str) -> Option<String> {
+    let key = format!("{}=", name);
+    let mut search = tag;
+    while let Some(pos) = search.find(&key) {
+        let boundary = pos == 0 || search[..pos].ends_with(char::is_whitespace);
+        let rest = &search[pos + key.len()..];
+        let quote = rest.chars().next()?;
+        if boundary && (quote == '"' || quote == '\'') {
+            let value = &rest[1..];
+            let end = value.find(quote)?;
+            return Some(value[..end].replace("&amp;", "&").replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\""));
+        }
+        search = rest;
+    }
+    None
+}
+
+/// Walk exported JSON and collect addresses from `addr`, `ip`, `address` and `target`
+/// fields; a sibling `hostname` becomes the note. Plain string arrays count only at the
+/// top level or under a `targets` key.
+fn collect_json_targets(v: &Value, bare_strings: bool, out: &mut Vec<(String, Option<String>)>) {
+    match v {
+        Value::Object(map) => {
+            for key in ["target", "addr", "ip", "address"] {
+                if let Some(Value::String(s)) = map.get(key) {
+                    let note = map.get("hostname").and_then(Value::as_str).map(str::to_string);
+                    out.push((s.clone(), note));
+                }
+            }
+            for (key, child) in map {
+                collect_json_targets(child, key == "targets", out);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                match item {
+                    Value::String(s) if bare_strings => out.push((s.clone(), None)),
+                    other => collect_json_targets(other, false, out),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(r: &ImportResult) -> Vec<&str> {
+        r.targets.iter().map(|t| t.token.as_str()).collect()
+    }
+
+    #[test]
+    fn test_import_text_dedup() {
+        let r = import_targets("10.0.0.1\n10.0.0.2 # gw\n10.0.0.1\nprinter01\nbad!\n10.0.0.0/30", &ImportFormat::Text).unwrap();
+        assert_eq!(tokens(&r), vec!["10.0.0.1", "10.0.0.2", "printer01", "10.0.0.0/30"]);
+        assert_eq!(r.duplicates, 1);
+        assert_eq!(r.invalid, vec!["bad!"]);
+        assert_eq!(r.summary(), "4 valid targets, 1 duplicates removed, 1 invalid entries");
+        let mut r = r;
+        r.drop_existing("10.0.0.2  # gw\nPRINTER01\n");
+        assert_eq!(tokens(&r), vec!["10.0.0.1", "10.0.0.0/30"]);
+        assert_eq!(r.duplicates, 3);
+    }
+
+    #[test]
+    fn test_import_csv_column() {
+        let csv = "name,\"IP Address\",site\nnas,10.0.0.5,\"Tokyo, HQ\"\nprinter,10.0.0.9,Osaka\ndup,10.0.0.5,x\n";
+        assert_eq!(default_csv_column(csv), "IP Address");
+        let r = import_targets(csv, &ImportFormat::Csv { column: "ip address".to_string() }).unwrap();
+        assert_eq!(tokens(&r), vec!["10.0.0.5", "10.0.0.9"]);
+        assert_eq!(r.duplicates, 1);
+        // by index; the header row is detected and skipped
+        let r = import_targets(csv, &ImportFormat::Csv { column: "2".to_string() }).unwrap();
+        assert_eq!(tokens(&r), vec!["10.0.0.5", "10.0.0.9"]);
+        assert!(r.invalid.is_empty());
+        // no header
+        let r = import_targets("10.0.0.1,a\n10.0.0.2,b\n", &ImportFormat::Csv { column: "1".to_string() }).unwrap();
+        assert_eq!(tokens(&r), vec!["10.0.0.1", "10.0.0.2"]);
+        assert!(import_targets(csv, &ImportFormat::Csv { column: "mac".to_string() }).is_err());
+        assert_eq!(split_csv_line("a,\"b,\"\"c\"\"\",d"), vec!["a", "b,\"c\"", "d"]);
+    }
+
+    #[test]
+    fn test_import_hosts_file() {
+        let hosts = "127.0.0.1 localhost\n::1 localhost ip6-localhost\n# lab\n192.168.10.5\tnas nas.lab\n192.168.10.6 printer # color\n";
+        assert_eq!(detect_format(Path::new("hosts"), hosts), ImportFormat::Hosts);
+        let r = import_targets(hosts, &ImportFormat::Hosts).unwrap();
+        assert_eq!(tokens(&r), vec!["192.168.10.5", "192.168.10.6"]);
+        assert_eq!(r.targets[0].note.as_deref(), Some("nas nas.lab"));
+        assert_eq!(r.to_list_text(), "192.168.10.5  # nas nas.lab\n192.168.10.6  # printer\n");
+    }
+
+    #[test]
+    fn test_import_nmap_xml() {
+        let xml = r#"<?xml version="1.0"?>
+<nmaprun scanner="nmap" args="nmap -sn -oX - 10.0.0.0/24">
+<host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/><address addr="AA:BB:CC:DD:EE:FF" addrtype="mac" vendor="X"/>
+<hostnames><hostname name="gw.lan" type="PTR"/></hostnames></host>
+<host><status state="up"/><address addrtype="ipv4" addr='10.0.0.7'/><hostnames/></host>
+<hosthint><address addr="10.0.0.99" addrtype="ipv4"/></hosthint>
+<runstats><hosts up="2" down="0" total="2"/></runstats>
+</nmaprun>"#;
+        assert_eq!(detect_format(Path::new("scan.xml"), xml), ImportFormat::NmapXml);
+        let r = import_targets(xml, &ImportFormat::NmapXml).unwrap();
+        assert_eq!(tokens(&r), vec!["10.0.0.1", "10.0.0.7"]);
+        assert_eq!(r.targets[0].note.as_deref(), Some("gw.lan"));
+        assert!(import_targets("<foo/>", &ImportFormat::NmapXml).is_err());
+    }
+
+    #[test]
+    fn test_import_own_json() {
+        // hop export (hops_to_json) and MTR report (MtrStats::to_json)
+        let hops = r#"[{"hop":1,"responders":[{"addr":"192.168.0.1","hostname":"gw.lan"}],"rtts":[1.0],"annotations":[]},
+                      {"hop":2,"responders":[],"rtts":[null],"annotations":[]}]"#;
+        assert_eq!(detect_format(Path::new("trace.json"), hops), ImportFormat::Json);
+        let r = import_targets(hops, &ImportFormat::Json).unwrap();
+        assert_eq!(tokens(&r), vec!["192.168.0.1"]);
+        assert_eq!(r.targets[0].note.as_deref(), Some("gw.lan"));
+        let mtr = r#"{"target":"8.8.8.8","rounds":3,"hops":[{"hop":1,"responders":[{"addr":"192.168.0.1","hostname":null}]}]}"#;
+        let r = import_targets(mtr, &ImportFormat::Json).unwrap();
+        assert_eq!(tokens(&r), vec!["8.8.8.8", "192.168.0.1"]);
+        let list = r#"{"targets":["10.0.0.1","10.0.0.2","10.0.0.1"]}"#;
+        let r = import_targets(list, &ImportFormat::Json).unwrap();
+        assert_eq!((tokens(&r), r.duplicates), (vec!["10.0.0.1", "10.0.0.2"], 1));
+        assert!(import_targets("{", &ImportFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(detect_format(Path::new("list.txt"), "10.0.0.1\n10.0.0.2 10.0.0.3\n"), ImportFormat::Text);
+        assert_eq!(detect_format(Path::new("a.csv"), "host,ip\n"), ImportFormat::Csv { column: "host".to_string() });
+        assert_eq!(detect_format(Path::new("a.csv"), "x,y\n"), ImportFormat::Csv { column: "1".to_string() });
+    }
+}
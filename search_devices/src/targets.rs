@@ -0,0 +1,472 @@
+// Scan target lists: IPv4 addresses, CIDRs, address ranges and host names, with `#` comments
+
+use ipnetwork::Ipv4Network;
+use std::net::{IpAddr, Ipv4Addr};
+
+/// Most addresses a target list may expand to; larger lists are refused before
+/// anything is allocated (a /12).
+pub const MAX_EXPANDED_ADDRESSES: u64 = 1 << 20;
+
+/// One entry of a target list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Ip(Ipv4Addr),
+    Cidr(Ipv4Network),
+    /// Inclusive address range, e.g. `10.0.0.5-10.0.0.40` or `10.0.0.5-40`
+    Range(Ipv4Addr, Ipv4Addr),
+    /// Host name or FQDN, resolved when the list is expanded
+    Host(String),
+}
+
+impl Target {
+    /// Addresses covered by this target. CIDRs skip the network and broadcast
+    /// addresses (except /31 and /32); host names resolve to all their IPv4 addresses.
+    pub fn expand(&self) -> Result<Vec<Ipv4Addr>, String> {
+        self.expand_with(resolve_host)
+    }
+
+    /// Number of addresses `expand` yields; a host name counts as one.
+    pub fn address_count(&self) -> u64 {
+        match self {
+            Target::Ip(_) | Target::Host(_) => 1,
+            Target::Cidr(net) => {
+                let size = 1u64 << (32 - u32::from(net.prefix()));
+                if net.prefix() >= 31 { size } else { size - 2 }
+            }
+            Target::Range(a, b) => u64::from(u32::from(*b)).saturating_sub(u64::from(u32::from(*a))) + 1,
+        }
+    }
+
+    /// Like `expand`, resolving host names with `resolve` instead of the system resolver.
+    /// Targets larger than `MAX_EXPANDED_ADDRESSES` are refused.
+    pub fn expand_with(&self, resolve: impl FnOnce(&str) -> Result<Vec<Ipv4Addr>, String>) -> Result<Vec<Ipv4Addr>, String> {
+        let count = self.address_count();
+        if count > MAX_EXPANDED_ADDRESSES {
+            return Err(format!("Too many addresses: {} (limit {})", count, MAX_EXPANDED_ADDRESSES));
+        }
+        match self {
+            Target::Ip(ip) => Ok(vec![*ip]),
+            Target::Cidr(net) => Ok(cidr_hosts(net)),
+            Target::Range(a, b) => Ok((u32::from(*a)..=u32::from(*b)).map(Ipv4Addr::from).collect()),
+            Target::Host(name) => resolve(name),
+        }
+    }
+}
+
+/// Usable host addresses of a network.
+pub fn cidr_hosts(net: &Ipv4Network) -> Vec<Ipv4Addr> {
+    net.iter()
+        .filter(|ip| net.prefix() >= 31 || (*ip != net.network() && *ip != net.broadcast()))
+        .collect()
+}
+
+/// Resolve a host name to its IPv4 addresses, without duplicates.
+pub fn resolve_host(name: &str) -> Result<Vec<Ipv4Addr>, String> {
+    let addrs = dns_lookup::lookup_host(name).map_err(|_| format!("Cannot resolve {}", name))?;
+    ipv4_addrs(name, addrs)
+}
+
+/// IPv4 addresses among the resolved `addrs` of `name`, without duplicates.
+fn ipv4_addrs(name: &str, addrs: Vec<IpAddr>) -> Result<Vec<Ipv4Addr>, String> {
+    let mut out: Vec<Ipv4Addr> = Vec::new();
+    for a in addrs {
+        if let IpAddr::V4(v4) = a {
+            if !out.contains(&v4) { out.push(v4); }
+        }
+    }
+    if out.is_empty() {
+        return Err(format!("No IPv4 address for {}", name));
+    }
+    Ok(out)
+}
+
+fn is_hostname(s: &str) -> bool {
+    !s.is_empty()
+        && s.len() <= 253
+        && !s.starts_with('-')
+        && s.chars().any(|c| c.is_ascii_alphabetic())
+        && s.trim_end_matches('.').split('.').all(|label| {
+            !label.is_empty() && label.len() <= 63 && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// Parse one target token.
+pub fn parse_target(token: &str) -> Result<Target, String> {
+    let token = token.trim();
+    if let Ok(ip) = token.parse::<Ipv4Addr>() {
+        return Ok(Target::Ip(ip));
+    }
+    if token.contains('/') {
+        return token.parse::<Ipv4Network>().map(Target::Cidr).map_err(|_| format!("Invalid CIDR: {}", token));
+    }
+    if let Some((start, end)) = token.split_once('-') {
+        if let Ok(start) = start.trim().parse::<Ipv4Addr>() {
+            let end = end.trim();
+            let end = match end.parse::<Ipv4Addr>() {
+                Ok(e) => e,
+                // Short form: last octet only
+                Err(_) => {
+                    let last: u8 = end.parse().map_err(|_| format!("Invalid range: {}", token))?;
+                    let o = start.octets();
+                    Ipv4Addr::new(o[0], o[1], o[2], last)
+                }
+            };
+            if end < start {
+                return Err(format!("Invalid range (end before start): {}", token));
+            }
+            return Ok(Target::Range(start, end));
+        }
+    }
+    if is_hostname(token) {
+        return Ok(Target::Host(token.to_string()));
+    }
+    Err(format!("Invalid target: {}", token))
+}
+
+/// First and last usable host address of a network (see `cidr_hosts`).
+fn host_bounds(net: &Ipv4Network) -> (Ipv4Addr, Ipv4Addr) {
+    if net.prefix() >= 31 {
+        (net.network(), net.broadcast())
+    } else {
+        (Ipv4Addr::from(u32::from(net.network()) + 1), Ipv4Addr::from(u32::from(net.broadcast()) - 1))
+    }
+}
+
+/// Parse one sweep entry: an address, CIDR, range or netmask notation
+/// (`192.168.1.0 255.255.255.0` or `192.168.1.0/255.255.255.0`). Host names are rejected.
+pub fn parse_sweep_entry(entry: &str) -> Result<(Ipv4Addr, Ipv4Addr), String> {
+    let entry = entry.trim();
+    let parts: Vec<&str> = entry.split(|c: char| c.is_whitespace() || c == '/').filter(|p| !p.is_empty()).collect();
+    if parts.len() == 2 && parts[1].contains('.') && !entry.contains('-') {
+        let invalid = || format!("Invalid netmask: {}", entry);
+        let addr = parts[0].parse::<Ipv4Addr>().map_err(|_| invalid())?;
+        let mask = parts[1].parse::<Ipv4Addr>().map_err(|_| invalid())?;
+        let net = Ipv4Network::with_netmask(addr, mask).map_err(|_| invalid())?;
+        return Ok(host_bounds(&net));
+    }
+    let compact: String = entry.split_whitespace().collect();
+    match parse_target(&compact)? {
+        Target::Ip(ip) => Ok((ip, ip)),
+        Target::Cidr(net) => Ok(host_bounds(&net)),
+        Target::Range(a, b) => Ok((a, b)),
+        Target::Host(_) => Err(format!("Invalid CIDR or range: {}", entry)),
+    }
+}
+
+/// Address ranges of a sweep, sorted with overlapping and adjacent ranges merged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SweepPlan {
+    pub ranges: Vec<(Ipv4Addr, Ipv4Addr)>,
+}
+
+impl SweepPlan {
+    /// Parse entries separated by commas or new lines (`#` starts a comment).
+    /// Returns every invalid entry as an error message.
+    pub fn parse(text: &str) -> Result<SweepPlan, Vec<String>> {
+        let mut ranges = Vec::new();
+        let mut errors = Vec::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            for entry in line.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                match parse_sweep_entry(entry) {
+                    Ok(r) => ranges.push(r),
+                    Err(e) => errors.push(e),
+                }
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        if ranges.is_empty() {
+            return Err(vec!["No CIDR or range given".to_string()]);
+        }
+        Ok(SweepPlan { ranges: merge_ranges(ranges) })
+    }
+
+    /// Number of addresses to sweep.
+    pub fn total(&self) -> u64 {
+        self.ranges.iter().map(|(a, b)| u64::from(u32::from(*b)) - u64::from(u32::from(*a)) + 1).sum()
+    }
+
+    /// All addresses in ascending order.
+    pub fn into_addresses(self) -> impl Iterator<Item = Ipv4Addr> {
+        self.ranges.into_iter().flat_map(|(a, b)| (u32::from(a)..=u32::from(b)).map(Ipv4Addr::from))
+    }
+
+    /// Address at position `index` of the ascending order.
+    fn address_at(&self, mut index: u64) -> Ipv4Addr {
+        for (a, b) in &self.ranges {
+            let len = u64::from(u32::from(*b)) - u64::from(u32::from(*a)) + 1;
+            if index < len {
+                return Ipv4Addr::from(u32::from(*a) + index as u32);
+            }
+            index -= len;
+        }
+        unreachable!("index out of range")
+    }
+
+    /// All addresses in the given order. `seed` picks the permutation for `ScanOrder::Random`.
+    pub fn into_ordered(self, order: ScanOrder, seed: u64) -> Box<dyn Iterator<Item = Ipv4Addr> + Send> {
+        match order {
+            ScanOrder::Sequential => Box::new(self.into_addresses()),
+            ScanOrder::Random => {
+                let perm = CyclicPermutation::new(self.total(), seed);
+                Box::new(perm.map(move |i| self.address_at(i)))
+            }
+            ScanOrder::Interleave => Box::new(Interleave::new(&self.ranges)),
+        }
+    }
+}
+
+/// Order in which a sweep visits its addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanOrder {
+    Sequential,
+    /// Pseudo-random permutation of all addresses
+    Random,
+    /// Round robin over /24 blocks, one address from each block in turn
+    Interleave,
+}
+
+impl ScanOrder {
+    pub const ALL: [ScanOrder; 3] = [ScanOrder::Sequential, ScanOrder::Random, ScanOrder::Interleave];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScanOrder::Sequential => "Sequential",
+            ScanOrder::Random => "Random",
+            ScanOrder::Interleave => "Interleave",
+        }
+    }
+}
+
+/// Visits every index in `0..n` exactly once in a scrambled order, without storing them.
+/// A full-period linear congruential generator over the next power of two (Hull-Dobell:
+/// odd increment, multiplier = 1 mod 4) cycles through all values; those >= n are skipped.
+struct CyclicPermutation {
+    n: u64,
+    mask: u64,
+    mul: u64,
+    inc: u64,
+    state: u64,
+    emitted: u64,
+}
+
+impl CyclicPermutation {
+    fn new(n: u64, seed: u64) -> CyclicPermutation {
+        let mask = n.next_power_of_two().max(4) - 1;
+        let mix = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        CyclicPermutation {
+            n,
+            mask,
+            mul: ((mix >> 7) << 2 | 1) & mask,
+            inc: ((mix >> 29) | 1) & mask,
+            state: (mix >> 41) & mask,
+            emitted: 0,
+        }
+    }
+}
+
+impl Iterator for CyclicPermutation {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.emitted >= self.n {
+            return None;
+        }
+        loop {
+            self.state = self.state.wrapping_mul(self.mul).wrapping_add(self.inc) & self.mask;
+            if self.state < self.n {
+                self.emitted += 1;
+                return Some(self.state);
+            }
+        }
+    }
+}
+
+/// Round robin over the /24 blocks of a set of ranges.
+struct Interleave {
+    /// Next and last address of each block
+    blocks: Vec<(u32, u32)>,
+    pos: usize,
+}
+
+impl Interleave {
+    fn new(ranges: &[(Ipv4Addr, Ipv4Addr)]) -> Interleave {
+        let mut blocks = Vec::new();
+        for (a, b) in ranges {
+            let (mut start, end) = (u32::from(*a), u32::from(*b));
+            loop {
+                let block_end = (start | 0xff).min(end);
+                blocks.push((start, block_end));
+                if block_end == end { break; }
+                start = block_end + 1;
+            }
+        }
+        Interleave { blocks, pos: 0 }
+    }
+}
+
+impl Iterator for Interleave {
+    type Item = Ipv4Addr;
+
+    fn next(&mut self) -> Option<Ipv4Addr> {
+        if self.blocks.is_empty() {
+            return None;
+        }
+        if self.pos >= self.blocks.len() {
+            self.pos = 0;
+        }
+        let (next, last) = self.blocks[self.pos];
+        if next == last {
+            // Exhausted blocks leave the rotation
+            self.blocks.remove(self.pos);
+        } else {
+            self.blocks[self.pos].0 += 1;
+            self.pos += 1;
+        }
+        Some(Ipv4Addr::from(next))
+    }
+}
+
+/// Sort ranges and merge those that overlap or touch.
+pub fn merge_ranges(mut ranges: Vec<(Ipv4Addr, Ipv4Addr)>) -> Vec<(Ipv4Addr, Ipv4Addr)> {
+    ranges.sort();
+    let mut out: Vec<(Ipv4Addr, Ipv4Addr)> = Vec::new();
+    for (a, b) in ranges {
+        match out.last_mut() {
+            Some(last) if u64::from(u32::from(a)) <= u64::from(u32::from(last.1)) + 1 => {
+                if b > last.1 { last.1 = b; }
+            }
+            _ => out.push((a, b)),
+        }
+    }
+    out
+}
+
+/// Split list text into target tokens. Entries are separated by new lines, commas or
+/// spaces; everything after `#` is a comment. Spaces around a range dash are allowed.
+pub fn split_target_tokens(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut line = line.replace(',', " ");
+        while line.contains(" -") || line.contains("- ") {
+            line = line.replace(" -", "-").replace("- ", "-");
+        }
+        out.extend(line.split_whitespace().map(str::to_string));
+    }
+    out
+}
+
+/// Parse a whole list. Each token is returned with its parse result, in input order.
+pub fn parse_target_list(text: &str) -> Vec<(String, Result<Target, String>)> {
+    split_target_tokens(text)
+        .into_iter()
+        .map(|t| { let r = parse_target(&t); (t, r) })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> Ipv4Addr { s.parse().unwrap() }
+
+    #[test]
+    fn test_parse_targets() {
+        assert_eq!(parse_target("10.0.0.1"), Ok(Target::Ip(ip("10.0.0.1"))));
+        assert_eq!(parse_target("10.0.0.0/30"), Ok(Target::Cidr("10.0.0.0/30".parse().unwrap())));
+        assert_eq!(parse_target("10.0.0.5-10.0.0.40"), Ok(Target::Range(ip("10.0.0.5"), ip("10.0.0.40"))));
+        assert_eq!(parse_target("10.0.0.5-40"), Ok(Target::Range(ip("10.0.0.5"), ip("10.0.0.40"))));
+        assert_eq!(parse_target("printer01"), Ok(Target::Host("printer01".to_string())));
+        assert_eq!(parse_target("web-01.corp.example.com."), Ok(Target::Host("web-01.corp.example.com.".to_string())));
+        assert!(parse_target("10.0.0.40-5").is_err());
+        assert!(parse_target("10.0.0.5-300").is_err());
+        assert!(parse_target("10.0.0.0/33").is_err());
+        assert!(parse_target("300.1.1.1").is_err());
+        assert!(parse_target("bad host!").is_err());
+    }
+
+    #[test]
+    fn test_split_tokens_and_comments() {
+        let text = "# office\n10.0.0.1, 10.0.0.2  # gateway\n\nprinter01\n10.0.0.5 - 10.0.0.7\n  # only comment\n192.168.0.0/30";
+        assert_eq!(split_target_tokens(text), vec!["10.0.0.1", "10.0.0.2", "printer01", "10.0.0.5-10.0.0.7", "192.168.0.0/30"]);
+        let parsed = parse_target_list("10.0.0.1\nfoo!\n");
+        assert_eq!(parsed.len(), 2);
+        assert!(parsed[0].1.is_ok());
+        assert_eq!(parsed[1].0, "foo!");
+        assert!(parsed[1].1.is_err());
+    }
+
+    #[test]
+    fn test_sweep_plan() {
+        assert_eq!(parse_sweep_entry("192.168.1.0 255.255.255.0"), Ok((ip("192.168.1.1"), ip("192.168.1.254"))));
+        assert_eq!(parse_sweep_entry("192.168.1.77/255.255.255.252"), Ok((ip("192.168.1.77"), ip("192.168.1.78"))));
+        assert_eq!(parse_sweep_entry("10.0.0.5 - 10.0.0.9"), Ok((ip("10.0.0.5"), ip("10.0.0.9"))));
+        assert!(parse_sweep_entry("192.168.1.0 255.0.255.0").is_err());
+        assert!(parse_sweep_entry("printer01").is_err());
+
+        let plan = SweepPlan::parse("10.0.0.0/24, 10.0.0.100-10.0.1.10\n# lab\n192.168.1.0 255.255.255.252\n10.0.0.50").unwrap();
+        assert_eq!(plan.ranges, vec![(ip("10.0.0.1"), ip("10.0.1.10")), (ip("192.168.1.1"), ip("192.168.1.2"))]);
+        assert_eq!(plan.total(), 266 + 2);
+        assert_eq!(plan.clone().into_addresses().count() as u64, plan.total());
+        assert_eq!(merge_ranges(vec![(ip("10.0.0.5"), ip("10.0.0.9")), (ip("10.0.0.1"), ip("10.0.0.4"))]), vec![(ip("10.0.0.1"), ip("10.0.0.9"))]);
+
+        let errors = SweepPlan::parse("10.0.0.0/33, host.lan").unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(SweepPlan::parse("  # nothing").is_err());
+    }
+
+    #[test]
+    fn test_scan_order() {
+        for n in [1u64, 2, 3, 5, 254, 1000] {
+            for seed in [0u64, 1, 42, 0xdead_beef] {
+                let mut seen: Vec<u64> = CyclicPermutation::new(n, seed).collect();
+                assert_eq!(seen.len() as u64, n);
+                seen.sort();
+                seen.dedup();
+                assert_eq!(seen.len() as u64, n, "n={} seed={}", n, seed);
+            }
+        }
+        let plan = SweepPlan::parse("10.0.0.0/24, 10.0.5.1-10.0.5.3").unwrap();
+        let random: Vec<Ipv4Addr> = plan.clone().into_ordered(ScanOrder::Random, 7).collect();
+        let mut sorted = random.clone();
+        sorted.sort();
+        assert_eq!(sorted, plan.clone().into_addresses().collect::<Vec<_>>());
+        assert_ne!(random, sorted);
+
+        let plan = SweepPlan::parse("10.0.0.1-10.0.1.2, 10.0.9.1-2").unwrap();
+        let inter: Vec<Ipv4Addr> = plan.clone().into_ordered(ScanOrder::Interleave, 0).collect();
+        assert_eq!(&inter[..6], &[ip("10.0.0.1"), ip("10.0.1.0"), ip("10.0.9.1"), ip("10.0.0.2"), ip("10.0.1.1"), ip("10.0.9.2")]);
+        assert_eq!(inter.len() as u64, plan.total());
+        assert_eq!(inter[6..8], [ip("10.0.0.3"), ip("10.0.1.2")]);
+        assert_eq!(inter.last(), Some(&ip("10.0.0.255")));
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(Target::Cidr("10.0.0.0/30".parse().unwrap()).expand().unwrap(), vec![ip("10.0.0.1"), ip("10.0.0.2")]);
+        assert_eq!(Target::Cidr("10.0.0.4/31".parse().unwrap()).expand().unwrap().len(), 2);
+        assert_eq!(Target::Range(ip("10.0.0.254"), ip("10.0.1.1")).expand().unwrap().len(), 4);
+        let stub = |name: &str| match name {
+            "printer.lan" => Ok(vec![ip("10.0.0.9")]),
+            _ => Err(format!("Cannot resolve {}", name)),
+        };
+        assert_eq!(Target::Host("printer.lan".to_string()).expand_with(stub).unwrap(), vec![ip("10.0.0.9")]);
+        assert!(Target::Host("no-such-host.invalid".to_string()).expand_with(stub).is_err());
+        assert_eq!(Target::Ip(ip("10.0.0.1")).expand_with(stub).unwrap(), vec![ip("10.0.0.1")]);
+
+        assert_eq!(Target::Cidr("10.0.0.0/24".parse().unwrap()).address_count(), 254);
+        assert_eq!(Target::Cidr("0.0.0.0/0".parse().unwrap()).address_count(), (1 << 32) - 2);
+        assert_eq!(Target::Range(ip("10.0.0.254"), ip("10.0.1.1")).address_count(), 4);
+        assert!(Target::Cidr("10.0.0.0/8".parse().unwrap()).expand().is_err());
+        assert!(Target::Range(ip("1.0.0.0"), ip("255.0.0.0")).expand().is_err());
+        assert_eq!(Target::Cidr("10.0.0.0/12".parse().unwrap()).expand().unwrap().len() as u64, MAX_EXPANDED_ADDRESSES - 2);
+
+        let v6: IpAddr = "::1".parse().unwrap();
+        let v4 = IpAddr::V4(ip("10.0.0.9"));
+        assert_eq!(ipv4_addrs("h", vec![v6, v4, v4]).unwrap(), vec![ip("10.0.0.9")]);
+        assert!(ipv4_addrs("h", vec![v6]).is_err());
+    }
+}
@@ -3,17 +3,26 @@ use fltk::{
     frame::Frame,
     input::{Input, IntInput},
     button::{Button, CheckButton},
+    menu::Choice,
     text::{TextDisplay, TextBuffer},
     app,
 };
 use std::{
-    net::Ipv4Addr,
+    net::{IpAddr, Ipv4Addr},
     process::{Command, Stdio, Child},
-    sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}},
+    sync::{Arc, Mutex, RwLock, atomic::{AtomicBool, Ordering}},
     io::{BufRead, BufReader},
     thread,
 };
-use crate::utils::{ms_to_secs_ceil, tracert_args_windows, traceroute_args_unix};
+use crate::utils::{ms_to_secs_ceil, tracert_args_windows, traceroute_args_unix, choose_save_path, choose_open_path, resolve_target_ipv4, now_utc_string};
+use crate::hops::{HopRecord, TraceFormat, parse_hop_line, format_hop_line, format_hop_table, hops_to_csv, hops_to_json};
+use crate::native_trace::{self, ProbeMode, TraceOptions, UDP_BASE_PORT, TCP_DEFAULT_PORT};
+use crate::mtr::{MtrStats, ROUND_INTERVAL_MS};
+use crate::asn::{AsnDb, annotate};
+use crate::config::Config;
+use crate::route_graph::{self, RouteGraph};
+use crate::rate::RateLimiter;
+use crate::rdns::{RdnsPool, DEFAULT_DNS_TIMEOUT_MS};
 
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
@@ -21,7 +30,9 @@ use std::os::windows::process::CommandExt;
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
-pub fn build_tracert_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>) {
+/// Build the Tracert tab. Returns the running flag, the raw output buffer, the display
+/// and the hop table buffer.
+pub fn build_tracert_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>, config: Arc<Config>, limiter: Arc<RateLimiter>, rdns: Arc<RdnsPool>) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>, TextBuffer) {
     Frame::new(10, 30, 200, 25, "Target (host or IPv4)");
     let mut input = Input::new(10, 70, 200, 30, "");
     input.set_value("8.8.8.8");
@@ -39,21 +50,152 @@ pub fn build_tracert_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>)
     to_inp.set_value("1000");
     let mut resolve_cb = CheckButton::new(320, 110, 120, 25, "Resolve DNS");
     resolve_cb.set_value(true);
+    let mut graph_btn = Button::new(440, 110, 50, 25, "Graph");
+
+    // View row: raw output or parsed hop table, trace engine
+    let mut table_cb = CheckButton::new(10, 140, 100, 25, "Hop table");
+    let mut engine_choice = Choice::new(170, 140, 90, 25, "Engine");
+    engine_choice.add_choice("System|UDP|ICMP|TCP");
+    engine_choice.set_value(0);
+    let continuous_cb = CheckButton::new(270, 140, 120, 25, "Continuous");
+    let mut export_btn = Button::new(410, 140, 80, 25, "Export");
+
+    // Native engine options
+    let _first_label = Frame::new(10, 170, 60, 25, "First TTL");
+    let mut first_inp = IntInput::new(70, 170, 40, 25, "");
+    first_inp.set_value("1");
+    let _queries_label = Frame::new(115, 170, 55, 25, "Queries");
+    let mut queries_inp = IntInput::new(170, 170, 40, 25, "");
+    queries_inp.set_value("3");
+    let _parallel_label = Frame::new(215, 170, 55, 25, "Parallel");
+    let mut parallel_inp = IntInput::new(270, 170, 40, 25, "");
+    parallel_inp.set_value("1");
+    let _port_label = Frame::new(315, 170, 40, 25, "Port");
+    let mut port_inp = IntInput::new(355, 170, 60, 25, "");
+    port_inp.set_value(&UDP_BASE_PORT.to_string());
+    let mut asn_btn = Button::new(420, 170, 70, 25, "ASN DB");
 
     // Output area
-    let mut display = TextDisplay::new(10, 140, 480, 230, "");
+    let mut display = TextDisplay::new(10, 200, 480, 170, "");
     let buff = TextBuffer::default();
+    let table_buff = TextBuffer::default();
     display.set_buffer(buff.clone());
     let display_ref = Arc::new(Mutex::new(display));
 
     let running = Arc::new(AtomicBool::new(false));
     let child_handle: Arc<Mutex<Option<Child>>> = Arc::new(Mutex::new(None));
+    let hops: Arc<Mutex<Vec<HopRecord>>> = Arc::new(Mutex::new(Vec::new()));
+    // Statistics of the last continuous (MTR-style) run
+    let mtr: Arc<Mutex<Option<MtrStats>>> = Arc::new(Mutex::new(None));
+    // Offline IP-to-ASN database used to annotate hops
+    let asn_db: Arc<RwLock<Option<AsnDb>>> = Arc::new(RwLock::new(None));
+    // Completed traces drawn in the route graph panel, shown in place of the output
+    let graph: Arc<Mutex<RouteGraph>> = Arc::new(Mutex::new(RouteGraph::default()));
+    let mut graph_panel = route_graph::build_graph_panel(10, 200, 480, 170, graph.clone());
+    graph_panel.hide();
+    if let Some(path) = &config.asn_db {
+        load_asn_db(std::path::PathBuf::from(path), asn_db.clone(), sender);
+    }
+
+    // Load an ASN database file
+    {
+        let asn_db = asn_db.clone();
+        asn_btn.set_callback(move |_| {
+            if let Some(path) = choose_open_path("Open IP-to-ASN database", "IP-to-ASN\t*.{tsv,gz,mmdb}") {
+                load_asn_db(path, asn_db.clone(), sender);
+            }
+        });
+    }
+
+    // Switch between the output and the route graph
+    {
+        let display_ref = display_ref.clone();
+        graph_btn.set_callback(move |b| {
+            let Ok(mut display) = display_ref.lock() else { return };
+            if graph_panel.visible() {
+                graph_panel.hide();
+                display.show();
+                b.set_label("Graph");
+            } else {
+                display.hide();
+                graph_panel.show();
+                b.set_label("Output");
+            }
+        });
+    }
+
+    // Default port follows the probe mode (UDP base port / TCP destination port)
+    {
+        let mut port_inp = port_inp.clone();
+        engine_choice.set_callback(move |c| {
+            match c.value() {
+                1 => port_inp.set_value(&UDP_BASE_PORT.to_string()),
+                3 => port_inp.set_value(&TCP_DEFAULT_PORT.to_string()),
+                _ => {}
+            }
+        });
+    }
+
+    // Switch between raw output and hop table
+    {
+        let display_ref = display_ref.clone();
+        let raw = buff.clone();
+        let table = table_buff.clone();
+        table_cb.set_callback(move |cb| {
+            if let Ok(mut display) = display_ref.lock() {
+                display.set_buffer(if cb.value() { table.clone() } else { raw.clone() });
+                display.redraw();
+            }
+        });
+    }
+
+    // Export hop table, or the MTR report after a continuous run
+    {
+        let hops = hops.clone();
+        let mtr = mtr.clone();
+        let mut b = buff.clone();
+        export_btn.set_callback(move |_| {
+            let mtr_snapshot = mtr.lock().ok().and_then(|m| m.clone()).filter(|m| m.rounds > 0);
+            if let Some(stats) = mtr_snapshot {
+                let Some(path) = choose_save_path("Export MTR report", "Text\t*.txt\nCSV\t*.csv\nJSON\t*.json", "mtr_report.txt") else { return };
+                let ext = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+                let text = match ext.as_str() {
+                    "csv" => stats.to_csv(),
+                    "json" => stats.to_json(),
+                    _ => stats.to_report(),
+                };
+                match std::fs::write(&path, text) {
+                    Ok(_) => b.append(&format!("[Info] Exported MTR report ({} rounds) to {}\n", stats.rounds, path.display())),
+                    Err(e) => b.append(&format!("[Error] Export failed: {}\n", e)),
+                }
+                return;
+            }
+            let snapshot = hops.lock().map(|h| h.clone()).unwrap_or_default();
+            if snapshot.is_empty() {
+                b.append("[Error] No hops to export.\n");
+                return;
+            }
+            let Some(path) = choose_save_path("Export hops", "CSV\t*.csv\nJSON\t*.json", "trace.csv") else { return };
+            let is_json = path.extension().map(|e| e.eq_ignore_ascii_case("json")).unwrap_or(false);
+            let text = if is_json { hops_to_json(&snapshot) } else { hops_to_csv(&snapshot) };
+            match std::fs::write(&path, text) {
+                Ok(_) => b.append(&format!("[Info] Exported {} hops to {}\n", snapshot.len(), path.display())),
+                Err(e) => b.append(&format!("[Error] Export failed: {}\n", e)),
+            }
+        });
+    }
 
     // Clear
     {
         let mut b = buff.clone();
+        let mut tb = table_buff.clone();
+        let hops = hops.clone();
+        let mtr = mtr.clone();
         clear_btn.set_callback(move |_| {
             b.set_text("");
+            tb.set_text("");
+            if let Ok(mut h) = hops.lock() { h.clear(); }
+            if let Ok(mut m) = mtr.lock() { *m = None; }
         });
     }
 
@@ -66,7 +208,22 @@ pub fn build_tracert_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>)
         let max_inp = max_inp.clone();
         let to_inp = to_inp.clone();
         let res_cb = resolve_cb.clone();
+        let engine_choice = engine_choice.clone();
+        let first_inp = first_inp.clone();
+        let queries_inp = queries_inp.clone();
+        let parallel_inp = parallel_inp.clone();
+        let port_inp = port_inp.clone();
+        let continuous_cb = continuous_cb.clone();
+        let mut table_cb = table_cb.clone();
+        let display_ref = display_ref.clone();
         let mut b = buff.clone();
+        let mut tb = table_buff.clone();
+        let hops = hops.clone();
+        let mtr = mtr.clone();
+        let asn_db = asn_db.clone();
+        let graph = graph.clone();
+        let limiter = limiter.clone();
+        let rdns = rdns.clone();
         trace_btn.set_callback(move |_| {
             if flag.load(Ordering::SeqCst) {
                 return; // already running
@@ -81,23 +238,70 @@ pub fn build_tracert_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>)
             let max_hops: u32 = max_inp.value().parse().ok().filter(|v| *v >= 1).unwrap_or(30);
             let timeout_ms: u32 = to_inp.value().parse().ok().filter(|v| *v >= 1).unwrap_or(1000);
             let resolve_dns = res_cb.value();
+            let native_opts = TraceOptions {
+                mode: match engine_choice.value() { 2 => ProbeMode::Icmp, 3 => ProbeMode::Tcp, _ => ProbeMode::Udp },
+                first_ttl: first_inp.value().parse().ok().filter(|v| *v >= 1).unwrap_or(1),
+                max_hops: max_hops.min(255) as u8,
+                queries: queries_inp.value().parse().ok().filter(|v| *v >= 1).unwrap_or(3),
+                timeout_ms,
+                parallel: parallel_inp.value().parse().ok().filter(|v| *v >= 1).unwrap_or(1),
+                port: port_inp.value().parse().ok().unwrap_or(UDP_BASE_PORT),
+                resolve_dns,
+            };
+            let use_system = engine_choice.value() == 0;
+            let continuous = continuous_cb.value();
 
             // Header
             b.set_text(&format!(
-                "Tracing route to {} (max {} hops, timeout {}ms, DNS {})\n",
+                "Tracing route to {} (max {} hops, timeout {}ms, DNS {}, engine {})\n",
                 target,
                 max_hops,
                 timeout_ms,
-                if resolve_dns {"on"} else {"off"}
+                if resolve_dns {"on"} else {"off"},
+                engine_choice.choice().unwrap_or_default()
             ));
+            tb.set_text(&format_hop_table(&[]));
+            if let Ok(mut h) = hops.lock() { h.clear(); }
+            if let Ok(mut m) = mtr.lock() {
+                *m = if continuous { Some(MtrStats::new(&target, &now_utc_string())) } else { None };
+            }
+            if continuous {
+                // Live statistics are shown in the table view
+                b.append("[Info] Continuous mode: statistics are shown in the hop table. Press Stop to end.\n");
+                table_cb.set_value(true);
+                if let Ok(mut display) = display_ref.lock() {
+                    display.set_buffer(tb.clone());
+                    display.redraw();
+                }
+            }
 
             flag.store(true, Ordering::SeqCst);
             let flag_thread = flag.clone();
             let sender = s.clone();
             let child_ref = child_ref.clone();
             let target_clone = target.clone();
+            let hops = hops.clone();
+            let mtr = mtr.clone();
+            let asn_db = asn_db.clone();
+            let graph = graph.clone();
+            let limiter = limiter.clone();
+            let rdns = rdns.clone();
 
             thread::spawn(move || {
+                if continuous {
+                    // System engine has no per-hop control: use ICMP probes like mtr
+                    let opts = if use_system { TraceOptions { mode: ProbeMode::Icmp, ..native_opts } } else { native_opts };
+                    run_continuous(&target_clone, &opts, &flag_thread, &limiter, sender, &mtr, &asn_db, &rdns);
+                    flag_thread.store(false, Ordering::SeqCst);
+                    return;
+                }
+                if !use_system {
+                    run_native(&target_clone, &native_opts, &flag_thread, &limiter, sender, &hops, &asn_db, &rdns);
+                    finish_trace(&target_clone, &hops, &graph, sender);
+                    flag_thread.store(false, Ordering::SeqCst);
+                    return;
+                }
+
                 // Build command per platform
                 #[cfg(windows)]
                 let mut cmd = {
@@ -139,6 +343,9 @@ pub fn build_tracert_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>)
                                     break;
                                 }
                                 let line = line.unwrap_or_default();
+                                if let Some(rec) = parse_hop_line(&line, TraceFormat::native()) {
+                                    push_hop(&hops, rec, sender, &asn_db);
+                                }
                                 sender.send(("TRACERT".to_string(), Ipv4Addr::UNSPECIFIED, false, line));
                             }
                         }
@@ -157,11 +364,18 @@ pub fn build_tracert_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>)
                             }
                         }
                     }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                        // No traceroute binary: fall back to the built-in ICMP engine
+                        sender.send(("TRACERT".to_string(), Ipv4Addr::UNSPECIFIED, false, "[Info] traceroute not found, using built-in ICMP engine".to_string()));
+                        let opts = TraceOptions { mode: ProbeMode::Icmp, ..native_opts };
+                        run_native(&target_clone, &opts, &flag_thread, &limiter, sender, &hops, &asn_db, &rdns);
+                    }
                     Err(e) => {
                         sender.send(("TRACERT".to_string(), Ipv4Addr::UNSPECIFIED, false, format!("[Error] Failed to start traceroute: {}", e)));
                     }
                 }
 
+                finish_trace(&target_clone, &hops, &graph, sender);
                 flag_thread.store(false, Ordering::SeqCst);
             });
         });
@@ -181,7 +395,148 @@ pub fn build_tracert_tab(sender: app::Sender<(String, Ipv4Addr, bool, String)>)
         });
     }
 
-    (running, buff, display_ref)
+    (running, buff, display_ref, table_buff)
+}
+
+/// Load an ASN database in the background and report the outcome in the output.
+fn load_asn_db(path: std::path::PathBuf, slot: Arc<RwLock<Option<AsnDb>>>, sender: app::Sender<(String, Ipv4Addr, bool, String)>) {
+    thread::spawn(move || {
+        let msg = match AsnDb::open(&path) {
+            Ok(db) => {
+                if let Ok(mut s) = slot.write() { *s = Some(db); }
+                format!("[Info] ASN database loaded: {}", path.display())
+            }
+            Err(e) => format!("[Error] {}", e),
+        };
+        sender.send(("TRACERT".to_string(), Ipv4Addr::UNSPECIFIED, false, msg));
+    });
+}
+
+/// Fill in the network owner (ASN or special-range label) of every responder.
+fn annotate_owners(rec: &mut HopRecord, asn_db: &RwLock<Option<AsnDb>>) {
+    let guard = asn_db.read().ok();
+    let db = guard.as_ref().and_then(|g| g.as_ref());
+    for r in &mut rec.responders {
+        r.owner = annotate(db, r.addr);
+    }
+}
+
+/// Store a parsed hop and send the refreshed hop table.
+fn push_hop(hops: &Mutex<Vec<HopRecord>>, mut rec: HopRecord, sender: app::Sender<(String, Ipv4Addr, bool, String)>, asn_db: &RwLock<Option<AsnDb>>) {
+    annotate_owners(&mut rec, asn_db);
+    let table = match hops.lock() {
+        Ok(mut h) => { h.push(rec); format_hop_table(&h) }
+        Err(_) => String::new(),
+    };
+    sender.send(("TRACERT_TABLE".to_string(), Ipv4Addr::UNSPECIFIED, false, table));
+}
+
+/// Add the finished trace to the route graph and report the hop count.
+fn finish_trace(target: &str, hops: &Mutex<Vec<HopRecord>>, graph: &Mutex<RouteGraph>, sender: app::Sender<(String, Ipv4Addr, bool, String)>) {
+    let snapshot = hops.lock().map(|h| h.clone()).unwrap_or_default();
+    if snapshot.is_empty() { return; }
+    if let Ok(mut g) = graph.lock() {
+        g.set_trace(target.trim(), &snapshot);
+    }
+    sender.send(("TRACERT".to_string(), Ipv4Addr::UNSPECIFIED, false, format!("[Info] Trace finished: {} hops", snapshot.len())));
+}
+
+/// Fill responder names from the reverse DNS cache and look up the missing ones in the
+/// background; `on_name` is called for each name that arrives later.
+fn resolve_responders(rec: &mut HopRecord, rdns: &RdnsPool, on_name: impl Fn(IpAddr, String) + Clone + Send + 'static) {
+    // Lookups outlive the trace so the last hops still get their names
+    let pending = Arc::new(AtomicBool::new(true));
+    for r in rec.responders.iter_mut().filter(|r| r.hostname.is_none()) {
+        match rdns.cached(r.addr) {
+            Some(name) => r.hostname = name,
+            None => {
+                let (addr, on_name) = (r.addr, on_name.clone());
+                rdns.resolve_async(addr, DEFAULT_DNS_TIMEOUT_MS, &pending, move |name| {
+                    if let Some(name) = name {
+                        on_name(addr, name);
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Trace with the in-process engine, streaming hops as traceroute-style lines.
+/// Host names that are not cached yet are filled into the hop table when they arrive.
+#[allow(clippy::too_many_arguments)]
+fn run_native(target: &str, opts: &TraceOptions, running: &AtomicBool, limiter: &RateLimiter, sender: app::Sender<(String, Ipv4Addr, bool, String)>, hops: &Arc<Mutex<Vec<HopRecord>>>, asn_db: &RwLock<Option<AsnDb>>, rdns: &RdnsPool) {
+    let Some(ip) = resolve_target_ipv4(target.trim()) else {
+        sender.send(("TRACERT".to_string(), Ipv4Addr::UNSPECIFIED, false, "[Error] Failed to resolve target to IPv4.".to_string()));
+        return;
+    };
+    let on_name = {
+        let hops = hops.clone();
+        move |addr: IpAddr, name: String| {
+            let table = match hops.lock() {
+                Ok(mut h) => {
+                    for r in h.iter_mut().flat_map(|rec| rec.responders.iter_mut()).filter(|r| r.addr == addr && r.hostname.is_none()) {
+                        r.hostname = Some(name.clone());
+                    }
+                    format_hop_table(&h)
+                }
+                Err(_) => return,
+            };
+            sender.send(("TRACERT_TABLE".to_string(), Ipv4Addr::UNSPECIFIED, false, table));
+        }
+    };
+    let result = native_trace::trace(ip, opts, running, limiter, |mut rec| {
+        if opts.resolve_dns {
+            resolve_responders(&mut rec, rdns, on_name.clone());
+        }
+        sender.send(("TRACERT".to_string(), Ipv4Addr::UNSPECIFIED, false, format_hop_line(&rec)));
+        push_hop(hops, rec, sender, asn_db);
+    });
+    if let Err(e) = result {
+        sender.send(("TRACERT".to_string(), Ipv4Addr::UNSPECIFIED, false, format!("[Error] {}", e)));
+    }
+}
+
+/// Re-probe every hop in rounds until stopped, updating the MTR statistics after each round.
+/// Host names resolved in the background show up from the next round on.
+#[allow(clippy::too_many_arguments)]
+fn run_continuous(target: &str, opts: &TraceOptions, running: &AtomicBool, limiter: &RateLimiter, sender: app::Sender<(String, Ipv4Addr, bool, String)>, mtr: &Mutex<Option<MtrStats>>, asn_db: &RwLock<Option<AsnDb>>, rdns: &RdnsPool) {
+    let Some(ip) = resolve_target_ipv4(target.trim()) else {
+        sender.send(("TRACERT".to_string(), Ipv4Addr::UNSPECIFIED, false, "[Error] Failed to resolve target to IPv4.".to_string()));
+        return;
+    };
+    // One probe per hop per round, all hops probed at once
+    let opts = TraceOptions { queries: 1, parallel: u32::from(opts.max_hops), ..opts.clone() };
+    while running.load(Ordering::SeqCst) {
+        let started = std::time::Instant::now();
+        let mut round = Vec::new();
+        let collect = |mut rec: HopRecord| {
+            annotate_owners(&mut rec, asn_db);
+            if opts.resolve_dns {
+                resolve_responders(&mut rec, rdns, |_, _| {});
+            }
+            round.push(rec);
+        };
+        if let Err(e) = native_trace::trace(ip, &opts, running, limiter, collect) {
+            sender.send(("TRACERT".to_string(), Ipv4Addr::UNSPECIFIED, false, format!("[Error] {}", e)));
+            return;
+        }
+        if !running.load(Ordering::SeqCst) { break; }
+        let table = match mtr.lock() {
+            Ok(mut m) => {
+                let stats = m.get_or_insert_with(MtrStats::default);
+                stats.add_round(&round);
+                format!("Rounds: {}\n{}", stats.rounds, stats.format_table())
+            }
+            Err(_) => String::new(),
+        };
+        sender.send(("TRACERT_TABLE".to_string(), Ipv4Addr::UNSPECIFIED, false, table));
+        // Keep a steady pace between rounds
+        let wait = std::time::Duration::from_millis(ROUND_INTERVAL_MS).saturating_sub(started.elapsed());
+        let until = std::time::Instant::now() + wait;
+        while running.load(Ordering::SeqCst) && std::time::Instant::now() < until {
+            thread::sleep(std::time::Duration::from_millis(50));
+        }
+    }
 }
 
 #[cfg(test)]
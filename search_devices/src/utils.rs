@@ -31,6 +31,81 @@ pub fn ping_args_unix(count: u32, timeout_ms: u32, ip: &str) -> Vec<String> {
     ]
 }
 
+/// Interval option of a Unix ping sending several echo requests. Unprivileged users
+/// cannot go below 200 ms, so shorter intervals are raised to that.
+pub fn ping_interval_args_unix(count: u32, interval_ms: u64) -> Vec<String> {
+    if count <= 1 || interval_ms == 0 {
+        return Vec::new();
+    }
+    let ms = interval_ms.max(200);
+    vec!["-i".into(), format!("{}.{:03}", ms / 1000, ms % 1000)]
+}
+
+/// Run a command to completion, killing it as soon as `running` is cleared.
+/// Returns whether it exited successfully, or None when it was stopped.
+pub fn run_until_stopped(cmd: &mut std::process::Command, running: &std::sync::atomic::AtomicBool) -> Option<bool> {
+    use std::process::Stdio;
+    use std::sync::atomic::Ordering;
+    let mut child = match cmd.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn() {
+        Ok(c) => c,
+        Err(_) => return Some(false),
+    };
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status.success()),
+            Ok(None) => {}
+            Err(_) => return Some(false),
+        }
+        if !running.load(Ordering::SeqCst) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+}
+
+/// Start/stop state of a scan tab. `busy` stays set until the worker exits, and every run
+/// gets its own stop flag, so Stop or Clear followed by Scan cannot revive the old worker.
+#[derive(Clone, Default)]
+pub struct ScanRun {
+    busy: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    current: std::sync::Arc<std::sync::Mutex<std::sync::Arc<std::sync::atomic::AtomicBool>>>,
+}
+
+impl ScanRun {
+    /// Whether the worker of the previous run has not exited yet.
+    pub fn is_busy(&self) -> bool {
+        self.busy.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Mark a new run as started and return its stop flag.
+    pub fn start(&self) -> std::sync::Arc<std::sync::atomic::AtomicBool> {
+        self.busy.store(true, std::sync::atomic::Ordering::SeqCst);
+        let flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+        if let Ok(mut current) = self.current.lock() {
+            *current = flag.clone();
+        }
+        flag
+    }
+
+    /// Stop the current run.
+    pub fn stop(&self) {
+        if let Ok(current) = self.current.lock() {
+            current.store(false, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    /// Called by the worker when it exits.
+    pub fn finish(&self) {
+        self.busy.store(false, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn busy_flag(&self) -> std::sync::Arc<std::sync::atomic::AtomicBool> {
+        self.busy.clone()
+    }
+}
+
 /// Build Windows tracert arguments.
 pub fn tracert_args_windows(max_hops: u32, timeout_ms: u32, resolve_dns: bool, target: &str) -> Vec<String> {
     let mut args = Vec::new();
@@ -75,10 +150,287 @@ pub fn parse_ports(s: &str) -> Result<Vec<u16>, String> {
     Ok(out)
 }
 
+/// Resolve a host name or IPv4 literal to the first IPv4 address.
+pub fn resolve_target_ipv4(target: &str) -> Option<std::net::Ipv4Addr> {
+    if let Ok(ip) = target.parse::<std::net::Ipv4Addr>() { return Some(ip); }
+    if let Ok(addrs) = dns_lookup::lookup_host(target) {
+        for a in addrs {
+            if let std::net::IpAddr::V4(v4) = a { return Some(v4); }
+        }
+    }
+    None
+}
+
+/// Character offset of the Host Info column in CIDR / IP List rows ("{:<15} {:<7} {:<12} ").
+const HOST_INFO_COLUMN: usize = 37;
+
+/// Byte offset at which a late host name belongs in a result buffer: the start of the
+/// Host Info column of the row for `ip` that has no name yet, after a bracketed tag
+/// such as "[printer01] " and before notes appended since (NetBIOS, mDNS, ...).
+pub fn host_info_insert_pos(text: &str, ip: &str) -> Option<usize> {
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_end_matches('\n');
+        if body.split_whitespace().next() == Some(ip) {
+            if let Some(pos) = host_name_pos(body) {
+                return Some(start + pos);
+            }
+        }
+        start += line.len();
+    }
+    None
+}
+
+/// Byte offset of the missing host name in a result row, None when the row already has one.
+/// Notes always follow the name after two spaces.
+fn host_name_pos(row: &str) -> Option<usize> {
+    let mut pos = row.char_indices().nth(HOST_INFO_COLUMN).map(|(i, _)| i).unwrap_or(row.len());
+    if row[pos..].starts_with('[') {
+        pos += row[pos..].find("] ")? + 2;
+    }
+    let rest = &row[pos..];
+    (rest.is_empty() || rest.starts_with("  ")).then_some(pos)
+}
+
+/// Byte offset of the end of the result line for `ip` that does not mention `note` yet,
+/// where a note about the host (e.g. a discovered service) can be appended.
+pub fn row_note_pos(text: &str, ip: &str, note: &str) -> Option<usize> {
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_end_matches('\n');
+        if body.split_whitespace().next() == Some(ip) && !body.contains(note) {
+            return Some(start + body.len());
+        }
+        start += line.len();
+    }
+    None
+}
+
+/// Start offset and text of each result row for `ip` in a buffer. The buffer is searched in
+/// place, so a late update does not copy the whole result text.
+fn buffer_rows<'a>(buf: &'a fltk::text::TextBuffer, ip: &'a str) -> impl Iterator<Item = (i32, String)> + 'a {
+    // Rows are padded after the IP column, so the address is always followed by a space
+    let needle = format!("{} ", ip);
+    let mut from = 0;
+    std::iter::from_fn(move || loop {
+        let pos = buf.search_forward(from, &needle, true)?;
+        from = pos + 1;
+        if buf.line_start(pos) == pos {
+            return Some((pos, buf.line_text(pos)));
+        }
+    })
+}
+
+/// [`host_info_insert_pos`] on a result buffer, as a buffer position.
+pub fn buffer_host_info_pos(buf: &fltk::text::TextBuffer, ip: &str) -> Option<i32> {
+    buffer_rows(buf, ip).find_map(|(start, row)| host_info_insert_pos(&row, ip).map(|pos| start + pos as i32))
+}
+
+/// [`row_note_pos`] on a result buffer, as a buffer position.
+pub fn buffer_note_pos(buf: &fltk::text::TextBuffer, ip: &str, note: &str) -> Option<i32> {
+    buffer_rows(buf, ip).find_map(|(start, row)| row_note_pos(&row, ip, note).map(|pos| start + pos as i32))
+}
+
+/// Whether a CIDR / IP List result buffer has a row for `ip`.
+pub fn buffer_has_row(buf: &fltk::text::TextBuffer, ip: &str) -> bool {
+    buffer_rows(buf, ip).next().is_some()
+}
+
+/// Addresses of the rows marked "alive" in a CIDR / IP List result text, without duplicates.
+pub fn alive_rows(text: &str) -> Vec<std::net::Ipv4Addr> {
+    rows_with_status(text, "alive")
+}
+
+/// Addresses of the rows marked "unreachable", without duplicates.
+pub fn unreachable_rows(text: &str) -> Vec<std::net::Ipv4Addr> {
+    rows_with_status(text, "unreachable")
+}
+
+fn rows_with_status(text: &str, status: &str) -> Vec<std::net::Ipv4Addr> {
+    let mut out = Vec::new();
+    for line in text.lines() {
+        let mut cols = line.split_whitespace();
+        let (Some(ip), Some(_), Some(s)) = (cols.next(), cols.next(), cols.next()) else { continue };
+        if s != status { continue }
+        if let Ok(ip) = ip.parse() {
+            if !out.contains(&ip) {
+                out.push(ip);
+            }
+        }
+    }
+    out
+}
+
+/// Sort the result rows of a buffer text by the IP address in their first column.
+/// Lines before the first row stay on top, other lines after it move below the rows.
+pub fn sort_rows_by_ip(text: &str) -> String {
+    let ip_of = |line: &str| line.split_whitespace().next().and_then(|t| t.parse::<std::net::Ipv4Addr>().ok());
+    let mut head = Vec::new();
+    let mut rows = Vec::new();
+    let mut tail = Vec::new();
+    for line in text.lines() {
+        match ip_of(line) {
+            Some(ip) => rows.push((ip, line)),
+            None if rows.is_empty() => head.push(line),
+            None => tail.push(line),
+        }
+    }
+    rows.sort_by_key(|(ip, _)| *ip);
+    let mut out = String::new();
+    for line in head.into_iter().chain(rows.into_iter().map(|(_, l)| l)).chain(tail) {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// Format seconds since the Unix epoch as "YYYY-MM-DD HH:MM:SS UTC".
+pub fn format_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // civil-from-days (Howard Hinnant)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, rem / 3600, rem / 60 % 60, rem % 60)
+}
+
+/// Current time formatted by `format_utc`.
+pub fn now_utc_string() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format_utc(secs)
+}
+
+/// Ask the user for a file to save to. `filter` uses FLTK syntax, e.g. "CSV\t*.csv\nJSON\t*.json".
+/// Returns None when the dialog is cancelled.
+pub fn choose_save_path(title: &str, filter: &str, preset_name: &str) -> Option<std::path::PathBuf> {
+    use fltk::dialog::{NativeFileChooser, NativeFileChooserType, NativeFileChooserOptions};
+    let mut dlg = NativeFileChooser::new(NativeFileChooserType::BrowseSaveFile);
+    dlg.set_title(title);
+    dlg.set_filter(filter);
+    dlg.set_preset_file(preset_name);
+    dlg.set_option(NativeFileChooserOptions::SaveAsConfirm);
+    dlg.show();
+    let path = dlg.filename();
+    if path.as_os_str().is_empty() { None } else { Some(path) }
+}
+
+/// Ask the user for an existing file to open. Returns None when the dialog is cancelled.
+pub fn choose_open_path(title: &str, filter: &str) -> Option<std::path::PathBuf> {
+    use fltk::dialog::{NativeFileChooser, NativeFileChooserType};
+    let mut dlg = NativeFileChooser::new(NativeFileChooserType::BrowseFile);
+    dlg.set_title(title);
+    dlg.set_filter(filter);
+    dlg.show();
+    let path = dlg.filename();
+    if path.as_os_str().is_empty() { None } else { Some(path) }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[cfg(unix)]
+    #[test]
+    fn test_run_until_stopped() {
+        use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+        let running = Arc::new(AtomicBool::new(true));
+        assert_eq!(run_until_stopped(&mut std::process::Command::new("true"), &running), Some(true));
+        assert_eq!(run_until_stopped(&mut std::process::Command::new("false"), &running), Some(false));
+        let flag = running.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            flag.store(false, Ordering::SeqCst);
+        });
+        let started = std::time::Instant::now();
+        assert_eq!(run_until_stopped(std::process::Command::new("sleep").arg("5"), &running), None);
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_scan_run_flags() {
+        use std::sync::atomic::Ordering;
+        let run = ScanRun::default();
+        assert!(!run.is_busy());
+        let first = run.start();
+        assert!(run.is_busy() && first.load(Ordering::SeqCst));
+        run.stop();
+        assert!(!first.load(Ordering::SeqCst));
+        // Still busy until the stopped worker exits
+        assert!(run.is_busy());
+        run.finish();
+        let second = run.start();
+        assert!(second.load(Ordering::SeqCst));
+        // The old worker stays stopped; Stop now reaches the new run only
+        assert!(!first.load(Ordering::SeqCst));
+        run.stop();
+        assert!(!second.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_row_note_pos() {
+        let text = "IP Address Result\n10.0.0.1 〇 alive gw\n10.0.0.12 〇 alive\n";
+        let pos = row_note_pos(text, "10.0.0.1", "mDNS: x").unwrap();
+        let mut noted = text.to_string();
+        noted.insert_str(pos, "  mDNS: x");
+        assert_eq!(noted, "IP Address Result\n10.0.0.1 〇 alive gw  mDNS: x\n10.0.0.12 〇 alive\n");
+        assert_eq!(row_note_pos(&noted, "10.0.0.1", "mDNS: x"), None);
+        assert!(row_note_pos(&noted, "10.0.0.1", "mDNS: y").is_some());
+        assert_eq!(row_note_pos(text, "10.0.0.2", "mDNS: x"), None);
+    }
+
+    #[test]
+    fn test_alive_rows() {
+        let text = "IP Address      Result  Status       Host Info\n10.0.0.1        〇       alive        gw\n10.0.0.2        ×       unreachable\n[Info] done\n10.0.0.1        〇       alive\n";
+        assert_eq!(alive_rows(text), vec![std::net::Ipv4Addr::new(10, 0, 0, 1)]);
+        assert_eq!(unreachable_rows(text), vec![std::net::Ipv4Addr::new(10, 0, 0, 2)]);
+    }
+
+    #[test]
+    fn test_sort_rows_by_ip() {
+        let text = "[Info] 3 address(es)\nIP Address Result\n10.0.0.10 〇 alive\n10.0.0.9 × dead\n[Info] Skipped 1\n10.0.0.2 〇 alive gw\n";
+        assert_eq!(sort_rows_by_ip(text),
+            "[Info] 3 address(es)\nIP Address Result\n10.0.0.2 〇 alive gw\n10.0.0.9 × dead\n10.0.0.10 〇 alive\n[Info] Skipped 1\n");
+        assert_eq!(sort_rows_by_ip(""), "");
+    }
+
+    #[test]
+    fn test_host_info_insert_pos() {
+        let text = format!("{:<15} {:<7} {:<12} {}\n{:<15} {:<7} {:<12} {}\n{:<15} {:<7} {:<12} {}\n",
+            "IP Address", "Result", "Status", "Host Info",
+            "10.0.0.1", "〇", "alive", "gw.lan",
+            "10.0.0.2", "〇", "alive", "");
+        assert_eq!(host_info_insert_pos(&text, "10.0.0.1"), None);
+        let pos = host_info_insert_pos(&text, "10.0.0.2").unwrap();
+        let mut filled = text.clone();
+        filled.insert_str(pos, "nas.lan");
+        assert!(filled.ends_with(&format!("{:<15} {:<7} {:<12} {}\n", "10.0.0.2", "〇", "alive", "nas.lan")));
+        assert_eq!(host_info_insert_pos(&text, "10.0.0.3"), None);
+        let listed = format!("{:<15} {:<7} {:<12} {}\n", "10.0.0.7", "〇", "alive", "[printer01] ");
+        assert_eq!(host_info_insert_pos(&listed, "10.0.0.7"), Some(listed.len() - 1));
+
+        // Notes that arrived before the name stay behind it
+        for tag in ["", "[tcp/443] "] {
+            let noted = format!("{:<15} {:<7} {:<12} {}  NetBIOS: NAS01  mDNS: _smb._tcp\n", "10.0.0.8", "〇", "alive", tag);
+            let pos = host_info_insert_pos(&noted, "10.0.0.8").unwrap();
+            let mut filled = noted.clone();
+            filled.insert_str(pos, "nas.lan");
+            assert_eq!(filled, format!("{:<15} {:<7} {:<12} {}nas.lan  NetBIOS: NAS01  mDNS: _smb._tcp\n", "10.0.0.8", "〇", "alive", tag));
+            assert_eq!(host_info_insert_pos(&filled, "10.0.0.8"), None);
+        }
+        let unreachable = format!("{:<15} {:<7} {:<12} {}\n", "10.0.0.9", "×", "unreachable", "");
+        assert_eq!(host_info_insert_pos(&unreachable, "10.0.0.9"), Some(unreachable.len() - 1));
+    }
+
     #[test]
     fn test_ms_to_secs_ceil_basic() {
         assert_eq!(ms_to_secs_ceil(0), 1);
@@ -118,6 +470,9 @@ mod tests {
         assert_eq!(u, vec!["-c","2","-W","1","10.0.0.1"]);
         let u2 = ping_args_unix(2, 1999, "10.0.0.1");
         assert_eq!(u2, vec!["-c","2","-W","2","10.0.0.1"]);
+        assert!(ping_interval_args_unix(1, 500).is_empty());
+        assert_eq!(ping_interval_args_unix(3, 1500), vec!["-i","1.500"]);
+        assert_eq!(ping_interval_args_unix(3, 50), vec!["-i","0.200"]);
     }
 
     #[test]
@@ -133,6 +488,13 @@ mod tests {
         assert_eq!(t_unix2, vec!["-m","32","-w","2","8.8.8.8"]);
     }
 
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_utc(1_700_000_000), "2023-11-14 22:13:20 UTC");
+    }
+
     #[test]
     fn test_parse_ports_simple_and_range() {
         let v = parse_ports("22, 80,443").unwrap();
@@ -0,0 +1,191 @@
+// Wake-on-LAN: magic packets by MAC, with MACs remembered from earlier ARP discovery
+
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    net::{Ipv4Addr, SocketAddrV4, UdpSocket},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use crate::liveness::arp_table;
+
+/// File next to the configuration that keeps the IP -> MAC pairs seen in the ARP cache.
+pub const MAC_FILE_NAME: &str = "search_devices_macs.json";
+
+/// Settings read from the `[wol]` section of the configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct WolConfig {
+    /// Destination of the magic packet: limited or directed broadcast (e.g. "10.0.5.255").
+    pub broadcast: String,
+    /// UDP port, usually 9 (discard) or 7 (echo).
+    pub port: u16,
+    /// SecureOn password: 6 bytes as a MAC ("01:02:03:04:05:06") or 4 bytes as an IPv4 address.
+    pub password: String,
+    /// Seconds to keep re-pinging a woken host.
+    pub wait_up_secs: u64,
+}
+
+impl Default for WolConfig {
+    fn default() -> Self {
+        WolConfig { broadcast: "255.255.255.255".to_string(), port: 9, password: String::new(), wait_up_secs: 120 }
+    }
+}
+
+/// Parse "aa:bb:cc:dd:ee:ff", "aa-bb-...", "aabb.ccdd.eeff" or "aabbccddeeff".
+pub fn parse_mac(s: &str) -> Result<[u8; 6], String> {
+    let hex: String = s.trim().chars().filter(|c| !matches!(c, ':' | '-' | '.')).collect();
+    let grouped = s.trim().split([':', '-']).count();
+    if hex.len() != 12 || !hex.chars().all(|c| c.is_ascii_hexdigit()) || !(grouped == 1 || grouped == 6) {
+        return Err(format!("Invalid MAC address: {}", s.trim()));
+    }
+    let mut mac = [0u8; 6];
+    for (i, b) in mac.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| format!("Invalid MAC address: {}", s.trim()))?;
+    }
+    Ok(mac)
+}
+
+/// SecureOn password bytes; empty text means none.
+pub fn parse_password(s: &str) -> Result<Option<Vec<u8>>, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(ip) = s.parse::<Ipv4Addr>() {
+        return Ok(Some(ip.octets().to_vec()));
+    }
+    parse_mac(s).map(|p| Some(p.to_vec())).map_err(|_| format!("Invalid SecureOn password (6 hex bytes or 4 dotted bytes): {}", s))
+}
+
+pub fn format_mac(mac: &[u8; 6]) -> String {
+    mac.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
+
+/// 6 x 0xff, the MAC 16 times, then the SecureOn password if any.
+pub fn magic_packet(mac: &[u8; 6], password: Option<&[u8]>) -> Vec<u8> {
+    let mut pkt = vec![0xff; 6];
+    for _ in 0..16 {
+        pkt.extend_from_slice(mac);
+    }
+    pkt.extend_from_slice(password.unwrap_or_default());
+    pkt
+}
+
+/// Send the magic packet for `mac` to `target`.
+pub fn send_magic(mac: &[u8; 6], password: Option<&[u8]>, target: SocketAddrV4) -> Result<(), String> {
+    let sock = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
+    sock.set_broadcast(true).map_err(|e| e.to_string())?;
+    sock.send_to(&magic_packet(mac, password), target).map_err(|e| format!("Cannot send magic packet to {}: {}", target, e))?;
+    Ok(())
+}
+
+/// IP -> MAC pairs remembered across runs, updated from the ARP cache after each sweep.
+pub struct MacMemory {
+    path: Option<PathBuf>,
+    macs: Mutex<BTreeMap<Ipv4Addr, String>>,
+}
+
+impl MacMemory {
+    /// Load from `path` (missing or invalid file: empty). None keeps the pairs in memory only.
+    pub fn open(path: Option<PathBuf>) -> MacMemory {
+        let macs = path.as_deref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        MacMemory { path, macs: Mutex::new(macs) }
+    }
+
+    /// The memory file in the configuration's directory, or the working directory.
+    pub fn load() -> MacMemory {
+        let dir = crate::config::config_path().and_then(|p| p.parent().map(Path::to_path_buf)).unwrap_or_default();
+        MacMemory::open(Some(dir.join(MAC_FILE_NAME)))
+    }
+
+    pub fn get(&self, ip: Ipv4Addr) -> Option<String> {
+        self.macs.lock().ok()?.get(&ip).cloned()
+    }
+
+    /// Address last seen with `mac`.
+    pub fn ip_for(&self, mac: &str) -> Option<Ipv4Addr> {
+        self.macs.lock().ok()?.iter().find(|(_, m)| m.as_str() == mac).map(|(ip, _)| *ip)
+    }
+
+    /// Remember `pairs` and save the file when something changed. Returns the number of new or changed entries.
+    pub fn record(&self, pairs: impl IntoIterator<Item = (Ipv4Addr, String)>) -> usize {
+        let Ok(mut macs) = self.macs.lock() else { return 0 };
+        let mut changed = 0;
+        for (ip, mac) in pairs {
+            if macs.get(&ip) != Some(&mac) {
+                macs.insert(ip, mac);
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            if let (Some(path), Ok(json)) = (&self.path, serde_json::to_string_pretty(&*macs)) {
+                if let Err(e) = std::fs::write(path, json) {
+                    println!("[Debug] Cannot save {}: {}", path.display(), e);
+                }
+            }
+        }
+        changed
+    }
+
+    /// Snapshot the system ARP cache.
+    pub fn record_arp_cache(&self) -> usize {
+        self.record(arp_table())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mac_and_password() {
+        let mac = [0x00, 0x1b, 0x54, 0xaa, 0xbb, 0x98];
+        for s in ["00:1b:54:aa:bb:98", "00-1B-54-AA-BB-98", "001b.54aa.bb98", "001b54aabb98"] {
+            assert_eq!(parse_mac(s), Ok(mac), "{}", s);
+        }
+        assert!(parse_mac("00:1b:54:aa:bb").is_err());
+        assert!(parse_mac("001b:54aa:bb98").is_err());
+        assert!(parse_mac("10.0.0.5").is_err());
+        assert_eq!(parse_password(""), Ok(None));
+        assert_eq!(parse_password("192.168.1.2"), Ok(Some(vec![192, 168, 1, 2])));
+        assert_eq!(parse_password("01:02:03:04:05:06"), Ok(Some(vec![1, 2, 3, 4, 5, 6])));
+        assert!(parse_password("secret").is_err());
+    }
+
+    #[test]
+    fn test_magic_packet_over_udp() {
+        let mac = [0x00, 0x1b, 0x54, 0xaa, 0xbb, 0x98];
+        let pkt = magic_packet(&mac, None);
+        assert_eq!(pkt.len(), 102);
+        assert_eq!(&pkt[..6], &[0xff; 6]);
+        assert!(pkt[6..].chunks(6).all(|c| c == mac));
+
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let target = match listener.local_addr().unwrap() { std::net::SocketAddr::V4(a) => a, _ => unreachable!() };
+        send_magic(&mac, Some(&[1, 2, 3, 4, 5, 6]), target).unwrap();
+        let mut buf = [0u8; 256];
+        let n = listener.recv(&mut buf).unwrap();
+        assert_eq!(n, 108);
+        assert_eq!(&buf[102..108], &[1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_mac_memory_persists() {
+        let path = std::env::temp_dir().join(format!("search_devices_macs_test_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let ip = Ipv4Addr::new(10, 0, 0, 5);
+        let memory = MacMemory::open(Some(path.clone()));
+        assert_eq!(memory.get(ip), None);
+        assert_eq!(memory.record([(ip, "aa:bb:cc:dd:ee:05".to_string()), (Ipv4Addr::new(10, 0, 0, 6), "aa:bb:cc:dd:ee:06".to_string())]), 2);
+        assert_eq!(memory.record([(ip, "aa:bb:cc:dd:ee:05".to_string())]), 0);
+        // Reloaded from the file
+        let memory = MacMemory::open(Some(path.clone()));
+        assert_eq!(memory.get(ip).as_deref(), Some("aa:bb:cc:dd:ee:05"));
+        assert_eq!(memory.ip_for("aa:bb:cc:dd:ee:06"), Some(Ipv4Addr::new(10, 0, 0, 6)));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,220 @@
+use fltk::{
+    prelude::*,
+    frame::Frame,
+    input::{Input, IntInput},
+    menu::Choice,
+    button::{Button, CheckButton},
+    text::{TextDisplay, TextBuffer},
+    app,
+};
+use std::{
+    net::{Ipv4Addr, SocketAddrV4},
+    sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}},
+    thread,
+    time::{Duration, Instant},
+};
+use crate::config::Config;
+use crate::exclusions::ExcludeList;
+use crate::ip_list_tab::is_alive;
+use crate::liveness::arp_lookup;
+use crate::rate::RateLimiter;
+use crate::utils::{unreachable_rows, ScanRun};
+use crate::wol::{self, MacMemory};
+
+/// Pause between re-pings of a woken host.
+const REPING_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The pause is slept in slices this long so that Stop ends the wait promptly.
+const REPING_STEP: Duration = Duration::from_millis(50);
+
+/// Build the Wake-on-LAN tab. `cidr_buff` and `list_buff` are the CIDR and IP List results
+/// whose unreachable hosts with a remembered MAC are offered for waking.
+pub fn build_wol_tab(
+    sender: app::Sender<(String, Ipv4Addr, bool, String)>,
+    config: Arc<Config>,
+    macs: Arc<MacMemory>,
+    limiter: Arc<RateLimiter>,
+    cidr_buff: TextBuffer,
+    list_buff: TextBuffer,
+) -> (Arc<AtomicBool>, TextBuffer, Arc<Mutex<TextDisplay>>) {
+    Frame::new(10, 30, 480, 25, "IP (uses the MAC remembered from ARP) or MAC address");
+    let input = Input::new(10, 70, 200, 30, "");
+
+    let mut clear_btn = Button::new(240, 70, 80, 30, "Clear");
+    let mut wake_btn = Button::new(320, 70, 80, 30, "Wake");
+    let mut stop_btn = Button::new(410, 70, 80, 30, "Stop");
+
+    let mut unreachable_choice = Choice::new(90, 105, 150, 25, "Unreachable");
+    let mut refresh_btn = Button::new(245, 105, 70, 25, "Refresh");
+    let mut reping_cb = CheckButton::new(330, 105, 160, 25, "Re-ping until up");
+    reping_cb.set_value(true);
+
+    let mut bcast_inp = Input::new(90, 135, 110, 25, "Broadcast");
+    bcast_inp.set_value(&config.wol.broadcast);
+    let mut port_inp = IntInput::new(240, 135, 50, 25, "Port");
+    port_inp.set_value(&config.wol.port.to_string());
+    let mut pass_inp = Input::new(370, 135, 120, 25, "SecureOn");
+    pass_inp.set_value(&config.wol.password);
+    let mut wait_inp = IntInput::new(90, 165, 60, 25, "Timeout (s)");
+    wait_inp.set_value(&config.wol.wait_up_secs.to_string());
+
+    let mut display = TextDisplay::new(10, 195, 480, 175, "");
+    let buff = TextBuffer::default();
+    display.set_buffer(buff.clone());
+    let display_ref = Arc::new(Mutex::new(display));
+
+    let run = ScanRun::default();
+
+    // Clear
+    {
+        let mut b = buff.clone();
+        clear_btn.set_callback(move |_| b.set_text(""));
+    }
+
+    // Refresh: unreachable hosts of the scan results whose MAC is known
+    {
+        let macs = macs.clone();
+        let mut choice = unreachable_choice.clone();
+        let mut b = buff.clone();
+        refresh_btn.set_callback(move |_| {
+            choice.clear();
+            let mut hosts = unreachable_rows(&cidr_buff.text());
+            for ip in unreachable_rows(&list_buff.text()) {
+                if !hosts.contains(&ip) {
+                    hosts.push(ip);
+                }
+            }
+            let mut known = 0;
+            for ip in &hosts {
+                if let Some(mac) = macs.get(*ip) {
+                    choice.add_choice(&format!("{}  {}", ip, mac));
+                    known += 1;
+                }
+            }
+            b.append(&format!("[Info] {} unreachable host(s), {} with a remembered MAC\n", hosts.len(), known));
+            if known > 0 {
+                choice.set_value(0);
+                choice.do_callback();
+            }
+        });
+    }
+    {
+        let mut inp = input.clone();
+        unreachable_choice.set_callback(move |c| {
+            if let Some(ip) = c.choice().and_then(|t| t.split_whitespace().next().map(str::to_string)) {
+                inp.set_value(&ip);
+            }
+        });
+    }
+
+    // Wake: magic packet, then optionally ping until the host answers
+    {
+        let inp = input.clone();
+        let run = run.clone();
+        let mut b = buff.clone();
+        wake_btn.set_callback(move |_| {
+            if run.is_busy() {
+                return; // already running
+            }
+            let text = inp.value().trim().to_string();
+            // IP: remembered MAC, else the current ARP cache; MAC: the IP last seen with it
+            let (mac, ip) = match text.parse::<Ipv4Addr>() {
+                Ok(ip) => match macs.get(ip).or_else(|| arp_lookup(ip)) {
+                    Some(mac) => (mac, Some(ip)),
+                    None => {
+                        b.append(&format!("[Error] No MAC known for {} (scan it while it is up, or enter the MAC)\n", ip));
+                        return;
+                    }
+                },
+                Err(_) => (text.clone(), None),
+            };
+            let mac = match wol::parse_mac(&mac) {
+                Ok(m) => m,
+                Err(e) => { b.append(&format!("[Error] {}\n", e)); return; }
+            };
+            let ip = ip.or_else(|| macs.ip_for(&wol::format_mac(&mac)));
+            let Ok(bcast) = bcast_inp.value().trim().parse::<Ipv4Addr>() else {
+                b.append(&format!("[Error] Invalid broadcast address: {}\n", bcast_inp.value().trim()));
+                return;
+            };
+            let Some(port) = port_inp.value().parse::<u16>().ok().filter(|p| *p > 0) else {
+                b.append(&format!("[Error] Invalid port: {}\n", port_inp.value()));
+                return;
+            };
+            let password = match wol::parse_password(&pass_inp.value()) {
+                Ok(p) => p,
+                Err(e) => { b.append(&format!("[Error] {}\n", e)); return; }
+            };
+            let target = SocketAddrV4::new(bcast, port);
+            if let Err(e) = wol::send_magic(&mac, password.as_deref(), target) {
+                b.append(&format!("[Error] {}\n", e));
+                return;
+            }
+            b.append(&format!("[Info] Magic packet for {} sent to {}{}\n", wol::format_mac(&mac), target,
+                if password.is_some() { " (SecureOn)" } else { "" }));
+
+            if !reping_cb.value() {
+                return;
+            }
+            let Some(ip) = ip else {
+                b.append("[Info] Re-ping skipped: no IP known for this MAC\n");
+                return;
+            };
+            // Never ping an excluded address, nor anything while the exclusions are unknown
+            match ExcludeList::for_profile(&config, None) {
+                Ok(list) => if let Some(rule) = list.matches(ip) {
+                    b.append(&format!("[Info] Re-ping skipped: {} is excluded by {}\n", ip, rule.label()));
+                    return;
+                },
+                Err(e) => {
+                    b.append(&format!("[Error] Re-ping skipped: {}\n", e));
+                    return;
+                }
+            }
+            let wait = Duration::from_secs(wait_inp.value().parse().ok().filter(|v| (1..=3600).contains(v)).unwrap_or(config.wol.wait_up_secs));
+            b.append(&format!("[Info] Pinging {} until it answers (up to {} s)...\n", ip, wait.as_secs()));
+
+            let flag_thread = run.start();
+            let run = run.clone();
+            let limiter = limiter.clone();
+            thread::spawn(move || {
+                let send = |line: String| sender.send(("WOL".to_string(), ip, false, line));
+                let start = Instant::now();
+                let up = loop {
+                    // Each re-ping goes through the shared rate limits like any other probe
+                    let alive = {
+                        let Some(_permit) = limiter.in_flight(1, &flag_thread) else { break None };
+                        if !limiter.pace(ip, 1, &flag_thread) { break None }
+                        is_alive(&ip, 1, 1000, limiter.limits().ping_interval_ms, &flag_thread)
+                    };
+                    match alive {
+                        Some(true) => break Some(true),
+                        None => break None,
+                        Some(false) => {}
+                    }
+                    if start.elapsed() >= wait {
+                        break Some(false);
+                    }
+                    let next = Instant::now() + REPING_INTERVAL;
+                    while flag_thread.load(Ordering::SeqCst) && Instant::now() < next {
+                        thread::sleep(REPING_STEP.min(next.saturating_duration_since(Instant::now())));
+                    }
+                };
+                match up {
+                    Some(true) => send(format!("[Info] {} is up after {} s", ip, start.elapsed().as_secs())),
+                    Some(false) => send(format!("[Error] {} did not answer within {} s", ip, wait.as_secs())),
+                    None => send(format!("[Info] Stopped waiting for {}", ip)),
+                }
+                run.finish();
+            });
+        });
+    }
+
+    // Stop
+    {
+        let run = run.clone();
+        stop_btn.set_callback(move |_| run.stop());
+    }
+
+    (run.busy_flag(), buff, display_ref)
+}